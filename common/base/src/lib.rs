@@ -14,3 +14,9 @@
  * See the License for the specific language governing permissions and
  * limitations under the License.
  */
+
+pub mod node;
+pub mod url;
+
+pub use node::Node;
+pub use url::{Url, UrlParam};
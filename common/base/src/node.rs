@@ -0,0 +1,67 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::Arc;
+
+use crate::Url;
+
+/// A node of the cluster, such as the invoker of one provider.
+pub trait Node {
+    fn get_url(&self) -> Arc<Url>;
+
+    fn is_available(&self) -> bool;
+
+    fn destroy(&self);
+
+    fn is_destroyed(&self) -> bool;
+}
+
+impl<T: Node + ?Sized> Node for Box<T> {
+    fn get_url(&self) -> Arc<Url> {
+        (**self).get_url()
+    }
+
+    fn is_available(&self) -> bool {
+        (**self).is_available()
+    }
+
+    fn destroy(&self) {
+        (**self).destroy()
+    }
+
+    fn is_destroyed(&self) -> bool {
+        (**self).is_destroyed()
+    }
+}
+
+impl<T: Node + ?Sized> Node for Arc<T> {
+    fn get_url(&self) -> Arc<Url> {
+        (**self).get_url()
+    }
+
+    fn is_available(&self) -> bool {
+        (**self).is_available()
+    }
+
+    fn destroy(&self) {
+        (**self).destroy()
+    }
+
+    fn is_destroyed(&self) -> bool {
+        (**self).is_destroyed()
+    }
+}
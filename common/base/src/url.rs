@@ -64,8 +64,7 @@ impl Url {
         self.inner
             .query_pairs()
             .find(|(k, _)| k == T::name())
-            .map(|(_, v)| T::from_str(&v).ok())
-            .flatten()
+            .and_then(|(_, v)| T::from_str(&v).ok())
     }
 
     pub fn query_param_by_key(&self, key: &str) -> Option<String> {
@@ -103,7 +102,7 @@ impl Url {
     }
 
     pub fn set_path(&mut self, path: &str) {
-        let _ = self.inner.set_path(path);
+        self.inner.set_path(path);
     }
 
     pub fn extend_pairs(&mut self, pairs: impl Iterator<Item = (String, String)>) {
@@ -173,5 +172,5 @@ pub trait UrlParam: FromStr {
 
     fn value(&self) -> Self::TargetType;
 
-    fn as_str(&self) -> Cow<str>;
+    fn as_str(&self) -> Cow<'_, str>;
 }
@@ -82,9 +82,23 @@ pub fn generate<T: Service>(
                     }
                 }
 
+                pub fn with_serialization(self, serialization: &str) -> Self {
+                    Self {
+                        inner: self.inner.with_serialization(serialization),
+                    }
+                }
+
                 #methods
 
             }
+
+            impl NewClient for #service_ident {
+                const INTERFACE: &'static str = #path;
+
+                fn new_client(builder: ClientBuilder) -> Self {
+                    Self::new(builder)
+                }
+            }
         }
     }
 }
@@ -117,12 +117,10 @@ pub fn generate<T: Service>(
                         #methods
 
                         _ => Box::pin(async move {
-                            Ok(http::Response::builder()
-                               .status(200)
-                               .header("grpc-status", "12")
-                               .header("content-type", "application/grpc")
-                               .body(empty_body())
-                               .unwrap())
+                            Ok(dubbo::status::Status::new(
+                                dubbo::status::Code::Unimplemented,
+                                "method not found".to_string(),
+                            ).to_http())
                         }),
                     }
                 }
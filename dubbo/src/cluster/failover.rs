@@ -25,14 +25,34 @@ use tower_service::Service;
 
 pub struct Failover<N> {
     inner: N, // loadbalancer service
+    policy: FailoverPolicy,
 }
 
-#[derive(Clone)]
-pub struct FailoverPolicy;
+/// Retries a failed call on another invoker, at most `retries` times. The
+/// default policy retries until a call succeeds.
+#[derive(Clone, Default)]
+pub struct FailoverPolicy {
+    retries: Option<usize>,
+}
+
+impl FailoverPolicy {
+    pub fn new(retries: usize) -> Self {
+        Self {
+            retries: Some(retries),
+        }
+    }
+}
 
 impl<N> Failover<N> {
     pub fn new(inner: N) -> Self {
-        Self { inner }
+        Self {
+            inner,
+            policy: FailoverPolicy::default(),
+        }
+    }
+
+    pub fn with_policy(self, policy: FailoverPolicy) -> Self {
+        Self { policy, ..self }
     }
 }
 
@@ -46,7 +66,11 @@ where
         //TODO some error handling or logging
         match result {
             Ok(_) => None,
-            Err(_) => Some(future::ready(self.clone())),
+            Err(_) => match self.retries {
+                Some(0) => None,
+                Some(retries) => Some(future::ready(Self::new(retries - 1))),
+                None => Some(future::ready(self.clone())),
+            },
         }
     }
 
@@ -81,7 +105,7 @@ where
     }
 
     fn call(&mut self, req: Request<B>) -> Self::Future {
-        let retry = Retry::new(FailoverPolicy, self.inner.clone());
+        let retry = Retry::new(self.policy.clone(), self.inner.clone());
         retry.oneshot(req)
     }
 }
@@ -0,0 +1,155 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use dubbo_base::Node;
+use futures_util::FutureExt;
+use http::{HeaderMap, HeaderValue};
+use protocol_base::invoker::BaseInvoker;
+use tower::ServiceExt;
+use tower_service::Service;
+
+use crate::{
+    codegen::RpcInvocation,
+    invoker::clone_body::CloneBody,
+    status::{Code, Status},
+    svc::NewService,
+    triple::{
+        client::{
+            builder::{ClientBuilder, ServiceMK},
+            triple::grpc_timeout,
+        },
+        codec::registry,
+    },
+    Url,
+};
+
+/// Invokes the providers of the service of its url, as a client would: the
+/// ones discovered are routed, balanced and retried by the stack of `mk`.
+pub struct ClusterInvoker {
+    base: BaseInvoker,
+    mk: ServiceMK,
+    invocation: RpcInvocation,
+    options: CallOptions,
+}
+
+/// The group, version, serialization and timeout of the calls of a
+/// `TripleClient`, for an invoker to call as the client would.
+#[derive(Clone, Debug, Default)]
+pub struct CallOptions {
+    // the group and version, and the content-type of the calls without one
+    headers: HeaderMap,
+    timeout: Option<Duration>,
+}
+
+impl CallOptions {
+    /// The options of the clients `builder` builds. The requests are taken
+    /// to be in its serialization unless they have a content-type.
+    pub fn from_builder(builder: &ClientBuilder) -> Result<Self, crate::Error> {
+        let mut headers = HeaderMap::new();
+        if let Some(group) = &builder.group {
+            headers.insert("tri-service-group", HeaderValue::from_str(group)?);
+        }
+        if let Some(version) = &builder.version {
+            headers.insert("tri-service-version", HeaderValue::from_str(version)?);
+        }
+        if let Some(serialization) = &builder.serialization {
+            let content_type = registry::content_type(serialization);
+            headers.insert(http::header::CONTENT_TYPE, content_type.parse()?);
+        }
+        Ok(CallOptions {
+            headers,
+            timeout: builder.timeout.map(Duration::from_millis),
+        })
+    }
+}
+
+impl ClusterInvoker {
+    pub fn new(url: Url, interface: String, mk: ServiceMK) -> Self {
+        Self {
+            base: BaseInvoker::new(url),
+            mk,
+            invocation: RpcInvocation::default().with_service_unique_name(interface),
+            options: CallOptions::default(),
+        }
+    }
+
+    /// Calls with `options`, see `CallOptions::from_builder`.
+    pub fn with_options(self, options: CallOptions) -> Self {
+        Self { options, ..self }
+    }
+}
+
+impl Service<http::Request<CloneBody>> for ClusterInvoker {
+    type Response = http::Response<crate::BoxBody>;
+
+    type Error = crate::Error;
+
+    type Future = crate::BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, mut req: http::Request<CloneBody>) -> Self::Future {
+        let headers = req.headers_mut();
+        for (name, value) in &self.options.headers {
+            if name != http::header::CONTENT_TYPE || !headers.contains_key(name) {
+                headers.insert(name, value.clone());
+            }
+        }
+        // the directory of the service is cached, only the stack is built
+        let cluster = self.mk.new_service(self.invocation.clone());
+        match self.options.timeout {
+            None => Box::pin(cluster.oneshot(req)),
+            Some(timeout) => {
+                let grpc_timeout = HeaderValue::from_str(&grpc_timeout(timeout)).unwrap();
+                req.headers_mut().insert("grpc-timeout", grpc_timeout);
+                let response = tokio::time::timeout(timeout, cluster.oneshot(req));
+                Box::pin(response.map(move |response| {
+                    response.unwrap_or_else(|_| {
+                        let message = format!("no response within {:?}", timeout);
+                        Err(Status::new(Code::DeadlineExceeded, message).into())
+                    })
+                }))
+            }
+        }
+    }
+}
+
+impl Node for ClusterInvoker {
+    fn get_url(&self) -> Arc<Url> {
+        self.base.get_url()
+    }
+
+    fn is_available(&self) -> bool {
+        self.base.is_available()
+    }
+
+    fn destroy(&self) {
+        self.base.destroy()
+    }
+
+    fn is_destroyed(&self) -> bool {
+        self.base.is_destroyed()
+    }
+}
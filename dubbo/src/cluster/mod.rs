@@ -23,11 +23,17 @@ use crate::{
 };
 
 use self::failover::Failover;
+pub use self::{
+    failover::FailoverPolicy,
+    invoker::{CallOptions, ClusterInvoker},
+};
 
 mod failover;
+mod invoker;
 
 pub struct NewCluster<N> {
     inner: N, // new loadbalancer service
+    policy: FailoverPolicy,
 }
 
 pub struct Cluster<S> {
@@ -36,9 +42,19 @@ pub struct Cluster<S> {
 
 impl<N> NewCluster<N> {
     pub fn layer() -> impl tower_layer::Layer<N, Service = Self> {
-        tower_layer::layer_fn(|inner: N| {
+        Self::layer_with_policy(FailoverPolicy::default())
+    }
+
+    /// Retries a failed call at most `retries` times, on other invokers.
+    pub fn layer_with_retries(retries: usize) -> impl tower_layer::Layer<N, Service = Self> {
+        Self::layer_with_policy(FailoverPolicy::new(retries))
+    }
+
+    pub fn layer_with_policy(policy: FailoverPolicy) -> impl tower_layer::Layer<N, Service = Self> {
+        tower_layer::layer_fn(move |inner: N| {
             NewCluster {
                 inner, // new loadbalancer service
+                policy: policy.clone(),
             }
         })
     }
@@ -54,7 +70,7 @@ where
 
     fn new_service(&self, target: T) -> Self::Service {
         Cluster {
-            inner: Failover::new(self.inner.new_service(target)),
+            inner: Failover::new(self.inner.new_service(target)).with_policy(self.policy.clone()),
         }
     }
 }
@@ -83,3 +99,26 @@ where
         self.inner.call(req)
     }
 }
+
+// as called by `ClusterInvoker`, its requests are cloneable already
+impl<S> Service<Request<CloneBody>> for Cluster<S>
+where
+    S: Service<Request<CloneBody>>,
+{
+    type Response = S::Response;
+
+    type Error = S::Error;
+
+    type Future = S::Future;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<CloneBody>) -> Self::Future {
+        self.inner.call(req)
+    }
+}
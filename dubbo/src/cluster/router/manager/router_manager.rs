@@ -20,12 +20,14 @@ use crate::cluster::router::{
     nacos_config_center::nacos_client::NacosClient,
     router_chain::RouterChain,
 };
-use crate::Url;
-use dubbo_config::{
-    get_global_config,
-    router::{ConditionRouterConfig, NacosConfig, TagRouterConfig},
+use crate::{
+    config::{
+        router::{ConditionRouterConfig, NacosConfig, TagRouterConfig},
+        RootConfig,
+    },
+    logger::tracing::{info, trace},
+    Url,
 };
-use crate::logger::tracing::{info, trace};
 use once_cell::sync::OnceCell;
 use std::{
     collections::HashMap,
@@ -43,6 +45,18 @@ pub struct RouterManager {
 }
 
 impl RouterManager {
+    /// A router manager routing as the `routers` section of `config` says.
+    pub fn new(config: Arc<RootConfig>) -> Self {
+        let mut router_manager = RouterManager {
+            condition_router_manager: ConditionRouterManager::default(),
+            tag_router_manager: TagRouterManager::default(),
+            nacos: None,
+            consumer: HashMap::new(),
+        };
+        router_manager.init(&config);
+        router_manager
+    }
+
     pub fn get_router_chain(&self, service: String) -> RouterChain {
         let mut chain = RouterChain::new();
         if let Some(url) = self.consumer.get(service.as_str()) {
@@ -107,9 +121,9 @@ impl RouterManager {
         }
     }
 
-    pub fn init(&mut self) {
-        let config = get_global_config().routers.clone();
-        self.init_consumer_configs();
+    pub fn init(&mut self, config: &RootConfig) {
+        self.init_consumer_configs(config);
+        let config = config.routers.clone();
         if let Some(nacos_config) = &config.nacos {
             self.init_nacos(nacos_config.clone());
         } else {
@@ -130,8 +144,8 @@ impl RouterManager {
         }
     }
 
-    fn init_consumer_configs(&mut self) {
-        let consumer_configs = get_global_config()
+    fn init_consumer_configs(&mut self, config: &RootConfig) {
+        let consumer_configs = config
             .routers
             .consumer
             .clone()
@@ -148,17 +162,18 @@ impl RouterManager {
     }
 }
 
+/// Routes as the `routers` section of `config` says. Only the first call
+/// sets the config of the global router manager.
+pub fn init_global_router_manager(
+    config: Arc<RootConfig>,
+) -> &'static Arc<RwLock<RouterManager>> {
+    GLOBAL_ROUTER_MANAGER.get_or_init(|| Arc::new(RwLock::new(RouterManager::new(config))))
+}
+
+/// The global router manager, without routers unless
+/// `init_global_router_manager` was called first.
 pub fn get_global_router_manager() -> &'static Arc<RwLock<RouterManager>> {
-    GLOBAL_ROUTER_MANAGER.get_or_init(|| {
-        let mut router_manager = RouterManager {
-            condition_router_manager: ConditionRouterManager::default(),
-            tag_router_manager: TagRouterManager::default(),
-            nacos: None,
-            consumer: HashMap::new(),
-        };
-        router_manager.init();
-        return Arc::new(RwLock::new(router_manager));
-    })
+    init_global_router_manager(Arc::default())
 }
 
 #[derive(Debug, Default, Clone)]
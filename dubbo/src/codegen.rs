@@ -45,7 +45,8 @@ pub use super::{
 pub use crate::{
     filter::{service::FilterService, Filter},
     triple::{
-        client::builder::ClientBuilder, server::builder::ServerBuilder,
+        client::builder::{ClientBuilder, NewClient},
+        server::builder::ServerBuilder,
         transport::connection::Connection,
     },
 };
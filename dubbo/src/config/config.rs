@@ -15,13 +15,17 @@
  * limitations under the License.
  */
 
-use std::{collections::HashMap, env, path::PathBuf};
-
-use super::{protocol::Protocol, registry::RegistryConfig, router::RouterConfig};
-use crate::{
-    logger::tracing::{debug, error, info, warn},
-    utils::yaml_utils::yaml_file_parser,
+use std::{collections::HashMap, env, sync::Arc};
+
+use super::{
+    consumer::ConsumerConfig,
+    format::ConfigFormat,
+    loader::{ConfigError, ConfigLoader},
+    protocol::Protocol,
+    registry::RegistryConfig,
+    router::RouterConfig,
 };
+use crate::logger::tracing::{debug, error};
 use once_cell::sync::OnceCell;
 use serde::{Deserialize, Serialize};
 
@@ -29,7 +33,7 @@ use super::{protocol::ProtocolConfig, provider::ProviderConfig, service::Service
 
 pub const DUBBO_CONFIG_PATH: &str = "application.yaml";
 
-pub static GLOBAL_ROOT_CONFIG: OnceCell<RootConfig> = OnceCell::new();
+pub static GLOBAL_ROOT_CONFIG: OnceCell<Arc<RootConfig>> = OnceCell::new();
 pub const DUBBO_CONFIG_PREFIX: &str = "dubbo";
 
 /// used to storage all structed config, from some source: cmd, file..;
@@ -43,6 +47,9 @@ pub struct RootConfig {
     #[serde(default)]
     pub provider: ProviderConfig,
 
+    #[serde(default)]
+    pub consumer: ConsumerConfig,
+
     #[serde(default)]
     pub registries: HashMap<String, RegistryConfig>,
 
@@ -53,13 +60,26 @@ pub struct RootConfig {
     pub data: HashMap<String, String>,
 }
 
-pub fn get_global_config() -> &'static RootConfig {
-    GLOBAL_ROOT_CONFIG.get_or_init(|| {
-        debug!("current path: {:?}", env::current_dir());
-        RootConfig::new()
-            .load()
-            .unwrap_or_else(|err| panic!("Failed to load global config, error: {}", err))
-    })
+/// The config loaded from `DUBBO_CONFIG_PATH`, used by a `Dubbo` started
+/// without a config of its own.
+pub fn get_global_config() -> Arc<RootConfig> {
+    try_global_config().unwrap_or_else(|err| panic!("Failed to load global config, error: {}", err))
+}
+
+/// Like `get_global_config`, failing rather than panicking. A failed load
+/// is retried on the next call.
+///
+/// Only the files and the environment are read, see `ConfigLoader::from_env`:
+/// the process arguments are the application's. For the `--set` flags to
+/// apply, load the config with `ConfigLoader::with_args` and hand it to
+/// `Dubbo::with_config`.
+pub fn try_global_config() -> Result<Arc<RootConfig>, ConfigError> {
+    GLOBAL_ROOT_CONFIG
+        .get_or_try_init(|| {
+            debug!("current path: {:?}", env::current_dir());
+            RootConfig::new().load().map(Arc::new)
+        })
+        .cloned()
 }
 
 impl RootConfig {
@@ -68,35 +88,21 @@ impl RootConfig {
             protocols: HashMap::new(),
             registries: HashMap::new(),
             provider: ProviderConfig::new(),
+            consumer: ConsumerConfig::new(),
             routers: RouterConfig::default(),
             data: HashMap::new(),
         }
     }
 
-    pub fn load(&self) -> std::io::Result<Self> {
-        let config_path = match env::var("DUBBO_CONFIG_PATH") {
-            Ok(v) => {
-                info!("read config_path from env: {:?}", v);
-                v
-            }
-            Err(err) => {
-                warn!(
-                    "error loading config_path: {:?}, use default path: {:?}",
-                    err, DUBBO_CONFIG_PATH
-                );
-                crate::app_root_dir()
-                    .join(DUBBO_CONFIG_PATH)
-                    .to_str()
-                    .unwrap()
-                    .to_string()
-            }
-        };
-
-        let conf: HashMap<String, RootConfig> =
-            yaml_file_parser(PathBuf::new().join(config_path)).unwrap();
-        let root_config: RootConfig = conf.get(DUBBO_CONFIG_PREFIX).unwrap().clone();
-        debug!("origin config: {:?}", conf);
-        Ok(root_config)
+    /// Loads the config of `DUBBO_CONFIG_PATH`, see `ConfigLoader::from_env`.
+    pub fn load(&self) -> Result<Self, ConfigError> {
+        ConfigLoader::from_env().load()
+    }
+
+    /// Loads the config of the document `text`, holding a `dubbo` section
+    /// like the config files, over the defaults.
+    pub fn from_str(format: ConfigFormat, text: &str) -> Result<Self, ConfigError> {
+        ConfigLoader::new().with_str(format, text).load()
     }
 
     pub fn test_config(&mut self) {
@@ -139,11 +145,6 @@ impl RootConfig {
         );
         // self.data.insert("dubbo.consume.", v)
     }
-
-    #[inline]
-    pub fn leak(self) -> &'static Self {
-        Box::leak(Box::new(self))
-    }
 }
 
 impl Config for RootConfig {
@@ -0,0 +1,146 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+/// The `consumer` section, the services called by the application.
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct ConsumerConfig {
+    #[serde(default)]
+    pub references: HashMap<String, ReferenceConfig>,
+}
+
+impl ConsumerConfig {
+    pub fn new() -> Self {
+        ConsumerConfig {
+            references: HashMap::new(),
+        }
+    }
+
+    pub fn with_references(mut self, references: HashMap<String, ReferenceConfig>) -> Self {
+        self.references = references;
+        self
+    }
+}
+
+/// A service called by the application, through the providers at `url` or
+/// those its registries discover.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ReferenceConfig {
+    /// The interface called, the one of the generated client when empty.
+    #[serde(default)]
+    pub interface: String,
+    /// The providers called directly, comma separated, e.g.
+    /// `tri://10.0.0.1:8888`.
+    #[serde(default)]
+    pub url: Option<String>,
+    /// `tri`, the only protocol references call over, when set.
+    #[serde(default)]
+    pub protocol: String,
+    #[serde(default)]
+    pub registry_ids: Vec<String>,
+    #[serde(default)]
+    pub group: Option<String>,
+    #[serde(default)]
+    pub version: Option<String>,
+    /// The time a call waits for its response, in milliseconds.
+    #[serde(default)]
+    pub timeout: Option<u64>,
+    /// How often a failed call is retried by the `failover` cluster.
+    #[serde(default)]
+    pub retries: Option<usize>,
+    /// `p2c` (the default) or `random`.
+    #[serde(default)]
+    pub loadbalance: Option<String>,
+    /// `failover` (the default) or `failfast`, which never retries.
+    #[serde(default)]
+    pub cluster: Option<String>,
+    /// e.g. `proto` or `json`.
+    #[serde(default)]
+    pub serialization: Option<String>,
+}
+
+impl ReferenceConfig {
+    pub fn interface(self, interface: String) -> Self {
+        Self { interface, ..self }
+    }
+
+    pub fn url(self, url: String) -> Self {
+        Self {
+            url: Some(url),
+            ..self
+        }
+    }
+
+    pub fn registry_ids(self, registry_ids: Vec<String>) -> Self {
+        Self {
+            registry_ids,
+            ..self
+        }
+    }
+
+    pub fn group(self, group: String) -> Self {
+        Self {
+            group: Some(group),
+            ..self
+        }
+    }
+
+    pub fn version(self, version: String) -> Self {
+        Self {
+            version: Some(version),
+            ..self
+        }
+    }
+
+    pub fn timeout(self, timeout: u64) -> Self {
+        Self {
+            timeout: Some(timeout),
+            ..self
+        }
+    }
+
+    pub fn retries(self, retries: usize) -> Self {
+        Self {
+            retries: Some(retries),
+            ..self
+        }
+    }
+
+    pub fn loadbalance(self, loadbalance: String) -> Self {
+        Self {
+            loadbalance: Some(loadbalance),
+            ..self
+        }
+    }
+
+    pub fn cluster(self, cluster: String) -> Self {
+        Self {
+            cluster: Some(cluster),
+            ..self
+        }
+    }
+
+    pub fn serialization(self, serialization: String) -> Self {
+        Self {
+            serialization: Some(serialization),
+            ..self
+        }
+    }
+}
@@ -0,0 +1,90 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::path::Path;
+
+use serde_yaml::Value;
+
+use crate::StdError;
+
+/// The format of a config document, YAML, TOML or JSON with the same schema.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Yaml,
+    Toml,
+    Json,
+}
+
+impl ConfigFormat {
+    pub const ALL: [ConfigFormat; 3] = [ConfigFormat::Yaml, ConfigFormat::Toml, ConfigFormat::Json];
+
+    /// The format of `path` by its extension, YAML unless it is `.toml` or
+    /// `.json`.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|s| s.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("toml") => ConfigFormat::Toml,
+            Some(extension) if extension.eq_ignore_ascii_case("json") => ConfigFormat::Json,
+            _ => ConfigFormat::Yaml,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ConfigFormat::Yaml => "yaml",
+            ConfigFormat::Toml => "toml",
+            ConfigFormat::Json => "json",
+        }
+    }
+
+    // the document `text`, as YAML whatever its format
+    pub(crate) fn parse(self, text: &str) -> Result<Value, StdError> {
+        Ok(match self {
+            ConfigFormat::Yaml => serde_yaml::from_str(text)?,
+            ConfigFormat::Toml => toml::from_str(text)?,
+            ConfigFormat::Json => serde_json::from_str(text)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let yaml = "dubbo:\n  protocols:\n    triple:\n      port: 8888\n      name: tri\n";
+        let toml = "[dubbo.protocols.triple]\nport = 8888\nname = \"tri\"\n";
+        let json = r#"{"dubbo": {"protocols": {"triple": {"port": 8888, "name": "tri"}}}}"#;
+        let expected = ConfigFormat::Yaml.parse(yaml).unwrap();
+        assert_eq!(ConfigFormat::Toml.parse(toml).unwrap(), expected);
+        assert_eq!(ConfigFormat::Json.parse(json).unwrap(), expected);
+        assert!(ConfigFormat::Toml.parse("[dubbo").is_err());
+
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("conf/application.TOML")),
+            ConfigFormat::Toml
+        );
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("application.json")),
+            ConfigFormat::Json
+        );
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("application.yml")),
+            ConfigFormat::Yaml
+        );
+    }
+}
@@ -0,0 +1,782 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Loads a `RootConfig` from layered sources. Later sources override earlier
+//! ones:
+//!
+//! 1. the defaults of `RootConfig`;
+//! 2. the config files, in the order added;
+//! 3. their profile files, `application-{profile}.yaml` next to
+//!    `application.yaml`;
+//! 4. the documents of `ConfigLoader::with_str`;
+//! 5. the `DUBBO_*` environment variables, e.g. `DUBBO_PROTOCOLS_TRIPLE_PORT`
+//!    for `protocols.triple.port`, naming a setting, others are ignored;
+//! 6. the `--set key=value` flags of `ConfigArgs`.
+//!
+//! Files are YAML, TOML or JSON by their extension, see `ConfigFormat`, with
+//! the settings under a `dubbo` section in any format.
+//!
+//! Strings of the files may refer to environment variables as `${NAME}` or
+//! `${NAME:default}`.
+//!
+//! The loaded config is validated, and the keys of the files no setting is
+//! named by are logged, or reported as problems with `UnknownKeys::Deny`.
+//!
+//! The global config, of `get_global_config` and `try_global_config`, is
+//! loaded by `ConfigLoader::from_env` and reads no flags: the process
+//! arguments are the application's, so `--config`, `--profile` and `--set`
+//! only apply to a config loaded with `ConfigLoader::with_args` and handed to
+//! `Dubbo::with_config`. Its profile is that of `DUBBO_PROFILE`.
+
+use std::{
+    collections::HashMap,
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use argh::FromArgs;
+use serde_yaml::{Mapping, Value};
+use thiserror::Error;
+
+use super::{
+    format::ConfigFormat,
+    validate::{self, ConfigProblem, UnknownKeys, ValidationError},
+    RootConfig, DUBBO_CONFIG_PATH, DUBBO_CONFIG_PREFIX,
+};
+use crate::{
+    logger::tracing::{debug, warn},
+    StdError,
+};
+
+/// The environment variable of the config file.
+pub const DUBBO_CONFIG_PATH_ENV: &str = "DUBBO_CONFIG_PATH";
+/// The environment variable of the profile.
+pub const DUBBO_PROFILE_ENV: &str = "DUBBO_PROFILE";
+const ENV_PREFIX: &str = "DUBBO_";
+
+/// Why a config failed to load.
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("config file {} not found", path.display())]
+    NotFound { path: PathBuf },
+    #[error("failed to read {}: {source}", path.display())]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("invalid config file {}: {source}", path.display())]
+    Parse { path: PathBuf, source: StdError },
+    #[error("no `dubbo` section in {}", path.display())]
+    MissingSection { path: PathBuf },
+    #[error("{key}: `${{{name}}}` is not set and has no default")]
+    Placeholder { key: String, name: String },
+    #[error("invalid arguments: {0}")]
+    Args(String),
+    #[error("invalid config: {0}")]
+    Invalid(serde_yaml::Error),
+    #[error(transparent)]
+    Validation(#[from] ValidationError),
+}
+
+/// The config flags of a dubbo application.
+#[derive(FromArgs, Debug, Default, PartialEq)]
+pub struct ConfigArgs {
+    /// config file read in place of application.yaml, may be repeated
+    #[argh(option)]
+    pub config: Vec<String>,
+    /// profile whose application-{profile}.yaml is read too
+    #[argh(option)]
+    pub profile: Option<String>,
+    /// setting overriding the config files, e.g. protocols.triple.port=8889
+    #[argh(option)]
+    pub set: Vec<String>,
+}
+
+impl ConfigArgs {
+    /// Parses the flags of `args`, without the program name.
+    pub fn parse(args: &[&str]) -> Result<Self, ConfigError> {
+        Self::from_args(&["dubbo"], args).map_err(|exit| ConfigError::Args(exit.output))
+    }
+}
+
+/// Loads a `RootConfig` from its sources, see the module docs.
+#[derive(Clone, Debug, Default)]
+pub struct ConfigLoader {
+    // path and whether it must exist
+    files: Vec<(PathBuf, bool)>,
+    texts: Vec<(ConfigFormat, String)>,
+    profile: Option<String>,
+    env: HashMap<String, String>,
+    overrides: Vec<String>,
+    unknown_keys: UnknownKeys,
+}
+
+impl ConfigLoader {
+    /// A loader of the defaults only.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The sources of the global config: the file of `DUBBO_CONFIG_PATH`, or
+    /// else `application.yaml` of the application root, or its `.toml` or
+    /// `.json` sibling if only that exists, the profile of `DUBBO_PROFILE`
+    /// and the environment variables of the process.
+    pub fn from_env() -> Self {
+        let path = match env::var(DUBBO_CONFIG_PATH_ENV) {
+            Ok(path) => PathBuf::from(path),
+            Err(_) => {
+                let path = crate::app_root_dir().join(DUBBO_CONFIG_PATH);
+                ConfigFormat::ALL
+                    .iter()
+                    .map(|format| path.with_extension(format.extension()))
+                    .find(|path| path.is_file())
+                    .unwrap_or(path)
+            }
+        };
+        Self::new()
+            .with_file(path)
+            .with_env(env::vars())
+            .with_profile_from_env()
+    }
+
+    /// Reads `path`, which must exist.
+    pub fn with_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.files.push((path.into(), true));
+        self
+    }
+
+    /// Reads `path` if it exists.
+    pub fn with_optional_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.files.push((path.into(), false));
+        self
+    }
+
+    /// Reads the document `text` of `format`, e.g. generated by a
+    /// deployment tool, after the files.
+    pub fn with_str(mut self, format: ConfigFormat, text: impl Into<String>) -> Self {
+        self.texts.push((format, text.into()));
+        self
+    }
+
+    pub fn with_profile(mut self, profile: impl Into<String>) -> Self {
+        self.profile = Some(profile.into());
+        self
+    }
+
+    /// Reads the `DUBBO_*` overrides and the placeholders of the files from
+    /// `vars`.
+    pub fn with_env(mut self, vars: impl IntoIterator<Item = (String, String)>) -> Self {
+        self.env = vars.into_iter().collect();
+        self
+    }
+
+    /// Applies the flags of `args` over the other sources.
+    pub fn with_args(mut self, args: ConfigArgs) -> Self {
+        if !args.config.is_empty() {
+            self.files = args
+                .config
+                .into_iter()
+                .map(|path| (PathBuf::from(path), true))
+                .collect();
+        }
+        if let Some(profile) = args.profile {
+            self.profile = Some(profile);
+        }
+        self.overrides.extend(args.set);
+        self
+    }
+
+    /// Overrides `key`, a path like `protocols.triple.port`, with `value`.
+    pub fn with_override(mut self, key: &str, value: &str) -> Self {
+        self.overrides.push(format!("{}={}", key, value));
+        self
+    }
+
+    pub fn with_unknown_keys(mut self, unknown_keys: UnknownKeys) -> Self {
+        self.unknown_keys = unknown_keys;
+        self
+    }
+
+    fn with_profile_from_env(mut self) -> Self {
+        if let Some(profile) = self.env.get(DUBBO_PROFILE_ENV) {
+            self.profile = Some(profile.clone());
+        }
+        self
+    }
+
+    /// Loads the config and validates it, reporting every problem at once.
+    pub fn load(&self) -> Result<RootConfig, ConfigError> {
+        let mut problems = Vec::new();
+        let mut config =
+            serde_yaml::to_value(RootConfig::default()).map_err(ConfigError::Invalid)?;
+
+        let profile_files = self.profile.iter().flat_map(|profile| {
+            self.files
+                .iter()
+                .map(move |(path, _)| (profile_path(path, profile), false))
+        });
+        let files: Vec<_> = self.files.iter().cloned().chain(profile_files).collect();
+        for (path, required) in files {
+            if !path.is_file() {
+                match required {
+                    true => return Err(ConfigError::NotFound { path }),
+                    false => continue,
+                }
+            }
+            debug!("reading config file {}", path.display());
+            let text = fs::read_to_string(&path).map_err(|source| ConfigError::Read {
+                path: path.clone(),
+                source,
+            })?;
+            let file = self.section(&path, ConfigFormat::from_path(&path), &text, &mut problems)?;
+            merge(&mut config, file);
+        }
+        for (format, text) in &self.texts {
+            let path = PathBuf::from(format!("<{} text>", format.extension()));
+            let file = self.section(&path, *format, text, &mut problems)?;
+            merge(&mut config, file);
+        }
+
+        let mut vars: Vec<_> = self
+            .env
+            .iter()
+            .filter(|(name, _)| name.starts_with(ENV_PREFIX))
+            .filter(|(name, _)| *name != DUBBO_CONFIG_PATH_ENV && *name != DUBBO_PROFILE_ENV)
+            .collect();
+        vars.sort();
+        for (name, value) in vars {
+            let segments: Vec<_> = name[ENV_PREFIX.len()..]
+                .to_lowercase()
+                .split('_')
+                .map(str::to_string)
+                .collect();
+            // other programs have `DUBBO_*` variables too
+            let Some(keys) = validate::env_keys(&config, &segments) else {
+                debug!("ignoring {}, no setting is named by it", name);
+                continue;
+            };
+            let mut node = &mut config;
+            for key in keys {
+                node = entry(node, key);
+            }
+            *node = typed(node, value);
+        }
+
+        for setting in &self.overrides {
+            let (key, value) = setting
+                .split_once('=')
+                .ok_or_else(|| ConfigError::Args(format!("expected key=value: {}", setting)))?;
+            let key = key.strip_prefix("dubbo.").unwrap_or(key);
+            let mut node = &mut config;
+            for segment in key.split('.') {
+                node = entry(node, segment.to_string());
+            }
+            *node = typed(node, value);
+        }
+
+        let config: RootConfig = serde_yaml::from_value(config).map_err(ConfigError::Invalid)?;
+        if let Err(err) = config.validate() {
+            problems.extend(err.problems);
+        }
+        match problems.is_empty() {
+            true => Ok(config),
+            false => {
+                problems.sort();
+                Err(ValidationError { problems }.into())
+            }
+        }
+    }
+
+    // the `dubbo` section of the document `text` of `path`, expanded, its
+    // unknown keys logged or added to `problems`
+    fn section(
+        &self,
+        path: &Path,
+        format: ConfigFormat,
+        text: &str,
+        problems: &mut Vec<ConfigProblem>,
+    ) -> Result<Value, ConfigError> {
+        let file = format.parse(text).map_err(|source| ConfigError::Parse {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let mut section = match file {
+            Value::Mapping(mut file) => {
+                file.remove(DUBBO_CONFIG_PREFIX)
+                    .ok_or_else(|| ConfigError::MissingSection {
+                        path: path.to_path_buf(),
+                    })
+            }
+            _ => Err(ConfigError::MissingSection {
+                path: path.to_path_buf(),
+            }),
+        }?;
+        self.expand(&mut section, DUBBO_CONFIG_PREFIX)?;
+        for key in validate::unknown_keys(&section) {
+            match self.unknown_keys {
+                UnknownKeys::Ignore => {}
+                UnknownKeys::Warn => warn!("unknown key {} in {}", key, path.display()),
+                UnknownKeys::Deny => problems.push(ConfigProblem {
+                    path: key,
+                    message: format!("unknown key in {}", path.display()),
+                }),
+            }
+        }
+        Ok(section)
+    }
+
+    // expands the placeholders of the strings of `value`, at `key`
+    fn expand(&self, value: &mut Value, key: &str) -> Result<(), ConfigError> {
+        match value {
+            Value::String(s) if s.contains("${") => {
+                let expanded = self.expand_str(s, key)?;
+                // a placeholder alone takes the type of its value, e.g. a number
+                *value =
+                    match s.starts_with("${") && s.ends_with('}') && s.matches("${").count() == 1 {
+                        true => serde_yaml::from_str(&expanded).unwrap_or(Value::String(expanded)),
+                        false => Value::String(expanded),
+                    };
+            }
+            Value::Mapping(mapping) => {
+                for (k, v) in mapping.iter_mut() {
+                    let k = k.as_str().map(str::to_string).unwrap_or_default();
+                    self.expand(v, &format!("{}.{}", key, k))?;
+                }
+            }
+            Value::Sequence(values) => {
+                for (i, v) in values.iter_mut().enumerate() {
+                    self.expand(v, &format!("{}[{}]", key, i))?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn expand_str(&self, s: &str, key: &str) -> Result<String, ConfigError> {
+        let mut expanded = String::new();
+        let mut rest = s;
+        while let Some(start) = rest.find("${") {
+            let Some(len) = rest[start..].find('}') else {
+                break;
+            };
+            expanded.push_str(&rest[..start]);
+            let placeholder = &rest[start + 2..start + len];
+            let (name, default) = match placeholder.split_once(':') {
+                Some((name, default)) => (name, Some(default)),
+                None => (placeholder, None),
+            };
+            match (self.env.get(name), default) {
+                (Some(value), _) => expanded.push_str(value),
+                (None, Some(default)) => expanded.push_str(default),
+                (None, None) => {
+                    return Err(ConfigError::Placeholder {
+                        key: key.to_string(),
+                        name: name.to_string(),
+                    })
+                }
+            }
+            rest = &rest[start + len + 1..];
+        }
+        expanded.push_str(rest);
+        Ok(expanded)
+    }
+}
+
+// `dir/application-{profile}.yaml` of `dir/application.yaml`
+fn profile_path(path: &Path, profile: &str) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default();
+    let name = match path.extension().and_then(|s| s.to_str()) {
+        Some(extension) => format!("{}-{}.{}", stem, profile, extension),
+        None => format!("{}-{}", stem, profile),
+    };
+    path.with_file_name(name)
+}
+
+fn merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Mapping(base), Value::Mapping(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+// the value of `key` in the mapping `node`, created if missing
+fn entry(node: &mut Value, key: String) -> &mut Value {
+    if !node.is_mapping() {
+        *node = Value::Mapping(Mapping::new());
+    }
+    let mapping = node.as_mapping_mut().unwrap();
+    mapping.entry(Value::String(key)).or_insert(Value::Null)
+}
+
+// `value` typed as the `existing` value it replaces
+fn typed(existing: &Value, value: &str) -> Value {
+    match existing {
+        Value::String(_) => Value::String(value.to_string()),
+        Value::Sequence(_) => Value::Sequence(
+            value
+                .split(',')
+                .map(|item| Value::String(item.trim().to_string()))
+                .collect(),
+        ),
+        _ => serde_yaml::from_str(value).unwrap_or_else(|_| Value::String(value.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a scratch directory holding `files`
+    fn scratch(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = env::temp_dir().join(format!("dubbo-config-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for (file, content) in files {
+            fs::write(dir.join(file), content).unwrap();
+        }
+        dir
+    }
+
+    fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    const APPLICATION: &str = r#"
+logging:
+  level: INFO
+dubbo:
+  protocols:
+    triple:
+      ip: 0.0.0.0
+      port: '8888'
+      name: tri
+  registries:
+    zk:
+      protocol: zookeeper
+      address: 127.0.0.1:2181
+    nacos:
+      protocol: nacos
+      address: 127.0.0.1:8848
+  provider:
+    registry_ids: [zk]
+    services:
+      GreeterProvider:
+        version: 1.0.0
+        group: test
+        tag: red
+        protocol: triple
+        interface: org.apache.dubbo.sample.tri.Greeter
+"#;
+
+    #[test]
+    fn test_precedence() {
+        let dir = scratch(
+            "precedence",
+            &[
+                ("application.yaml", APPLICATION),
+                (
+                    "application-dev.yaml",
+                    "dubbo:\n  protocols:\n    triple:\n      port: '9000'\n      ip: 10.0.0.1\n",
+                ),
+            ],
+        );
+        let loader = ConfigLoader::new()
+            .with_file(dir.join("application.yaml"))
+            .with_env(vars(&[
+                ("DUBBO_PROFILE", "dev"),
+                ("DUBBO_PROTOCOLS_TRIPLE_IP", "127.0.0.1"),
+                ("DUBBO_PROVIDER_REGISTRY_IDS", "zk,nacos"),
+                ("PATH", "/usr/bin"),
+                // named by no setting
+                ("DUBBO_HOME", "/opt/dubbo"),
+                ("DUBBO_PROTOCOLS_TRIPLE", "tri"),
+            ]))
+            .with_profile_from_env()
+            .with_unknown_keys(UnknownKeys::Deny);
+
+        let config = loader.load().unwrap();
+        let triple = &config.protocols["triple"];
+        assert_eq!(triple.port, "9000");
+        assert_eq!(triple.ip, "127.0.0.1");
+        assert_eq!(triple.name, "tri");
+        assert_eq!(config.provider.registry_ids, ["zk", "nacos"]);
+        assert_eq!(config.provider.services["GreeterProvider"].tag, "red");
+
+        let args = ConfigArgs::parse(&["--set", "protocols.triple.port=9100"]).unwrap();
+        let config = loader.with_args(args).load().unwrap();
+        assert_eq!(config.protocols["triple"].port, "9100");
+    }
+
+    #[test]
+    fn test_placeholders() {
+        let dir = scratch(
+            "placeholders",
+            &[(
+                "application.yaml",
+                r#"
+dubbo:
+  protocols:
+    triple:
+      ip: ${IP}
+      port: ${PORT:8888}
+      name: tri${SUFFIX:}
+  consumer:
+    references:
+      GreeterClientImpl:
+        url: tri://${HOST:localhost}:${PORT:8888}
+        timeout: ${TIMEOUT:3000}
+"#,
+            )],
+        );
+        let path = dir.join("application.yaml");
+
+        let config = ConfigLoader::new()
+            .with_file(&path)
+            .with_env(vars(&[("IP", "10.0.0.2"), ("PORT", "20000")]))
+            .load()
+            .unwrap();
+        let triple = &config.protocols["triple"];
+        assert_eq!(triple.ip, "10.0.0.2");
+        assert_eq!(triple.port, "20000");
+        assert_eq!(triple.name, "tri");
+        let reference = &config.consumer.references["GreeterClientImpl"];
+        assert_eq!(reference.url.as_deref(), Some("tri://localhost:20000"));
+        assert_eq!(reference.timeout, Some(3000));
+
+        let err = ConfigLoader::new().with_file(&path).load().unwrap_err();
+        assert!(
+            matches!(&err, ConfigError::Placeholder { key, name } if key == "dubbo.protocols.triple.ip" && name == "IP"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let dir = scratch(
+            "errors",
+            &[
+                ("logging.yaml", "logging:\n  level: INFO\n"),
+                ("broken.yaml", "dubbo: [\n"),
+            ],
+        );
+
+        let missing = dir.join("missing.yaml");
+        assert!(matches!(
+            ConfigLoader::new().with_file(&missing).load(),
+            Err(ConfigError::NotFound { path }) if path == missing
+        ));
+        let config = ConfigLoader::new()
+            .with_optional_file(&missing)
+            .load()
+            .unwrap();
+        assert!(config.protocols.is_empty());
+        assert!(matches!(
+            ConfigLoader::new()
+                .with_file(dir.join("logging.yaml"))
+                .load(),
+            Err(ConfigError::MissingSection { .. })
+        ));
+        assert!(matches!(
+            ConfigLoader::new()
+                .with_file(dir.join("broken.yaml"))
+                .load(),
+            Err(ConfigError::Parse { .. })
+        ));
+        assert!(matches!(
+            ConfigLoader::new().with_override("provider", "[").load(),
+            Err(ConfigError::Invalid(_))
+        ));
+
+        assert!(matches!(
+            ConfigArgs::parse(&["--unknown"]),
+            Err(ConfigError::Args(_))
+        ));
+        let args = ConfigArgs::parse(&["--set", "protocols"]).unwrap();
+        assert!(matches!(
+            ConfigLoader::new().with_args(args).load(),
+            Err(ConfigError::Args(_))
+        ));
+    }
+
+    #[test]
+    fn test_validation() {
+        let dir = scratch(
+            "validation",
+            &[(
+                "application.yaml",
+                r#"
+dubbo:
+  protocols:
+    triple:
+      ip: 0.0.0.0
+      port: '88888'
+      name: tri
+      prams:
+        a: b
+  provider:
+    services:
+      GreeterProvider:
+        version: 1.0.0
+        group: test
+        tag: red
+        protocol: tripple
+        interface: org.apache.dubbo.sample.tri.Greeter
+        params: {}
+"#,
+            )],
+        );
+        let path = dir.join("application.yaml");
+
+        let err = ConfigLoader::new().with_file(&path).load().unwrap_err();
+        let ConfigError::Validation(err) = err else {
+            panic!("{}", err);
+        };
+        let paths: Vec<_> = err.problems.iter().map(|p| p.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            [
+                "dubbo.protocols.triple.port",
+                "dubbo.provider.services.GreeterProvider.protocol"
+            ]
+        );
+
+        let err = ConfigLoader::new()
+            .with_file(&path)
+            .with_override("protocols.triple.port", "8888")
+            .with_override("provider.services.GreeterProvider.protocol", "triple")
+            .with_unknown_keys(UnknownKeys::Deny)
+            .load()
+            .unwrap_err();
+        let ConfigError::Validation(err) = err else {
+            panic!("{}", err);
+        };
+        let paths: Vec<_> = err.problems.iter().map(|p| p.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            [
+                "dubbo.protocols.triple.prams",
+                "dubbo.provider.services.GreeterProvider.params"
+            ]
+        );
+        assert!(err.to_string().starts_with("2 problem(s) in the config\n"));
+    }
+
+    #[test]
+    fn test_formats() {
+        let toml = r#"
+[logging]
+level = "INFO"
+
+[dubbo.protocols.triple]
+ip = "0.0.0.0"
+port = 8888
+name = "tri"
+
+[dubbo.provider.services.GreeterProvider]
+version = "1.0.0"
+group = "test"
+tag = "red"
+protocol = "triple"
+interface = "org.apache.dubbo.sample.tri.Greeter"
+"#;
+        let json = r#"{"dubbo": {"protocols": {"triple": {"port": "${PORT:8889}"}}}}"#;
+        let dir = scratch(
+            "formats",
+            &[
+                ("application.toml", toml),
+                (
+                    "application-dev.toml",
+                    "[dubbo.protocols.triple]\nport = \"${PORT:8889}\"\n",
+                ),
+            ],
+        );
+
+        let config = ConfigLoader::new()
+            .with_file(dir.join("application.toml"))
+            .with_profile("dev")
+            .load()
+            .unwrap();
+        let triple = &config.protocols["triple"];
+        assert_eq!(triple.port, "8889");
+        assert_eq!(triple.name, "tri");
+        assert_eq!(config.provider.services["GreeterProvider"].tag, "red");
+
+        let config = RootConfig::from_str(ConfigFormat::Toml, toml).unwrap();
+        assert_eq!(config.protocols["triple"].port, "8888");
+        let config = ConfigLoader::new()
+            .with_str(ConfigFormat::Toml, toml)
+            .with_str(ConfigFormat::Json, json)
+            .load()
+            .unwrap();
+        assert_eq!(config.protocols["triple"].port, "8889");
+
+        assert!(matches!(
+            RootConfig::from_str(ConfigFormat::Json, "{\"dubbo\": "),
+            Err(ConfigError::Parse { .. })
+        ));
+        assert!(matches!(
+            RootConfig::from_str(ConfigFormat::Toml, "[logging]\nlevel = \"INFO\"\n"),
+            Err(ConfigError::MissingSection { .. })
+        ));
+    }
+
+    #[test]
+    fn test_args() {
+        let args = ConfigArgs::parse(&[
+            "--config",
+            "a.yaml",
+            "--config",
+            "b.yaml",
+            "--profile",
+            "prod",
+            "--set",
+            "protocols.triple.port=1",
+        ])
+        .unwrap();
+        let loader = ConfigLoader::new()
+            .with_file("application.yaml")
+            .with_args(args);
+        assert_eq!(
+            loader.files,
+            [
+                (PathBuf::from("a.yaml"), true),
+                (PathBuf::from("b.yaml"), true)
+            ]
+        );
+        assert_eq!(loader.profile.as_deref(), Some("prod"));
+        assert_eq!(
+            profile_path(Path::new("conf/application.yaml"), "prod"),
+            Path::new("conf/application-prod.yaml")
+        );
+    }
+}
@@ -18,8 +18,12 @@
 pub use config::*;
 
 pub mod config;
+pub mod consumer;
+pub mod format;
+pub mod loader;
 pub mod protocol;
 pub mod provider;
 pub mod registry;
 pub mod router;
 pub mod service;
+pub mod validate;
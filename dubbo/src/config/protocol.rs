@@ -17,20 +17,39 @@
 
 use std::collections::HashMap;
 
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 
 pub const DEFAULT_PROTOCOL: &str = "triple";
 
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct Protocol {
+    /// The address to listen on, an IP address or a host name.
     pub ip: String,
+    /// Either `'8888'` or `8888`, as written by placeholders and environment
+    /// variables.
+    #[serde(deserialize_with = "string_or_number")]
     pub port: String,
     pub name: String,
 
-    #[serde(skip_serializing, skip_deserializing)]
+    /// Settings of the server of the protocol, passed on as parameters of
+    /// its url, e.g. `acceptors` or `tcp-nodelay`.
+    #[serde(default)]
     pub params: HashMap<String, String>,
 }
 
+fn string_or_number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Port {
+        String(String),
+        Number(u64),
+    }
+    Ok(match Port::deserialize(deserializer)? {
+        Port::String(port) => port,
+        Port::Number(port) => port.to_string(),
+    })
+}
+
 pub type ProtocolConfig = HashMap<String, Protocol>;
 
 pub trait ProtocolRetrieve {
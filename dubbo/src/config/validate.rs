@@ -0,0 +1,618 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::{self, Display, Formatter},
+    net::IpAddr,
+};
+
+use serde::Serialize;
+use serde_yaml::Value;
+use thiserror::Error;
+
+use super::{
+    consumer::ReferenceConfig, protocol::Protocol, registry::RegistryConfig,
+    service::ServiceConfig, RootConfig, DUBBO_CONFIG_PREFIX,
+};
+use crate::{loadbalancer::LOADBALANCES, Url};
+
+const CLUSTERS: [&str; 2] = ["failover", "failfast"];
+/// The protocols references call over.
+pub(crate) const REFERENCE_PROTOCOLS: [&str; 1] = ["tri"];
+
+/// A problem of a config, at the YAML path of the setting, e.g.
+/// `dubbo.protocols.triple.port`.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ConfigProblem {
+    pub path: String,
+    pub message: String,
+}
+
+impl Display for ConfigProblem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// Every problem found in a config.
+#[derive(Debug, Error, PartialEq)]
+pub struct ValidationError {
+    pub problems: Vec<ConfigProblem>,
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} problem(s) in the config", self.problems.len())?;
+        for problem in &self.problems {
+            write!(f, "\n  {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+/// What loading a config does with the keys no setting is named by.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UnknownKeys {
+    Ignore,
+    /// Logs them, the default.
+    #[default]
+    Warn,
+    /// Reports them as problems.
+    Deny,
+}
+
+#[derive(Default)]
+struct Problems(Vec<ConfigProblem>);
+
+impl Problems {
+    fn push(&mut self, path: String, message: impl Into<String>) {
+        self.0.push(ConfigProblem {
+            path,
+            message: message.into(),
+        });
+    }
+}
+
+impl RootConfig {
+    /// Checks the settings and the references between services, protocols
+    /// and registries, reporting every problem at once.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let mut problems = Problems::default();
+        let root = DUBBO_CONFIG_PREFIX;
+
+        for (name, protocol) in sorted(&self.protocols) {
+            validate_protocol(
+                &mut problems,
+                &format!("{}.protocols.{}", root, name),
+                protocol,
+            );
+        }
+        for (name, registry) in sorted(&self.registries) {
+            validate_registry(
+                &mut problems,
+                &format!("{}.registries.{}", root, name),
+                registry,
+            );
+        }
+
+        let provider = format!("{}.provider", root);
+        for (i, id) in self.provider.protocol_ids.iter().enumerate() {
+            self.check_protocol(
+                &mut problems,
+                format!("{}.protocol_ids[{}]", provider, i),
+                id,
+            );
+        }
+        for (i, id) in self.provider.registry_ids.iter().enumerate() {
+            self.check_registry(
+                &mut problems,
+                format!("{}.registry_ids[{}]", provider, i),
+                id,
+            );
+        }
+        // by interface, group and version
+        let mut exported = HashMap::new();
+        for (name, service) in sorted(&self.provider.services) {
+            let path = format!("{}.services.{}", provider, name);
+            self.validate_service(&mut problems, &path, service);
+            let key = (&service.interface, &service.group, &service.version);
+            if service.interface.is_empty() {
+                continue;
+            }
+            if let Some(first) = exported.insert(key, name) {
+                problems.push(
+                    format!("{}.interface", path),
+                    format!("{} is exported by {} too", service.interface, first),
+                );
+            }
+        }
+
+        for (name, reference) in sorted(&self.consumer.references) {
+            let path = format!("{}.consumer.references.{}", root, name);
+            self.validate_reference(&mut problems, &path, reference);
+        }
+
+        match problems.0.is_empty() {
+            true => Ok(()),
+            false => Err(ValidationError {
+                problems: problems.0,
+            }),
+        }
+    }
+
+    fn validate_service(&self, problems: &mut Problems, path: &str, service: &ServiceConfig) {
+        if service.interface.is_empty() {
+            problems.push(format!("{}.interface", path), "is empty");
+        }
+        self.check_protocol(problems, format!("{}.protocol", path), &service.protocol);
+    }
+
+    fn validate_reference(&self, problems: &mut Problems, path: &str, reference: &ReferenceConfig) {
+        match &reference.url {
+            Some(urls) => {
+                for url in urls.split(',') {
+                    if url.trim().parse::<Url>().is_err() {
+                        problems.push(format!("{}.url", path), format!("invalid url {}", url));
+                    }
+                }
+            }
+            None if reference.registry_ids.is_empty() => {
+                problems.push(path.to_string(), "neither url nor registry_ids is set");
+            }
+            None => {}
+        }
+        if !reference.protocol.is_empty() {
+            check_one_of(
+                problems,
+                format!("{}.protocol", path),
+                &reference.protocol,
+                &REFERENCE_PROTOCOLS,
+            );
+        }
+        for (i, id) in reference.registry_ids.iter().enumerate() {
+            self.check_registry(problems, format!("{}.registry_ids[{}]", path, i), id);
+        }
+        if let Some(loadbalance) = &reference.loadbalance {
+            check_one_of(
+                problems,
+                format!("{}.loadbalance", path),
+                loadbalance,
+                &LOADBALANCES,
+            );
+        }
+        if let Some(cluster) = &reference.cluster {
+            check_one_of(problems, format!("{}.cluster", path), cluster, &CLUSTERS);
+        }
+    }
+
+    fn check_protocol(&self, problems: &mut Problems, path: String, id: &str) {
+        if !self.protocols.contains_key(id) {
+            let known: Vec<_> = sorted(&self.protocols)
+                .map(|(name, _)| name.as_str())
+                .collect();
+            check_one_of(problems, path, id, &known);
+        }
+    }
+
+    fn check_registry(&self, problems: &mut Problems, path: String, id: &str) {
+        if !self.registries.contains_key(id) {
+            let known: Vec<_> = sorted(&self.registries)
+                .map(|(name, _)| name.as_str())
+                .collect();
+            check_one_of(problems, path, id, &known);
+        }
+    }
+}
+
+fn validate_protocol(problems: &mut Problems, path: &str, protocol: &Protocol) {
+    if protocol.name.is_empty() {
+        problems.push(format!("{}.name", path), "is empty");
+    }
+    if protocol.ip.parse::<IpAddr>().is_err() && !is_host_name(&protocol.ip) {
+        problems.push(
+            format!("{}.ip", path),
+            format!("invalid IP address or host name {:?}", protocol.ip),
+        );
+    }
+    if protocol.port.parse::<u16>().is_err() {
+        problems.push(
+            format!("{}.port", path),
+            format!("invalid port {:?}", protocol.port),
+        );
+    }
+}
+
+// Whether `host` is a host name by its syntax, it may not resolve.
+fn is_host_name(host: &str) -> bool {
+    let host = host.strip_suffix('.').unwrap_or(host);
+    !host.is_empty()
+        && host.len() <= 253
+        && host.split('.').all(|label| {
+            (1..=63).contains(&label.len())
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b == b'-')
+        })
+}
+
+fn validate_registry(problems: &mut Problems, path: &str, registry: &RegistryConfig) {
+    if registry.protocol.is_empty() {
+        problems.push(format!("{}.protocol", path), "is empty");
+    }
+    if registry.address.is_empty() {
+        problems.push(format!("{}.address", path), "is empty");
+    }
+}
+
+fn check_one_of(problems: &mut Problems, path: String, value: &str, known: &[&str]) {
+    if !known.contains(&value) {
+        let message = match known.is_empty() {
+            true => format!("unknown {:?}, none is configured", value),
+            false => format!("unknown {:?}, expected one of {}", value, known.join(", ")),
+        };
+        problems.push(path, message);
+    }
+}
+
+fn sorted<V>(map: &HashMap<String, V>) -> impl Iterator<Item = (&String, &V)> {
+    map.iter().collect::<BTreeMap<_, _>>().into_iter()
+}
+
+// The settings of the config, the keys of a struct being the fields it
+// serializes.
+enum Schema {
+    Any,
+    Struct(HashMap<String, Schema>),
+    Map(Box<Schema>),
+}
+
+impl Schema {
+    fn fields<T: Serialize + Default>() -> Self {
+        let fields = match serde_yaml::to_value(T::default()) {
+            Ok(Value::Mapping(mapping)) => mapping
+                .into_iter()
+                .filter_map(|(key, _)| key.as_str().map(|key| (key.to_string(), Schema::Any)))
+                .collect(),
+            _ => HashMap::new(),
+        };
+        Schema::Struct(fields)
+    }
+
+    fn with(mut self, field: &str, schema: Schema) -> Self {
+        if let Schema::Struct(fields) = &mut self {
+            fields.insert(field.to_string(), schema);
+        }
+        self
+    }
+
+    fn map(schema: Schema) -> Self {
+        Schema::Map(Box::new(schema))
+    }
+
+    fn root() -> Self {
+        Schema::fields::<RootConfig>()
+            .with("protocols", Schema::map(Schema::fields::<Protocol>()))
+            .with(
+                "provider",
+                Schema::fields::<super::provider::ProviderConfig>()
+                    .with("services", Schema::map(Schema::fields::<ServiceConfig>())),
+            )
+            .with(
+                "consumer",
+                Schema::fields::<super::consumer::ConsumerConfig>().with(
+                    "references",
+                    Schema::map(Schema::fields::<ReferenceConfig>()),
+                ),
+            )
+            .with(
+                "registries",
+                Schema::map(Schema::fields::<RegistryConfig>()),
+            )
+    }
+
+    fn unknown_keys(&self, value: &Value, path: &str, unknown: &mut Vec<String>) {
+        let Value::Mapping(mapping) = value else {
+            return;
+        };
+        for (key, value) in mapping {
+            let key = match key.as_str() {
+                Some(key) => key.to_string(),
+                None => format!("{:?}", key),
+            };
+            let path = format!("{}.{}", path, key);
+            match self {
+                Schema::Any => {}
+                Schema::Map(schema) => schema.unknown_keys(value, &path, unknown),
+                Schema::Struct(fields) => match fields.get(&key) {
+                    Some(schema) => schema.unknown_keys(value, &path, unknown),
+                    None => unknown.push(path),
+                },
+            }
+        }
+    }
+
+    // The keys of the setting named by the `_` separated `segments` of an
+    // environment variable, in `node`. Keys may hold `_` or `-` themselves:
+    // the longest existing key or field matching the segments wins, new keys
+    // of maps are the shortest leaving segments naming a setting.
+    fn env_keys(&self, node: Option<&Value>, segments: &[String]) -> Option<Vec<String>> {
+        if segments.is_empty() {
+            // structs and maps are not set from a string
+            return matches!(self, Schema::Any).then(Vec::new);
+        }
+        let existing = node
+            .and_then(Value::as_mapping)
+            .into_iter()
+            .flat_map(|mapping| mapping.iter())
+            .filter_map(|(key, value)| key.as_str().map(|key| (key, Some(value))));
+        let (candidates, new_keys): (Vec<_>, bool) = match self {
+            Schema::Struct(fields) => (
+                fields
+                    .iter()
+                    .map(|(key, schema)| {
+                        (key.as_str(), node.and_then(|node| node.get(key)), schema)
+                    })
+                    .collect(),
+                false,
+            ),
+            Schema::Map(schema) => (
+                existing
+                    .map(|(key, value)| (key, value, &**schema))
+                    .collect(),
+                true,
+            ),
+            Schema::Any => (
+                existing.map(|(key, value)| (key, value, self)).collect(),
+                true,
+            ),
+        };
+        for len in (1..=segments.len()).rev() {
+            let name = segments[..len].join("_");
+            let matching = candidates
+                .iter()
+                .find(|(key, _, _)| key.to_lowercase().replace('-', "_") == name);
+            if let Some((key, value, schema)) = matching {
+                if let Some(keys) = schema.env_keys(*value, &segments[len..]) {
+                    return Some([key.to_string()].into_iter().chain(keys).collect());
+                }
+            }
+        }
+        if !new_keys {
+            return None;
+        }
+        let schema = match self {
+            Schema::Map(schema) => schema,
+            _ => self,
+        };
+        (1..=segments.len()).find_map(|len| {
+            let keys = schema.env_keys(None, &segments[len..])?;
+            Some(
+                [segments[..len].join("_")]
+                    .into_iter()
+                    .chain(keys)
+                    .collect(),
+            )
+        })
+    }
+}
+
+// The paths of the keys of the `dubbo` section `value` no setting is named by.
+pub(crate) fn unknown_keys(value: &Value) -> Vec<String> {
+    let mut unknown = Vec::new();
+    Schema::root().unknown_keys(value, DUBBO_CONFIG_PREFIX, &mut unknown);
+    unknown.sort();
+    unknown
+}
+
+// The keys of the setting of the `dubbo` section `value` named by the
+// `_` separated `segments` of an environment variable, none if there is no
+// such setting.
+pub(crate) fn env_keys(value: &Value, segments: &[String]) -> Option<Vec<String>> {
+    Schema::root().env_keys(Some(value), segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn protocol(ip: &str, port: &str) -> Protocol {
+        Protocol {
+            ip: ip.to_string(),
+            port: port.to_string(),
+            name: "tri".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn service(interface: &str, protocol: &str) -> ServiceConfig {
+        ServiceConfig::default()
+            .interface(interface.to_string())
+            .protocol(protocol.to_string())
+    }
+
+    fn paths(config: &RootConfig) -> Vec<String> {
+        match config.validate() {
+            Ok(()) => vec![],
+            Err(err) => err.problems.into_iter().map(|p| p.path).collect(),
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut config = RootConfig::default();
+        config
+            .protocols
+            .insert("triple".to_string(), protocol("0.0.0.0", "8888"));
+        config.registries.insert(
+            "zk".to_string(),
+            RegistryConfig {
+                protocol: "zookeeper".to_string(),
+                address: "127.0.0.1:2181".to_string(),
+            },
+        );
+        config.provider.registry_ids = vec!["zk".to_string()];
+        config
+            .provider
+            .services
+            .insert("A".to_string(), service("demo.Greeter", "triple"));
+        config.consumer.references.insert(
+            "GreeterClient".to_string(),
+            ReferenceConfig::default().registry_ids(vec!["zk".to_string()]),
+        );
+        assert!(config.validate().is_ok());
+
+        config
+            .protocols
+            .insert("grpc".to_string(), protocol("local host", "-1"));
+        config.provider.registry_ids.push("nacos".to_string());
+        config
+            .provider
+            .services
+            .insert("B".to_string(), service("demo.Greeter", "triple"));
+        config
+            .provider
+            .services
+            .insert("C".to_string(), service("", "dubbo"));
+        config.consumer.references.insert(
+            "EchoClient".to_string(),
+            ReferenceConfig {
+                protocol: "dubbo".to_string(),
+                ..ReferenceConfig::default()
+                    .loadbalance("round".to_string())
+                    .cluster("failback".to_string())
+            },
+        );
+        assert_eq!(
+            paths(&config),
+            [
+                "dubbo.protocols.grpc.ip",
+                "dubbo.protocols.grpc.port",
+                "dubbo.provider.registry_ids[1]",
+                "dubbo.provider.services.B.interface",
+                "dubbo.provider.services.C.interface",
+                "dubbo.provider.services.C.protocol",
+                "dubbo.consumer.references.EchoClient",
+                "dubbo.consumer.references.EchoClient.protocol",
+                "dubbo.consumer.references.EchoClient.loadbalance",
+                "dubbo.consumer.references.EchoClient.cluster",
+            ]
+        );
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains(
+            "dubbo.provider.services.C.protocol: unknown \"dubbo\", expected one of grpc, triple"
+        ));
+        assert!(
+            err.contains("dubbo.provider.services.B.interface: demo.Greeter is exported by A too")
+        );
+    }
+
+    #[test]
+    fn test_is_host_name() {
+        for host in [
+            "localhost",
+            "provider-1.dubbo.svc.cluster.local",
+            "example.com.",
+        ] {
+            assert!(is_host_name(host), "{}", host);
+        }
+        for host in [
+            "",
+            "local host",
+            "-provider",
+            "a..b",
+            "[::1]",
+            "10.0.0.1:8888",
+        ] {
+            assert!(!is_host_name(host), "{}", host);
+        }
+        let mut config = RootConfig::default();
+        config
+            .protocols
+            .insert("triple".to_string(), protocol("localhost", "8888"));
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_env_keys() {
+        let value: Value = serde_yaml::from_str(
+            r#"
+protocols:
+  triple_v2:
+    port: '8888'
+"#,
+        )
+        .unwrap();
+        let keys = |name: &str| {
+            let segments: Vec<_> = name.split('_').map(str::to_string).collect();
+            env_keys(&value, &segments)
+        };
+        let expected = |keys: &[&str]| Some(keys.iter().map(|key| key.to_string()).collect());
+        assert_eq!(
+            keys("protocols_triple_v2_port"),
+            expected(&["protocols", "triple_v2", "port"])
+        );
+        assert_eq!(
+            keys("protocols_grpc_web_ip"),
+            expected(&["protocols", "grpc_web", "ip"])
+        );
+        assert_eq!(
+            keys("provider_registry_ids"),
+            expected(&["provider", "registry_ids"])
+        );
+        assert_eq!(
+            keys("protocols_triple_v2_params_a"),
+            expected(&["protocols", "triple_v2", "params", "a"])
+        );
+        assert_eq!(keys("home"), None);
+        assert_eq!(keys("protocols_triple_v2"), None);
+        assert_eq!(keys("protocols_triple_v2_prot"), None);
+    }
+
+    #[test]
+    fn test_unknown_keys() {
+        let value: Value = serde_yaml::from_str(
+            r#"
+protocols:
+  triple:
+    port: '8888'
+    params:
+      anything: goes
+    prot: '8889'
+provider:
+  services:
+    A:
+      interface: demo.Greeter
+  service: {}
+routers:
+  anything: goes
+registrys: {}
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            unknown_keys(&value),
+            [
+                "dubbo.protocols.triple.prot",
+                "dubbo.provider.service",
+                "dubbo.registrys"
+            ]
+        );
+    }
+}
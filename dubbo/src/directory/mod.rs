@@ -23,11 +23,13 @@ use std::{
 };
 
 use crate::{
-    codegen::{RpcInvocation, TripleInvoker},
+    codegen::RpcInvocation,
     invocation::Invocation,
     invoker::{clone_invoker::CloneInvoker, NewInvoker},
     logger::tracing::{debug, error},
     param::Param,
+    protocol::BoxInvoker,
+    route::router::Routers,
     svc::NewService,
     StdError, Url,
 };
@@ -46,6 +48,10 @@ use crate::{
 };
 use tower_service::Service;
 
+/// The invokers of the providers of a service, with the routers of the
+/// calls to them.
+pub type Providers = (Vec<CloneInvoker<BoxInvoker>>, Routers);
+
 type BufferedDirectory =
     Buffer<Directory<ReceiverStream<Result<Change<String, ()>, StdError>>>, ()>;
 
@@ -69,12 +75,14 @@ where
 pub struct NewDirectory<N> {
     // registry
     inner: N,
+    new_invoker: NewInvoker,
 }
 
 pub struct Directory<D> {
-    directory: HashMap<String, CloneInvoker<TripleInvoker>>,
+    directory: HashMap<String, CloneInvoker<BoxInvoker>>,
     discover: D,
     new_invoker: NewInvoker,
+    routers: Routers,
 }
 
 impl<N> NewCachedDirectory<N>
@@ -83,10 +91,18 @@ where
     <N as Service<()>>::Future: Send + 'static,
 {
     pub fn layer() -> impl tower_layer::Layer<N, Service = Self> {
-        tower_layer::layer_fn(|inner: N| {
+        Self::layer_with_invoker(NewInvoker::new())
+    }
+
+    pub fn layer_with_invoker(
+        new_invoker: NewInvoker,
+    ) -> impl tower_layer::Layer<N, Service = Self> {
+        tower_layer::layer_fn(move |inner: N| {
             NewCachedDirectory {
                 // inner is registry
-                inner: CachedDirectory::new(NewDirectory::new(inner)),
+                inner: CachedDirectory::new(
+                    NewDirectory::new(inner).with_invoker(new_invoker.clone()),
+                ),
             }
         })
     }
@@ -148,7 +164,17 @@ impl<N> NewDirectory<N> {
     const MAX_DIRECTORY_BUFFER_SIZE: usize = 16;
 
     pub fn new(inner: N) -> Self {
-        NewDirectory { inner }
+        NewDirectory {
+            inner,
+            new_invoker: NewInvoker::new(),
+        }
+    }
+
+    pub fn with_invoker(self, new_invoker: NewInvoker) -> Self {
+        NewDirectory {
+            new_invoker,
+            ..self
+        }
     }
 }
 
@@ -165,9 +191,13 @@ where
         let service_name = target.param().get_target_service_unique_name();
 
         let fut = self.inner.clone().oneshot(());
+        let new_invoker = self.new_invoker.clone();
 
         let (tx, rx) = channel(Self::MAX_DIRECTORY_BUFFER_SIZE);
+        let routers = Routers::default();
 
+        let directory = Directory::new_with_invoker(ReceiverStream::new(rx), new_invoker)
+            .with_routers(routers.clone());
         tokio::spawn(async move {
             // todo use dubbo url model generate subscribe url
             // category:serviceInterface:version:group
@@ -180,7 +210,10 @@ where
                 return;
             };
 
-            let receiver = registry.subscribe(subscribe_url).await;
+            let receiver = registry.subscribe(subscribe_url.clone()).await;
+            for (name, router) in registry.routers(subscribe_url).await {
+                routers.add(&name, router);
+            }
             debug!("discover start!");
             match receiver {
                 Err(_e) => {
@@ -203,21 +236,28 @@ where
             }
         });
 
-        Buffer::new(
-            Directory::new(ReceiverStream::new(rx)),
-            Self::MAX_DIRECTORY_BUFFER_SIZE,
-        )
+        Buffer::new(directory, Self::MAX_DIRECTORY_BUFFER_SIZE)
     }
 }
 
 impl<D> Directory<D> {
     pub fn new(discover: D) -> Self {
+        Self::new_with_invoker(discover, NewInvoker::new())
+    }
+
+    pub fn new_with_invoker(discover: D, new_invoker: NewInvoker) -> Self {
         Directory {
             directory: Default::default(),
             discover,
-            new_invoker: NewInvoker,
+            new_invoker,
+            routers: Routers::default(),
         }
     }
+
+    /// Routes the calls to the providers through `routers`.
+    pub fn with_routers(self, routers: Routers) -> Self {
+        Directory { routers, ..self }
+    }
 }
 
 impl<D> Service<()> for Directory<D>
@@ -226,7 +266,7 @@ where
     D: Discover<Key = String> + Unpin + Send,
     D::Error: Into<StdError>,
 {
-    type Response = Vec<CloneInvoker<TripleInvoker>>;
+    type Response = Providers;
 
     type Error = StdError;
 
@@ -271,7 +311,7 @@ where
             .directory
             .values()
             .map(|val| val.clone())
-            .collect::<Vec<CloneInvoker<TripleInvoker>>>();
-        future::ok(vec)
+            .collect::<Vec<CloneInvoker<BoxInvoker>>>();
+        future::ok((vec, self.routers.clone()))
     }
 }
@@ -24,6 +24,7 @@ use tower::discover::Change;
 
 use crate::{
     params::{extension_param::ExtensionName, registry_param::RegistryUrl},
+    route::router::BoxRouter,
     url::UrlParam,
     StdError, Url,
 };
@@ -59,6 +60,13 @@ pub trait Registry {
 
     async fn unsubscribe(&self, url: Url) -> Result<(), StdError>;
 
+    /// The routers, by name, of the calls to the providers `subscribe` found
+    /// for `url`, e.g. by the routes the registry receives with them. The
+    /// directory of the subscription holds them.
+    async fn routers(&self, _url: Url) -> Vec<(String, BoxRouter)> {
+        Vec::new()
+    }
+
     fn url(&self) -> &Url;
 }
 
@@ -193,7 +201,7 @@ pub mod proxy {
     use thiserror::Error;
     use tokio::sync::oneshot;
 
-    use crate::{logger::tracing::error, StdError, Url};
+    use crate::{logger::tracing::error, route::router::BoxRouter, StdError, Url};
 
     use crate::extension::registry_extension::{DiscoverStream, Registry};
 
@@ -202,6 +210,7 @@ pub mod proxy {
         Unregister(Url, oneshot::Sender<Result<(), StdError>>),
         Subscribe(Url, oneshot::Sender<Result<DiscoverStream, StdError>>),
         UnSubscribe(Url, oneshot::Sender<Result<(), StdError>>),
+        Routers(Url, oneshot::Sender<Vec<(String, BoxRouter)>>),
     }
 
     #[derive(Clone)]
@@ -330,6 +339,29 @@ pub mod proxy {
             }
         }
 
+        async fn routers(&self, url: Url) -> Vec<(String, BoxRouter)> {
+            let (tx, rx) = oneshot::channel();
+            if self
+                .sender
+                .send(RegistryOpt::Routers(url.clone(), tx))
+                .await
+                .is_err()
+            {
+                error!(
+                    "registry proxy error: send routers request failed, url: {}",
+                    url
+                );
+                return Vec::new();
+            }
+            rx.await.unwrap_or_else(|_| {
+                error!(
+                    "registry proxy error: receive routers response failed, url: {}",
+                    url
+                );
+                Vec::new()
+            })
+        }
+
         fn url(&self) -> &Url {
             &self.url
         }
@@ -368,6 +400,12 @@ pub mod proxy {
                                 error!("registry proxy error: send unsubscribe response failed");
                             }
                         }
+                        RegistryOpt::Routers(url, tx) => {
+                            let routers = registry.routers(url).await;
+                            if tx.send(routers).is_err() {
+                                error!("registry proxy error: send routers response failed");
+                            }
+                        }
                     }
                 }
             });
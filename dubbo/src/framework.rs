@@ -15,44 +15,110 @@
  * limitations under the License.
  */
 
-use std::{collections::HashMap, error::Error, pin::Pin};
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    net::SocketAddr,
+    sync::Arc,
+};
 
 use crate::{
-    config::{get_global_config, protocol::ProtocolRetrieve, RootConfig},
+    config::{
+        consumer::ReferenceConfig, loader::ConfigError, protocol::ProtocolRetrieve,
+        try_global_config, validate::REFERENCE_PROTOCOLS, RootConfig,
+    },
     extension,
-    extension::registry_extension::Registry,
-    logger::tracing::{debug, info},
-    protocol::{BoxExporter, Protocol},
+    extension::registry_extension::{proxy::RegistryProxy, Registry},
+    loadbalancer::LOADBALANCES,
+    logger::tracing::{debug, info, warn},
+    params::registry_param::InterfaceName,
+    protocol::Protocol,
     registry::protocol::RegistryProtocol,
-    Url,
+    triple::{
+        client::{builder::ClientBuilder, NewClient},
+        server::health::HealthReporter,
+    },
+    url::UrlParam,
+    StdError, Url,
 };
-use futures::{future, Future};
+use futures::future;
+use thiserror::Error;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
 
 // Invoker是否可以基于hyper写一个通用的
 
 #[derive(Default)]
 pub struct Dubbo {
-    protocols: HashMap<String, Vec<Url>>,
+    // started in the order of their names
+    protocols: BTreeMap<String, Vec<Url>>,
     registries: Vec<Url>,
     service_registry: HashMap<String, Vec<Url>>, // registry: Urls
-    config: Option<&'static RootConfig>,
+    config: Option<Arc<RootConfig>>,
+    shutdown: ShutdownHandle,
+    health: HealthReporter,
+    ignore_signals: bool,
+}
+
+/// Shuts a started `Dubbo` down, from any task.
+#[derive(Clone, Debug, Default)]
+pub struct ShutdownHandle {
+    token: CancellationToken,
+}
+
+impl ShutdownHandle {
+    pub fn shutdown(&self) {
+        self.token.cancel()
+    }
+
+    pub fn is_shutdown(&self) -> bool {
+        self.token.is_cancelled()
+    }
 }
 
 impl Dubbo {
     pub fn new() -> Dubbo {
         Self {
-            protocols: HashMap::new(),
+            protocols: BTreeMap::new(),
             registries: Vec::default(),
             service_registry: HashMap::new(),
             config: None,
+            shutdown: ShutdownHandle::default(),
+            health: HealthReporter::new(),
+            ignore_signals: false,
         }
     }
 
-    pub fn with_config(mut self, config: RootConfig) -> Self {
-        self.config = Some(config.leak());
+    /// Whether SIGTERM and SIGINT shut the started services down, which they
+    /// do by default. Binaries serving more than dubbo may rather shut it
+    /// down through its `DubboHandle`.
+    pub fn with_shutdown_signals(mut self, enabled: bool) -> Self {
+        self.ignore_signals = !enabled;
         self
     }
 
+    /// Shuts the started services down as SIGTERM or SIGINT would.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
+    /// The health reported by the `grpc.health.v1.Health` service of every
+    /// server.
+    pub fn health(&self) -> HealthReporter {
+        self.health.clone()
+    }
+
+    /// Serves the services of `config` rather than those of the global
+    /// config, so that each `Dubbo` of a process can have its own.
+    pub fn with_config(mut self, config: impl Into<Arc<RootConfig>>) -> Self {
+        self.config = Some(config.into());
+        self
+    }
+
+    pub fn config(&self) -> Option<&Arc<RootConfig>> {
+        self.config.as_ref()
+    }
+
     pub fn add_registry(mut self, registry: &str) -> Self {
         let url: Url = registry.parse().unwrap();
         let url = extension::registry_extension::to_extension_url(url);
@@ -60,13 +126,20 @@ impl Dubbo {
         self
     }
 
-    pub fn init(&mut self) -> Result<(), Box<dyn Error>> {
-        if self.config.is_none() {
-            self.config = Some(get_global_config())
+    // the config given, or else the global one
+    fn root_config(&mut self) -> Result<Arc<RootConfig>, ConfigError> {
+        match &self.config {
+            Some(config) => Ok(config.clone()),
+            None => Ok(self.config.insert(try_global_config()?).clone()),
         }
+    }
 
-        let root_config = self.config.as_ref().unwrap();
+    /// Reads the services to export from the config, again on every call.
+    pub fn init(&mut self) -> Result<(), Box<dyn Error>> {
+        let root_config = self.root_config()?;
+        root_config.validate()?;
         debug!("global conf: {:?}", root_config);
+        self.protocols.clear();
         // env::set_var("ZOOKEEPER_SERVERS",root_config);
         for (_, service_config) in root_config.provider.services.iter() {
             info!("init service name: {}", service_config.interface);
@@ -78,6 +151,7 @@ impl Dubbo {
                     .protocols
                     .get_protocol_or_default(service_config.protocol.as_str());
                 let interface_name = service_config.interface.clone();
+                let params = protocol.params.clone();
                 let protocol_url = format!(
                     "{}/{}?interface={}",
                     protocol.to_url(),
@@ -85,7 +159,10 @@ impl Dubbo {
                     interface_name
                 );
                 info!("protocol_url: {:?}", protocol_url);
-                protocol_url.parse().ok()
+                protocol_url.parse::<Url>().ok().map(|mut url| {
+                    url.extend_pairs(params.into_iter());
+                    url
+                })
             } else {
                 return Err(format!("base {:?} not exists", service_config.protocol).into());
             };
@@ -107,50 +184,329 @@ impl Dubbo {
         Ok(())
     }
 
-    pub async fn start(&mut self) {
-        self.init().unwrap();
+    /// Builds the client of the reference `name` of the `consumer` section,
+    /// e.g. `dubbo.reference::<GreeterClient>("GreeterClientImpl")`.
+    pub fn reference<C: NewClient>(&mut self, name: &str) -> Result<C, ReferenceError> {
+        let root_config = self.root_config()?;
+        let reference = root_config
+            .consumer
+            .references
+            .get(name)
+            .ok_or_else(|| ReferenceError::NotFound(name.to_string()))?;
+        let builder =
+            reference_builder(&root_config, reference, C::INTERFACE).map_err(|reason| {
+                ReferenceError::Invalid {
+                    name: name.to_string(),
+                    reason,
+                }
+            })?;
+        Ok(C::new_client(builder))
+    }
+
+    /// Exports the services and registers them, returning once they are
+    /// served. They are served until SIGTERM, SIGINT or
+    /// `DubboHandle::shutdown`, shutting down unregisters them from every
+    /// registry, marks them not serving, then lets the calls in flight
+    /// complete before closing the connections.
+    pub async fn start(&mut self) -> Result<DubboHandle, StartError> {
+        self.init()
+            .map_err(|err| StartError::Config(err.to_string()))?;
         info!("starting...");
-        // TODO: server registry
 
         let mut registry_extensions = Vec::new();
 
         for registry_url in &self.registries {
-            let registry_url = registry_url.clone();
-            let registry_extension = extension::EXTENSIONS.load_registry(registry_url).await;
-            if let Ok(registry_extension) = registry_extension {
-                registry_extensions.push(registry_extension);
-            }
+            let registry_extension = extension::EXTENSIONS
+                .load_registry(registry_url.clone())
+                .await
+                .map_err(|source| StartError::Registry {
+                    url: registry_url.to_string(),
+                    source,
+                })?;
+            registry_extensions.push(registry_extension);
         }
 
+        let servers = CancellationToken::new();
+        // the servers started so far stop if starting fails
+        let guard = servers.clone().drop_guard();
         let mem_reg = Box::new(
             RegistryProtocol::new()
                 .with_registries(registry_extensions.clone())
-                .with_services(self.service_registry.clone()),
+                .with_services(self.service_registry.clone())
+                .with_health(self.health.clone())
+                .with_shutdown(servers.clone()),
         );
-        let mut async_vec: Vec<Pin<Box<dyn Future<Output = BoxExporter> + Send>>> = Vec::new();
-        for (name, items) in self.protocols.iter() {
-            for url in items.iter() {
-                info!("base: {:?}, service url: {:?}", name, url);
-                let exporter = mem_reg.clone().export(url.to_owned());
-                async_vec.push(exporter);
-
-                //TODO multiple registry
-                for registry_extension in &registry_extensions {
-                    let _ = registry_extension.register(url.clone()).await;
+        let mut registered = Vec::new();
+        let mut exporters = Vec::new();
+        let mut services = Vec::new();
+        let exported = async {
+            for (name, items) in self.protocols.iter() {
+                for url in items.iter() {
+                    info!("base: {:?}, service url: {:?}", name, url);
+                    // registered by the registry protocol on export
+                    let urls = url
+                        .query::<InterfaceName>()
+                        .and_then(|name| self.service_registry.get(name.as_str().as_ref()));
+                    for url in urls.into_iter().flatten() {
+                        for registry_extension in &registry_extensions {
+                            registered.push((registry_extension.clone(), url.clone()));
+                        }
+                    }
+                    let exporter =
+                        mem_reg
+                            .clone()
+                            .export(url.to_owned())
+                            .await
+                            .map_err(|source| StartError::Bind {
+                                url: url.to_string(),
+                                source,
+                            })?;
+                    // registers the port picked for port 0
+                    let mut url = url.clone();
+                    if let Some(local_addr) = exporter.local_addr() {
+                        url.set_port(local_addr.port());
+                    }
+                    exporters.push(exporter);
+
+                    for registry_extension in &registry_extensions {
+                        registry_extension
+                            .register(url.clone())
+                            .await
+                            .map_err(|source| StartError::Registry {
+                                url: url.to_string(),
+                                source,
+                            })?;
+                        registered.push((registry_extension.clone(), url.clone()));
+                    }
+                    services.push(ExportedService {
+                        local_addr: exporters.last().and_then(|exporter| exporter.local_addr()),
+                        url,
+                    });
                 }
             }
+            Ok(())
+        };
+        if let Err(err) = exported.await {
+            // consumers may not pick services which are not served
+            unregister(registered).await;
+            return Err(err);
         }
+        guard.disarm();
+
+        let shutdown = self.shutdown.clone();
+        let health = self.health.clone();
+        let handle_signals = !self.ignore_signals;
+        let task = tokio::spawn(async move {
+            let closed = future::join_all(exporters.iter().map(|exporter| exporter.closed()));
+            let signal = async {
+                match handle_signals {
+                    true => shutdown_signal().await,
+                    false => future::pending().await,
+                }
+            };
+            tokio::pin!(closed);
+            tokio::select! {
+                _ = &mut closed => return,
+                _ = shutdown.token.cancelled() => info!("shutting down"),
+                _ = signal => info!("shutdown signal received, shutting down"),
+            }
+
+            // consumers stop picking the providers before they go away
+            unregister(registered).await;
+            health.set_all_not_serving();
+            servers.cancel();
+            closed.await;
+            info!("shutdown complete");
+        });
 
-        let _res = future::join_all(async_vec).await;
+        Ok(DubboHandle {
+            shutdown: self.shutdown.clone(),
+            services,
+            task,
+        })
     }
 }
 
-impl Drop for Dubbo {
-    fn drop(&mut self) {
-        unsafe {
-            if let Some(config) = self.config.take() {
-                let _ = Box::from_raw(config as *const RootConfig as *mut RootConfig);
+async fn unregister(registered: Vec<(RegistryProxy, Url)>) {
+    for (registry_extension, url) in registered {
+        if let Err(err) = registry_extension.unregister(url.clone()).await {
+            warn!("failed to unregister {}: {:?}", url, err);
+        }
+    }
+}
+
+/// Why `Dubbo::start` failed.
+#[derive(Debug, Error)]
+pub enum StartError {
+    #[error("invalid config: {0}")]
+    Config(String),
+    #[error("registry {url} failed: {source}")]
+    Registry { url: String, source: StdError },
+    #[error("failed to serve {url}: {source}")]
+    Bind { url: String, source: StdError },
+}
+
+/// Why `Dubbo::reference` failed.
+#[derive(Debug, Error)]
+pub enum ReferenceError {
+    #[error("reference {0} is not configured")]
+    NotFound(String),
+    #[error("invalid reference {name}: {reason}")]
+    Invalid { name: String, reason: String },
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+}
+
+fn reference_builder(
+    root_config: &RootConfig,
+    reference: &ReferenceConfig,
+    interface: &str,
+) -> Result<ClientBuilder, String> {
+    let interface = match reference.interface.is_empty() {
+        true => interface,
+        false => reference.interface.as_str(),
+    };
+    if !reference.protocol.is_empty() && !REFERENCE_PROTOCOLS.contains(&reference.protocol.as_str())
+    {
+        return Err(format!("unsupported protocol {}", reference.protocol));
+    }
+    let mut builder = match (&reference.url, reference.registry_ids.as_slice()) {
+        (Some(urls), _) => {
+            let hosts = urls
+                .split(',')
+                .map(|url| {
+                    let mut url: Url = url
+                        .trim()
+                        .parse()
+                        .map_err(|err| format!("invalid url {}: {}", url, err))?;
+                    if url.query::<InterfaceName>().is_none() {
+                        url.add_query_param(InterfaceName::new(interface.to_string()));
+                    }
+                    Ok(url)
+                })
+                .collect::<Result<_, String>>()?;
+            ClientBuilder::new().with_hosts(hosts)
+        }
+        (None, [registry_id]) => {
+            let registry = root_config
+                .registries
+                .get(registry_id)
+                .ok_or_else(|| format!("registry {} is not configured", registry_id))?;
+            let url = format!("{}://{}", registry.protocol, registry.address);
+            let url = url
+                .parse()
+                .map_err(|err| format!("invalid registry url {}: {}", url, err))?;
+            ClientBuilder::new().with_registry(url)
+        }
+        (None, []) => return Err("neither a url nor a registry is configured".to_string()),
+        (None, _) => return Err("a reference is discovered by one registry".to_string()),
+    };
+
+    match reference.cluster.as_deref() {
+        None | Some("failover") => {
+            if let Some(retries) = reference.retries {
+                builder = builder.with_retries(retries);
+            }
+        }
+        Some("failfast") => builder = builder.with_retries(0),
+        Some(cluster) => return Err(format!("unknown cluster {}", cluster)),
+    }
+    if let Some(loadbalance) = &reference.loadbalance {
+        if !LOADBALANCES.contains(&loadbalance.as_str()) {
+            return Err(format!("unknown loadbalance {}", loadbalance));
+        }
+        builder = builder.with_loadbalance(loadbalance);
+    }
+    if let Some(timeout) = reference.timeout {
+        builder = builder.with_timeout(timeout);
+    }
+    if let Some(group) = &reference.group {
+        builder = builder.with_group(group);
+    }
+    if let Some(version) = &reference.version {
+        builder = builder.with_version(version);
+    }
+    if let Some(serialization) = &reference.serialization {
+        builder = builder.with_serialization(serialization);
+    }
+    Ok(builder)
+}
+
+/// A service served by a started `Dubbo`.
+#[derive(Clone, Debug)]
+pub struct ExportedService {
+    /// The url of the service as registered.
+    pub url: Url,
+    pub local_addr: Option<SocketAddr>,
+}
+
+/// A started `Dubbo`, serving its services in the background.
+#[derive(Debug)]
+pub struct DubboHandle {
+    shutdown: ShutdownHandle,
+    services: Vec<ExportedService>,
+    task: JoinHandle<()>,
+}
+
+impl DubboHandle {
+    /// Starts shutting down, `wait` tells when it completed.
+    pub fn shutdown(&self) {
+        self.shutdown.shutdown()
+    }
+
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
+    pub fn services(&self) -> &[ExportedService] {
+        &self.services
+    }
+
+    /// The addresses the services are served on.
+    pub fn local_addrs(&self) -> Vec<SocketAddr> {
+        let mut addrs: Vec<_> = self
+            .services
+            .iter()
+            .filter_map(|service| service.local_addr)
+            .collect();
+        addrs.sort();
+        addrs.dedup();
+        addrs
+    }
+
+    /// Completes once every service stopped being served, after a shutdown.
+    pub async fn wait(self) {
+        if let Err(err) = self.task.await {
+            warn!("shutdown failed: {:?}", err);
+        }
+    }
+}
+
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
             }
+            Err(err) => {
+                warn!("failed to listen for SIGTERM: {:?}", err);
+                future::pending::<()>().await
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = future::pending::<()>();
+
+    let interrupt = async {
+        if let Err(err) = tokio::signal::ctrl_c().await {
+            warn!("failed to listen for SIGINT: {:?}", err);
+            future::pending::<()>().await
         }
+    };
+
+    tokio::select! {
+        _ = interrupt => {}
+        _ = terminate => {}
     }
 }
@@ -15,9 +15,12 @@
  * limitations under the License.
  */
 
-use std::{collections::HashMap, fmt::Debug, str::FromStr};
+use std::{collections::HashMap, fmt::Debug, net::SocketAddr, str::FromStr};
 
 use futures_core::Stream;
+use tokio_util::sync::CancellationToken;
+
+use crate::triple::server::tls::{PeerAddr, PeerIdentity};
 
 pub struct Request<T> {
     pub message: T,
@@ -46,8 +49,12 @@ impl<T> Request<T> {
 
     pub fn from_http(req: http::Request<T>) -> Self {
         let (parts, body) = req.into_parts();
+        let peer_identity = parts.extensions.get::<PeerIdentity>().cloned();
+        let remote_addr = parts.extensions.get::<PeerAddr>().map(|addr| addr.0);
         Request {
-            metadata: Metadata::from_headers(parts.headers),
+            metadata: Metadata::from_headers(parts.headers)
+                .with_peer_identity(peer_identity)
+                .with_remote_addr(remote_addr),
             message: body,
         }
     }
@@ -170,12 +177,18 @@ where
 #[derive(Debug, Clone, Default)]
 pub struct Metadata {
     inner: HashMap<String, String>,
+    cancellation: CancellationToken,
+    peer_identity: Option<PeerIdentity>,
+    remote_addr: Option<SocketAddr>,
 }
 
 impl Metadata {
     pub fn new() -> Self {
         Metadata {
             inner: HashMap::new(),
+            cancellation: CancellationToken::new(),
+            peer_identity: None,
+            remote_addr: None,
         }
     }
 
@@ -184,6 +197,49 @@ impl Metadata {
         self
     }
 
+    pub fn get(&self, key: &str) -> Option<&String> {
+        self.inner.get(key)
+    }
+
+    /// Returns the token that is cancelled once the peer abandons the call,
+    /// so that handlers can stop producing messages nobody will read.
+    pub fn cancellation(&self) -> CancellationToken {
+        self.cancellation.clone()
+    }
+
+    pub(crate) fn with_cancellation(self, cancellation: CancellationToken) -> Self {
+        Metadata {
+            cancellation,
+            ..self
+        }
+    }
+
+    /// Returns the certificate the client authenticated with, if the call came
+    /// in over TLS with a client certificate.
+    pub fn peer_identity(&self) -> Option<&PeerIdentity> {
+        self.peer_identity.as_ref()
+    }
+
+    pub(crate) fn with_peer_identity(self, peer_identity: Option<PeerIdentity>) -> Self {
+        Metadata {
+            peer_identity,
+            ..self
+        }
+    }
+
+    /// Returns the address of the client on the server side, the one behind
+    /// the load balancer for servers reading the PROXY protocol.
+    pub fn remote_addr(&self) -> Option<SocketAddr> {
+        self.remote_addr
+    }
+
+    pub(crate) fn with_remote_addr(self, remote_addr: Option<SocketAddr>) -> Self {
+        Metadata {
+            remote_addr,
+            ..self
+        }
+    }
+
     pub fn from_headers(headers: http::HeaderMap) -> Self {
         let mut h: HashMap<String, String> = HashMap::new();
         for (k, v) in headers.into_iter() {
@@ -192,7 +248,12 @@ impl Metadata {
             }
         }
 
-        Metadata { inner: h }
+        Metadata {
+            inner: h,
+            cancellation: CancellationToken::new(),
+            peer_identity: None,
+            remote_addr: None,
+        }
     }
 
     pub fn into_headers(&self) -> http::HeaderMap {
@@ -208,10 +269,7 @@ impl Metadata {
     }
 }
 
-pub trait Invocation {
-    fn get_target_service_unique_name(&self) -> String;
-    fn get_method_name(&self) -> String;
-}
+pub use protocol_base::invocation::Invocation;
 
 #[derive(Default, Clone, Debug)]
 pub struct RpcInvocation {
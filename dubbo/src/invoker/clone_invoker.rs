@@ -14,9 +14,18 @@
  * See the License for the specific language governing permissions and
  * limitations under the License.
  */
-use std::{mem, pin::Pin, task::Poll};
+use std::{
+    mem,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    task::Poll,
+};
 
-use crate::{logger::tracing::debug, StdError};
+use crate::{logger::tracing::debug, StdError, Url};
+use dubbo_base::Node;
 use futures_core::{future::BoxFuture, ready, Future, TryFuture};
 use futures_util::FutureExt;
 use pin_project::pin_project;
@@ -176,17 +185,20 @@ where
     rx: Receiver<ObserveState>,
     poll: ReusableBoxFuture<'static, ObserveState>,
     polling: bool,
+    url: Arc<Url>,
+    goaway: Option<Arc<AtomicBool>>,
 }
 
 impl<Inv> CloneInvoker<Inv>
 where
-    Inv: Service<http::Request<CloneBody>> + Send + 'static,
+    Inv: Service<http::Request<CloneBody>> + Node + Send + 'static,
     Inv::Error: Into<StdError> + Send + Sync + 'static,
     Inv::Future: Send,
 {
     const MAX_INVOKER_BUFFER_SIZE: usize = 16;
 
     pub fn new(invoker: Inv) -> Self {
+        let url = invoker.get_url();
         let (ready_service, rx) = ReadyService::new(invoker);
 
         let buffer: Buffer<ReadyService<Inv>, http::Request<CloneBody>> =
@@ -197,10 +209,42 @@ where
             rx,
             polling: false,
             poll: ReusableBoxFuture::new(futures::future::pending()),
+            url,
+            goaway: None,
         }
     }
 }
 
+impl<Inv> CloneInvoker<Inv>
+where
+    Inv: Service<http::Request<CloneBody>> + Send + 'static,
+    Inv::Error: Into<StdError> + Send + Sync + 'static,
+    Inv::Future: Send,
+{
+    /// The url of the provider behind the invoker.
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// Makes the invoker unavailable while `goaway` is set, i.e. while its
+    /// provider is shutting down.
+    pub fn with_goaway(self, goaway: Arc<AtomicBool>) -> Self {
+        Self {
+            goaway: Some(goaway),
+            ..self
+        }
+    }
+
+    /// Whether new calls should go to the provider, load balancers pick
+    /// the available invokers first.
+    pub fn is_available(&self) -> bool {
+        !self
+            .goaway
+            .as_ref()
+            .is_some_and(|goaway| goaway.load(Ordering::SeqCst))
+    }
+}
+
 impl<Inv> Service<http::Request<CloneBody>> for CloneInvoker<Inv>
 where
     Inv: Service<http::Request<CloneBody>> + Send + 'static,
@@ -265,6 +309,8 @@ where
             rx: self.rx.clone(),
             polling: false,
             poll: ReusableBoxFuture::new(futures::future::pending()),
+            url: self.url.clone(),
+            goaway: self.goaway.clone(),
         }
     }
 }
@@ -14,20 +14,58 @@
  * See the License for the specific language governing permissions and
  * limitations under the License.
  */
-use crate::{codegen::TripleInvoker, invoker::clone_invoker::CloneInvoker, svc::NewService};
+use crate::{
+    invoker::clone_invoker::CloneInvoker,
+    protocol::{
+        dubbo2::dubbo2_invoker::Dubbo2Invoker, triple::triple_invoker::new_triple_invoker,
+        BoxInvoker,
+    },
+    svc::NewService,
+    triple::client::tls::ClientTls,
+    Url,
+};
 
 pub mod clone_body;
 pub mod clone_invoker;
 
-pub struct NewInvoker;
+#[derive(Clone, Default)]
+pub struct NewInvoker {
+    tls: Option<ClientTls>,
+    connections: Option<usize>,
+}
+
+impl NewInvoker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Connects every invoker over TLS with `tls`.
+    pub fn with_tls(self, tls: Option<ClientTls>) -> Self {
+        Self { tls, ..self }
+    }
+
+    /// Keeps `connections` connections open to every Triple provider whose
+    /// url has no `connections` param.
+    pub fn with_connections(self, connections: Option<usize>) -> Self {
+        Self {
+            connections,
+            ..self
+        }
+    }
+}
 
 impl NewService<String> for NewInvoker {
-    type Service = CloneInvoker<TripleInvoker>;
+    type Service = CloneInvoker<BoxInvoker>;
 
     fn new_service(&self, url: String) -> Self::Service {
-        // todo create another invoker by url protocol
-
-        let url = url.parse().unwrap();
-        CloneInvoker::new(TripleInvoker::new(url))
+        let url: Url = url.parse().unwrap();
+        match url.protocol() {
+            "dubbo" => CloneInvoker::new(BoxInvoker::new(Dubbo2Invoker::new(url))),
+            _ => {
+                let invoker = new_triple_invoker(url, self.tls.clone(), self.connections);
+                let goaway = invoker.conn().goaway();
+                CloneInvoker::new(BoxInvoker::new(invoker)).with_goaway(goaway)
+            }
+        }
     }
 }
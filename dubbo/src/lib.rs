@@ -35,14 +35,17 @@ pub mod route;
 pub mod status;
 pub mod svc;
 pub mod triple;
-pub mod url;
 pub mod utils;
 
 use http_body::Body;
 use std::{env, future::Future, path::PathBuf, pin::Pin};
 
+pub use dubbo_base::url;
+
 pub use crate::url::Url;
-pub use framework::Dubbo;
+pub use framework::{
+    Dubbo, DubboHandle, ExportedService, ReferenceError, ShutdownHandle, StartError,
+};
 
 pub type BoxFuture<T, E> = self::Pin<Box<dyn self::Future<Output = Result<T, E>> + Send + 'static>>;
 pub(crate) type Error = Box<dyn std::error::Error + Send + Sync>;
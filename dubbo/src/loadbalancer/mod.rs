@@ -18,7 +18,7 @@
 pub mod random;
 
 use futures_core::future::BoxFuture;
-use std::error::Error;
+use std::{error::Error, sync::Arc};
 use tokio::time::Duration;
 use tower::{discover::ServiceList, ServiceExt};
 use tower_service::Service;
@@ -30,25 +30,41 @@ use crate::{
     invoker::{clone_body::CloneBody, clone_invoker::CloneInvoker},
     loadbalancer::random::RandomLoadBalancer,
     param::Param,
-    protocol::triple::triple_invoker::TripleInvoker,
+    protocol::BoxInvoker,
     svc::NewService,
     StdError,
 };
 
+/// The load balancer used unless configured otherwise.
+pub const DEFAULT_LOADBALANCE: &str = "p2c";
+
+/// The names of the load balancers.
+pub const LOADBALANCES: [&str; 2] = ["p2c", "random"];
+
 pub struct NewLoadBalancer<N> {
     inner: N,
+    loadbalance: Arc<str>,
 }
 
 #[derive(Clone)]
 pub struct LoadBalancerSvc<S> {
     inner: S, // Routes service
+    loadbalance: Arc<str>,
 }
 
 impl<N> NewLoadBalancer<N> {
     pub fn layer() -> impl tower_layer::Layer<N, Service = Self> {
-        tower_layer::layer_fn(|inner| {
+        Self::layer_with_loadbalance(DEFAULT_LOADBALANCE)
+    }
+
+    /// Balances the calls with the load balancer named `loadbalance`, see
+    /// `LOADBALANCES`.
+    pub fn layer_with_loadbalance(loadbalance: &str) -> impl tower_layer::Layer<N, Service = Self> {
+        let loadbalance: Arc<str> = loadbalance.into();
+        tower_layer::layer_fn(move |inner| {
             NewLoadBalancer {
                 inner, // NewRoutes
+                loadbalance: loadbalance.clone(),
             }
         })
     }
@@ -66,18 +82,21 @@ where
         // Routes service
         let svc = self.inner.new_service(target);
 
-        LoadBalancerSvc { inner: svc }
+        LoadBalancerSvc {
+            inner: svc,
+            loadbalance: self.loadbalance.clone(),
+        }
     }
 }
 
 impl<N> Service<http::Request<CloneBody>> for LoadBalancerSvc<N>
 where
     // Routes service
-    N: Service<(), Response = Vec<CloneInvoker<TripleInvoker>>> + Clone,
+    N: Service<(), Response = Vec<CloneInvoker<BoxInvoker>>> + Clone,
     N::Error: Into<StdError> + Send,
     N::Future: Send + 'static,
 {
-    type Response = <CloneInvoker<TripleInvoker> as Service<http::Request<CloneBody>>>::Response;
+    type Response = http::Response<crate::BoxBody>;
 
     type Error = StdError;
 
@@ -92,14 +111,25 @@ where
 
     fn call(&mut self, req: http::Request<CloneBody>) -> Self::Future {
         let routes = self.inner.call(());
+        let loadbalance = self.loadbalance.clone();
 
         let fut = async move {
             let routes = routes.await;
 
-            let routes: Vec<CloneInvoker<TripleInvoker>> = match routes {
+            let routes: Vec<CloneInvoker<BoxInvoker>> = match routes {
                 Err(e) => return Err(Into::<StdError>::into(e)),
                 Ok(routes) => routes,
             };
+            // providers shutting down only get calls when all are
+            let available: Vec<_> = routes
+                .iter()
+                .filter(|invoker| invoker.is_available())
+                .cloned()
+                .collect();
+            let routes = match available.is_empty() {
+                true => routes,
+                false => available,
+            };
 
             // let service_list: Vec<_> = routes
             //     .into_iter()
@@ -114,7 +144,7 @@ where
 
             // let p2c = tower::balance::p2c::Balance::new(service_list);
             // let p: Box<dyn LoadBalancer<Invoker = BoxService<http::Request<CloneBody>, http::Response<UnsyncBoxBody<bytes::Bytes, status::Status>>, Box<dyn std::error::Error + std::marker::Send + std::marker::Sync>>> + std::marker::Send + std::marker::Sync> = get_loadbalancer("p2c").into();
-            let p = get_loadbalancer("p2c");
+            let p = get_loadbalancer(&loadbalance);
             // let ivk = p.select_invokers(invokers, metadata);
             let ivk = p.select_invokers(routes, metadata);
 
@@ -136,7 +166,7 @@ pub trait LoadBalancer {
 
     fn select_invokers(
         &self,
-        invokers: Vec<CloneInvoker<TripleInvoker>>,
+        invokers: Vec<CloneInvoker<BoxInvoker>>,
         metadata: Metadata,
     ) -> Self::Invoker;
 }
@@ -145,10 +175,7 @@ fn get_loadbalancer(
     loadbalancer: &str,
 ) -> Box<dyn LoadBalancer<Invoker = DubboBoxService> + Send + Sync + 'static> {
     match loadbalancer {
-        "random" => {
-            println!("random!");
-            Box::new(RandomLoadBalancer::default())
-        }
+        "random" => Box::new(RandomLoadBalancer::default()),
         "p2c" => Box::new(P2cBalancer::default()),
         _ => Box::new(P2cBalancer::default()),
     }
@@ -162,7 +189,7 @@ impl LoadBalancer for P2cBalancer {
 
     fn select_invokers(
         &self,
-        invokers: Vec<CloneInvoker<TripleInvoker>>,
+        invokers: Vec<CloneInvoker<BoxInvoker>>,
         _metadata: Metadata,
     ) -> Self::Invoker {
         debug!("p2c load balancer");
@@ -19,10 +19,7 @@ use rand::prelude::SliceRandom;
 use tracing::debug;
 
 use super::{DubboBoxService, LoadBalancer};
-use crate::{
-    invocation::Metadata, loadbalancer::CloneInvoker,
-    protocol::triple::triple_invoker::TripleInvoker,
-};
+use crate::{invocation::Metadata, loadbalancer::CloneInvoker, protocol::BoxInvoker};
 
 #[derive(Clone, Default)]
 pub struct RandomLoadBalancer {}
@@ -32,7 +29,7 @@ impl LoadBalancer for RandomLoadBalancer {
 
     fn select_invokers(
         &self,
-        invokers: Vec<CloneInvoker<TripleInvoker>>,
+        invokers: Vec<CloneInvoker<BoxInvoker>>,
         metadata: Metadata,
     ) -> Self::Invoker {
         debug!("random loadbalance {:?}", metadata);
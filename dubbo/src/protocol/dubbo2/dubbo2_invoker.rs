@@ -0,0 +1,422 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Invokes Dubbo2 providers with the requests of the Triple client, so they
+//! can be mixed with Triple providers behind one cluster.
+//!
+//! Only unary calls of the `hessian2` serialization are supported: the
+//! request message becomes the single argument of the invocation, typed by
+//! its Java class, and the return value becomes the response message.
+
+use std::{
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use bytes::{Buf, BufMut};
+use dubbo_base::Node;
+use futures_util::{future, stream, TryStreamExt};
+use http::HeaderValue;
+use protocol_base::invoker::BaseInvoker;
+use protocol_dubbo2::{hessian2::Value, ResponseData, RpcInvocation};
+use remoting_base::{
+    codec::{dubbo2::Dubbo2Codec, BoxedCodec},
+    error::ClientError,
+    exchange::{client::Client, status, ClientConfig, ExchangeClient},
+    Request,
+};
+use remoting_net::Address;
+use tokio::sync::OnceCell;
+use tower_service::Service;
+
+use crate::{
+    invoker::clone_body::CloneBody,
+    status::{Code, Status},
+    triple::{
+        codec::{registry, DecodeBuf, Decoder, EncodeBuf, Encoder},
+        compression::{CompressionEncoding, COMPRESSIONS},
+        decode::Decoding,
+        encode::encode_server,
+        server::triple::GRPC_ENCODING,
+    },
+    Url,
+};
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(3);
+
+const DEFAULT_PORT: u16 = 20880;
+
+// headers of the Triple client that are not attachments
+const RESERVED_HEADERS: [&str; 7] = [
+    "path",
+    "method",
+    "scheme",
+    "authority",
+    "content-type",
+    "te",
+    "user-agent",
+];
+
+pub struct Dubbo2Invoker {
+    base: BaseInvoker,
+    // host and port of the provider, resolved by the first call
+    host: String,
+    port: u16,
+    client: Arc<OnceCell<ExchangeClient>>,
+    timeout: Duration,
+}
+
+impl Dubbo2Invoker {
+    /// Creates the invoker of the provider at `url`, e.g.
+    /// `dubbo://127.0.0.1:20880/org.apache.dubbo.Greeter?timeout=1000`.
+    /// The address is resolved and the connection is made by the first call.
+    pub fn new(url: Url) -> Self {
+        let timeout = url
+            .query_param_by_key("timeout")
+            .and_then(|timeout| timeout.parse().ok())
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_TIMEOUT);
+
+        Self {
+            host: url.host().unwrap_or_default().to_string(),
+            port: url.port().unwrap_or(DEFAULT_PORT),
+            base: BaseInvoker::new(url),
+            client: Arc::new(OnceCell::new()),
+            timeout,
+        }
+    }
+}
+
+/// Resolves `host` and creates the client of the first address. A failed
+/// resolution is tried again by the next call.
+async fn connect(host: &str, port: u16) -> Result<ExchangeClient, Status> {
+    let addr = tokio::net::lookup_host((host, port))
+        .await
+        .ok()
+        .and_then(|mut addrs| addrs.next())
+        .ok_or_else(|| {
+            Status::new(
+                Code::Unavailable,
+                format!("can not resolve the address of {}:{}", host, port),
+            )
+        })?;
+    Ok(ExchangeClient::new(
+        Address::Ip(addr),
+        BoxedCodec::new(Arc::new(Dubbo2Codec::new())),
+        ClientConfig::default(),
+    ))
+}
+
+impl Service<http::Request<CloneBody>> for Dubbo2Invoker {
+    type Response = http::Response<crate::BoxBody>;
+
+    type Error = crate::Error;
+
+    type Future = crate::BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<CloneBody>) -> Self::Future {
+        let client = self.client.clone();
+        let (host, port) = (self.host.clone(), self.port);
+        let version = self.base.url().query_param_by_key("version");
+        let timeout = self.timeout;
+
+        Box::pin(async move {
+            let resp = match client.get_or_try_init(|| connect(&host, port)).await {
+                Ok(client) => invoke(client, req, version, timeout).await,
+                Err(status) => Err(status),
+            };
+            Ok(resp.unwrap_or_else(|status| status.to_http()))
+        })
+    }
+}
+
+impl Node for Dubbo2Invoker {
+    fn get_url(&self) -> Arc<Url> {
+        self.base.get_url()
+    }
+
+    fn is_available(&self) -> bool {
+        self.base.is_available()
+    }
+
+    fn destroy(&self) {
+        self.base.destroy();
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            let client = self.client.clone();
+            handle.spawn(async move {
+                if let Some(client) = client.get() {
+                    let _ = client.close().await;
+                }
+            });
+        }
+    }
+
+    fn is_destroyed(&self) -> bool {
+        self.base.is_destroyed()
+    }
+}
+
+async fn invoke(
+    client: &ExchangeClient,
+    req: http::Request<CloneBody>,
+    version: Option<String>,
+    timeout: Duration,
+) -> Result<http::Response<crate::BoxBody>, Status> {
+    let (parts, body) = req.into_parts();
+
+    let content_type = parts
+        .headers
+        .get(http::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or(registry::GRPC_CONTENT_TYPE)
+        .to_string();
+    if registry::serialization_name(&content_type) != registry::HESSIAN2_SERIALIZATION {
+        return Err(Status::new(
+            Code::Unimplemented,
+            format!(
+                "content-type: {} not support by dubbo2 providers, use {}",
+                content_type,
+                registry::content_type(registry::HESSIAN2_SERIALIZATION)
+            ),
+        ));
+    }
+
+    let path = parts
+        .headers
+        .get("path")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    let Some((service, method)) = path.trim_start_matches('/').rsplit_once('/') else {
+        return Err(Status::new(
+            Code::InvalidArgument,
+            format!("invalid path: {}", path),
+        ));
+    };
+
+    // the Triple client compresses messages without telling, the Triple
+    // invoker adds the `grpc-encoding` header
+    let compression = match parts.headers.get(GRPC_ENCODING) {
+        Some(encoding) => encoding
+            .to_str()
+            .ok()
+            .and_then(|encoding| COMPRESSIONS.get(encoding).cloned().flatten()),
+        None => Some(CompressionEncoding::Gzip),
+    };
+    let argument = request_message(body, compression).await?;
+
+    let mut invocation = RpcInvocation::new(service, method);
+    if let Some(version) = version {
+        invocation = invocation.with_version(&version);
+    }
+    let mut invocation = invocation.with_argument(&java_type(&argument), argument);
+    for (key, value) in parts.headers.iter() {
+        let key = key.as_str();
+        if RESERVED_HEADERS.contains(&key) || key.starts_with("grpc-") || key.starts_with("tri-") {
+            continue;
+        }
+        if let Ok(value) = value.to_str() {
+            invocation = invocation.with_attachment(key, Value::from(value));
+        }
+    }
+
+    let response = client
+        .request(Request::new(invocation), timeout)
+        .await
+        .map_err(client_error)?;
+    if !response.is_ok() {
+        return Err(Status::new(
+            status_code(response.status),
+            response.error.clone().unwrap_or_default(),
+        ));
+    }
+    let value = match response.body::<ResponseData>() {
+        Some(ResponseData::Value { value, .. }) => value.clone(),
+        Some(ResponseData::Exception { exception, .. }) => {
+            return Err(Status::new(Code::Unknown, exception_message(exception)))
+        }
+        _ => {
+            return Err(Status::new(
+                Code::Internal,
+                "unexpected dubbo2 response".to_string(),
+            ))
+        }
+    };
+
+    let body = encode_server(
+        Box::new(ValueEncoder),
+        stream::once(future::ready(Ok(value))),
+        None,
+        true,
+    );
+    let mut resp = http::Response::new(crate::BoxBody::new(body));
+    resp.headers_mut().insert(
+        http::header::CONTENT_TYPE,
+        HeaderValue::from_str(&content_type).unwrap(),
+    );
+    Ok(resp)
+}
+
+/// Reads the only message of a unary request.
+async fn request_message(
+    body: CloneBody,
+    compression: Option<CompressionEncoding>,
+) -> Result<Value, Status> {
+    let mut messages = Decoding::new(body, Box::new(ValueDecoder), compression, true);
+    let message = messages
+        .try_next()
+        .await?
+        .ok_or_else(|| Status::new(Code::InvalidArgument, "missing request message".to_string()))?;
+    if messages.try_next().await?.is_some() {
+        return Err(Status::new(
+            Code::Unimplemented,
+            "streaming calls are not supported by dubbo2 providers".to_string(),
+        ));
+    }
+    Ok(message)
+}
+
+/// The Java type an argument is declared with.
+fn java_type(value: &Value) -> String {
+    let typ = match value {
+        Value::Object { class, .. } => return class.clone(),
+        Value::Map { typ: Some(typ), .. } => return typ.clone(),
+        Value::Bool(_) => "boolean",
+        Value::Int(_) => "int",
+        Value::Long(_) => "long",
+        Value::Double(_) => "double",
+        Value::Date(_) => "java.util.Date",
+        Value::String(_) => "java.lang.String",
+        Value::Binary(_) => "byte[]",
+        Value::List { .. } => "java.util.List",
+        Value::Map { .. } => "java.util.Map",
+        Value::Null | Value::Ref(_) => "java.lang.Object",
+    };
+    typ.to_string()
+}
+
+fn exception_message(exception: &Value) -> String {
+    match exception {
+        Value::Object { class, fields } => {
+            let message = fields.iter().find_map(|(name, value)| match value {
+                Value::String(message) if name == "detailMessage" => Some(message),
+                _ => None,
+            });
+            match message {
+                Some(message) => format!("{}: {}", class, message),
+                None => class.clone(),
+            }
+        }
+        exception => format!("{:?}", exception),
+    }
+}
+
+fn client_error(err: ClientError) -> Status {
+    let code = match err {
+        ClientError::Connect(_) | ClientError::Closed => Code::Unavailable,
+        ClientError::Timeout(..) => Code::DeadlineExceeded,
+        ClientError::Codec(_) => Code::Internal,
+        ClientError::Unknown => Code::Unknown,
+    };
+    Status::new(code, err.to_string())
+}
+
+fn status_code(status: u8) -> Code {
+    match status {
+        status::CLIENT_TIMEOUT | status::SERVER_TIMEOUT => Code::DeadlineExceeded,
+        status::BAD_REQUEST => Code::InvalidArgument,
+        status::SERVICE_NOT_FOUND => Code::Unimplemented,
+        status::SERVICE_ERROR => Code::Unknown,
+        _ => Code::Internal,
+    }
+}
+
+struct ValueEncoder;
+
+impl Encoder for ValueEncoder {
+    type Item = Value;
+
+    type Error = Status;
+
+    fn encode(&mut self, item: Self::Item, dst: &mut EncodeBuf<'_>) -> Result<(), Self::Error> {
+        let mut encoder = protocol_dubbo2::hessian2::Encoder::new();
+        encoder.write(&item);
+        dst.put(encoder.into_bytes());
+        Ok(())
+    }
+}
+
+struct ValueDecoder;
+
+impl Decoder for ValueDecoder {
+    type Item = Value;
+
+    type Error = Status;
+
+    fn decode(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<Self::Item>, Self::Error> {
+        let bytes = src.copy_to_bytes(src.remaining());
+        let value = protocol_dubbo2::hessian2::Decoder::new(&bytes)
+            .read()
+            .map_err(|err| Status::new(Code::Internal, err.to_string()))?;
+        Ok(Some(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_java_type() {
+        let user = Value::object("org.apache.dubbo.User", vec![("name", "dubbo".into())]);
+        assert_eq!(java_type(&user), "org.apache.dubbo.User");
+        assert_eq!(java_type(&Value::from("dubbo")), "java.lang.String");
+        assert_eq!(java_type(&Value::from(1)), "int");
+        assert_eq!(java_type(&Value::list(vec![])), "java.util.List");
+    }
+
+    #[tokio::test]
+    async fn test_unresolved_host() {
+        let url: Url = "dubbo://dubbo.invalid:20880/org.apache.dubbo.Greeter"
+            .parse()
+            .unwrap();
+        let invoker = Dubbo2Invoker::new(url);
+        assert_eq!(invoker.host, "dubbo.invalid");
+        assert!(invoker.client.get().is_none());
+
+        match connect(&invoker.host, invoker.port).await {
+            Err(status) => assert_eq!(status.code(), Code::Unavailable),
+            Ok(_) => panic!("dubbo.invalid resolved"),
+        }
+    }
+
+    #[test]
+    fn test_exception_message() {
+        let exception = Value::object(
+            "java.lang.IllegalStateException",
+            vec![("detailMessage", "closed".into()), ("cause", Value::Ref(0))],
+        );
+        assert_eq!(
+            exception_message(&exception),
+            "java.lang.IllegalStateException: closed"
+        );
+    }
+}
@@ -15,63 +15,153 @@
  * limitations under the License.
  */
 
-use std::task::{Context, Poll};
+use std::{
+    net::SocketAddr,
+    sync::Arc,
+    task::{Context, Poll},
+};
 
 use async_trait::async_trait;
-use aws_smithy_http::body::SdkBody;
+use dubbo_base::Node;
 use tower_service::Service;
 
-use crate::Url;
+use crate::{invoker::clone_body::CloneBody, Url};
 
+pub mod dubbo2;
 pub mod server_desc;
 pub mod triple;
 
+pub use protocol_base::invoker::Invoker;
+
 #[async_trait]
 pub trait Protocol {
     type Invoker;
 
     fn destroy(&self);
-    async fn export(self, url: Url) -> BoxExporter;
-    async fn refer(self, url: Url) -> Self::Invoker;
+    /// Serves the service of `url` in the background, until unexported.
+    async fn export(self, url: Url) -> Result<BoxExporter, crate::Error>;
+    /// Creates the invoker calling the service of the consumer `url`.
+    async fn refer(self, url: Url) -> Result<Self::Invoker, crate::Error>;
 }
 
 pub trait Exporter {
     fn unexport(&self);
-}
 
-pub trait Invoker<ReqBody>: Service<ReqBody> {
-    fn get_url(&self) -> Url;
+    /// The address the service is served on.
+    fn local_addr(&self) -> Option<SocketAddr> {
+        None
+    }
+
+    /// Completes once the service is not served anymore.
+    fn closed(&self) -> futures_util::future::BoxFuture<'static, ()> {
+        Box::pin(futures_util::future::ready(()))
+    }
 }
 
 pub type BoxExporter = Box<dyn Exporter + Send + Sync>;
-pub type BoxInvoker = Box<
-    dyn Invoker<
-            http::Request<SdkBody>,
-            Response = http::Response<crate::BoxBody>,
-            Error = crate::Error,
-            Future = crate::BoxFuture<http::Response<crate::BoxBody>, crate::Error>,
-        > + Send
-        + Sync,
->;
-
-pub struct WrapperInvoker<T>(T);
-
-impl<T, ReqBody> Service<http::Request<ReqBody>> for WrapperInvoker<T>
+
+type DynInvoker = dyn Invoker<
+        http::Request<CloneBody>,
+        Response = http::Response<crate::BoxBody>,
+        Error = crate::Error,
+        Future = crate::BoxFuture<http::Response<crate::BoxBody>, crate::Error>,
+    > + Send
+    + Sync;
+
+/// An invoker of any protocol, as handed to the cluster and load balancer.
+pub struct BoxInvoker {
+    inner: Box<DynInvoker>,
+}
+
+impl BoxInvoker {
+    pub fn new<I>(invoker: I) -> Self
+    where
+        I: Invoker<
+                http::Request<CloneBody>,
+                Response = http::Response<crate::BoxBody>,
+                Error = crate::Error,
+            > + Send
+            + Sync
+            + 'static,
+        I::Future: Send + 'static,
+    {
+        Self {
+            inner: Box::new(BoxFuture(invoker)),
+        }
+    }
+}
+
+/// Boxes the responses of the invoker `I`.
+struct BoxFuture<I>(I);
+
+impl<I> Service<http::Request<CloneBody>> for BoxFuture<I>
 where
-    T: Invoker<http::Request<ReqBody>, Response = http::Response<crate::BoxBody>>,
-    T::Error: Into<crate::Error>,
+    I: Service<http::Request<CloneBody>>,
+    I::Future: Send + 'static,
 {
-    type Response = T::Response;
+    type Response = I::Response;
+
+    type Error = I::Error;
+
+    type Future = futures_util::future::BoxFuture<'static, Result<I::Response, I::Error>>;
 
-    type Error = T::Error;
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.0.poll_ready(cx)
+    }
 
-    type Future = T::Future;
+    fn call(&mut self, req: http::Request<CloneBody>) -> Self::Future {
+        Box::pin(self.0.call(req))
+    }
+}
+
+impl<I: Node> Node for BoxFuture<I> {
+    fn get_url(&self) -> Arc<Url> {
+        self.0.get_url()
+    }
+
+    fn is_available(&self) -> bool {
+        self.0.is_available()
+    }
+
+    fn destroy(&self) {
+        self.0.destroy()
+    }
+
+    fn is_destroyed(&self) -> bool {
+        self.0.is_destroyed()
+    }
+}
+
+impl Service<http::Request<CloneBody>> for BoxInvoker {
+    type Response = http::Response<crate::BoxBody>;
+
+    type Error = crate::Error;
+
+    type Future = crate::BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<CloneBody>) -> Self::Future {
+        self.inner.call(req)
+    }
+}
+
+impl Node for BoxInvoker {
+    fn get_url(&self) -> Arc<Url> {
+        self.inner.get_url()
+    }
+
+    fn is_available(&self) -> bool {
+        self.inner.is_available()
+    }
 
-    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        Poll::Ready(Ok(()))
+    fn destroy(&self) {
+        self.inner.destroy()
     }
 
-    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
-        self.0.call(req)
+    fn is_destroyed(&self) -> bool {
+        self.inner.is_destroyed()
     }
 }
@@ -15,25 +15,51 @@
  * limitations under the License.
  */
 
+use std::net::SocketAddr;
+
+use futures_util::future::BoxFuture;
+use tokio::sync::watch;
+use tokio_util::sync::CancellationToken;
+
 use crate::protocol::Exporter;
 
+/// The exporter of a service served by a Triple server.
 #[derive(Clone)]
-pub struct TripleExporter {}
-
-impl TripleExporter {
-    pub fn new() -> Self {
-        TripleExporter {}
-    }
+pub struct TripleExporter {
+    local_addr: SocketAddr,
+    shutdown: CancellationToken,
+    // set once the server shut down
+    closed: watch::Receiver<bool>,
 }
 
-impl Default for TripleExporter {
-    fn default() -> Self {
-        Self::new()
+impl TripleExporter {
+    pub fn new(
+        local_addr: SocketAddr,
+        shutdown: CancellationToken,
+        closed: watch::Receiver<bool>,
+    ) -> Self {
+        TripleExporter {
+            local_addr,
+            shutdown,
+            closed,
+        }
     }
 }
 
 impl Exporter for TripleExporter {
+    /// Shuts the server down, draining its connections.
     fn unexport(&self) {
-        todo!()
+        self.shutdown.cancel()
+    }
+
+    fn local_addr(&self) -> Option<SocketAddr> {
+        Some(self.local_addr)
+    }
+
+    fn closed(&self) -> BoxFuture<'static, ()> {
+        let mut closed = self.closed.clone();
+        Box::pin(async move {
+            let _ = closed.wait_for(|closed| *closed).await;
+        })
     }
 }
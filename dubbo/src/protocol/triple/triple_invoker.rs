@@ -15,145 +15,38 @@
  * limitations under the License.
  */
 
-use crate::Url;
-use http::{HeaderValue, Uri};
-use std::{
-    fmt::{Debug, Formatter},
-    str::FromStr,
-};
-use tower_service::Service;
+use std::str::FromStr;
 
 use crate::{
-    invoker::clone_body::CloneBody,
-    triple::transport::{self, connection::Connection},
+    triple::{client::tls::ClientTls, transport::connection::Connection},
+    Url,
 };
 
-pub struct TripleInvoker {
-    url: Url,
-    conn: Connection,
-}
-
-impl TripleInvoker {
-    pub fn new(url: Url) -> TripleInvoker {
-        let uri = http::Uri::from_str(url.as_str()).unwrap();
-        Self {
-            url,
-            conn: Connection::new().with_host(uri).build(),
-        }
-    }
-}
-
-impl Debug for TripleInvoker {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        f.write_str(format!("{:?}", self.url).as_str())
-    }
-}
-
-impl TripleInvoker {
-    pub fn map_request(&self, req: http::Request<CloneBody>) -> http::Request<CloneBody> {
-        let (parts, body) = req.into_parts();
-
-        let path_and_query = parts.headers.get("path").unwrap().to_str().unwrap();
-
-        let authority = self.url.authority();
-
-        let uri = Uri::builder()
-            .scheme("http")
-            .authority(authority)
-            .path_and_query(path_and_query)
-            .build()
-            .unwrap();
-
-        let mut req = hyper::Request::builder()
-            .version(http::Version::HTTP_2)
-            .uri(uri.clone())
-            .method("POST")
-            .body(body)
-            .unwrap();
+/// The Triple invoker of `protocol_triple` over a [`Connection`].
+pub type TripleInvoker = protocol_triple::TripleInvoker<Connection>;
 
-        // add header of source
-        for (k, v) in parts.headers.iter() {
-            req.headers_mut().insert(k, v.to_owned());
-        }
-        // *req.version_mut() = http::Version::HTTP_2;
-        req.headers_mut()
-            .insert("method", HeaderValue::from_static("POST"));
-        req.headers_mut().insert(
-            "scheme",
-            HeaderValue::from_str(uri.scheme_str().unwrap()).unwrap(),
-        );
-        req.headers_mut()
-            .insert("path", HeaderValue::from_str(uri.path()).unwrap());
-        req.headers_mut().insert(
-            "authority",
-            HeaderValue::from_str(uri.authority().unwrap().as_str()).unwrap(),
-        );
-        req.headers_mut().insert(
-            "content-type",
-            HeaderValue::from_static("application/grpc+proto"),
-        );
-        req.headers_mut()
-            .insert("user-agent", HeaderValue::from_static("dubbo-rust/0.1.0"));
-        req.headers_mut()
-            .insert("te", HeaderValue::from_static("trailers"));
-        req.headers_mut().insert(
-            "tri-service-version",
-            HeaderValue::from_static("dubbo-rust/0.1.0"),
-        );
-        req.headers_mut()
-            .insert("tri-service-group", HeaderValue::from_static("cluster"));
-        req.headers_mut().insert(
-            "tri-unit-info",
-            HeaderValue::from_static("dubbo-rust/0.1.0"),
-        );
-        // if let Some(_encoding) = self.send_compression_encoding {
-
-        // }
-
-        req.headers_mut()
-            .insert("grpc-encoding", http::HeaderValue::from_static("gzip"));
-
-        req.headers_mut().insert(
-            "grpc-accept-encoding",
-            http::HeaderValue::from_static("gzip"),
-        );
-
-        // // const (
-        // //     TripleContentType    = "application/grpc+proto"
-        // //     TripleUserAgent      = "grpc-go/1.35.0-dev"
-        // //     TripleServiceVersion = "tri-service-version"
-        // //     TripleAttachement    = "tri-attachment"
-        // //     TripleServiceGroup   = "tri-service-group"
-        // //     TripleRequestID      = "tri-req-id"
-        // //     TripleTraceID        = "tri-trace-traceid"
-        // //     TripleTraceRPCID     = "tri-trace-rpcid"
-        // //     TripleTraceProtoBin  = "tri-trace-proto-bin"
-        // //     TripleUnitInfo       = "tri-unit-info"
-        // // )
-        req
-    }
-}
-
-impl Service<http::Request<CloneBody>> for TripleInvoker {
-    type Response = http::Response<crate::BoxBody>;
-
-    type Error = crate::Error;
-
-    type Future = crate::BoxFuture<Self::Response, Self::Error>;
-
-    fn poll_ready(
-        &mut self,
-        cx: &mut std::task::Context<'_>,
-    ) -> std::task::Poll<Result<(), Self::Error>> {
-        <transport::connection::Connection as Service<http::Request<CloneBody>>>::poll_ready(
-            &mut self.conn,
-            cx,
-        )
-    }
-
-    fn call(&mut self, req: http::Request<CloneBody>) -> Self::Future {
-        let req = self.map_request(req);
-
-        self.conn.call(req)
-    }
+/// Creates the invoker of the Triple provider at `url`, connecting over TLS
+/// when `tls` is set.
+///
+/// The `connections` param of `url`, or else `connections`, is the number of
+/// connections kept open to the provider, one when neither is set.
+pub fn new_triple_invoker(
+    url: Url,
+    tls: Option<ClientTls>,
+    connections: Option<usize>,
+) -> TripleInvoker {
+    let uri = http::Uri::from_str(url.as_str()).unwrap();
+    let connections = url
+        .query_param_by_key("connections")
+        .and_then(|connections| connections.parse().ok())
+        .or(connections)
+        .unwrap_or(1);
+    let conn = Connection::new()
+        .with_host(uri)
+        .with_connections(connections);
+    let (conn, is_tls) = match tls {
+        Some(tls) => (conn.with_tls(tls), true),
+        None => (conn, false),
+    };
+    TripleInvoker::new(url, conn.build()).with_tls(is_tls)
 }
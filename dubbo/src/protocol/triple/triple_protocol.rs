@@ -18,19 +18,24 @@
 use std::collections::HashMap;
 
 use super::{
-    triple_exporter::TripleExporter, triple_invoker::TripleInvoker, triple_server::TripleServer,
+    triple_invoker::{new_triple_invoker, TripleInvoker},
+    triple_server::TripleServer,
 };
 use crate::{
     params::registry_param::InterfaceName,
     protocol::{BoxExporter, Protocol},
+    triple::server::health::HealthReporter,
     url::UrlParam,
     Url,
 };
 use async_trait::async_trait;
+use tokio_util::sync::CancellationToken;
 
 #[derive(Clone)]
 pub struct TripleProtocol {
     servers: HashMap<String, TripleServer>,
+    health: Option<HealthReporter>,
+    shutdown: CancellationToken,
 }
 
 impl Default for TripleProtocol {
@@ -43,9 +48,22 @@ impl TripleProtocol {
     pub fn new() -> Self {
         TripleProtocol {
             servers: HashMap::new(),
+            health: None,
+            shutdown: CancellationToken::new(),
         }
     }
 
+    /// Reports the health of the exported services on their servers.
+    pub fn with_health(self, health: Option<HealthReporter>) -> Self {
+        Self { health, ..self }
+    }
+
+    /// Shuts the servers of the exported services down once `shutdown` is
+    /// cancelled, exporting then completes.
+    pub fn with_shutdown(self, shutdown: CancellationToken) -> Self {
+        Self { shutdown, ..self }
+    }
+
     pub fn get_server(&self, url: Url) -> Option<TripleServer> {
         let interface_name = url.query::<InterfaceName>().unwrap();
         self.servers
@@ -62,22 +80,20 @@ impl Protocol for TripleProtocol {
         todo!()
     }
 
-    async fn export(mut self, url: Url) -> BoxExporter {
+    async fn export(mut self, url: Url) -> Result<BoxExporter, crate::Error> {
         // service_key is same to key of TRIPLE_SERVICES
-        let server = TripleServer::new();
+        let server = TripleServer::new()
+            .with_health(self.health.clone())
+            .with_shutdown(self.shutdown.clone());
 
         let interface_name = url.query::<InterfaceName>().unwrap();
         let interface_name = interface_name.value();
 
         self.servers.insert(interface_name, server.clone());
-        server.serve(url).await;
-
-        Box::new(TripleExporter::new())
+        Ok(Box::new(server.start(url).await?))
     }
 
-    async fn refer(self, _url: Url) -> Self::Invoker {
-        todo!()
-        // TripleInvoker::new(url)
-        // Self::Invoker
+    async fn refer(self, url: Url) -> Result<Self::Invoker, crate::Error> {
+        Ok(new_triple_invoker(url, None, None))
     }
 }
@@ -15,22 +15,62 @@
  * limitations under the License.
  */
 
-use crate::{triple::server::builder::ServerBuilder, Url};
+use tokio::sync::watch;
+use tokio_util::sync::CancellationToken;
+
+use super::triple_exporter::TripleExporter;
+use crate::{
+    logger::tracing::error,
+    triple::server::{builder::ServerBuilder, health::HealthReporter},
+    Url,
+};
 
 #[derive(Default, Clone)]
 pub struct TripleServer {
     builder: ServerBuilder,
+    health: Option<HealthReporter>,
+    shutdown: CancellationToken,
 }
 
 impl TripleServer {
     pub fn new() -> TripleServer {
         Self {
             builder: ServerBuilder::new(),
+            health: None,
+            shutdown: CancellationToken::new(),
         }
     }
 
-    pub async fn serve(mut self, url: Url) {
+    /// Reports the health of the services served, see
+    /// `DubboServer::with_health`.
+    pub fn with_health(self, health: Option<HealthReporter>) -> Self {
+        Self { health, ..self }
+    }
+
+    /// Serves until `shutdown` is cancelled, then drains the connections.
+    pub fn with_shutdown(self, shutdown: CancellationToken) -> Self {
+        Self { shutdown, ..self }
+    }
+
+    /// Binds the server of `url`, then serves it in the background until
+    /// shut down or unexported.
+    pub async fn start(mut self, url: Url) -> Result<TripleExporter, crate::Error> {
         self.builder = ServerBuilder::from(url);
-        self.builder.build().serve().await.unwrap()
+        if let Some(health) = self.health {
+            self.builder = self.builder.with_health(health);
+        }
+        let server = self.builder.build().bind().await?;
+        let local_addr = server.local_addr();
+
+        let shutdown = self.shutdown.child_token();
+        let (closed_tx, closed_rx) = watch::channel(false);
+        let signal = shutdown.clone().cancelled_owned();
+        tokio::spawn(async move {
+            if let Err(err) = server.serve_with_graceful(signal).await {
+                error!("server of {} failed: {:?}", local_addr, err);
+            }
+            let _ = closed_tx.send(true);
+        });
+        Ok(TripleExporter::new(local_addr, shutdown, closed_rx))
     }
 }
@@ -16,6 +16,7 @@
  */
 
 use crate::{extension, extension::registry_extension::proxy::RegistryProxy, StdError, Url};
+use futures_util::future;
 use std::{
     future::Future,
     pin::Pin,
@@ -29,12 +30,27 @@ pub mod registry;
 
 #[derive(Clone)]
 pub struct MkRegistryService {
-    registry_url: Url,
+    registry: MkRegistry,
+}
+
+#[derive(Clone)]
+enum MkRegistry {
+    Url(Url),
+    Loaded(RegistryProxy),
 }
 
 impl MkRegistryService {
     pub fn new(registry_url: Url) -> Self {
-        Self { registry_url }
+        Self {
+            registry: MkRegistry::Url(registry_url),
+        }
+    }
+
+    /// Hands out `registry`, already loaded.
+    pub fn from_registry(registry: RegistryProxy) -> Self {
+        Self {
+            registry: MkRegistry::Loaded(registry),
+        }
     }
 }
 
@@ -49,7 +65,9 @@ impl Service<()> for MkRegistryService {
     }
 
     fn call(&mut self, _req: ()) -> Self::Future {
-        let fut = extension::EXTENSIONS.load_registry(self.registry_url.clone());
-        Box::pin(fut)
+        match &self.registry {
+            MkRegistry::Url(url) => Box::pin(extension::EXTENSIONS.load_registry(url.clone())),
+            MkRegistry::Loaded(registry) => Box::pin(future::ready(Ok(registry.clone()))),
+        }
     }
 }
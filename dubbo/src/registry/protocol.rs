@@ -15,18 +15,25 @@
  * limitations under the License.
  */
 
-use crate::{logger::tracing::error, params::registry_param::InterfaceName, url::UrlParam, Url};
+use crate::{
+    cluster::{CallOptions, ClusterInvoker},
+    logger::tracing::error,
+    params::registry_param::{InterfaceName, RegistryUrl},
+    registry::MkRegistryService,
+    triple::client::builder::ClientBuilder,
+    url::UrlParam,
+    Url,
+};
 use std::{
     collections::HashMap,
     sync::{Arc, RwLock},
 };
+use tokio_util::sync::CancellationToken;
 
 use crate::{
     extension::registry_extension::{proxy::RegistryProxy, Registry},
-    protocol::{
-        triple::{triple_exporter::TripleExporter, triple_protocol::TripleProtocol},
-        BoxExporter, BoxInvoker, Protocol,
-    },
+    protocol::{triple::triple_protocol::TripleProtocol, BoxExporter, BoxInvoker, Protocol},
+    triple::server::health::HealthReporter,
 };
 
 #[derive(Clone, Default)]
@@ -38,6 +45,8 @@ pub struct RegistryProtocol {
     exporters: Arc<RwLock<HashMap<String, BoxExporter>>>,
     // serviceName: registryUrls
     services: HashMap<String, Vec<Url>>,
+    health: Option<HealthReporter>,
+    shutdown: CancellationToken,
 }
 
 impl RegistryProtocol {
@@ -46,6 +55,8 @@ impl RegistryProtocol {
             registries: Vec::default(),
             exporters: Arc::new(RwLock::new(HashMap::new())),
             services: HashMap::new(),
+            health: None,
+            shutdown: CancellationToken::new(),
         }
     }
 
@@ -58,6 +69,18 @@ impl RegistryProtocol {
         self.services.extend(services);
         self
     }
+
+    /// See `TripleProtocol::with_health`.
+    pub fn with_health(mut self, health: HealthReporter) -> Self {
+        self.health = Some(health);
+        self
+    }
+
+    /// See `TripleProtocol::with_shutdown`.
+    pub fn with_shutdown(mut self, shutdown: CancellationToken) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
 }
 
 #[async_trait::async_trait]
@@ -68,7 +91,7 @@ impl Protocol for RegistryProtocol {
         todo!()
     }
 
-    async fn export(mut self, url: Url) -> BoxExporter {
+    async fn export(mut self, url: Url) -> Result<BoxExporter, crate::Error> {
         // getProviderUrl
         // getRegisterUrl
         // init Exporter based on provider_url
@@ -86,22 +109,59 @@ impl Protocol for RegistryProtocol {
 
         match url.clone().protocol() {
             "tri" => {
-                let pro = Box::new(TripleProtocol::new());
+                let pro = Box::new(
+                    TripleProtocol::new()
+                        .with_health(self.health.clone())
+                        .with_shutdown(self.shutdown.clone()),
+                );
                 return pro.export(url).await;
             }
-            _ => {
-                error!("base {:?} not implemented", url.protocol());
-                Box::new(TripleExporter::new())
+            protocol => {
+                error!("base {:?} not implemented", protocol);
+                Err(format!("protocol {:?} is not supported", protocol).into())
             }
         }
     }
 
-    async fn refer(self, _url: Url) -> Self::Invoker {
-        // getRegisterUrl
-        // get Registry from registry_url
-        // init directory based on registry_url and Registry
-        // init Cluster based on Directory generates Invoker
-        todo!()
-        //Box::new(TripleInvoker::new(url))
+    async fn refer(self, url: Url) -> Result<Self::Invoker, crate::Error> {
+        let interface = url
+            .query::<InterfaceName>()
+            .ok_or_else(|| format!("consumer url {} has no interface", url))?
+            .value();
+        let mut builder = ClientBuilder::new();
+        if let Some(retries) = url.query_param_by_key("retries") {
+            builder = builder.with_retries(retries.parse()?);
+        }
+        if let Some(loadbalance) = url.query_param_by_key("loadbalance") {
+            builder = builder.with_loadbalance(&loadbalance);
+        }
+        if let Some(timeout) = url.query_param_by_key("timeout") {
+            builder = builder.with_timeout(timeout.parse()?);
+        }
+        if let Some(group) = url.query_param_by_key("group") {
+            builder = builder.with_group(&group);
+        }
+        if let Some(version) = url.query_param_by_key("version") {
+            builder = builder.with_version(&version);
+        }
+        if let Some(serialization) = url.query_param_by_key("serialization") {
+            builder = builder.with_serialization(&serialization);
+        }
+        let options = CallOptions::from_builder(&builder)?;
+
+        // the registry of the url, or else the first of the protocol
+        let mk = match url.query::<RegistryUrl>() {
+            Some(registry) => builder.with_registry(registry.value()).try_build()?,
+            None => {
+                let registry =
+                    self.registries.first().cloned().ok_or_else(|| {
+                        format!("no registry to discover the providers of {}", url)
+                    })?;
+                builder.try_build_with_registry(MkRegistryService::from_registry(registry))?
+            }
+        };
+        Ok(BoxInvoker::new(
+            ClusterInvoker::new(url, interface, mk).with_options(options),
+        ))
     }
 }
@@ -15,20 +15,31 @@
  * limitations under the License.
  */
 
+//! Routes the calls of a client through the routers of their service.
+//!
+//! The routers of a service are held by its directory, which takes them from
+//! the registry it subscribed, such as the xds registry, see
+//! `Registry::routers`. Clients of the same service keep their own routers,
+//! and routing reads no `RootConfig`. The `routers` section of the config belongs to the router
+//! manager of `cluster::router`, which is made from a `RootConfig` by
+//! `RouterManager::new` but is not built into the crate yet.
+
+pub mod router;
+
 use std::pin::Pin;
 
-use crate::{logger::tracing::debug, StdError};
+use crate::{invocation::Invocation, logger::tracing::debug, status::DubboError, StdError};
 use futures_core::{ready, Future};
 use futures_util::{future::Ready, FutureExt, TryFutureExt};
 use tower::{buffer::Buffer, util::FutureService};
 use tower_service::Service;
 
+use crate::directory::Providers;
 use crate::{
-    codegen::{RpcInvocation, TripleInvoker},
-    invoker::clone_invoker::CloneInvoker,
-    param::Param,
-    svc::NewService,
+    codegen::RpcInvocation, invoker::clone_invoker::CloneInvoker, param::Param,
+    protocol::BoxInvoker, svc::NewService,
 };
+use router::Routers;
 
 pub struct NewRoutes<N> {
     inner: N,
@@ -42,23 +53,15 @@ pub struct NewRoutesFuture<S, T> {
 
 pub enum RoutesFutureInnerState<S> {
     Service(S),
-    Future(
-        Pin<
-            Box<
-                dyn Future<Output = Result<Vec<CloneInvoker<TripleInvoker>>, StdError>>
-                    + Send
-                    + 'static,
-            >,
-        >,
-    ),
-    Ready(Vec<CloneInvoker<TripleInvoker>>),
+    Future(Pin<Box<dyn Future<Output = Result<Providers, StdError>> + Send + 'static>>),
+    Ready(Vec<CloneInvoker<BoxInvoker>>, Routers),
 }
 
 #[derive(Clone)]
 pub struct Routes<T> {
-    #[allow(dead_code)]
     target: T,
-    invokers: Vec<CloneInvoker<TripleInvoker>>,
+    invokers: Vec<CloneInvoker<BoxInvoker>>,
+    routers: Routers,
 }
 
 impl<N> NewRoutes<N> {
@@ -81,7 +84,7 @@ where
     // NewDirectory
     N: NewService<T>,
     // Directory
-    N::Service: Service<(), Response = Vec<CloneInvoker<TripleInvoker>>> + Unpin + Send + 'static,
+    N::Service: Service<(), Response = Providers> + Unpin + Send + 'static,
     <N::Service as Service<()>>::Error: Into<StdError>,
     <N::Service as Service<()>>::Future: Send + 'static,
 {
@@ -105,7 +108,7 @@ impl<N, T> Future for NewRoutesFuture<N, T>
 where
     T: Param<RpcInvocation> + Clone + Unpin,
     // Directory
-    N: Service<(), Response = Vec<CloneInvoker<TripleInvoker>>> + Unpin,
+    N: Service<(), Response = Providers> + Unpin,
     N::Error: Into<StdError>,
     N::Future: Send + 'static,
 {
@@ -127,14 +130,15 @@ where
                 }
                 RoutesFutureInnerState::Future(ref mut futures) => {
                     debug!("RoutesFutureInnerState::Future");
-                    let invokers = ready!(futures.as_mut().poll(cx))?;
-                    this.inner = RoutesFutureInnerState::Ready(invokers);
+                    let (invokers, routers) = ready!(futures.as_mut().poll(cx))?;
+                    this.inner = RoutesFutureInnerState::Ready(invokers, routers);
                 }
-                RoutesFutureInnerState::Ready(ref invokers) => {
+                RoutesFutureInnerState::Ready(ref invokers, ref routers) => {
                     debug!("RoutesFutureInnerState::Ready");
                     let target = this.target.clone();
                     return std::task::Poll::Ready(Ok(Routes {
                         invokers: invokers.clone(),
+                        routers: routers.clone(),
                         target,
                     }));
                 }
@@ -147,7 +151,7 @@ impl<T> Service<()> for Routes<T>
 where
     T: Param<RpcInvocation> + Clone,
 {
-    type Response = Vec<CloneInvoker<TripleInvoker>>;
+    type Response = Vec<CloneInvoker<BoxInvoker>>;
 
     type Error = StdError;
 
@@ -161,8 +165,14 @@ where
     }
 
     fn call(&mut self, _: ()) -> Self::Future {
-        // some router operator
-        // if new_invokers changed, send new invokers to routes_rx after router operator
-        futures_util::future::ok(self.invokers.clone())
+        let invocation = self.target.param();
+        let service = invocation.get_target_service_unique_name();
+        let invokers = self.routers.route(self.invokers.clone(), &invocation);
+        if invokers.is_empty() {
+            return futures_util::future::err(
+                DubboError::new(format!("no provider of {} takes the call", service)).into(),
+            );
+        }
+        futures_util::future::ok(invokers)
     }
 }
@@ -0,0 +1,121 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, RwLock},
+};
+
+use crate::{codegen::RpcInvocation, invoker::clone_invoker::CloneInvoker, protocol::BoxInvoker};
+
+/// Narrows the invokers of a service down to the ones a call may take, e.g.
+/// by the route configuration a registry receives next to the providers.
+pub trait Router: Send + Sync {
+    fn route(
+        &self,
+        invokers: Vec<CloneInvoker<BoxInvoker>>,
+        invocation: &RpcInvocation,
+    ) -> Vec<CloneInvoker<BoxInvoker>>;
+}
+
+pub type BoxRouter = Arc<dyn Router>;
+
+/// The routers of the calls to one service, held by its directory. Clones
+/// share the routers.
+#[derive(Clone, Default)]
+pub struct Routers(Arc<RwLock<BTreeMap<String, BoxRouter>>>);
+
+impl Routers {
+    /// Adds the router `name`, replacing the router that had the name.
+    pub fn add(&self, name: &str, router: BoxRouter) {
+        self.0.write().unwrap().insert(name.to_string(), router);
+    }
+
+    pub fn remove(&self, name: &str) {
+        self.0.write().unwrap().remove(name);
+    }
+
+    /// Runs the invokers through the routers in the order of their names.
+    pub fn route(
+        &self,
+        mut invokers: Vec<CloneInvoker<BoxInvoker>>,
+        invocation: &RpcInvocation,
+    ) -> Vec<CloneInvoker<BoxInvoker>> {
+        for router in self.0.read().unwrap().values() {
+            invokers = router.route(invokers, invocation);
+        }
+        invokers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{invoker::NewInvoker, svc::NewService};
+
+    const SERVICE_NAME: &str = "org.apache.dubbo.Greeter";
+
+    /// Keeps the invokers of the provider on `port`.
+    struct Port(u16);
+
+    impl Router for Port {
+        fn route(
+            &self,
+            invokers: Vec<CloneInvoker<BoxInvoker>>,
+            _invocation: &RpcInvocation,
+        ) -> Vec<CloneInvoker<BoxInvoker>> {
+            invokers
+                .into_iter()
+                .filter(|invoker| invoker.url().port() == Some(self.0))
+                .collect()
+        }
+    }
+
+    fn ports(invokers: &[CloneInvoker<BoxInvoker>]) -> Vec<u16> {
+        invokers
+            .iter()
+            .filter_map(|invoker| invoker.url().port())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_routers_of_two_clients() {
+        let invokers: Vec<_> = [8001, 8002]
+            .into_iter()
+            .map(|port| {
+                NewInvoker::new().new_service(format!(
+                    "tri://127.0.0.1:{}/{}?interface={}",
+                    port, SERVICE_NAME, SERVICE_NAME
+                ))
+            })
+            .collect();
+        let invocation = RpcInvocation::default()
+            .with_service_unique_name(SERVICE_NAME.to_string())
+            .with_method_name("greet".to_string());
+
+        // two clients of the service, each with routes of its own registry
+        let (first, second) = (Routers::default(), Routers::default());
+        first.add("xds", Arc::new(Port(8001)));
+        second.add("xds", Arc::new(Port(8002)));
+        assert_eq!(ports(&first.route(invokers.clone(), &invocation)), [8001]);
+        assert_eq!(ports(&second.route(invokers.clone(), &invocation)), [8002]);
+
+        second.remove("xds");
+        assert_eq!(ports(&first.route(invokers.clone(), &invocation)), [8001]);
+        assert_eq!(ports(&second.route(invokers, &invocation)), [8001, 8002]);
+    }
+}
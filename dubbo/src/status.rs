@@ -281,53 +281,126 @@ impl Status {
         self.code
     }
 
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Builds a trailers-only response: the status is carried in the headers
+    /// of a response without any message, as the gRPC spec requires for
+    /// errors raised before a message is sent.
     pub fn to_http(&self) -> http::Response<crate::BoxBody> {
         let (mut parts, _) = http::Response::new(()).into_parts();
-
-        parts.headers.insert(
-            http::header::CONTENT_TYPE,
-            http::HeaderValue::from_static("application/grpc"),
-        );
-
-        parts
-            .headers
-            .insert(GRPC_STATUS, self.code.to_http_header_value());
-        parts.headers.insert(
-            GRPC_MESSAGE,
-            http::HeaderValue::from_str(&self.message).unwrap(),
-        );
-
-        parts.headers.insert(
-            "grpc-accept-encoding",
-            http::HeaderValue::from_static("gzip,identity"),
-        );
+        self.add_response_headers(&mut parts.headers);
 
         http::Response::from_parts(parts, crate::empty_body())
     }
 
     pub fn to_hyper_body(&self) -> http::Response<hyper::Body> {
         let (mut parts, _) = http::Response::new(()).into_parts();
+        self.add_response_headers(&mut parts.headers);
 
-        parts.headers.insert(
+        http::Response::from_parts(parts, hyper::Body::empty())
+    }
+
+    /// Returns the `grpc-status` and `grpc-message` trailers of this status.
+    pub fn to_header_map(&self) -> http::HeaderMap {
+        let mut header = http::HeaderMap::with_capacity(2);
+        header.insert(GRPC_STATUS, self.code.to_http_header_value());
+        if !self.message.is_empty() {
+            let message = encode_grpc_message(&self.message);
+            // the encoded message is always visible ASCII
+            header.insert(GRPC_MESSAGE, HeaderValue::from_str(&message).unwrap());
+        }
+        header
+    }
+
+    /// Parses `grpc-status` and `grpc-message`, returns `None` if there is
+    /// no `grpc-status`.
+    pub fn from_header_map(header: &http::HeaderMap) -> Option<Status> {
+        let code = header.get(GRPC_STATUS)?;
+        let code = match code.to_str().ok().and_then(|v| v.parse::<i32>().ok()) {
+            Some(v) => Code::from_i32(v),
+            None => {
+                return Some(Status::new(
+                    Code::Unknown,
+                    format!("invalid grpc-status: {:?}", code),
+                ))
+            }
+        };
+        let message = header
+            .get(GRPC_MESSAGE)
+            .map(|v| decode_grpc_message(v.as_bytes()))
+            .unwrap_or_default();
+        Some(Status::new(code, message))
+    }
+
+    /// Maps the HTTP status of a response that did not come from a gRPC
+    /// server, following `doc/http-grpc-status-mapping.md` of grpc.
+    pub fn from_http_status(status: http::StatusCode) -> Option<Status> {
+        let code = match status {
+            http::StatusCode::OK => return None,
+            http::StatusCode::BAD_REQUEST => Code::Internal,
+            http::StatusCode::UNAUTHORIZED => Code::Unauthenticated,
+            http::StatusCode::FORBIDDEN => Code::PermissionDenied,
+            http::StatusCode::NOT_FOUND => Code::Unimplemented,
+            http::StatusCode::TOO_MANY_REQUESTS
+            | http::StatusCode::BAD_GATEWAY
+            | http::StatusCode::SERVICE_UNAVAILABLE
+            | http::StatusCode::GATEWAY_TIMEOUT => Code::Unavailable,
+            _ => Code::Unknown,
+        };
+        Some(Status::new(
+            code,
+            format!("unexpected http status: {}", status),
+        ))
+    }
+
+    fn add_response_headers(&self, header: &mut http::HeaderMap) {
+        header.insert(
             http::header::CONTENT_TYPE,
             http::HeaderValue::from_static("application/grpc"),
         );
-
-        parts
-            .headers
-            .insert(GRPC_STATUS, self.code.to_http_header_value());
-        parts.headers.insert(
-            GRPC_MESSAGE,
-            http::HeaderValue::from_str(&self.message).unwrap(),
-        );
-
-        parts.headers.insert(
+        header.extend(self.to_header_map());
+        header.insert(
             "grpc-accept-encoding",
             http::HeaderValue::from_static("gzip,identity"),
         );
+    }
+}
 
-        http::Response::from_parts(parts, hyper::Body::empty())
+/// Percent-encodes a `grpc-message`, every byte outside of visible ASCII and
+/// `%` itself is escaped.
+pub fn encode_grpc_message(message: &str) -> String {
+    let mut encoded = String::with_capacity(message.len());
+    for b in message.bytes() {
+        if (0x20..=0x7e).contains(&b) && b != b'%' {
+            encoded.push(b as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", b));
+        }
     }
+    encoded
+}
+
+/// Decodes a percent-encoded `grpc-message`, malformed escapes are kept as is.
+pub fn decode_grpc_message(message: &[u8]) -> String {
+    let mut decoded = Vec::with_capacity(message.len());
+    let mut i = 0;
+    while i < message.len() {
+        if message[i] == b'%' && i + 2 < message.len() {
+            let hex = std::str::from_utf8(&message[i + 1..i + 3])
+                .ok()
+                .and_then(|v| u8::from_str_radix(v, 16).ok());
+            if let Some(b) = hex {
+                decoded.push(b);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(message[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
 }
 
 impl From<std::io::Error> for Status {
@@ -369,3 +442,57 @@ impl Error for DubboError {}
 unsafe impl Send for DubboError {}
 
 unsafe impl Sync for DubboError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grpc_message_roundtrip() {
+        let message = "héllo 100%\nwörld";
+        let encoded = encode_grpc_message(message);
+        assert_eq!(encoded, "h%C3%A9llo 100%25%0Aw%C3%B6rld");
+        assert_eq!(decode_grpc_message(encoded.as_bytes()), message);
+
+        // malformed escapes are passed through
+        assert_eq!(decode_grpc_message(b"100%zz%4"), "100%zz%4");
+    }
+
+    #[test]
+    fn test_header_map_roundtrip() {
+        let status = Status::new(Code::NotFound, "no such key: ключ".to_string());
+        let header = status.to_header_map();
+        assert_eq!(header.get(GRPC_STATUS).unwrap(), "5");
+
+        let parsed = Status::from_header_map(&header).unwrap();
+        assert_eq!(parsed.code(), Code::NotFound);
+        assert_eq!(parsed.message(), status.message());
+
+        let ok = Status::new(Code::Ok, String::new()).to_header_map();
+        assert!(ok.get(GRPC_MESSAGE).is_none());
+        assert!(Status::from_header_map(&http::HeaderMap::new()).is_none());
+    }
+
+    #[test]
+    fn test_from_http_status() {
+        assert!(Status::from_http_status(http::StatusCode::OK).is_none());
+        let code = |status| Status::from_http_status(status).unwrap().code();
+        assert_eq!(code(http::StatusCode::NOT_FOUND), Code::Unimplemented);
+        assert_eq!(
+            code(http::StatusCode::SERVICE_UNAVAILABLE),
+            Code::Unavailable
+        );
+        assert_eq!(code(http::StatusCode::IM_A_TEAPOT), Code::Unknown);
+    }
+
+    #[test]
+    fn test_to_http_is_trailers_only() {
+        use http_body::Body;
+
+        let resp = Status::new(Code::Unimplemented, "not found".to_string()).to_http();
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        assert_eq!(resp.headers().get(GRPC_STATUS).unwrap(), "12");
+        assert_eq!(resp.headers().get(GRPC_MESSAGE).unwrap(), "not found");
+        assert!(resp.body().is_end_stream());
+    }
+}
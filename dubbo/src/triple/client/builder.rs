@@ -18,8 +18,13 @@
 use std::sync::Arc;
 
 use crate::{
-    cluster::NewCluster, directory::NewCachedDirectory, extension, loadbalancer::NewLoadBalancer,
-    route::NewRoutes, utils::boxed_clone::BoxCloneService,
+    cluster::{FailoverPolicy, NewCluster},
+    directory::NewCachedDirectory,
+    extension,
+    invoker::NewInvoker,
+    loadbalancer::{NewLoadBalancer, DEFAULT_LOADBALANCE},
+    route::NewRoutes,
+    utils::boxed_clone::BoxCloneService,
 };
 
 use crate::{
@@ -27,6 +32,8 @@ use crate::{
     Url,
 };
 use aws_smithy_http::body::SdkBody;
+
+use super::tls::TlsConfig;
 use tower::ServiceBuilder;
 
 pub type ClientBoxService =
@@ -35,12 +42,28 @@ pub type ClientBoxService =
 pub type ServiceMK =
     Arc<NewCluster<NewLoadBalancer<NewRoutes<NewCachedDirectory<MkRegistryService>>>>>;
 
+/// A client of one interface built from a `ClientBuilder`, as the clients
+/// generated by `dubbo-build` are, see `Dubbo::reference`.
+pub trait NewClient: Sized {
+    /// The interface called, e.g. `helloworld.Greeter`.
+    const INTERFACE: &'static str;
+
+    fn new_client(builder: ClientBuilder) -> Self;
+}
+
 #[derive(Default)]
 pub struct ClientBuilder {
     pub timeout: Option<u64>,
     pub connector: &'static str,
     registry_extension_url: Option<Url>,
     pub direct: bool,
+    pub serialization: Option<String>,
+    pub tls: Option<TlsConfig>,
+    pub retries: Option<usize>,
+    pub loadbalance: Option<String>,
+    pub group: Option<String>,
+    pub version: Option<String>,
+    pub connections: Option<usize>,
 }
 
 impl ClientBuilder {
@@ -50,6 +73,13 @@ impl ClientBuilder {
             connector: "",
             registry_extension_url: None,
             direct: false,
+            serialization: None,
+            tls: None,
+            retries: None,
+            loadbalance: None,
+            group: None,
+            version: None,
+            connections: None,
         }
     }
 
@@ -60,9 +90,18 @@ impl ClientBuilder {
             connector: "",
             registry_extension_url: Some(registry_extension_url),
             direct: true,
+            serialization: None,
+            tls: None,
+            retries: None,
+            loadbalance: None,
+            group: None,
+            version: None,
+            connections: None,
         }
     }
 
+    /// Fails calls not answered within `timeout` milliseconds with
+    /// `DeadlineExceeded`, telling the providers the deadline too.
     pub fn with_timeout(self, timeout: u64) -> Self {
         Self {
             timeout: Some(timeout),
@@ -87,6 +126,16 @@ impl ClientBuilder {
         }
     }
 
+    /// Calls the providers at `hosts` directly. Their protocol is the scheme
+    /// of their url, so Triple and Dubbo2 providers can be mixed.
+    pub fn with_hosts(self, hosts: Vec<Url>) -> Self {
+        Self {
+            registry_extension_url: Some(StaticRegistry::to_extension_url(hosts)),
+            direct: true,
+            ..self
+        }
+    }
+
     pub fn with_connector(self, connector: &'static str) -> Self {
         Self { connector, ..self }
     }
@@ -95,19 +144,126 @@ impl ClientBuilder {
         Self { direct, ..self }
     }
 
-    pub fn build(mut self) -> ServiceMK {
+    pub fn with_serialization(self, serialization: &str) -> Self {
+        Self {
+            serialization: Some(serialization.to_string()),
+            ..self
+        }
+    }
+
+    /// Connects to providers over TLS. The config is built once and shared
+    /// by every connection of the client, see `try_build`.
+    pub fn with_tls(self, tls: TlsConfig) -> Self {
+        Self {
+            tls: Some(tls),
+            ..self
+        }
+    }
+
+    /// Retries a failed call at most `retries` times, on other providers.
+    /// Without it a failed call is retried until it succeeds.
+    pub fn with_retries(self, retries: usize) -> Self {
+        Self {
+            retries: Some(retries),
+            ..self
+        }
+    }
+
+    /// Balances the calls with the load balancer named `loadbalance`, see
+    /// `loadbalancer::LOADBALANCES`.
+    pub fn with_loadbalance(self, loadbalance: &str) -> Self {
+        Self {
+            loadbalance: Some(loadbalance.to_string()),
+            ..self
+        }
+    }
+
+    /// Calls the providers of `group`, sent as `tri-service-group`.
+    pub fn with_group(self, group: &str) -> Self {
+        Self {
+            group: Some(group.to_string()),
+            ..self
+        }
+    }
+
+    /// Calls the providers of `version`, sent as `tri-service-version`.
+    pub fn with_version(self, version: &str) -> Self {
+        Self {
+            version: Some(version.to_string()),
+            ..self
+        }
+    }
+
+    /// Keeps `connections` HTTP/2 connections open to every Triple provider,
+    /// the calls taking turns on them. A provider url with a `connections`
+    /// param keeps its own number.
+    pub fn with_connections(self, connections: usize) -> Self {
+        Self {
+            connections: Some(connections),
+            ..self
+        }
+    }
+
+    /// Like `try_build`, for clients whose config cannot fail to load.
+    /// Clients with a tls config should use `try_build`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the tls config is invalid, e.g. its CA file is unreadable.
+    pub fn build(self) -> ServiceMK {
+        self.try_build()
+            .unwrap_or_else(|err| panic!("invalid tls config: {}", err))
+    }
+
+    /// Like `try_build_with_registry`, for clients whose config cannot fail
+    /// to load.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the tls config is invalid, e.g. its CA file is unreadable.
+    pub fn build_with_registry(self, registry: MkRegistryService) -> ServiceMK {
+        self.try_build_with_registry(registry)
+            .unwrap_or_else(|err| panic!("invalid tls config: {}", err))
+    }
+
+    /// Builds the client service, failing when the tls config cannot be
+    /// loaded.
+    pub fn try_build(mut self) -> Result<ServiceMK, crate::Error> {
         let registry = self
             .registry_extension_url
             .take()
             .expect("registry must not be empty");
+        self.try_build_with_registry(MkRegistryService::new(registry))
+    }
+
+    /// Like `try_build`, discovering the providers through `registry` rather
+    /// than the registry of the builder.
+    pub fn try_build_with_registry(
+        mut self,
+        registry: MkRegistryService,
+    ) -> Result<ServiceMK, crate::Error> {
+        // the `https` connector without a tls config verifies against the native roots
+        let tls = match (self.tls.take(), self.connector) {
+            (None, "https") => Some(TlsConfig::new()),
+            (tls, _) => tls,
+        };
+        let tls = tls.map(|tls| tls.build()).transpose()?;
 
         let mk_service = ServiceBuilder::new()
-            .layer(NewCluster::layer())
-            .layer(NewLoadBalancer::layer())
+            .layer(NewCluster::layer_with_policy(
+                self.retries.map(FailoverPolicy::new).unwrap_or_default(),
+            ))
+            .layer(NewLoadBalancer::layer_with_loadbalance(
+                self.loadbalance.as_deref().unwrap_or(DEFAULT_LOADBALANCE),
+            ))
             .layer(NewRoutes::layer())
-            .layer(NewCachedDirectory::layer())
-            .service(MkRegistryService::new(registry));
+            .layer(NewCachedDirectory::layer_with_invoker(
+                NewInvoker::new()
+                    .with_tls(tls)
+                    .with_connections(self.connections),
+            ))
+            .service(registry);
 
-        Arc::new(mk_service)
+        Ok(Arc::new(mk_service))
     }
 }
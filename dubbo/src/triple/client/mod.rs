@@ -16,6 +16,9 @@
  */
 
 pub mod builder;
+pub mod tls;
 pub mod triple;
 
+pub use builder::NewClient;
+pub use tls::TlsConfig;
 pub use triple::TripleClient;
@@ -0,0 +1,263 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use once_cell::sync::OnceCell;
+use rustls_native_certs::load_native_certs;
+use serde::{Deserialize, Serialize};
+use tokio_rustls::{
+    rustls::{
+        self,
+        client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier},
+        Certificate, ServerName,
+    },
+    TlsConnector,
+};
+
+use crate::{
+    logger::tracing::warn,
+    utils::{self, tls::Reloadable},
+};
+
+const ALPN_H2: &[u8] = b"h2";
+
+/// TLS settings of a reference.
+///
+/// Without a `ca_file` the server is verified against the native root
+/// certificates of the platform.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TlsConfig {
+    /// PEM bundle of the certificate authorities trusted by the client.
+    pub ca_file: Option<String>,
+    /// PEM certificate chain presented to servers requiring client auth.
+    pub cert_file: Option<String>,
+    /// PEM private key of `cert_file`.
+    pub key_file: Option<String>,
+    /// Name used for SNI and certificate verification instead of the host
+    /// of the provider url.
+    pub server_name: Option<String>,
+    /// Accepts any server certificate, only meant for tests.
+    #[serde(default)]
+    pub insecure_skip_verify: bool,
+    /// How often the files are checked for renewed certificates, in
+    /// milliseconds. Defaults to `utils::tls::DEFAULT_RELOAD_INTERVAL`.
+    pub reload_interval_ms: Option<u64>,
+}
+
+impl TlsConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_ca_file(self, ca_file: &str) -> Self {
+        Self {
+            ca_file: Some(ca_file.to_string()),
+            ..self
+        }
+    }
+
+    pub fn with_client_cert(self, cert_file: &str, key_file: &str) -> Self {
+        Self {
+            cert_file: Some(cert_file.to_string()),
+            key_file: Some(key_file.to_string()),
+            ..self
+        }
+    }
+
+    pub fn with_server_name(self, server_name: &str) -> Self {
+        Self {
+            server_name: Some(server_name.to_string()),
+            ..self
+        }
+    }
+
+    pub fn with_insecure_skip_verify(self, insecure_skip_verify: bool) -> Self {
+        Self {
+            insecure_skip_verify,
+            ..self
+        }
+    }
+
+    pub fn with_reload_interval(self, interval: Duration) -> Self {
+        Self {
+            reload_interval_ms: Some(interval.as_millis() as u64),
+            ..self
+        }
+    }
+
+    /// Loads the configured certificates and builds the rustls config shared
+    /// by every connection of the reference. The files are watched, so new
+    /// connections pick up renewed certificates.
+    pub fn build(&self) -> Result<ClientTls, crate::Error> {
+        let server_name = match &self.server_name {
+            Some(name) => Some(ServerName::try_from(name.as_str())?),
+            None => None,
+        };
+
+        let files: Vec<PathBuf> = [&self.ca_file, &self.cert_file, &self.key_file]
+            .into_iter()
+            .flatten()
+            .map(PathBuf::from)
+            .collect();
+        let interval = self
+            .reload_interval_ms
+            .map(Duration::from_millis)
+            .unwrap_or(utils::tls::DEFAULT_RELOAD_INTERVAL);
+        let tls = self.clone();
+        let config = Reloadable::watch(files, interval, move || tls.client_config())?;
+
+        Ok(ClientTls {
+            config,
+            server_name,
+        })
+    }
+
+    fn client_config(&self) -> Result<rustls::ClientConfig, crate::Error> {
+        let builder = rustls::ClientConfig::builder().with_safe_defaults();
+
+        let verifier: Arc<dyn ServerCertVerifier> = if self.insecure_skip_verify {
+            warn!("tls server certificate verification is disabled");
+            Arc::new(InsecureVerifier)
+        } else {
+            let mut root_store = rustls::RootCertStore::empty();
+            match &self.ca_file {
+                Some(ca_file) => {
+                    for cert in utils::tls::load_certs(Path::new(ca_file))? {
+                        root_store.add(&cert)?;
+                    }
+                }
+                None => {
+                    for cert in load_native_certs()? {
+                        root_store.add(&Certificate(cert.0))?;
+                    }
+                }
+            }
+            Arc::new(WebPkiVerifier::new(root_store, None))
+        };
+        let builder = builder.with_custom_certificate_verifier(verifier);
+
+        let mut config = match (&self.cert_file, &self.key_file) {
+            (Some(cert_file), Some(key_file)) => {
+                let certs = utils::tls::load_certs(Path::new(cert_file))?;
+                let mut keys = utils::tls::load_keys(Path::new(key_file))?;
+                builder.with_client_auth_cert(certs, keys.remove(0))?
+            }
+            (None, None) => builder.with_no_client_auth(),
+            _ => {
+                return Err("tls cert_file and key_file must be set together".into());
+            }
+        };
+        config.alpn_protocols = vec![ALPN_H2.to_vec()];
+        Ok(config)
+    }
+}
+
+/// A built `TlsConfig`, cheap to clone.
+#[derive(Clone)]
+pub struct ClientTls {
+    config: Reloadable<rustls::ClientConfig>,
+    server_name: Option<ServerName>,
+}
+
+impl ClientTls {
+    /// The default config used by the `https` connector, built once per
+    /// process.
+    pub(crate) fn native() -> Result<ClientTls, crate::Error> {
+        static NATIVE: OnceCell<ClientTls> = OnceCell::new();
+
+        NATIVE.get_or_try_init(|| TlsConfig::new().build()).cloned()
+    }
+
+    pub(crate) fn connector(&self) -> TlsConnector {
+        TlsConnector::from(self.config.current())
+    }
+
+    /// The config in use, replaced when the files are reloaded.
+    pub(crate) fn current(&self) -> Arc<rustls::ClientConfig> {
+        self.config.current()
+    }
+
+    pub(crate) fn server_name(&self, host: &str) -> Result<ServerName, crate::Error> {
+        match &self.server_name {
+            Some(name) => Ok(name.clone()),
+            None => Ok(ServerName::try_from(host)?),
+        }
+    }
+}
+
+struct InsecureVerifier;
+
+impl ServerCertVerifier for InsecureVerifier {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+    fn fixture(name: &str) -> String {
+        format!("{}/{}", FIXTURES, name)
+    }
+
+    #[test]
+    fn test_build() {
+        let tls = TlsConfig::new()
+            .with_ca_file(&fixture("ca.crt"))
+            .with_client_cert(&fixture("client.crt"), &fixture("client.key"))
+            .with_server_name("localhost")
+            .build()
+            .unwrap();
+        assert_eq!(tls.config.current().alpn_protocols, vec![b"h2".to_vec()]);
+        assert!(tls.config.current().client_auth_cert_resolver.has_certs());
+        assert_eq!(
+            tls.server_name("127.0.0.1").unwrap(),
+            ServerName::try_from("localhost").unwrap()
+        );
+
+        let tls = TlsConfig::new().with_insecure_skip_verify(true).build();
+        assert!(tls.is_ok());
+    }
+
+    #[test]
+    fn test_build_invalid() {
+        let missing_ca = TlsConfig::new()
+            .with_ca_file(&fixture("missing.crt"))
+            .build();
+        assert!(missing_ca.is_err());
+
+        let mut cert_without_key = TlsConfig::new().with_ca_file(&fixture("ca.crt"));
+        cert_without_key.cert_file = Some(fixture("client.crt"));
+        assert!(cert_without_key.build().is_err());
+    }
+}
@@ -16,20 +16,25 @@
  */
 
 use aws_smithy_http::body::SdkBody;
+use std::time::Duration;
+
 use futures_util::{future, stream, StreamExt, TryStreamExt};
 use http::HeaderValue;
 use prost::Message;
 use serde::{Deserialize, Serialize};
 use tower_service::Service;
 
-use crate::codegen::{ProstCodec, RpcInvocation, SerdeCodec};
+use crate::codegen::RpcInvocation;
 
 use crate::{
     invocation::{IntoStreamingRequest, Metadata, Request, Response},
     status::Status,
     svc::NewService,
     triple::{
-        codec::{Codec, Decoder, Encoder},
+        codec::{
+            registry::{self, BoxDecoder, BoxEncoder},
+            Decoder, Encoder,
+        },
         compression::CompressionEncoding,
         decode::Decoding,
         encode::encode,
@@ -41,7 +46,11 @@ use super::builder::{ClientBuilder, ServiceMK};
 #[derive(Clone)]
 pub struct TripleClient {
     pub(crate) send_compression_encoding: Option<CompressionEncoding>,
+    pub(crate) serialization: String,
     pub(crate) mk: ServiceMK,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) group: Option<String>,
+    pub(crate) version: Option<String>,
 }
 
 impl TripleClient {
@@ -51,15 +60,94 @@ impl TripleClient {
 
         TripleClient {
             send_compression_encoding: Some(CompressionEncoding::Gzip),
+            serialization: registry::PROTO_SERIALIZATION.to_string(),
             mk,
+            timeout: None,
+            group: None,
+            version: None,
         }
     }
 
+    /// Like `try_new`, for builders whose config cannot fail to load.
+    /// Clients with a tls config should use `try_new`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the tls config of `builder` is invalid, e.g. its CA file is
+    /// unreadable.
     pub fn new(builder: ClientBuilder) -> Self {
-        TripleClient {
+        Self::try_new(builder).unwrap_or_else(|err| panic!("invalid tls config: {}", err))
+    }
+
+    /// Creates a client from `builder`, failing when its tls config cannot
+    /// be loaded.
+    pub fn try_new(builder: ClientBuilder) -> Result<Self, crate::Error> {
+        let serialization = builder
+            .serialization
+            .clone()
+            .unwrap_or_else(|| registry::PROTO_SERIALIZATION.to_string());
+        let timeout = builder.timeout.map(Duration::from_millis);
+        let group = builder.group.clone();
+        let version = builder.version.clone();
+        Ok(TripleClient {
             send_compression_encoding: Some(CompressionEncoding::Gzip),
-            mk: builder.build(),
+            serialization,
+            mk: builder.try_build()?,
+            timeout,
+            group,
+            version,
+        })
+    }
+
+    /// Sets the serialization used by every call of this client, e.g. `proto`,
+    /// `json` or the name of a codec registered by `registry::register_codec`.
+    pub fn with_serialization(self, serialization: &str) -> Self {
+        TripleClient {
+            serialization: serialization.to_string(),
+            ..self
+        }
+    }
+
+    // A content-type in the request metadata overrides the client serialization.
+    fn content_type(&self, metadata: &Metadata) -> String {
+        metadata
+            .get("content-type")
+            .cloned()
+            .unwrap_or_else(|| registry::content_type(&self.serialization))
+    }
+
+    // Sends `request` with the group, version and deadline of the client.
+    async fn call<S>(
+        &self,
+        invoker: &mut S,
+        mut request: http::Request<hyper::Body>,
+    ) -> Result<S::Response, Status>
+    where
+        S: Service<http::Request<hyper::Body>>,
+        S::Error: Into<crate::Error>,
+    {
+        let headers = request.headers_mut();
+        if let Some(group) = &self.group {
+            headers.insert("tri-service-group", header_value(group)?);
         }
+        if let Some(version) = &self.version {
+            headers.insert("tri-service-version", header_value(version)?);
+        }
+        let response = match self.timeout {
+            None => invoker.call(request).await,
+            Some(timeout) => {
+                headers.insert("grpc-timeout", header_value(&grpc_timeout(timeout))?);
+                tokio::time::timeout(timeout, invoker.call(request))
+                    .await
+                    .map_err(|_| {
+                        Status::new(
+                            crate::status::Code::DeadlineExceeded,
+                            format!("no response within {:?}", timeout),
+                        )
+                    })?
+            }
+        };
+        response.map_err(|err| Status::from_error(err.into()))
     }
 
     pub fn map_request(
@@ -143,10 +231,11 @@ impl TripleClient {
         M1: Message + Send + Sync + 'static + Serialize,
         M2: Message + Send + Sync + 'static + for<'a> Deserialize<'a> + Default,
     {
+        let content_type = self.content_type(&req.metadata);
         let (decoder, encoder): (
             Box<dyn Decoder<Item = M2, Error = Status> + Send + 'static>,
             Box<dyn Encoder<Error = Status, Item = M1> + Send + 'static>,
-        ) = get_codec("application/grpc+proto");
+        ) = registry::get_codec(&content_type)?;
 
         let mt = req.metadata.clone();
 
@@ -171,16 +260,25 @@ impl TripleClient {
         for (k, v) in mt.into_headers().iter() {
             request.headers_mut().insert(k, v.to_owned());
         }
+        request.headers_mut().insert(
+            http::header::CONTENT_TYPE,
+            HeaderValue::from_str(&content_type)
+                .map_err(|err| Status::new(crate::status::Code::Internal, err.to_string()))?,
+        );
 
-        let response = invoker
-            .call(request)
-            .await
-            .map_err(|err| crate::status::Status::from_error(err.into()));
+        let response = self.call(&mut invoker, request).await;
 
         match response {
             Ok(v) => {
-                let resp = v
-                    .map(|body| Decoding::new(body, decoder, self.send_compression_encoding, true));
+                let check_trailers = check_response_head(&v)?;
+                let resp = v.map(|body| {
+                    let decoding =
+                        Decoding::new(body, decoder, self.send_compression_encoding, true);
+                    match check_trailers {
+                        true => decoding.with_grpc_status(),
+                        false => decoding,
+                    }
+                });
                 let (mut parts, body) = Response::from_http(resp).into_parts();
 
                 futures_util::pin_mut!(body);
@@ -214,12 +312,12 @@ impl TripleClient {
         M1: Message + Send + Sync + 'static + Serialize,
         M2: Message + Send + Sync + 'static + for<'a> Deserialize<'a> + Default,
     {
+        let req = req.into_streaming_request();
+        let content_type = self.content_type(&req.metadata);
         let (decoder, encoder): (
             Box<dyn Decoder<Item = M2, Error = Status> + Send + 'static>,
             Box<dyn Encoder<Error = Status, Item = M1> + Send + 'static>,
-        ) = get_codec("application/grpc+proto");
-
-        let req = req.into_streaming_request();
+        ) = registry::get_codec(&content_type)?;
         let mt = req.metadata.clone();
 
         let en = encode(
@@ -242,16 +340,25 @@ impl TripleClient {
         for (k, v) in mt.into_headers().iter() {
             request.headers_mut().insert(k, v.to_owned());
         }
+        request.headers_mut().insert(
+            http::header::CONTENT_TYPE,
+            HeaderValue::from_str(&content_type)
+                .map_err(|err| Status::new(crate::status::Code::Internal, err.to_string()))?,
+        );
 
-        let response = invoker
-            .call(request)
-            .await
-            .map_err(|err| crate::status::Status::from_error(err.into()));
+        let response = self.call(&mut invoker, request).await;
 
         match response {
             Ok(v) => {
-                let resp = v
-                    .map(|body| Decoding::new(body, decoder, self.send_compression_encoding, true));
+                let check_trailers = check_response_head(&v)?;
+                let resp = v.map(|body| {
+                    let decoding =
+                        Decoding::new(body, decoder, self.send_compression_encoding, true);
+                    match check_trailers {
+                        true => decoding.with_grpc_status(),
+                        false => decoding,
+                    }
+                });
 
                 Ok(Response::from_http(resp))
             }
@@ -269,11 +376,12 @@ impl TripleClient {
         M1: Message + Send + Sync + 'static + Serialize,
         M2: Message + Send + Sync + 'static + for<'a> Deserialize<'a> + Default,
     {
+        let req = req.into_streaming_request();
+        let content_type = self.content_type(&req.metadata);
         let (decoder, encoder): (
             Box<dyn Decoder<Item = M2, Error = Status> + Send + 'static>,
             Box<dyn Encoder<Error = Status, Item = M1> + Send + 'static>,
-        ) = get_codec("application/grpc+proto");
-        let req = req.into_streaming_request();
+        ) = registry::get_codec(&content_type)?;
         let mt = req.metadata.clone();
 
         let en = encode(
@@ -296,16 +404,25 @@ impl TripleClient {
         for (k, v) in mt.into_headers().iter() {
             request.headers_mut().insert(k, v.to_owned());
         }
+        request.headers_mut().insert(
+            http::header::CONTENT_TYPE,
+            HeaderValue::from_str(&content_type)
+                .map_err(|err| Status::new(crate::status::Code::Internal, err.to_string()))?,
+        );
 
-        let response = invoker
-            .call(request)
-            .await
-            .map_err(|err| crate::status::Status::from_error(err.into()));
+        let response = self.call(&mut invoker, request).await;
 
         match response {
             Ok(v) => {
-                let resp = v
-                    .map(|body| Decoding::new(body, decoder, self.send_compression_encoding, true));
+                let check_trailers = check_response_head(&v)?;
+                let resp = v.map(|body| {
+                    let decoding =
+                        Decoding::new(body, decoder, self.send_compression_encoding, true);
+                    match check_trailers {
+                        true => decoding.with_grpc_status(),
+                        false => decoding,
+                    }
+                });
                 let (mut parts, body) = Response::from_http(resp).into_parts();
 
                 futures_util::pin_mut!(body);
@@ -339,10 +456,11 @@ impl TripleClient {
         M1: Message + Send + Sync + 'static + Serialize,
         M2: Message + Send + Sync + 'static + for<'a> Deserialize<'a> + Default,
     {
+        let content_type = self.content_type(&req.metadata);
         let (decoder, encoder): (
             Box<dyn Decoder<Item = M2, Error = Status> + Send + 'static>,
             Box<dyn Encoder<Error = Status, Item = M1> + Send + 'static>,
-        ) = get_codec("application/grpc+proto");
+        ) = registry::get_codec(&content_type)?;
 
         let req = req.map(|m| stream::once(future::ready(m)));
         let mt = req.metadata.clone();
@@ -367,16 +485,25 @@ impl TripleClient {
         for (k, v) in mt.into_headers().iter() {
             request.headers_mut().insert(k, v.to_owned());
         }
+        request.headers_mut().insert(
+            http::header::CONTENT_TYPE,
+            HeaderValue::from_str(&content_type)
+                .map_err(|err| Status::new(crate::status::Code::Internal, err.to_string()))?,
+        );
 
-        let response = invoker
-            .call(request)
-            .await
-            .map_err(|err| crate::status::Status::from_error(err.into()));
+        let response = self.call(&mut invoker, request).await;
 
         match response {
             Ok(v) => {
-                let resp = v
-                    .map(|body| Decoding::new(body, decoder, self.send_compression_encoding, true));
+                let check_trailers = check_response_head(&v)?;
+                let resp = v.map(|body| {
+                    let decoding =
+                        Decoding::new(body, decoder, self.send_compression_encoding, true);
+                    match check_trailers {
+                        true => decoding.with_grpc_status(),
+                        false => decoding,
+                    }
+                });
 
                 Ok(Response::from_http(resp))
             }
@@ -385,25 +512,68 @@ impl TripleClient {
     }
 }
 
-pub fn get_codec<M1, M2>(
-    content_type: &str,
-) -> (
-    Box<dyn Decoder<Item = M2, Error = Status> + Send + 'static>,
-    Box<dyn Encoder<Error = Status, Item = M1> + Send + 'static>,
-)
+/// Checks the status carried by the head of a response, either a trailers-only
+/// response or an HTTP error from a non-gRPC peer. Returns whether the status
+/// is still expected in the trailers.
+fn check_response_head<B>(resp: &http::Response<B>) -> Result<bool, Status> {
+    if let Some(status) = Status::from_http_status(resp.status()) {
+        return Err(status);
+    }
+    match Status::from_header_map(resp.headers()) {
+        Some(status) if status.code() == crate::status::Code::Ok => Ok(false),
+        Some(status) => Err(status),
+        None => Ok(true),
+    }
+}
+
+/// The `grpc-timeout` of `timeout`, of at most 8 digits in the smallest unit
+/// they fit, rounded up.
+pub(crate) fn grpc_timeout(timeout: Duration) -> String {
+    const MAX: u128 = 99_999_999;
+    let millis = timeout.as_millis();
+    let units = [('m', 1), ('S', 1_000), ('M', 60_000), ('H', 3_600_000)];
+    for (unit, per) in units {
+        let value = millis.div_ceil(per);
+        if value <= MAX {
+            return format!("{}{}", value, unit);
+        }
+    }
+    format!("{}H", MAX)
+}
+
+fn header_value(value: &str) -> Result<HeaderValue, Status> {
+    HeaderValue::from_str(value)
+        .map_err(|err| Status::new(crate::status::Code::Internal, err.to_string()))
+}
+
+/// The codecs of `content_type`: json when it ends with `json`, else proto.
+#[deprecated(
+    since = "0.4.0",
+    note = "use `codec::registry::get_codec`, which also resolves the registered codecs"
+)]
+pub fn get_codec<M1, M2>(content_type: &str) -> (BoxDecoder<M2>, BoxEncoder<M1>)
 where
     M1: Message + Send + Sync + 'static + Serialize,
     M2: Message + Send + Sync + 'static + for<'a> Deserialize<'a> + Default,
 {
-    //Determine whether to use JSON as the serialization method.
-    match content_type.ends_with("json") {
-        true => {
-            let mut codec = SerdeCodec::<M1, M2>::default();
-            (Box::new(codec.decoder()), Box::new(codec.encoder()))
-        }
-        false => {
-            let mut codec = ProstCodec::<M1, M2>::default();
-            (Box::new(codec.decoder()), Box::new(codec.encoder()))
-        }
+    let serialization = match content_type.ends_with("json") {
+        true => registry::JSON_SERIALIZATION,
+        false => registry::PROTO_SERIALIZATION,
+    };
+    registry::get_codec(&registry::content_type(serialization))
+        .expect("proto and json codecs are always registered")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grpc_timeout() {
+        assert_eq!(grpc_timeout(Duration::from_millis(3000)), "3000m");
+        assert_eq!(grpc_timeout(Duration::from_millis(99_999_999)), "99999999m");
+        assert_eq!(grpc_timeout(Duration::from_millis(100_000_001)), "100001S");
+        assert_eq!(grpc_timeout(Duration::from_secs(200_000_000)), "3333334M");
+        assert_eq!(grpc_timeout(Duration::from_secs(u64::MAX)), "99999999H");
     }
 }
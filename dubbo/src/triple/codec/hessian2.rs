@@ -0,0 +1,92 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::marker::PhantomData;
+
+use bytes::{Buf, BufMut};
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::{Codec, DecodeBuf, Decoder, EncodeBuf, Encoder};
+use crate::status::{Code, Status};
+
+/// Hessian2 messages, for Java services whose Triple payloads are Hessian
+/// serialized. Structs are written as objects of the Java class they are
+/// renamed to, see `protocol_hessian2::to_value`.
+#[derive(Debug)]
+pub struct Hessian2Codec<T, U> {
+    _pd: PhantomData<(T, U)>,
+}
+
+impl<T, U> Default for Hessian2Codec<T, U> {
+    fn default() -> Self {
+        Self { _pd: PhantomData }
+    }
+}
+
+impl<T, U> Codec for Hessian2Codec<T, U>
+where
+    T: Serialize + Send + 'static,
+    U: DeserializeOwned + Send + 'static,
+{
+    type Encode = T;
+
+    type Decode = U;
+
+    type Encoder = Hessian2Encoder<T>;
+
+    type Decoder = Hessian2Decoder<U>;
+
+    fn encoder(&mut self) -> Self::Encoder {
+        Hessian2Encoder(PhantomData)
+    }
+
+    fn decoder(&mut self) -> Self::Decoder {
+        Hessian2Decoder(PhantomData)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Hessian2Encoder<T>(PhantomData<T>);
+
+impl<T: Serialize> Encoder for Hessian2Encoder<T> {
+    type Item = T;
+
+    type Error = Status;
+
+    fn encode(&mut self, item: Self::Item, dst: &mut EncodeBuf<'_>) -> Result<(), Self::Error> {
+        let bytes = protocol_hessian2::to_vec(&item)
+            .map_err(|err| Status::new(Code::Internal, err.to_string()))?;
+        dst.put_slice(&bytes);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Hessian2Decoder<U>(PhantomData<U>);
+
+impl<U: DeserializeOwned> Decoder for Hessian2Decoder<U> {
+    type Item = U;
+
+    type Error = Status;
+
+    fn decode(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<Self::Item>, Self::Error> {
+        let bytes = src.copy_to_bytes(src.remaining());
+        let item = protocol_hessian2::from_slice(&bytes)
+            .map_err(|err| Status::new(Code::Internal, err.to_string()))?;
+        Ok(Some(item))
+    }
+}
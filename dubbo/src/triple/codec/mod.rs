@@ -16,7 +16,9 @@
  */
 
 pub mod buffer;
+pub mod hessian2;
 pub mod prost;
+pub mod registry;
 pub mod serde_codec;
 
 use std::io;
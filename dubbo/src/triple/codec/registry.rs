@@ -0,0 +1,278 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    collections::HashMap,
+    marker::PhantomData,
+    sync::{Arc, RwLock},
+};
+
+use lazy_static::lazy_static;
+use prost::Message;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    hessian2::Hessian2Codec, prost::ProstCodec, serde_codec::SerdeCodec, Codec, DecodeBuf, Decoder,
+    EncodeBuf, Encoder,
+};
+use crate::status::{Code, Status};
+
+pub const GRPC_CONTENT_TYPE: &str = "application/grpc";
+pub const PROTO_SERIALIZATION: &str = "proto";
+pub const JSON_SERIALIZATION: &str = "json";
+pub const HESSIAN2_SERIALIZATION: &str = "hessian2";
+
+/// A user supplied serialization.
+///
+/// Messages are handed over as `serde_json::Value`, so any serde data format
+/// can be plugged in without knowing the concrete message types.
+pub trait CustomCodec: Send + Sync + 'static {
+    fn encode(&self, value: serde_json::Value, dst: &mut EncodeBuf<'_>) -> Result<(), Status>;
+
+    fn decode(&self, src: &mut DecodeBuf<'_>) -> Result<serde_json::Value, Status>;
+}
+
+pub type BoxDecoder<T> = Box<dyn Decoder<Item = T, Error = Status> + Send + 'static>;
+pub type BoxEncoder<T> = Box<dyn Encoder<Item = T, Error = Status> + Send + 'static>;
+
+#[derive(Clone)]
+pub enum Serialization {
+    Proto,
+    Json,
+    Hessian2,
+    Custom(Arc<dyn CustomCodec>),
+}
+
+lazy_static! {
+    static ref CODECS: RwLock<HashMap<String, Serialization>> = {
+        let mut v = HashMap::new();
+        v.insert(PROTO_SERIALIZATION.to_string(), Serialization::Proto);
+        v.insert(JSON_SERIALIZATION.to_string(), Serialization::Json);
+        RwLock::new(v)
+    };
+}
+
+/// Registers a custom codec under `name`, making `application/grpc+{name}`
+/// available to both clients and servers.
+pub fn register_codec<C: CustomCodec>(name: &str, codec: C) {
+    CODECS
+        .write()
+        .unwrap()
+        .insert(name.to_string(), Serialization::Custom(Arc::new(codec)));
+}
+
+/// Makes `application/grpc+hessian2` available to both clients and servers.
+/// It is off by default, as every Triple server of the process then decodes
+/// Hessian payloads from its peers.
+pub fn register_hessian2() {
+    CODECS
+        .write()
+        .unwrap()
+        .insert(HESSIAN2_SERIALIZATION.to_string(), Serialization::Hessian2);
+}
+
+pub fn get_serialization(name: &str) -> Option<Serialization> {
+    CODECS.read().unwrap().get(name).cloned()
+}
+
+/// Returns the serialization name carried by a content-type, e.g. `json` for
+/// `application/grpc+json`. A bare `application/grpc` means protobuf.
+pub fn serialization_name(content_type: &str) -> &str {
+    let mime = content_type.split(';').next().unwrap_or_default().trim();
+    match mime.strip_prefix(GRPC_CONTENT_TYPE) {
+        Some("") => PROTO_SERIALIZATION,
+        Some(suffix) => suffix.strip_prefix('+').unwrap_or(suffix),
+        // non-grpc requests, such as `application/json`
+        None => mime.rsplit('/').next().unwrap_or(mime),
+    }
+}
+
+pub fn content_type(serialization: &str) -> String {
+    format!("{}+{}", GRPC_CONTENT_TYPE, serialization)
+}
+
+pub fn get_codec<M1, M2>(content_type: &str) -> Result<(BoxDecoder<M2>, BoxEncoder<M1>), Status>
+where
+    M1: Message + Send + Sync + 'static + Serialize,
+    M2: Message + Send + Sync + 'static + for<'a> Deserialize<'a> + Default,
+{
+    let name = serialization_name(content_type);
+    let serialization = get_serialization(name).ok_or_else(|| {
+        Status::new(
+            Code::Unimplemented,
+            format!("content-type: {} not support!", content_type),
+        )
+    })?;
+
+    match serialization {
+        Serialization::Proto => {
+            let mut codec = ProstCodec::<M1, M2>::default();
+            Ok((Box::new(codec.decoder()), Box::new(codec.encoder())))
+        }
+        Serialization::Json => {
+            let mut codec = SerdeCodec::<M1, M2>::default();
+            Ok((Box::new(codec.decoder()), Box::new(codec.encoder())))
+        }
+        Serialization::Hessian2 => {
+            let mut codec = Hessian2Codec::<M1, M2>::default();
+            Ok((Box::new(codec.decoder()), Box::new(codec.encoder())))
+        }
+        Serialization::Custom(codec) => Ok((
+            Box::new(CustomDecoder(codec.clone(), PhantomData)),
+            Box::new(CustomEncoder(codec, PhantomData)),
+        )),
+    }
+}
+
+pub struct CustomEncoder<T>(Arc<dyn CustomCodec>, PhantomData<T>);
+
+impl<T: Serialize> Encoder for CustomEncoder<T> {
+    type Item = T;
+    type Error = Status;
+
+    fn encode(&mut self, item: Self::Item, dst: &mut EncodeBuf<'_>) -> Result<(), Self::Error> {
+        let value = serde_json::to_value(item)
+            .map_err(|err| Status::new(Code::Internal, err.to_string()))?;
+        self.0.encode(value, dst)
+    }
+}
+
+pub struct CustomDecoder<U>(Arc<dyn CustomCodec>, PhantomData<U>);
+
+impl<U: for<'a> Deserialize<'a>> Decoder for CustomDecoder<U> {
+    type Item = U;
+    type Error = Status;
+
+    fn decode(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<Self::Item>, Self::Error> {
+        let value = self.0.decode(src)?;
+        let item = serde_json::from_value(value)
+            .map_err(|err| Status::new(Code::Internal, err.to_string()))?;
+        Ok(Some(item))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::{Buf, BufMut, BytesMut};
+
+    use super::*;
+
+    #[derive(Clone, PartialEq, Message, Serialize, Deserialize)]
+    struct Greeting {
+        #[prost(string, tag = "1")]
+        name: String,
+    }
+
+    struct UpperJson;
+
+    impl CustomCodec for UpperJson {
+        fn encode(&self, value: serde_json::Value, dst: &mut EncodeBuf<'_>) -> Result<(), Status> {
+            dst.put(value.to_string().to_uppercase().as_bytes());
+            Ok(())
+        }
+
+        fn decode(&self, src: &mut DecodeBuf<'_>) -> Result<serde_json::Value, Status> {
+            let raw = src.copy_to_bytes(src.remaining());
+            serde_json::from_slice(&raw.to_ascii_lowercase())
+                .map_err(|err| Status::new(Code::Internal, err.to_string()))
+        }
+    }
+
+    #[test]
+    fn test_serialization_name() {
+        assert_eq!(serialization_name("application/grpc"), "proto");
+        assert_eq!(serialization_name("application/grpc+proto"), "proto");
+        assert_eq!(serialization_name("application/grpc+json"), "json");
+        assert_eq!(
+            serialization_name("application/grpc+json; charset=utf-8"),
+            "json"
+        );
+        assert_eq!(serialization_name("application/json"), "json");
+        assert_eq!(content_type("json"), "application/grpc+json");
+    }
+
+    #[test]
+    fn test_custom_codec_roundtrip() {
+        register_codec("upper", UpperJson);
+
+        let (mut decoder, mut encoder) =
+            get_codec::<Greeting, Greeting>("application/grpc+upper").unwrap();
+        let mut bytes = BytesMut::new();
+        encoder
+            .encode(
+                Greeting {
+                    name: "dubbo".to_string(),
+                },
+                &mut EncodeBuf::new(&mut bytes),
+            )
+            .unwrap();
+        assert_eq!(&bytes[..], br#"{"NAME":"DUBBO"}"#);
+
+        let len = bytes.len();
+        let msg = decoder
+            .decode(&mut DecodeBuf::new(&mut bytes, len))
+            .unwrap()
+            .unwrap();
+        assert_eq!(msg.name, "dubbo");
+    }
+
+    #[test]
+    fn test_hessian2_roundtrip() {
+        register_hessian2();
+        let (mut decoder, mut encoder) =
+            get_codec::<Greeting, Greeting>("application/grpc+hessian2").unwrap();
+        let mut bytes = BytesMut::new();
+        encoder
+            .encode(
+                Greeting {
+                    name: "dubbo".to_string(),
+                },
+                &mut EncodeBuf::new(&mut bytes),
+            )
+            .unwrap();
+        // a class definition of `Greeting` with one field, then the instance
+        assert_eq!(&bytes[..], b"C\x08Greeting\x91\x04name\x60\x05dubbo");
+
+        let len = bytes.len();
+        let msg = decoder
+            .decode(&mut DecodeBuf::new(&mut bytes, len))
+            .unwrap()
+            .unwrap();
+        assert_eq!(msg.name, "dubbo");
+    }
+
+    #[test]
+    fn test_unknown_codec() {
+        let res = get_codec::<Greeting, Greeting>("application/grpc+unknown");
+        assert!(matches!(res, Err(status) if status.code() == Code::Unimplemented));
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_deprecated_get_codec() {
+        let (_, mut encoder) =
+            crate::triple::client::triple::get_codec::<Greeting, Greeting>("application/json");
+        let mut bytes = BytesMut::new();
+        let greeting = Greeting {
+            name: "dubbo".to_string(),
+        };
+        encoder
+            .encode(greeting, &mut EncodeBuf::new(&mut bytes))
+            .unwrap();
+        assert_eq!(&bytes[..], br#"{"name":"dubbo"}"#);
+    }
+}
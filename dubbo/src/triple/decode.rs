@@ -39,6 +39,7 @@ pub struct Decoding<T> {
     compress: Option<CompressionEncoding>,
     decompress_buf: BytesMut,
     decode_as_grpc: bool,
+    check_grpc_status: bool,
 }
 
 #[derive(PartialEq)]
@@ -47,6 +48,7 @@ enum State {
     ReadHttpBody,
     ReadBody { len: usize, is_compressed: bool },
     Error,
+    Done,
 }
 
 impl<T> Decoding<T> {
@@ -78,6 +80,31 @@ impl<T> Decoding<T> {
             compress,
             decompress_buf: BytesMut::new(),
             decode_as_grpc,
+            check_grpc_status: false,
+        }
+    }
+
+    /// Makes the stream fail with the `grpc-status` of the trailers, used by
+    /// clients to decode responses. A missing `grpc-status` means `Unknown`.
+    pub(crate) fn with_grpc_status(mut self) -> Self {
+        self.check_grpc_status = true;
+        self
+    }
+
+    fn check_trailers(
+        &self,
+        trailers: Option<&http::HeaderMap>,
+    ) -> Result<(), crate::status::Status> {
+        if !self.check_grpc_status {
+            return Ok(());
+        }
+        match trailers.and_then(crate::status::Status::from_header_map) {
+            Some(status) if status.code() == crate::status::Code::Ok => Ok(()),
+            Some(status) => Err(status),
+            None => Err(crate::status::Status::new(
+                crate::status::Code::Unknown,
+                "missing grpc-status in response trailers".to_string(),
+            )),
         }
     }
 
@@ -95,8 +122,14 @@ impl<T> Decoding<T> {
         }
         // while self.message().await?.is_some() {}
 
-        let trailer = future::poll_fn(|cx| Pin::new(&mut self.body).poll_trailers(cx)).await;
-        trailer.map(|data| data.map(Metadata::from_headers))
+        if self.state == State::Done {
+            return Ok(None);
+        }
+
+        let trailer = future::poll_fn(|cx| Pin::new(&mut self.body).poll_trailers(cx)).await?;
+        self.state = State::Done;
+        self.check_trailers(trailer.as_ref())?;
+        Ok(trailer.map(Metadata::from_headers))
     }
 
     pub fn decode_http(&mut self) -> Result<Option<T>, crate::status::Status> {
@@ -232,7 +265,7 @@ impl<T> Stream for Decoding<T> {
         cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<Option<Self::Item>> {
         loop {
-            if self.state == State::Error {
+            if self.state == State::Error || self.state == State::Done {
                 return Poll::Ready(None);
             }
 
@@ -260,8 +293,13 @@ impl<T> Stream for Decoding<T> {
             }
         }
 
-        match ready!(Pin::new(&mut self.body).poll_trailers(cx)) {
+        let trailer = ready!(Pin::new(&mut self.body).poll_trailers(cx));
+        self.state = State::Done;
+        match trailer {
             Ok(trailer) => {
+                if let Err(status) = self.check_trailers(trailer.as_ref()) {
+                    return Poll::Ready(Some(Err(status)));
+                }
                 self.trailers = trailer.map(Metadata::from_headers);
             }
             Err(err) => {
@@ -21,12 +21,12 @@ use bytes::{BufMut, Bytes, BytesMut};
 use futures_core::{Stream, TryStream};
 use futures_util::{ready, StreamExt, TryStreamExt};
 use http_body::Body;
-use pin_project::pin_project;
+use pin_project::{pin_project, pinned_drop};
+use tokio_util::sync::CancellationToken;
 
 use super::compression::{compress, CompressionEncoding};
 use crate::triple::codec::{EncodeBuf, Encoder};
 
-#[allow(unused_must_use)]
 pub fn encode<E, B>(
     mut encoder: Box<dyn Encoder<Error = Status, Item = E> + Send + 'static>,
     resp_body: B,
@@ -58,14 +58,19 @@ where
                     if enable_compress {
                         uncompression_buf.clear();
 
-                        encoder.encode(item, &mut EncodeBuf::new(&mut uncompression_buf))
-                            .map_err(|_e| crate::status::Status::new(crate::status::Code::Internal, "encode error".to_string()));
+                        if let Err(err) = encoder.encode(item, &mut EncodeBuf::new(&mut uncompression_buf)) {
+                            yield Err(err);
+                            break;
+                        }
 
                         let len = uncompression_buf.len();
-                        compress(compression_encoding.unwrap(), &mut uncompression_buf, &mut buf, len)
-                            .map_err(|_| crate::status::Status::new(crate::status::Code::Internal, "compress error".to_string()));
-                    } else {
-                        encoder.encode(item, &mut EncodeBuf::new(&mut buf)).map_err(|_e| crate::status::Status::new(crate::status::Code::Internal, "encode error".to_string()));
+                        if let Err(err) = compress(compression_encoding.unwrap(), &mut uncompression_buf, &mut buf, len) {
+                            yield Err(crate::status::Status::new(crate::status::Code::Internal, format!("compress error: {}", err)));
+                            break;
+                        }
+                    } else if let Err(err) = encoder.encode(item, &mut EncodeBuf::new(&mut buf)) {
+                        yield Err(err);
+                        break;
                     }
                     let result=match encode_as_grpc{
                         true=>{
@@ -122,13 +127,14 @@ enum Role {
     Client,
 }
 
-#[pin_project]
+#[pin_project(PinnedDrop)]
 pub struct EncodeBody<S> {
     #[pin]
     inner: S,
     role: Role,
     is_end_stream: bool,
     error: Option<crate::status::Status>,
+    cancellation: Option<CancellationToken>,
 }
 
 impl<S> EncodeBody<S> {
@@ -138,6 +144,7 @@ impl<S> EncodeBody<S> {
             role: Role::Server,
             is_end_stream: false,
             error: None,
+            cancellation: None,
         }
     }
 
@@ -147,6 +154,27 @@ impl<S> EncodeBody<S> {
             role: Role::Client,
             is_end_stream: false,
             error: None,
+            cancellation: None,
+        }
+    }
+
+    /// Cancels `token` if the body is dropped before the trailers are sent,
+    /// and stops polling `inner` once `token` is cancelled.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+}
+
+#[pinned_drop]
+impl<S> PinnedDrop for EncodeBody<S> {
+    fn drop(self: Pin<&mut Self>) {
+        let self_proj = self.project();
+        // hyper drops the body when the peer resets the stream or goes away.
+        if !*self_proj.is_end_stream {
+            if let Some(token) = self_proj.cancellation.take() {
+                token.cancel();
+            }
         }
     }
 }
@@ -168,6 +196,15 @@ where
         cx: &mut std::task::Context<'_>,
     ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
         let mut self_proj = self.project();
+        if let Some(token) = self_proj.cancellation.as_ref() {
+            if token.is_cancelled() {
+                *self_proj.error = Some(crate::status::Status::new(
+                    crate::status::Code::Cancelled,
+                    "stream cancelled".to_string(),
+                ));
+                return None.into();
+            }
+        }
         match ready!(self_proj.inner.try_poll_next_unpin(cx)) {
             Some(Ok(d)) => Some(Ok(d)).into(),
             Some(Err(status)) => {
@@ -187,17 +224,13 @@ where
             return Poll::Ready(Ok(None));
         }
 
+        *self_proj.is_end_stream = true;
         let status = if let Some(status) = self_proj.error.take() {
-            *self_proj.is_end_stream = true;
             status
         } else {
-            crate::status::Status::new(
-                crate::status::Code::Ok,
-                "poll trailer successfully.".to_string(),
-            )
+            crate::status::Status::new(crate::status::Code::Ok, String::new())
         };
-        let http = status.to_http();
 
-        Poll::Ready(Ok(Some(http.headers().to_owned())))
+        Poll::Ready(Ok(Some(status.to_header_map())))
     }
 }
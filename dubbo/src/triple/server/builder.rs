@@ -16,9 +16,12 @@
  */
 
 use std::{
+    collections::HashMap,
+    future::Future,
     net::{SocketAddr, ToSocketAddrs},
-    path::Path,
     str::FromStr,
+    sync::Arc,
+    time::Duration,
 };
 
 use crate::{
@@ -29,17 +32,30 @@ use crate::{
 };
 use http::{Request, Response, Uri};
 use hyper::body::Body;
-use tokio_rustls::rustls::{Certificate, PrivateKey};
 use tower_service::Service;
 
-use crate::{triple::transport::DubboServer, utils, BoxBody};
+use super::{health::HealthReporter, tls::ClientAuth};
+use crate::{
+    triple::transport::{
+        listener::{ConnectionLimits, ConnectionStats, SocketOptions},
+        BoundServer, DubboServer,
+    },
+    BoxBody,
+};
 
 #[derive(Clone, Default, Debug)]
 pub struct ServerBuilder {
     pub listener: String,
     pub addr: Option<SocketAddr>,
-    pub certs: Vec<Certificate>,
-    pub keys: Vec<PrivateKey>,
+    pub cert_file: Option<String>,
+    pub key_file: Option<String>,
+    pub client_auth: ClientAuth,
+    pub client_ca_file: Option<String>,
+    pub allowed_identities: HashMap<String, Vec<String>>,
+    pub proxy_protocol: bool,
+    pub socket_options: SocketOptions,
+    pub connection_limits: ConnectionLimits,
+    pub drain_timeout: Option<Duration>,
     pub service_names: Vec<String>,
     server: DubboServer,
 }
@@ -53,22 +69,74 @@ impl ServerBuilder {
         Self { listener, ..self }
     }
 
+    /// Serves TLS with the PEM files `certs` and `keys`. They are read when
+    /// serving starts, which fails if they are unusable, and reloaded when
+    /// they change.
     pub fn with_tls(self, certs: &str, keys: &str) -> ServerBuilder {
         Self {
-            certs: match utils::tls::load_certs(Path::new(certs)) {
-                Ok(v) => v,
-                Err(err) => {
-                    error!("error loading tls certs {:?}", err);
-                    Vec::new()
-                }
-            },
-            keys: match utils::tls::load_keys(Path::new(keys)) {
-                Ok(v) => v,
-                Err(err) => {
-                    error!("error loading tls keys {:?}", err);
-                    Vec::new()
-                }
-            },
+            cert_file: Some(certs.to_string()),
+            key_file: Some(keys.to_string()),
+            ..self
+        }
+    }
+
+    pub fn with_client_auth(self, client_auth: ClientAuth, client_ca: &str) -> ServerBuilder {
+        Self {
+            client_auth,
+            client_ca_file: Some(client_ca.to_string()),
+            ..self
+        }
+    }
+
+    pub fn with_allowed_identities(
+        mut self,
+        service_name: &str,
+        identities: Vec<String>,
+    ) -> ServerBuilder {
+        self.allowed_identities
+            .insert(service_name.to_string(), identities);
+        self
+    }
+
+    /// Reads a PROXY protocol header ahead of every connection, see
+    /// `DubboServer::with_proxy_protocol`.
+    pub fn with_proxy_protocol(self, proxy_protocol: bool) -> ServerBuilder {
+        Self {
+            proxy_protocol,
+            ..self
+        }
+    }
+
+    pub fn with_socket_options(self, socket_options: SocketOptions) -> ServerBuilder {
+        Self {
+            socket_options,
+            ..self
+        }
+    }
+
+    /// See `DubboServer::with_connection_limits`.
+    pub fn with_connection_limits(self, connection_limits: ConnectionLimits) -> ServerBuilder {
+        Self {
+            connection_limits,
+            ..self
+        }
+    }
+
+    pub fn connection_stats(&self) -> Arc<ConnectionStats> {
+        self.server.connection_stats()
+    }
+
+    /// See `DubboServer::with_health`.
+    pub fn with_health(self, health: HealthReporter) -> ServerBuilder {
+        Self {
+            server: self.server.with_health(health),
+            ..self
+        }
+    }
+
+    pub fn with_drain_timeout(self, drain_timeout: Duration) -> ServerBuilder {
+        Self {
+            drain_timeout: Some(drain_timeout),
             ..self
         }
     }
@@ -88,11 +156,25 @@ impl ServerBuilder {
     }
 
     pub fn build(self) -> Self {
-        let mut server = self.server.with_listener(self.listener.clone());
+        let mut server = self
+            .server
+            .with_listener(self.listener.clone())
+            .with_proxy_protocol(self.proxy_protocol)
+            .with_socket_options(self.socket_options)
+            .with_connection_limits(self.connection_limits);
+        if let Some(drain_timeout) = self.drain_timeout {
+            server = server.with_drain_timeout(drain_timeout);
+        }
 
         {
-            if self.certs.len() != 0 && self.keys.len() != 0 {
-                server = server.with_tls(self.certs.clone(), self.keys.clone());
+            if let (Some(cert_file), Some(key_file)) = (&self.cert_file, &self.key_file) {
+                server = server.with_tls_files(cert_file.into(), key_file.into());
+            }
+            if let Some(client_ca_file) = &self.client_ca_file {
+                server = server.with_client_auth_file(self.client_auth, client_ca_file.into());
+            }
+            for (name, identities) in self.allowed_identities.iter() {
+                server = server.with_allowed_identities(name.clone(), identities.clone());
             }
         }
 
@@ -132,6 +214,24 @@ impl ServerBuilder {
         info!("server starting. addr: {:?}", self.addr.unwrap());
         self.server.serve(self.addr.unwrap()).await
     }
+
+    /// Binds the server, see `DubboServer::bind`.
+    pub async fn bind(self) -> Result<BoundServer, crate::Error> {
+        let addr = self.addr.ok_or("server address is missing")?;
+        self.server.bind(addr).await
+    }
+
+    /// Serves until `signal` completes, then drains the connections, see
+    /// `DubboServer::serve_with_graceful`.
+    pub async fn serve_with_graceful(
+        self,
+        signal: impl Future<Output = ()>,
+    ) -> Result<(), crate::Error> {
+        info!("server starting. addr: {:?}", self.addr.unwrap());
+        self.server
+            .serve_with_graceful(self.addr.unwrap(), signal)
+            .await
+    }
 }
 
 impl From<Url> for ServerBuilder {
@@ -155,8 +255,20 @@ impl From<Url> for ServerBuilder {
             addr: authority.to_string().to_socket_addrs().unwrap().next(),
             service_names: vec![service_name],
             server: DubboServer::default(),
-            certs: Vec::new(),
-            keys: Vec::new(),
+            cert_file: None,
+            key_file: None,
+            client_auth: ClientAuth::None,
+            client_ca_file: None,
+            allowed_identities: HashMap::new(),
+            proxy_protocol: u
+                .query_param_by_key("proxy-protocol")
+                .is_some_and(|v| v == "true"),
+            socket_options: SocketOptions::from_url(&u),
+            connection_limits: ConnectionLimits::from_url(&u),
+            drain_timeout: u
+                .query_param_by_key("drain-timeout")
+                .and_then(|secs| secs.parse().ok())
+                .map(Duration::from_secs),
         }
     }
 }
@@ -0,0 +1,166 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! The `grpc.health.v1.Health` service, telling whether a server is serving
+//! its services. `Check` is supported, `Watch` is not.
+
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    sync::{Arc, RwLock},
+    task::{Context, Poll},
+};
+
+use hyper::Body;
+use tower_service::Service;
+
+use crate::{
+    invocation::{Request, Response},
+    status::{Code, Status},
+    triple::server::TripleServer,
+    BoxBody, BoxFuture,
+};
+
+pub const HEALTH_SERVICE_NAME: &str = "grpc.health.v1.Health";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum ServingStatus {
+    Unknown = 0,
+    Serving = 1,
+    NotServing = 2,
+    ServiceUnknown = 3,
+}
+
+#[derive(Clone, PartialEq, prost::Message, serde::Serialize, serde::Deserialize)]
+pub struct HealthCheckRequest {
+    /// The service to check, or the whole server when empty.
+    #[prost(string, tag = "1")]
+    pub service: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message, serde::Serialize, serde::Deserialize)]
+pub struct HealthCheckResponse {
+    #[prost(enumeration = "ServingStatus", tag = "1")]
+    pub status: i32,
+}
+
+/// The statuses the health service answers with, shared by its clones.
+#[derive(Clone, Debug, Default)]
+pub struct HealthReporter {
+    statuses: Arc<RwLock<HashMap<String, ServingStatus>>>,
+}
+
+impl HealthReporter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the status of `service`, the empty name standing for the server.
+    pub fn set_status(&self, service: &str, status: ServingStatus) {
+        self.statuses
+            .write()
+            .unwrap()
+            .insert(service.to_string(), status);
+    }
+
+    pub fn set_serving(&self, service: &str) {
+        self.set_status(service, ServingStatus::Serving)
+    }
+
+    pub fn set_not_serving(&self, service: &str) {
+        self.set_status(service, ServingStatus::NotServing)
+    }
+
+    /// Sets every service known so far, and the server, to `NotServing`.
+    pub fn set_all_not_serving(&self) {
+        let mut statuses = self.statuses.write().unwrap();
+        statuses.insert(String::new(), ServingStatus::NotServing);
+        for status in statuses.values_mut() {
+            *status = ServingStatus::NotServing;
+        }
+    }
+
+    pub fn status(&self, service: &str) -> ServingStatus {
+        self.statuses
+            .read()
+            .unwrap()
+            .get(service)
+            .copied()
+            .unwrap_or(ServingStatus::ServiceUnknown)
+    }
+}
+
+/// Serves the statuses of a `HealthReporter`, to be added under
+/// `HEALTH_SERVICE_NAME`.
+#[derive(Clone, Debug)]
+pub struct HealthServer {
+    reporter: HealthReporter,
+}
+
+impl HealthServer {
+    pub fn new(reporter: HealthReporter) -> Self {
+        HealthServer { reporter }
+    }
+}
+
+impl Service<Request<HealthCheckRequest>> for HealthReporter {
+    type Response = Response<HealthCheckResponse>;
+    type Error = Status;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<HealthCheckRequest>) -> Self::Future {
+        let status = self.status(&req.message.service);
+        Box::pin(async move {
+            match status {
+                ServingStatus::ServiceUnknown => {
+                    Err(Status::new(Code::NotFound, "unknown service".to_string()))
+                }
+                status => Ok(Response::new(HealthCheckResponse {
+                    status: status as i32,
+                })),
+            }
+        })
+    }
+}
+
+impl Service<http::Request<Body>> for HealthServer {
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<Body>) -> Self::Future {
+        let reporter = self.reporter.clone();
+        Box::pin(async move {
+            match req.uri().path().rsplit('/').next() {
+                Some("Check") => {
+                    let mut server = TripleServer::<HealthCheckRequest, HealthCheckResponse>::new();
+                    Ok(server.unary(reporter, req).await)
+                }
+                _ => Ok(Status::new(Code::Unimplemented, "method not found".to_string()).to_http()),
+            }
+        })
+    }
+}
@@ -16,7 +16,9 @@
  */
 
 pub mod builder;
+pub mod health;
 pub mod service;
+pub mod tls;
 pub mod triple;
 
 pub use triple::TripleServer;
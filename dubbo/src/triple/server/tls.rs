@@ -0,0 +1,367 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    collections::HashMap,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+    path::{Path, PathBuf},
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use http::{Request, Response};
+use hyper::body::Body;
+use serde::{Deserialize, Serialize};
+use tokio_rustls::rustls::{
+    self,
+    server::{AllowAnyAnonymousOrAuthenticatedClient, AllowAnyAuthenticatedClient, NoClientAuth},
+    Certificate, PrivateKey, RootCertStore,
+};
+use tower_service::Service;
+use x509_parser::{certificate::X509Certificate, extensions::GeneralName, prelude::FromDer};
+
+use crate::{
+    logger::tracing::debug,
+    status::{Code, Status},
+    utils::tls::{load_certs, load_keys, Reloadable, DEFAULT_RELOAD_INTERVAL},
+    BoxBody,
+};
+
+/// Whether a server asks TLS clients for a certificate.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClientAuth {
+    #[default]
+    None,
+    /// Verifies the certificate of clients that send one.
+    Optional,
+    /// Rejects clients without a certificate issued by the client CA.
+    Required,
+}
+
+/// The address of the client of a request, in the extensions of the requests
+/// a server takes. Handlers get it from `Metadata::remote_addr`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeerAddr(pub SocketAddr);
+
+/// The certificate chain a TLS client authenticated with, handlers get it
+/// from `Metadata::peer_identity`.
+#[derive(Debug, Clone)]
+pub struct PeerIdentity {
+    certificates: Arc<[Certificate]>,
+    identities: Arc<[String]>,
+}
+
+impl PeerIdentity {
+    pub fn new(certificates: Vec<Certificate>) -> Self {
+        let identities = certificates
+            .first()
+            .map(|cert| subject_alt_names(&cert.0))
+            .unwrap_or_default();
+        PeerIdentity {
+            certificates: certificates.into(),
+            identities: identities.into(),
+        }
+    }
+
+    /// The chain sent by the client, the client certificate comes first.
+    pub fn certificates(&self) -> &[Certificate] {
+        &self.certificates
+    }
+
+    /// The DNS, URI, IP and email subject alternative names of the client
+    /// certificate, e.g. `spiffe://cluster.local/ns/default/sa/greeter`.
+    pub fn identities(&self) -> &[String] {
+        &self.identities
+    }
+
+    pub fn has_identity(&self, identity: &str) -> bool {
+        self.identities.iter().any(|v| v == identity)
+    }
+}
+
+fn subject_alt_names(der: &[u8]) -> Vec<String> {
+    let Ok((_, cert)) = X509Certificate::from_der(der) else {
+        return Vec::new();
+    };
+    let Ok(Some(san)) = cert.subject_alternative_name() else {
+        return Vec::new();
+    };
+
+    san.value
+        .general_names
+        .iter()
+        .filter_map(|name| match name {
+            GeneralName::DNSName(v) | GeneralName::URI(v) | GeneralName::RFC822Name(v) => {
+                Some(v.to_string())
+            }
+            GeneralName::IPAddress(ip) => match ip.len() {
+                4 => <[u8; 4]>::try_from(*ip)
+                    .ok()
+                    .map(|v| Ipv4Addr::from(v).to_string()),
+                16 => <[u8; 16]>::try_from(*ip)
+                    .ok()
+                    .map(|v| Ipv6Addr::from(v).to_string()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+/// The TLS material of a server, given directly or read from PEM files.
+/// Files take precedence and are reloaded once they change.
+#[derive(Clone, Debug)]
+pub(crate) struct ServerTls {
+    pub(crate) certs: Vec<Certificate>,
+    pub(crate) keys: Vec<PrivateKey>,
+    pub(crate) cert_file: Option<PathBuf>,
+    pub(crate) key_file: Option<PathBuf>,
+    pub(crate) client_auth: ClientAuth,
+    pub(crate) client_ca: Vec<Certificate>,
+    pub(crate) client_ca_file: Option<PathBuf>,
+    pub(crate) reload_interval: Duration,
+}
+
+impl Default for ServerTls {
+    fn default() -> Self {
+        ServerTls {
+            certs: Vec::new(),
+            keys: Vec::new(),
+            cert_file: None,
+            key_file: None,
+            client_auth: ClientAuth::None,
+            client_ca: Vec::new(),
+            client_ca_file: None,
+            reload_interval: DEFAULT_RELOAD_INTERVAL,
+        }
+    }
+}
+
+impl ServerTls {
+    pub(crate) fn is_enabled(&self) -> bool {
+        !self.certs.is_empty() || self.cert_file.is_some()
+    }
+
+    /// Builds the server config, failing if any file cannot be used, and
+    /// keeps it up to date with the files.
+    pub(crate) fn watch(
+        &self,
+        alpn_protocols: Vec<Vec<u8>>,
+    ) -> Result<Reloadable<rustls::ServerConfig>, crate::Error> {
+        let files = [&self.cert_file, &self.key_file, &self.client_ca_file]
+            .into_iter()
+            .flatten()
+            .cloned()
+            .collect();
+        let tls = self.clone();
+        Reloadable::watch(files, self.reload_interval, move || {
+            tls.server_config(alpn_protocols.clone())
+        })
+    }
+
+    fn server_config(
+        &self,
+        alpn_protocols: Vec<Vec<u8>>,
+    ) -> Result<rustls::ServerConfig, crate::Error> {
+        let load = |file: &Option<PathBuf>, certs: &Vec<Certificate>| match file {
+            Some(file) => load_certs(file),
+            None => Ok(certs.clone()),
+        };
+        let certs = load(&self.cert_file, &self.certs)?;
+        let client_ca = load(&self.client_ca_file, &self.client_ca)?;
+        let keys = match &self.key_file {
+            Some(file) => load_keys(Path::new(file))?,
+            None => self.keys.clone(),
+        };
+        server_config(certs, keys, self.client_auth, &client_ca, alpn_protocols)
+    }
+}
+
+/// Builds the rustls config of a server. Clients are verified against
+/// `client_ca` unless `client_auth` is `None`, and the first key rustls can
+/// sign with is used for `certs`.
+pub(crate) fn server_config(
+    certs: Vec<Certificate>,
+    keys: Vec<PrivateKey>,
+    client_auth: ClientAuth,
+    client_ca: &[Certificate],
+    alpn_protocols: Vec<Vec<u8>>,
+) -> Result<rustls::ServerConfig, crate::Error> {
+    let verifier = match client_auth {
+        ClientAuth::None => NoClientAuth::boxed(),
+        _ => {
+            let mut roots = RootCertStore::empty();
+            for cert in client_ca {
+                roots.add(cert)?;
+            }
+            if roots.is_empty() {
+                return Err("tls client auth requires a client ca".into());
+            }
+            match client_auth {
+                ClientAuth::Optional => AllowAnyAnonymousOrAuthenticatedClient::new(roots).boxed(),
+                _ => AllowAnyAuthenticatedClient::new(roots).boxed(),
+            }
+        }
+    };
+
+    let key = keys
+        .into_iter()
+        .find(|key| rustls::sign::any_supported_type(key).is_ok())
+        .ok_or("no supported tls private key")?;
+
+    let mut config = rustls::ServerConfig::builder()
+        .with_safe_defaults()
+        .with_client_cert_verifier(verifier)
+        .with_single_cert(certs, key)?;
+    config.alpn_protocols = alpn_protocols;
+    Ok(config)
+}
+
+/// Serves the requests of one connection: attaches the address and TLS peer
+/// of the client to every request and rejects calls to services whose
+/// allow-list does not contain any identity of the peer.
+#[derive(Clone)]
+pub(crate) struct PeerService<S> {
+    inner: S,
+    remote_addr: SocketAddr,
+    peer: Option<PeerIdentity>,
+    allowed_identities: Arc<HashMap<String, Vec<String>>>,
+}
+
+impl<S> PeerService<S> {
+    pub(crate) fn new(
+        inner: S,
+        remote_addr: SocketAddr,
+        peer: Option<PeerIdentity>,
+        allowed_identities: Arc<HashMap<String, Vec<String>>>,
+    ) -> Self {
+        PeerService {
+            inner,
+            remote_addr,
+            peer,
+            allowed_identities,
+        }
+    }
+
+    fn authorize(&self, service_name: &str) -> Result<(), Status> {
+        let Some(allowed) = self.allowed_identities.get(service_name) else {
+            return Ok(());
+        };
+        match &self.peer {
+            None => Err(Status::new(
+                Code::Unauthenticated,
+                "client certificate required".to_string(),
+            )),
+            Some(peer) if allowed.iter().any(|v| peer.has_identity(v)) => Ok(()),
+            Some(_) => Err(Status::new(
+                Code::PermissionDenied,
+                format!("client is not allowed to call {}", service_name),
+            )),
+        }
+    }
+}
+
+impl<S> Service<Request<Body>> for PeerService<S>
+where
+    S: Service<Request<Body>, Response = Response<BoxBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = Response<BoxBody>;
+    type Error = S::Error;
+    type Future = crate::BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<Body>) -> Self::Future {
+        // the path is `/{service}/{method}`
+        let service_name = req.uri().path().split('/').nth(1).unwrap_or_default();
+        debug!(
+            "{} {} from {}",
+            req.method(),
+            req.uri().path(),
+            self.remote_addr
+        );
+        if let Err(status) = self.authorize(service_name) {
+            return Box::pin(async move { Ok(status.to_http()) });
+        }
+
+        req.extensions_mut().insert(PeerAddr(self.remote_addr));
+        if let Some(peer) = &self.peer {
+            req.extensions_mut().insert(peer.clone());
+        }
+        Box::pin(self.inner.call(req))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::utils::tls::{load_certs, load_keys};
+
+    const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+    fn certs(name: &str) -> Vec<Certificate> {
+        load_certs(Path::new(&format!("{}/{}", FIXTURES, name))).unwrap()
+    }
+
+    #[test]
+    fn test_peer_identity() {
+        let peer = PeerIdentity::new(certs("client.crt"));
+        assert_eq!(
+            peer.identities(),
+            [
+                "spiffe://dubbo.apache.org/client",
+                "client.dubbo.apache.org"
+            ]
+        );
+        assert!(peer.has_identity("client.dubbo.apache.org"));
+        assert!(!peer.has_identity("dubbo-test-client"));
+
+        let server = PeerIdentity::new(certs("server.crt"));
+        assert_eq!(server.identities(), ["localhost", "127.0.0.1"]);
+    }
+
+    #[test]
+    fn test_server_config() {
+        let keys = || load_keys(Path::new(&format!("{}/server.key", FIXTURES))).unwrap();
+
+        let config = server_config(
+            certs("server.crt"),
+            keys(),
+            ClientAuth::Required,
+            &certs("ca.crt"),
+            vec![b"h2".to_vec()],
+        )
+        .unwrap();
+        assert_eq!(config.alpn_protocols, vec![b"h2".to_vec()]);
+
+        let no_ca = server_config(
+            certs("server.crt"),
+            keys(),
+            ClientAuth::Optional,
+            &[],
+            Vec::new(),
+        );
+        assert!(no_ca.is_err());
+    }
+}
@@ -21,13 +21,13 @@ use http_body::Body;
 use prost::Message;
 use serde::{Deserialize, Serialize};
 use std::marker::PhantomData;
+use tokio_util::sync::CancellationToken;
 
 use crate::{
     invocation::Request,
     status::Status,
     triple::{
-        client::triple::get_codec,
-        codec::{Decoder, Encoder},
+        codec::{registry::get_codec, Decoder, Encoder},
         compression::{CompressionEncoding, COMPRESSIONS},
         decode::Decoding,
         encode::encode_server,
@@ -73,11 +73,19 @@ where
             .get("content-type")
             .cloned()
             .unwrap_or(HeaderValue::from_str("application/grpc+proto").unwrap());
-        let content_type_str = content_type.to_str().unwrap();
+        let content_type_str = match content_type.to_str() {
+            Ok(val) => val,
+            Err(err) => {
+                return Status::new(crate::status::Code::Internal, err.to_string()).to_http()
+            }
+        };
         let (decoder, encoder): (
             Box<dyn Decoder<Item = M1, Error = Status> + Send + 'static>,
             Box<dyn Encoder<Error = Status, Item = M2> + Send + 'static>,
-        ) = get_codec(content_type_str);
+        ) = match get_codec(content_type_str) {
+            Ok(val) => val,
+            Err(status) => return status.to_http(),
+        };
         let mut accept_encoding = CompressionEncoding::from_accept_encoding(req.headers());
         if self.compression.is_none() || accept_encoding.is_none() {
             accept_encoding = None;
@@ -90,8 +98,17 @@ where
         };
 
         let req_stream = req.map(|body| Decoding::new(body, decoder, compression, true));
+        let (parts, body) = Request::from_http(req_stream).into_parts();
 
-        let resp = service.call(Request::from_http(req_stream)).await;
+        let cancellation = CancellationToken::new();
+        let guard = cancellation.clone().drop_guard();
+        let resp = service
+            .call(Request::from_parts(
+                parts.with_cancellation(cancellation.clone()),
+                body,
+            ))
+            .await;
+        guard.disarm();
 
         let (mut parts, resp_body) = match resp {
             Ok(v) => v.into_http().into_parts(),
@@ -103,7 +120,8 @@ where
             stream::once(future::ready(resp_body)).map(Ok).into_stream(),
             accept_encoding,
             true,
-        );
+        )
+        .with_cancellation(cancellation);
 
         parts
             .headers
@@ -133,11 +151,19 @@ where
             .get("content-type")
             .cloned()
             .unwrap_or(HeaderValue::from_str("application/grpc+proto").unwrap());
-        let content_type_str = content_type.to_str().unwrap();
+        let content_type_str = match content_type.to_str() {
+            Ok(val) => val,
+            Err(err) => {
+                return Status::new(crate::status::Code::Internal, err.to_string()).to_http()
+            }
+        };
         let (decoder, encoder): (
             Box<dyn Decoder<Item = M1, Error = Status> + Send + 'static>,
             Box<dyn Encoder<Error = Status, Item = M2> + Send + 'static>,
-        ) = get_codec(content_type_str);
+        ) = match get_codec(content_type_str) {
+            Ok(val) => val,
+            Err(status) => return status.to_http(),
+        };
         // Firstly, get grpc_accept_encoding from http_header, get compression
         // Secondly, if server enable compression and compression is valid, this method should compress response
         let mut accept_encoding = CompressionEncoding::from_accept_encoding(req.headers());
@@ -152,14 +178,24 @@ where
         };
 
         let req_stream = req.map(|body| Decoding::new(body, decoder, compression, true));
+        let (parts, body) = Request::from_http(req_stream).into_parts();
 
-        let resp = service.call(Request::from_http(req_stream)).await;
+        let cancellation = CancellationToken::new();
+        let guard = cancellation.clone().drop_guard();
+        let resp = service
+            .call(Request::from_parts(
+                parts.with_cancellation(cancellation.clone()),
+                body,
+            ))
+            .await;
+        guard.disarm();
 
         let (mut parts, resp_body) = match resp {
             Ok(v) => v.into_http().into_parts(),
             Err(err) => return err.to_http(),
         };
-        let resp_body = encode_server(encoder, resp_body, compression, true);
+        let resp_body =
+            encode_server(encoder, resp_body, compression, true).with_cancellation(cancellation);
 
         parts
             .headers
@@ -189,11 +225,19 @@ where
             .get("content-type")
             .cloned()
             .unwrap_or(HeaderValue::from_str("application/grpc+proto").unwrap());
-        let content_type_str = content_type.to_str().unwrap();
+        let content_type_str = match content_type.to_str() {
+            Ok(val) => val,
+            Err(err) => {
+                return Status::new(crate::status::Code::Internal, err.to_string()).to_http()
+            }
+        };
         let (decoder, encoder): (
             Box<dyn Decoder<Item = M1, Error = Status> + Send + 'static>,
             Box<dyn Encoder<Error = Status, Item = M2> + Send + 'static>,
-        ) = get_codec(content_type_str);
+        ) = match get_codec(content_type_str) {
+            Ok(val) => val,
+            Err(status) => return status.to_http(),
+        };
         // Firstly, get grpc_accept_encoding from http_header, get compression
         // Secondly, if server enable compression and compression is valid, this method should compress response
         let mut accept_encoding = CompressionEncoding::from_accept_encoding(req.headers());
@@ -208,21 +252,35 @@ where
         };
         let req_stream = req.map(|body| Decoding::new(body, decoder, compression, true));
         let (parts, mut body) = Request::from_http(req_stream).into_parts();
-        let msg = body.try_next().await.unwrap().ok_or_else(|| {
-            crate::status::Status::new(crate::status::Code::Unknown, "request wrong".to_string())
+        let msg = body.try_next().await.and_then(|msg| {
+            msg.ok_or_else(|| {
+                crate::status::Status::new(
+                    crate::status::Code::Internal,
+                    "missing request message".to_string(),
+                )
+            })
         });
         let msg = match msg {
             Ok(v) => v,
             Err(err) => return err.to_http(),
         };
 
-        let resp = service.call(Request::from_parts(parts, msg)).await;
+        let cancellation = CancellationToken::new();
+        let guard = cancellation.clone().drop_guard();
+        let resp = service
+            .call(Request::from_parts(
+                parts.with_cancellation(cancellation.clone()),
+                msg,
+            ))
+            .await;
+        guard.disarm();
 
         let (mut parts, resp_body) = match resp {
             Ok(v) => v.into_http().into_parts(),
             Err(err) => return err.to_http(),
         };
-        let resp_body = encode_server(encoder, resp_body, compression, true);
+        let resp_body =
+            encode_server(encoder, resp_body, compression, true).with_cancellation(cancellation);
 
         parts
             .headers
@@ -259,25 +317,46 @@ where
             .get("content-type")
             .cloned()
             .unwrap_or(HeaderValue::from_str("application/grpc+proto").unwrap());
-        let content_type_str = content_type.to_str().unwrap();
+        let content_type_str = match content_type.to_str() {
+            Ok(val) => val,
+            Err(err) => {
+                return Status::new(crate::status::Code::Internal, err.to_string()).to_http()
+            }
+        };
         //Determine whether to use the gRPC mode to handle request data
         let handle_request_as_grpc = content_type_str.contains("grpc");
         let (decoder, encoder): (
             Box<dyn Decoder<Item = M1, Error = Status> + Send + 'static>,
             Box<dyn Encoder<Error = Status, Item = M2> + Send + 'static>,
-        ) = get_codec(content_type_str);
+        ) = match get_codec(content_type_str) {
+            Ok(val) => val,
+            Err(status) => return status.to_http(),
+        };
         let req_stream =
             req.map(|body| Decoding::new(body, decoder, compression, handle_request_as_grpc));
         let (parts, mut body) = Request::from_http(req_stream).into_parts();
-        let msg = body.try_next().await.unwrap().ok_or_else(|| {
-            crate::status::Status::new(crate::status::Code::Unknown, "request wrong".to_string())
+        let msg = body.try_next().await.and_then(|msg| {
+            msg.ok_or_else(|| {
+                crate::status::Status::new(
+                    crate::status::Code::Internal,
+                    "missing request message".to_string(),
+                )
+            })
         });
         let msg = match msg {
             Ok(v) => v,
             Err(err) => return err.to_http(),
         };
 
-        let resp = service.call(Request::from_parts(parts, msg)).await;
+        let cancellation = CancellationToken::new();
+        let guard = cancellation.clone().drop_guard();
+        let resp = service
+            .call(Request::from_parts(
+                parts.with_cancellation(cancellation.clone()),
+                msg,
+            ))
+            .await;
+        guard.disarm();
 
         let (mut parts, resp_body) = match resp {
             Ok(v) => v.into_http().into_parts(),
@@ -288,7 +367,8 @@ where
             stream::once(future::ready(resp_body)).map(Ok).into_stream(),
             accept_encoding,
             handle_request_as_grpc,
-        );
+        )
+        .with_cancellation(cancellation);
 
         parts
             .headers
@@ -307,7 +387,9 @@ where
         header: &http::HeaderMap,
     ) -> Result<Option<CompressionEncoding>, crate::status::Status> {
         let encoding = match header.get(GRPC_ENCODING) {
-            Some(val) => val.to_str().unwrap(),
+            Some(val) => val.to_str().map_err(|err| {
+                crate::status::Status::new(crate::status::Code::Internal, err.to_string())
+            })?,
             None => return Ok(None),
         };
 
@@ -15,23 +15,77 @@
  * limitations under the License.
  */
 
-use crate::StdError;
-use hyper::client::{conn::Builder, service::Connect};
+use std::{
+    io,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use crate::{logger::tracing::debug, StdError};
+use futures_util::{future, task::noop_waker_ref};
+use hyper::client::conn::{Builder, SendRequest};
+use remoting_net::{
+    pool::{Connect, Pool, PoolConfig},
+    Address,
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    sync::OnceCell,
+};
+use tower::ServiceExt;
 use tower_service::Service;
 
-use crate::{boxed, invoker::clone_body::CloneBody, triple::transport::connector::get_connector};
+use crate::{
+    boxed,
+    invoker::clone_body::CloneBody,
+    triple::{
+        client::tls::ClientTls,
+        transport::connector::{get_connector, https_connector::HttpsConnector, Connector},
+    },
+    utils::boxed_clone::BoxCloneService,
+};
+
+type BoxConnector = BoxCloneService<http::Uri, super::io::BoxIO, StdError>;
 
-type HyperConnect = Connect<
-    crate::utils::boxed_clone::BoxCloneService<http::Uri, super::io::BoxIO, StdError>,
-    CloneBody,
-    http::Uri,
->;
+/// How long the provider is left alone after its GOAWAY, before a call may
+/// connect to it again.
+const GOAWAY_RETRY_INTERVAL: Duration = Duration::from_secs(5);
 
+/// Calls a provider over one HTTP/2 connection, made on the first call and
+/// made again once it closes. Clones share the connection.
+///
+/// With more than one connection configured, see `with_connections`, the
+/// calls go over a [`Pool`] of connections to the provider instead.
+#[derive(Clone)]
 pub struct Connection {
     host: hyper::Uri,
     connector: String,
     builder: Builder,
-    connect: Option<HyperConnect>,
+    tls: Option<ClientTls>,
+    connect: Option<BoxConnector>,
+    connections: usize,
+    pool: Option<Pool<Http2Connect>>,
+    // the address of the provider keying the pool, resolved on the first call
+    addr: Arc<OnceCell<Address>>,
+    current: Arc<tokio::sync::Mutex<Current>>,
+    // held while connecting, for concurrent calls to share the connection
+    connecting: Arc<tokio::sync::Mutex<()>>,
+    // counts the connections made, the last one is the current one
+    id: Arc<AtomicU64>,
+    goaway: Arc<AtomicBool>,
+}
+
+/// The connection calls go over.
+#[derive(Default)]
+struct Current {
+    sender: Option<SendRequest<CloneBody>>,
+    // renewed certificates are used by the next connection
+    tls: Option<Arc<rustls::ClientConfig>>,
 }
 
 impl Default for Connection {
@@ -46,7 +100,15 @@ impl Connection {
             host: hyper::Uri::default(),
             connector: "http".to_string(),
             builder: Builder::new(),
+            tls: None,
             connect: None,
+            connections: 1,
+            pool: None,
+            addr: Arc::default(),
+            current: Arc::default(),
+            connecting: Arc::default(),
+            id: Arc::default(),
+            goaway: Arc::default(),
         }
     }
 
@@ -65,12 +127,49 @@ impl Connection {
         self
     }
 
+    /// Connects over TLS with `tls`, taking precedence over the connector.
+    pub fn with_tls(mut self, tls: ClientTls) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    /// Keeps `connections` connections open to the provider, the calls
+    /// taking turns on them. Renewed certificates are used by the
+    /// connections made after the renewal.
+    pub fn with_connections(mut self, connections: usize) -> Self {
+        self.connections = connections.max(1);
+        self
+    }
+
     pub fn build(mut self) -> Self {
-        let builder = self.builder.clone().http2_only(true).to_owned();
-        let hyper_connect: HyperConnect = Connect::new(get_connector(&self.connector), builder);
-        self.connect = Some(hyper_connect);
+        self.builder.http2_only(true);
+        let connector = match self.tls.clone() {
+            Some(tls) => BoxCloneService::new(Connector::new(HttpsConnector::new().with_tls(tls))),
+            None => get_connector(&self.connector),
+        };
+        if self.connections > 1 {
+            let connections = self.connections as u32;
+            let connect = Http2Connect {
+                host: self.host.clone(),
+                connect: connector.clone(),
+                builder: self.builder.clone(),
+                ids: self.id.clone(),
+                goaway: self.goaway.clone(),
+            };
+            let config = PoolConfig::default()
+                .with_max_per_key(connections)
+                .with_min_idle(Some(connections));
+            self.pool = Some(Pool::new(connect, config));
+        }
+        self.connect = Some(connector);
         self
     }
+
+    /// Set once the provider sent a GOAWAY as it shuts down, until a new
+    /// connection is made or `GOAWAY_RETRY_INTERVAL` passed.
+    pub fn goaway(&self) -> Arc<AtomicBool> {
+        self.goaway.clone()
+    }
 }
 
 impl Service<http::Request<CloneBody>> for Connection {
@@ -88,28 +187,284 @@ impl Service<http::Request<CloneBody>> for Connection {
             None => {
                 panic!("connection must be built before use")
             }
-            Some(ref mut connect) => connect.poll_ready(cx).map_err(|e| e.into()),
+            Some(ref mut connect) => connect.poll_ready(cx),
         }
     }
 
     fn call(&mut self, req: http::Request<CloneBody>) -> Self::Future {
-        match self.connect {
-            None => {
-                panic!("connection must be built before use")
-            }
-            Some(ref mut connect) => {
-                let uri = self.host.clone();
-                let call_fut = connect.call(uri);
-                let fut = async move {
-                    let mut con = call_fut.await.unwrap();
-                    con.call(req)
-                        .await
-                        .map_err(|err| err.into())
-                        .map(|res| res.map(boxed))
+        let Some(connect) = self.connect.clone() else {
+            panic!("connection must be built before use")
+        };
+        if let Some(pool) = self.pool.clone() {
+            let host = self.host.clone();
+            let addr = self.addr.clone();
+            return Box::pin(async move {
+                let addr = addr.get_or_try_init(|| resolve(&host)).await?;
+                let response = {
+                    let mut sender = pool.get(addr).await?;
+                    future::poll_fn(|cx| sender.poll_ready(cx)).await?;
+                    // given back once the request is queued, for the next
+                    // call to take the next connection
+                    sender.send_request(req)
                 };
+                response
+                    .await
+                    .map_err(|err| err.into())
+                    .map(|res| res.map(boxed))
+            });
+        }
+        let uri = self.host.clone();
+        let builder = self.builder.clone();
+        let tls = self.tls.as_ref().map(|tls| tls.current());
+        let current = self.current.clone();
+        let connecting = self.connecting.clone();
+        let ids = self.id.clone();
+        let goaway = self.goaway.clone();
 
-                return Box::pin(fut);
+        Box::pin(async move {
+            // queues the request, the response is awaited without the lock
+            let response = loop {
+                let id = {
+                    let mut locked = current.lock().await;
+                    let renewed = match (&locked.tls, &tls) {
+                        (Some(old), Some(new)) => !Arc::ptr_eq(old, new),
+                        _ => false,
+                    };
+                    if let Some(sender) = locked.sender.as_mut() {
+                        if !renewed && sender.ready().await.is_ok() {
+                            break sender.send_request(req);
+                        }
+                    }
+                    ids.load(Ordering::SeqCst)
+                };
+
+                // calls keep going over the current connection while connecting
+                let _connecting = connecting.lock().await;
+                if ids.load(Ordering::SeqCst) != id {
+                    // connected by another call meanwhile
+                    continue;
+                }
+                let id = id + 1;
+                let io = connect.oneshot(uri).await?;
+                let io = GoawayWatch::new(io, {
+                    let ids = ids.clone();
+                    let goaway = goaway.clone();
+                    move || on_goaway(id, ids, goaway)
+                });
+                let (mut sender, conn) = builder.handshake(io).await?;
+                tokio::spawn(async move {
+                    if let Err(err) = conn.await {
+                        debug!("connection closed: {:?}", err);
+                    }
+                });
+
+                let mut locked = current.lock().await;
+                ids.store(id, Ordering::SeqCst);
+                goaway.store(false, Ordering::SeqCst);
+                locked.tls = tls;
+                // a replaced connection closes once its calls completed
+                let response = sender.send_request(req);
+                locked.sender = Some(sender);
+                break response;
+            };
+            response
+                .await
+                .map_err(|err| err.into())
+                .map(|res| res.map(boxed))
+        })
+    }
+}
+
+/// Resolves the address of the provider at `host`.
+async fn resolve(host: &hyper::Uri) -> io::Result<Address> {
+    let name = host.host().unwrap_or_default();
+    let port = host.port_u16().unwrap_or(80);
+    tokio::net::lookup_host((name, port))
+        .await?
+        .next()
+        .map(Address::from)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("cannot resolve {host}")))
+}
+
+/// Makes the HTTP/2 connections of a [`Pool`] to the provider at `host`,
+/// the pool keying them by its address.
+struct Http2Connect {
+    host: hyper::Uri,
+    connect: BoxConnector,
+    builder: Builder,
+    ids: Arc<AtomicU64>,
+    goaway: Arc<AtomicBool>,
+}
+
+#[async_trait::async_trait]
+impl Connect for Http2Connect {
+    type Connection = SendRequest<CloneBody>;
+
+    async fn connect(&self, _addr: &Address) -> io::Result<Self::Connection> {
+        let io = self
+            .connect
+            .clone()
+            .oneshot(self.host.clone())
+            .await
+            .map_err(io::Error::other)?;
+        let id = self.ids.fetch_add(1, Ordering::SeqCst) + 1;
+        self.goaway.store(false, Ordering::SeqCst);
+        let io = GoawayWatch::new(io, {
+            let ids = self.ids.clone();
+            let goaway = self.goaway.clone();
+            move || on_goaway(id, ids, goaway)
+        });
+        let (sender, conn) = self.builder.handshake(io).await.map_err(io::Error::other)?;
+        tokio::spawn(async move {
+            if let Err(err) = conn.await {
+                debug!("connection closed: {:?}", err);
             }
+        });
+        Ok(sender)
+    }
+
+    async fn is_valid(&self, conn: &mut Self::Connection) -> io::Result<()> {
+        future::poll_fn(|cx| conn.poll_ready(cx))
+            .await
+            .map_err(io::Error::other)
+    }
+
+    fn has_broken(&self, conn: &mut Self::Connection) -> bool {
+        let mut cx = Context::from_waker(noop_waker_ref());
+        matches!(conn.poll_ready(&mut cx), Poll::Ready(Err(_)))
+    }
+}
+
+/// Makes the provider unavailable for a while, unless the connection `id`
+/// was replaced already.
+fn on_goaway(id: u64, ids: Arc<AtomicU64>, goaway: Arc<AtomicBool>) {
+    if ids.load(Ordering::SeqCst) != id {
+        return;
+    }
+    debug!("the provider sent a GOAWAY");
+    goaway.store(true, Ordering::SeqCst);
+    tokio::spawn(async move {
+        tokio::time::sleep(GOAWAY_RETRY_INTERVAL).await;
+        if ids.load(Ordering::SeqCst) == id {
+            goaway.store(false, Ordering::SeqCst);
+        }
+    });
+}
+
+/// Reads the frame headers of a connection as they arrive, to call
+/// `on_goaway` on a GOAWAY, which hyper only tells once the connection
+/// closed.
+struct GoawayWatch<IO, F> {
+    inner: IO,
+    on_goaway: Option<F>,
+    // the frame header being read
+    header: [u8; 9],
+    filled: usize,
+    // the payload bytes left of the current frame
+    skip: usize,
+}
+
+const FRAME_TYPE_GOAWAY: u8 = 0x7;
+
+impl<IO, F: FnOnce()> GoawayWatch<IO, F> {
+    fn new(inner: IO, on_goaway: F) -> Self {
+        GoawayWatch {
+            inner,
+            on_goaway: Some(on_goaway),
+            header: [0; 9],
+            filled: 0,
+            skip: 0,
+        }
+    }
+
+    fn scan(&mut self, mut bytes: &[u8]) {
+        while !bytes.is_empty() && self.on_goaway.is_some() {
+            if self.skip > 0 {
+                let n = self.skip.min(bytes.len());
+                self.skip -= n;
+                bytes = &bytes[n..];
+                continue;
+            }
+            let n = (self.header.len() - self.filled).min(bytes.len());
+            self.header[self.filled..self.filled + n].copy_from_slice(&bytes[..n]);
+            self.filled += n;
+            bytes = &bytes[n..];
+            if self.filled == self.header.len() {
+                let [a, b, c, kind, ..] = self.header;
+                if kind == FRAME_TYPE_GOAWAY {
+                    if let Some(on_goaway) = self.on_goaway.take() {
+                        on_goaway();
+                    }
+                }
+                self.skip = u32::from_be_bytes([0, a, b, c]) as usize;
+                self.filled = 0;
+            }
+        }
+    }
+}
+
+impl<IO: AsyncRead + Unpin, F: FnOnce() + Unpin> AsyncRead for GoawayWatch<IO, F> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        let res = Pin::new(&mut self.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = res {
+            self.scan(&buf.filled()[before..]);
+        }
+        res
+    }
+}
+
+impl<IO: AsyncWrite + Unpin, F: Unpin> AsyncWrite for GoawayWatch<IO, F> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    // frames the provider sends: SETTINGS, a PING and a GOAWAY
+    const FRAMES: &[u8] = &[
+        0, 0, 6, 0x4, 0, 0, 0, 0, 0, 0, 3, 0, 0, 0, 100, //
+        0, 0, 8, 0x6, 0, 0, 0, 0, 0, 0x7, 0x7, 0x7, 0x7, 0x7, 0x7, 0x7, 0x7, //
+        0, 0, 8, 0x7, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0,
+    ];
+
+    #[test]
+    fn test_goaway_watch() {
+        // a payload looking like a GOAWAY header is skipped
+        let seen = Cell::new(false);
+        let mut watch = GoawayWatch::new((), || seen.set(true));
+        watch.scan(&FRAMES[..32]);
+        assert!(!seen.get());
+
+        // split anywhere, the GOAWAY is seen once its header arrived
+        for split in 0..FRAMES.len() {
+            let seen = Cell::new(0);
+            let mut watch = GoawayWatch::new((), || seen.set(seen.get() + 1));
+            watch.scan(&FRAMES[..split]);
+            assert_eq!(seen.get(), usize::from(split >= 41));
+            watch.scan(&FRAMES[split..]);
+            assert_eq!(seen.get(), 1);
         }
     }
 }
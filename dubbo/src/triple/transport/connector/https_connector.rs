@@ -18,39 +18,49 @@
 use std::{
     net::{Ipv4Addr, SocketAddr, SocketAddrV4},
     str::FromStr,
-    sync::Arc,
 };
 
 use crate::logger::tracing::info;
 use http::Uri;
 use hyper::client::connect::dns::Name;
-use rustls_native_certs::load_native_certs;
 use tokio::net::TcpStream;
-use tokio_rustls::{
-    client::TlsStream,
-    rustls::{self},
-    TlsConnector as TlsConnectorTokio,
-};
+use tokio_rustls::client::TlsStream;
 use tower_service::Service;
 
-use crate::triple::transport::resolver::{dns::DnsResolver, Resolve};
+use crate::triple::{
+    client::tls::ClientTls,
+    transport::resolver::{dns::DnsResolver, Resolve},
+};
 
 #[derive(Clone, Default)]
 pub struct HttpsConnector<R = DnsResolver> {
     resolver: R,
+    tls: Option<ClientTls>,
 }
 
 impl HttpsConnector {
     pub fn new() -> Self {
         Self {
             resolver: DnsResolver::default(),
+            tls: None,
         }
     }
 }
 
 impl<R> HttpsConnector<R> {
     pub fn new_with_resolver(resolver: R) -> HttpsConnector<R> {
-        Self { resolver }
+        Self {
+            resolver,
+            tls: None,
+        }
+    }
+
+    /// Uses `tls` instead of the native root certificates.
+    pub fn with_tls(self, tls: ClientTls) -> Self {
+        Self {
+            tls: Some(tls),
+            ..self
+        }
     }
 }
 
@@ -106,24 +116,14 @@ where
             addrs[0]
         };
 
-        let mut root_store = rustls::RootCertStore::empty();
-
-        for cert in load_native_certs()? {
-            root_store.add(&rustls::Certificate(cert.0))?;
-        }
-
-        let config = rustls::ClientConfig::builder()
-            .with_safe_defaults()
-            .with_root_certificates(root_store)
-            .with_no_client_auth();
-
-        let connector = TlsConnectorTokio::from(Arc::new(config));
+        let tls = match &self.tls {
+            Some(tls) => tls.clone(),
+            None => ClientTls::native()?,
+        };
 
         let stream = TcpStream::connect(&addr).await?;
-        let domain = rustls::ServerName::try_from(host).map_err(|err| {
-            crate::status::Status::new(crate::status::Code::Internal, err.to_string())
-        })?;
-        let stream = connector.connect(domain, stream).await?;
+        let domain = tls.server_name(host)?;
+        let stream = tls.connector().connect(domain, stream).await?;
 
         Ok(stream)
     }
@@ -0,0 +1,721 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Keeping the connections of a server in check: how many there are, how
+//! many come from one address, how fast they come in and how long they may
+//! sit idle.
+
+use std::{
+    collections::HashMap,
+    fmt, io,
+    net::{IpAddr, SocketAddr},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use http::{HeaderMap, Request, Response};
+use http_body::Body;
+use remoting_net::proxy_protocol::ProxyHeaderReader;
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    sync::{watch, OwnedSemaphorePermit, Semaphore},
+    time::Instant,
+};
+use tower_service::Service;
+
+use super::{BoxListener, Listener};
+use crate::{
+    logger::tracing::{debug, warn},
+    status::Status,
+    triple::transport::io::BoxIO,
+    BoxBody, Url,
+};
+
+/// What becomes of connections beyond `ConnectionLimits::max_connections`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Overflow {
+    /// They are accepted and closed right away.
+    #[default]
+    Reject,
+    /// They are not accepted until another one closes, waiting in the
+    /// backlog of the socket.
+    Queue,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConnectionLimits {
+    /// The most connections open at once, counting the ones still sending
+    /// their PROXY protocol header.
+    pub max_connections: Option<usize>,
+    pub overflow: Overflow,
+    /// The most connections open at once from one IP address, further ones
+    /// are closed right away.
+    pub max_connections_per_ip: Option<usize>,
+    /// The most connections accepted per second, with bursts of as many.
+    pub accept_rate: Option<u32>,
+    /// HTTP/2 connections are closed once they served no request for this
+    /// long, or did not finish their TLS handshake in this time. Connections
+    /// of other protocols, such as Dubbo2, are left open.
+    pub idle_timeout: Option<Duration>,
+}
+
+impl ConnectionLimits {
+    pub fn with_max_connections(self, max_connections: usize, overflow: Overflow) -> Self {
+        Self {
+            max_connections: Some(max_connections),
+            overflow,
+            ..self
+        }
+    }
+
+    pub fn with_max_connections_per_ip(self, max_connections_per_ip: usize) -> Self {
+        Self {
+            max_connections_per_ip: Some(max_connections_per_ip),
+            ..self
+        }
+    }
+
+    pub fn with_accept_rate(self, per_second: u32) -> Self {
+        Self {
+            accept_rate: Some(per_second.max(1)),
+            ..self
+        }
+    }
+
+    pub fn with_idle_timeout(self, idle_timeout: Duration) -> Self {
+        Self {
+            idle_timeout: Some(idle_timeout),
+            ..self
+        }
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        *self == ConnectionLimits::default()
+    }
+
+    /// Reads the limits from the parameters of a protocol url:
+    /// `max-connections`, `connection-overflow` of `reject` or `queue`,
+    /// `max-connections-per-ip`, `accept-rate` per second and `idle-timeout`
+    /// in seconds. Values that do not parse are ignored.
+    pub fn from_url(url: &Url) -> Self {
+        fn param<T: std::str::FromStr>(url: &Url, key: &str) -> Option<T> {
+            let value = url.query_param_by_key(key)?;
+            let parsed = value.parse().ok();
+            if parsed.is_none() {
+                warn!("ignoring invalid {}: {:?}", key, value);
+            }
+            parsed
+        }
+
+        let mut limits = ConnectionLimits::default();
+        if let Some(max) = param(url, "max-connections") {
+            let overflow = match url.query_param_by_key("connection-overflow").as_deref() {
+                Some("queue") => Overflow::Queue,
+                _ => Overflow::Reject,
+            };
+            limits = limits.with_max_connections(max, overflow);
+        }
+        if let Some(max) = param(url, "max-connections-per-ip") {
+            limits = limits.with_max_connections_per_ip(max);
+        }
+        if let Some(rate) = param(url, "accept-rate") {
+            limits = limits.with_accept_rate(rate);
+        }
+        if let Some(secs) = param(url, "idle-timeout") {
+            limits = limits.with_idle_timeout(Duration::from_secs(secs));
+        }
+        limits
+    }
+}
+
+/// Counters of the connections of a server, for monitoring.
+#[derive(Debug, Default)]
+pub struct ConnectionStats {
+    active: AtomicUsize,
+    accepted: AtomicU64,
+    rejected: AtomicU64,
+    rejected_per_ip: AtomicU64,
+    throttled: AtomicU64,
+    closed_idle: AtomicU64,
+}
+
+impl ConnectionStats {
+    /// The connections open now.
+    pub fn active(&self) -> usize {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    /// The connections accepted and kept so far.
+    pub fn accepted(&self) -> u64 {
+        self.accepted.load(Ordering::Relaxed)
+    }
+
+    /// The connections closed for `max_connections`.
+    pub fn rejected(&self) -> u64 {
+        self.rejected.load(Ordering::Relaxed)
+    }
+
+    /// The connections closed for `max_connections_per_ip`.
+    pub fn rejected_per_ip(&self) -> u64 {
+        self.rejected_per_ip.load(Ordering::Relaxed)
+    }
+
+    /// How often accepting waited for `accept_rate`.
+    pub fn throttled(&self) -> u64 {
+        self.throttled.load(Ordering::Relaxed)
+    }
+
+    /// The connections closed for `idle_timeout`.
+    pub fn closed_idle(&self) -> u64 {
+        self.closed_idle.load(Ordering::Relaxed)
+    }
+}
+
+/// A token bucket of `burst` tokens, refilled at one per `interval`.
+struct RateLimiter {
+    interval: Duration,
+    burst: Duration,
+    // when the bucket is full again
+    full_at: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    fn new(per_second: u32) -> Self {
+        let interval = Duration::from_secs(1) / per_second;
+        RateLimiter {
+            interval,
+            burst: interval * per_second,
+            full_at: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// How long to wait for a token, which is taken right away.
+    fn take(&self) -> Duration {
+        let mut full_at = self.full_at.lock().unwrap();
+        let now = Instant::now();
+        *full_at = (*full_at).max(now) + self.interval;
+        (*full_at - now).saturating_sub(self.burst)
+    }
+}
+
+type IpCounts = Arc<Mutex<HashMap<IpAddr, usize>>>;
+
+/// Held by a connection for as long as it is open.
+struct ConnectionGuard {
+    stats: Arc<ConnectionStats>,
+    _permit: Option<OwnedSemaphorePermit>,
+    ip: Option<(IpAddr, IpCounts)>,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.stats.active.fetch_sub(1, Ordering::Relaxed);
+        if let Some((ip, counts)) = &self.ip {
+            let mut counts = counts.lock().unwrap();
+            if let Some(count) = counts.get_mut(ip) {
+                *count -= 1;
+                if *count == 0 {
+                    counts.remove(ip);
+                }
+            }
+        }
+    }
+}
+
+/// Takes the connections of a listener as fast as `accept_rate` lets it, as
+/// long as `max_connections` are not open.
+struct Admission {
+    inner: BoxListener,
+    overflow: Overflow,
+    stats: Arc<ConnectionStats>,
+    connections: Option<Arc<Semaphore>>,
+    rate: Option<RateLimiter>,
+}
+
+/// A connection let in by `Admission`.
+struct Admitted {
+    addr: SocketAddr,
+    permit: Option<OwnedSemaphorePermit>,
+}
+
+impl fmt::Debug for Admitted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.addr)
+    }
+}
+
+impl Admission {
+    async fn accept(&self) -> io::Result<(BoxIO, Admitted)> {
+        loop {
+            let queued = match (&self.connections, self.overflow) {
+                (Some(connections), Overflow::Queue) => {
+                    Some(connections.clone().acquire_owned().await.unwrap())
+                }
+                _ => None,
+            };
+            if let Some(rate) = &self.rate {
+                let wait = rate.take();
+                if !wait.is_zero() {
+                    self.stats.throttled.fetch_add(1, Ordering::Relaxed);
+                    tokio::time::sleep(wait).await;
+                }
+            }
+
+            let (io, addr) = self.inner.accept().await?;
+
+            let permit = match (queued, &self.connections) {
+                (Some(permit), _) => Some(permit),
+                (None, Some(connections)) => match connections.clone().try_acquire_owned() {
+                    Ok(permit) => Some(permit),
+                    Err(_) => {
+                        self.stats.rejected.fetch_add(1, Ordering::Relaxed);
+                        debug!("too many connections, closing the one of {:?}", addr);
+                        continue;
+                    }
+                },
+                (None, None) => None,
+            };
+            return Ok((io, Admitted { addr, permit }));
+        }
+    }
+}
+
+/// Wraps a listener to apply `ConnectionLimits` to its connections.
+pub struct LimitedListener {
+    admission: Arc<Admission>,
+    // the connections of `admission` with their PROXY protocol header read
+    proxied: Option<tokio::sync::Mutex<ProxyHeaderReader<BoxIO, Admitted>>>,
+    limits: ConnectionLimits,
+    stats: Arc<ConnectionStats>,
+    ips: IpCounts,
+}
+
+impl LimitedListener {
+    pub fn new(inner: BoxListener, limits: ConnectionLimits, stats: Arc<ConnectionStats>) -> Self {
+        let admission = Admission {
+            inner,
+            overflow: limits.overflow,
+            stats: stats.clone(),
+            connections: limits
+                .max_connections
+                .map(|max| Arc::new(Semaphore::new(max))),
+            rate: limits.accept_rate.map(RateLimiter::new),
+        };
+        LimitedListener {
+            admission: Arc::new(admission),
+            proxied: None,
+            limits,
+            stats,
+            ips: Arc::default(),
+        }
+    }
+
+    /// Reads the PROXY protocol header of every connection, see
+    /// `ProxyProtocolListener`. Connections count towards `max_connections`
+    /// from their accept, and towards `max_connections_per_ip` as the
+    /// connections of the client the header tells.
+    pub fn with_proxy_protocol(self) -> Self {
+        let accepted = futures_util::stream::unfold(self.admission.clone(), |admission| async {
+            let conn = admission.accept().await;
+            Some((conn, admission))
+        });
+        LimitedListener {
+            proxied: Some(tokio::sync::Mutex::new(ProxyHeaderReader::new(accepted))),
+            ..self
+        }
+    }
+
+    async fn admit(&self) -> io::Result<(BoxIO, Admitted)> {
+        let Some(proxied) = &self.proxied else {
+            return self.admission.accept().await;
+        };
+        match proxied.lock().await.accept().await {
+            Some(conn) => {
+                let (io, mut admitted, header) = conn?;
+                if let Some(header) = header {
+                    admitted.addr = header.source;
+                }
+                Ok((io, admitted))
+            }
+            None => Err(io::Error::other("listener closed")),
+        }
+    }
+
+    /// Counts a connection of `ip` unless it has `max` already.
+    fn count_ip(&self, ip: IpAddr, max: usize) -> Option<(IpAddr, IpCounts)> {
+        let mut counts = self.ips.lock().unwrap();
+        let count = counts.entry(ip).or_default();
+        if *count >= max {
+            return None;
+        }
+        *count += 1;
+        Some((ip, self.ips.clone()))
+    }
+}
+
+#[async_trait]
+impl Listener for LimitedListener {
+    type Conn = BoxIO;
+
+    async fn accept(&self) -> io::Result<(Self::Conn, SocketAddr)> {
+        loop {
+            let (io, Admitted { addr, permit }) = self.admit().await?;
+            let ip = match self.limits.max_connections_per_ip {
+                Some(max) => match self.count_ip(addr.ip(), max) {
+                    Some(ip) => Some(ip),
+                    None => {
+                        self.stats.rejected_per_ip.fetch_add(1, Ordering::Relaxed);
+                        debug!("too many connections of {}, closing one", addr.ip());
+                        continue;
+                    }
+                },
+                None => None,
+            };
+
+            self.stats.active.fetch_add(1, Ordering::Relaxed);
+            self.stats.accepted.fetch_add(1, Ordering::Relaxed);
+            let guard = ConnectionGuard {
+                stats: self.stats.clone(),
+                _permit: permit,
+                ip,
+            };
+            let io = LimitedIo {
+                inner: io,
+                _guard: guard,
+            };
+            return Ok((BoxIO::new(io), addr));
+        }
+    }
+}
+
+/// A connection of a `LimitedListener`, counted until it is dropped.
+struct LimitedIo {
+    inner: BoxIO,
+    _guard: ConnectionGuard,
+}
+
+impl AsyncRead for LimitedIo {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for LimitedIo {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// Counts the requests in flight on a connection, so that `idle_timeout`
+/// only closes connections serving none.
+#[derive(Clone)]
+pub(crate) struct InFlight(Arc<watch::Sender<usize>>);
+
+impl InFlight {
+    pub(crate) fn new() -> Self {
+        InFlight(Arc::new(watch::channel(0).0))
+    }
+
+    /// Counts a request until the guard is dropped.
+    fn start(&self) -> InFlightGuard {
+        self.0.send_modify(|requests| *requests += 1);
+        InFlightGuard(self.0.clone())
+    }
+
+    /// Resolves once no request was in flight for `timeout`, counting it in
+    /// `stats`.
+    pub(crate) async fn idle(&self, timeout: Duration, stats: &ConnectionStats) {
+        let mut requests = self.0.subscribe();
+        loop {
+            // the sender is kept by `self`, so waiting does not fail
+            let _ = requests.wait_for(|requests| *requests == 0).await;
+            let busy = tokio::time::timeout(timeout, requests.wait_for(|requests| *requests > 0))
+                .await
+                .is_ok();
+            if !busy {
+                stats.closed_idle.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        }
+    }
+}
+
+struct InFlightGuard(Arc<watch::Sender<usize>>);
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.0.send_modify(|requests| *requests -= 1);
+    }
+}
+
+/// Counts every request in `InFlight` until its response body is done.
+#[derive(Clone)]
+pub(crate) struct InFlightService<S> {
+    inner: S,
+    in_flight: InFlight,
+}
+
+impl<S> InFlightService<S> {
+    pub(crate) fn new(inner: S, in_flight: InFlight) -> Self {
+        InFlightService { inner, in_flight }
+    }
+}
+
+impl<S, B> Service<Request<B>> for InFlightService<S>
+where
+    S: Service<Request<B>, Response = Response<BoxBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = Response<BoxBody>;
+    type Error = S::Error;
+    type Future = crate::BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        let guard = self.in_flight.start();
+        let resp = self.inner.call(req);
+        Box::pin(async move {
+            let resp = resp.await?;
+            Ok(resp.map(|body| {
+                BoxBody::new(InFlightBody {
+                    inner: body,
+                    _guard: guard,
+                })
+            }))
+        })
+    }
+}
+
+struct InFlightBody {
+    inner: BoxBody,
+    _guard: InFlightGuard,
+}
+
+impl Body for InFlightBody {
+    type Data = Bytes;
+    type Error = Status;
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        Pin::new(&mut self.inner).poll_data(cx)
+    }
+
+    fn poll_trailers(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        Pin::new(&mut self.inner).poll_trailers(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> http_body::SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpStream,
+    };
+
+    use super::*;
+    use crate::triple::transport::listener::{ListenerExt, TcpListener};
+
+    async fn listen(limits: ConnectionLimits) -> (LimitedListener, SocketAddr) {
+        let inner = TcpListener::bind("127.0.0.1:0".parse().unwrap())
+            .await
+            .unwrap();
+        let addr = inner.local_addr();
+        let listener = LimitedListener::new(inner.boxed(), limits, Arc::default());
+        (listener, addr)
+    }
+
+    // whether the server closed the connection
+    async fn closed(stream: &mut TcpStream) -> bool {
+        let mut buf = [0; 1];
+        let read = tokio::time::timeout(Duration::from_millis(200), stream.read(&mut buf)).await;
+        matches!(read, Ok(Ok(0)) | Ok(Err(_)))
+    }
+
+    #[test]
+    fn test_from_url() {
+        let url: Url = "tri://0.0.0.0:8888/Greeter?max-connections=100\
+            &connection-overflow=queue&max-connections-per-ip=10&accept-rate=50&idle-timeout=x"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            ConnectionLimits::from_url(&url),
+            ConnectionLimits::default()
+                .with_max_connections(100, Overflow::Queue)
+                .with_max_connections_per_ip(10)
+                .with_accept_rate(50)
+        );
+        let url: Url = "tri://0.0.0.0:8888/Greeter".parse().unwrap();
+        assert!(ConnectionLimits::from_url(&url).is_unlimited());
+    }
+
+    #[tokio::test]
+    async fn test_max_connections() {
+        let (listener, addr) =
+            listen(ConnectionLimits::default().with_max_connections(1, Overflow::Reject)).await;
+        let stats = listener.stats.clone();
+        let listener = Arc::new(listener);
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let accepting = listener.clone();
+        let task = tokio::spawn(async move {
+            while let Ok((io, _)) = accepting.accept().await {
+                let _ = tx.send(io);
+            }
+        });
+
+        let mut first = TcpStream::connect(addr).await.unwrap();
+        let first_io = rx.recv().await.unwrap();
+        let mut second = TcpStream::connect(addr).await.unwrap();
+        assert!(closed(&mut second).await);
+        assert!(!closed(&mut first).await);
+        assert_eq!((stats.active(), stats.rejected()), (1, 1));
+
+        drop(first_io);
+        assert!(closed(&mut first).await);
+        let _third = TcpStream::connect(addr).await.unwrap();
+        let _third_io = rx.recv().await.unwrap();
+        assert_eq!((stats.accepted(), stats.active()), (2, 1));
+        task.abort();
+    }
+
+    #[tokio::test]
+    async fn test_max_connections_per_ip() {
+        let (listener, addr) =
+            listen(ConnectionLimits::default().with_max_connections_per_ip(2)).await;
+        let connecting = tokio::spawn(async move {
+            let mut streams = vec![];
+            for _ in 0..3 {
+                streams.push(TcpStream::connect(addr).await.unwrap());
+            }
+            streams
+        });
+        let (_a, _) = listener.accept().await.unwrap();
+        let (b, _) = listener.accept().await.unwrap();
+        let mut streams = connecting.await.unwrap();
+        // closes the third one, then waits for more
+        let accept = tokio::time::timeout(Duration::from_millis(200), listener.accept()).await;
+        assert!(accept.is_err());
+        assert!(closed(&mut streams[2]).await);
+        assert_eq!(listener.stats.rejected_per_ip(), 1);
+
+        drop(b);
+        let _d = TcpStream::connect(addr).await.unwrap();
+        let (_d, _) = listener.accept().await.unwrap();
+        assert_eq!(listener.stats.active(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_accept_rate() {
+        let (listener, addr) = listen(ConnectionLimits::default().with_accept_rate(20)).await;
+        let _streams = tokio::spawn(async move {
+            let mut streams = vec![];
+            for _ in 0..25 {
+                streams.push(TcpStream::connect(addr).await.unwrap());
+            }
+            streams
+        });
+        let start = Instant::now();
+        let mut conns = vec![];
+        for _ in 0..25 {
+            conns.push(listener.accept().await.unwrap());
+        }
+        // a burst of 20, then 5 more at 50ms each
+        assert!(start.elapsed() >= Duration::from_millis(200));
+        assert!(listener.stats.throttled() >= 4);
+    }
+
+    #[tokio::test]
+    async fn test_max_connections_with_proxy_protocol() {
+        let (listener, addr) =
+            listen(ConnectionLimits::default().with_max_connections(1, Overflow::Reject)).await;
+        let listener = listener.with_proxy_protocol();
+        let stats = listener.stats.clone();
+        let listener = Arc::new(listener);
+        let accepting = listener.clone();
+        let accepted = tokio::spawn(async move { accepting.accept().await.unwrap() });
+
+        // still sending its header, yet counted
+        let mut first = TcpStream::connect(addr).await.unwrap();
+        first.write_all(b"PROXY TCP4 10.0.0.1 ").await.unwrap();
+        let mut second = TcpStream::connect(addr).await.unwrap();
+        assert!(closed(&mut second).await);
+        assert_eq!(stats.rejected(), 1);
+
+        first.write_all(b"10.0.0.2 5000 8888\r\n").await.unwrap();
+        let (_io, peer) = accepted.await.unwrap();
+        assert_eq!(peer, "10.0.0.1:5000".parse().unwrap());
+        assert_eq!(stats.active(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_idle_timeout() {
+        let stats = ConnectionStats::default();
+        let in_flight = InFlight::new();
+        let timeout = Duration::from_millis(200);
+
+        // a request running past the timeout keeps the connection open
+        let request = in_flight.start();
+        let idle =
+            tokio::time::timeout(Duration::from_millis(500), in_flight.idle(timeout, &stats));
+        assert!(idle.await.is_err());
+        assert_eq!(stats.closed_idle(), 0);
+
+        drop(request);
+        let start = Instant::now();
+        in_flight.idle(timeout, &stats).await;
+        assert!(start.elapsed() >= timeout);
+        assert_eq!(stats.closed_idle(), 1);
+    }
+}
@@ -15,7 +15,10 @@
  * limitations under the License.
  */
 
+pub mod limited;
+pub mod proxy_protocol;
 pub mod tcp_listener;
+pub mod unified;
 #[cfg(any(target_os = "macos", target_family = "unix"))]
 pub mod unix_listener;
 
@@ -26,7 +29,10 @@ use async_trait::async_trait;
 use tokio::io::{AsyncRead, AsyncWrite};
 
 use super::io::BoxIO;
-pub use tcp_listener::TcpListener;
+pub use limited::{ConnectionLimits, ConnectionStats, LimitedListener, Overflow};
+pub use proxy_protocol::ProxyProtocolListener;
+pub use tcp_listener::{SocketOptions, TcpListener};
+pub use unified::{ConnectionHandler, Protocol, ProtocolHandlers, UnifiedListener};
 
 #[async_trait]
 pub trait Listener: Send + Sync {
@@ -62,11 +68,21 @@ impl<T: Listener> Listener for WrappedListener<T> {
     }
 }
 
-pub async fn get_listener(name: String, addr: SocketAddr) -> Result<BoxListener, crate::Error> {
+/// Binds the listener `name` to `addr`, returning it with the address it is
+/// bound to, which tells the port picked for port 0.
+pub async fn get_listener(
+    name: String,
+    addr: SocketAddr,
+    options: SocketOptions,
+) -> Result<(BoxListener, SocketAddr), crate::Error> {
     match name.as_str() {
-        "tcp" => Ok(TcpListener::bind(addr).await?.boxed()),
+        "tcp" => {
+            let listener = TcpListener::bind_with(addr, options).await?;
+            let local_addr = listener.local_addr();
+            Ok((listener.boxed(), local_addr))
+        }
         #[cfg(any(target_os = "macos", target_family = "unix"))]
-        "unix" => Ok(unix_listener::UnixListener::bind(addr).await?.boxed()),
+        "unix" => Ok((unix_listener::UnixListener::bind(addr).await?.boxed(), addr)),
         _ => {
             warn!("no support listener: {:?}", name);
             Err(Box::new(crate::status::DubboError::new(format!(
@@ -0,0 +1,62 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Listening behind L4 load balancers that send the address of the client in
+//! a PROXY protocol header.
+
+use std::{io, net::SocketAddr};
+
+use async_trait::async_trait;
+use remoting_net::proxy_protocol::ProxyHeaderReader;
+use tokio::sync::Mutex;
+
+use super::{BoxListener, Listener};
+use crate::triple::transport::io::BoxIO;
+
+/// Wraps a listener to read the PROXY protocol header of every connection.
+/// The address returned with a connection is the one of the client the header
+/// tells, connections without a valid header are closed.
+pub struct ProxyProtocolListener {
+    reader: Mutex<ProxyHeaderReader<BoxIO, SocketAddr>>,
+}
+
+impl ProxyProtocolListener {
+    pub fn new(inner: BoxListener) -> Self {
+        let accepted = futures_util::stream::unfold(inner, |inner| async move {
+            let conn = inner.accept().await;
+            Some((conn, inner))
+        });
+        ProxyProtocolListener {
+            reader: Mutex::new(ProxyHeaderReader::new(accepted)),
+        }
+    }
+}
+
+#[async_trait]
+impl Listener for ProxyProtocolListener {
+    type Conn = BoxIO;
+
+    async fn accept(&self) -> io::Result<(Self::Conn, SocketAddr)> {
+        match self.reader.lock().await.accept().await {
+            Some(conn) => {
+                let (io, proxy, header) = conn?;
+                Ok((io, header.map_or(proxy, |header| header.source)))
+            }
+            None => Err(io::Error::other("listener closed")),
+        }
+    }
+}
@@ -15,24 +15,273 @@
  * limitations under the License.
  */
 
-use std::{net::SocketAddr, task};
+use std::{
+    io,
+    net::SocketAddr,
+    sync::atomic::{AtomicUsize, Ordering},
+    task,
+    time::Duration,
+};
 
 use super::Listener;
-use crate::logger::tracing::error;
+use crate::{
+    logger::tracing::{debug, error, warn},
+    Url,
+};
 use async_trait::async_trait;
 use futures_core::Stream;
 use hyper::server::accept::Accept;
+use remoting_net::Address;
+use socket2::{Domain, Protocol, SockRef, Socket, TcpKeepalive, Type};
 use tokio::net::{TcpListener as tokioTcpListener, TcpStream};
 
+/// How the sockets of a `TcpListener` are set up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SocketOptions {
+    /// Binds a socket per acceptor with SO_REUSEPORT, the kernel spreads the
+    /// new connections over them. Only on unix and with more than one
+    /// acceptor, a single socket is bound otherwise.
+    pub reuse_port: bool,
+    /// The number of sockets accepting connections with `reuse_port`.
+    pub acceptors: usize,
+    /// The length of the queue of connections waiting to be accepted.
+    pub backlog: u32,
+    /// TCP_NODELAY of the connections.
+    pub nodelay: bool,
+    /// How long a connection is idle before TCP keepalive probes are sent,
+    /// none are without.
+    pub keepalive: Option<Duration>,
+    pub send_buffer_size: Option<usize>,
+    pub recv_buffer_size: Option<usize>,
+    /// IPV6_V6ONLY of IPv6 sockets, the system default if unset.
+    pub only_v6: Option<bool>,
+    /// Listens on `[::]` rather than `0.0.0.0` where IPv6 sockets take IPv4
+    /// connections too, see `Address::favor_dual_stack`.
+    pub dual_stack: bool,
+}
+
+impl Default for SocketOptions {
+    fn default() -> Self {
+        SocketOptions {
+            reuse_port: false,
+            acceptors: 1,
+            backlog: 1024,
+            nodelay: false,
+            keepalive: None,
+            send_buffer_size: None,
+            recv_buffer_size: None,
+            only_v6: None,
+            dual_stack: false,
+        }
+    }
+}
+
+impl SocketOptions {
+    pub fn with_reuse_port(self, reuse_port: bool) -> Self {
+        Self { reuse_port, ..self }
+    }
+
+    pub fn with_acceptors(self, acceptors: usize) -> Self {
+        Self {
+            acceptors: acceptors.max(1),
+            ..self
+        }
+    }
+
+    pub fn with_backlog(self, backlog: u32) -> Self {
+        Self { backlog, ..self }
+    }
+
+    pub fn with_nodelay(self, nodelay: bool) -> Self {
+        Self { nodelay, ..self }
+    }
+
+    pub fn with_keepalive(self, keepalive: Duration) -> Self {
+        Self {
+            keepalive: Some(keepalive),
+            ..self
+        }
+    }
+
+    pub fn with_send_buffer_size(self, size: usize) -> Self {
+        Self {
+            send_buffer_size: Some(size),
+            ..self
+        }
+    }
+
+    pub fn with_recv_buffer_size(self, size: usize) -> Self {
+        Self {
+            recv_buffer_size: Some(size),
+            ..self
+        }
+    }
+
+    pub fn with_only_v6(self, only_v6: bool) -> Self {
+        Self {
+            only_v6: Some(only_v6),
+            ..self
+        }
+    }
+
+    pub fn with_dual_stack(self, dual_stack: bool) -> Self {
+        Self { dual_stack, ..self }
+    }
+
+    /// Reads the options from the parameters of a protocol url: `reuse-port`,
+    /// `acceptors`, `backlog`, `tcp-nodelay`, `tcp-keepalive` in seconds,
+    /// `send-buffer-size`, `recv-buffer-size`, `ipv6-only` and `dual-stack`.
+    /// Values that do not parse are ignored.
+    pub fn from_url(url: &Url) -> Self {
+        fn param<T: std::str::FromStr>(url: &Url, key: &str) -> Option<T> {
+            let value = url.query_param_by_key(key)?;
+            let parsed = value.parse().ok();
+            if parsed.is_none() {
+                warn!("ignoring invalid {}: {:?}", key, value);
+            }
+            parsed
+        }
+
+        let mut options = SocketOptions::default();
+        if let Some(reuse_port) = param(url, "reuse-port") {
+            options = options.with_reuse_port(reuse_port);
+        }
+        if let Some(acceptors) = param(url, "acceptors") {
+            options = options.with_acceptors(acceptors);
+        }
+        if let Some(backlog) = param(url, "backlog") {
+            options = options.with_backlog(backlog);
+        }
+        if let Some(nodelay) = param(url, "tcp-nodelay") {
+            options = options.with_nodelay(nodelay);
+        }
+        if let Some(secs) = param(url, "tcp-keepalive") {
+            options = options.with_keepalive(Duration::from_secs(secs));
+        }
+        if let Some(size) = param(url, "send-buffer-size") {
+            options = options.with_send_buffer_size(size);
+        }
+        if let Some(size) = param(url, "recv-buffer-size") {
+            options = options.with_recv_buffer_size(size);
+        }
+        if let Some(only_v6) = param(url, "ipv6-only") {
+            options = options.with_only_v6(only_v6);
+        }
+        if let Some(dual_stack) = param(url, "dual-stack") {
+            options = options.with_dual_stack(dual_stack);
+        }
+        options
+    }
+
+    fn bind(&self, addr: SocketAddr) -> io::Result<tokioTcpListener> {
+        let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+        // like tokio, to rebind while old connections are in TIME_WAIT
+        #[cfg(unix)]
+        socket.set_reuse_address(true)?;
+        #[cfg(unix)]
+        socket.set_reuse_port(self.reuse_port && self.acceptors > 1)?;
+        if addr.is_ipv6() {
+            if let Some(only_v6) = self.only_v6.or(self.dual_stack.then_some(false)) {
+                socket.set_only_v6(only_v6)?;
+            }
+        }
+        // set ahead of listen, the connections inherit them
+        if let Some(size) = self.send_buffer_size {
+            socket.set_send_buffer_size(size)?;
+        }
+        if let Some(size) = self.recv_buffer_size {
+            socket.set_recv_buffer_size(size)?;
+        }
+        socket.set_nonblocking(true)?;
+        socket.bind(&addr.into())?;
+        socket.listen(self.backlog.min(i32::MAX as u32) as i32)?;
+        tokioTcpListener::from_std(socket.into())
+    }
+
+    /// Applies the options of the connections, failures only leave them
+    /// with the defaults of the system.
+    fn configure(&self, stream: &TcpStream) {
+        if self.nodelay {
+            if let Err(err) = stream.set_nodelay(true) {
+                debug!("failed to set TCP_NODELAY: {:?}", err);
+            }
+        }
+        if let Some(time) = self.keepalive {
+            let keepalive = TcpKeepalive::new().with_time(time);
+            if let Err(err) = SockRef::from(stream).set_tcp_keepalive(&keepalive) {
+                debug!("failed to set TCP keepalive: {:?}", err);
+            }
+        }
+    }
+}
+
 pub struct TcpListener {
-    inner: tokioTcpListener,
+    // one per acceptor, bound with SO_REUSEPORT when there are several
+    listeners: Vec<tokioTcpListener>,
+    // the socket accepted on first, for none to be left waiting
+    next: AtomicUsize,
+    options: SocketOptions,
+    local_addr: SocketAddr,
 }
 
 impl TcpListener {
     pub async fn bind(addr: SocketAddr) -> std::io::Result<TcpListener> {
-        let listener = tokioTcpListener::bind(addr).await?;
+        Self::bind_with(addr, SocketOptions::default()).await
+    }
 
-        Ok(TcpListener { inner: listener })
+    pub async fn bind_with(addr: SocketAddr, options: SocketOptions) -> io::Result<TcpListener> {
+        let addr = match options.dual_stack {
+            true => match Address::Ip(addr).favor_dual_stack() {
+                Address::Ip(addr) => addr,
+                #[cfg(target_family = "unix")]
+                _ => addr,
+            },
+            false => addr,
+        };
+
+        let first = options.bind(addr)?;
+        // the others take the port picked for the first one
+        let local_addr = first.local_addr()?;
+        let mut listeners = vec![first];
+        if options.reuse_port && options.acceptors > 1 {
+            match cfg!(unix) {
+                true => {
+                    for _ in 1..options.acceptors {
+                        listeners.push(options.bind(local_addr)?);
+                    }
+                }
+                false => warn!("SO_REUSEPORT is not supported, accepting on one socket"),
+            }
+        }
+
+        Ok(TcpListener {
+            listeners,
+            next: AtomicUsize::new(0),
+            options,
+            local_addr,
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    // for a single task to poll, the sockets wake the last one polling
+    fn poll_conn(
+        &self,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<io::Result<(TcpStream, SocketAddr)>> {
+        let first = self.next.fetch_add(1, Ordering::Relaxed);
+        for i in 0..self.listeners.len() {
+            let listener = &self.listeners[(first + i) % self.listeners.len()];
+            if let task::Poll::Ready(conn) = listener.poll_accept(cx) {
+                return task::Poll::Ready(conn.map(|(stream, addr)| {
+                    self.options.configure(&stream);
+                    (stream, addr)
+                }));
+            }
+        }
+        task::Poll::Pending
     }
 }
 
@@ -41,9 +290,17 @@ impl Listener for TcpListener {
     type Conn = TcpStream;
 
     async fn accept(&self) -> std::io::Result<(Self::Conn, SocketAddr)> {
-        let conn = self.inner.accept().await?;
-
-        Ok(conn)
+        let (stream, addr) = match self.listeners.as_slice() {
+            [listener] => listener.accept().await?,
+            listeners => {
+                let first = self.next.fetch_add(1, Ordering::Relaxed);
+                let accepts = (0..listeners.len())
+                    .map(|i| Box::pin(listeners[(first + i) % listeners.len()].accept()));
+                futures::future::select_all(accepts).await.0?
+            }
+        };
+        self.options.configure(&stream);
+        Ok((stream, addr))
     }
 }
 
@@ -54,8 +311,8 @@ impl Stream for TcpListener {
         self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<Option<Self::Item>> {
-        self.inner.poll_accept(cx).map(|res| match res {
-            Ok(data) => Some(data.0),
+        self.get_mut().poll_conn(cx).map(|res| match res {
+            Ok((stream, _)) => Some(stream),
             Err(err) => {
                 error!("TcpListener poll_next Error: {:?}", err);
                 None
@@ -73,8 +330,8 @@ impl Accept for TcpListener {
         self: std::pin::Pin<&mut Self>,
         cx: &mut task::Context<'_>,
     ) -> std::task::Poll<Option<Result<Self::Conn, Self::Error>>> {
-        self.inner.poll_accept(cx).map(|res| match res {
-            Ok(data) => Some(Ok(data.0)),
+        self.get_mut().poll_conn(cx).map(|res| match res {
+            Ok((stream, _)) => Some(Ok(stream)),
             Err(err) => {
                 error!("TcpListener poll_accept Error: {:?}", err);
                 None
@@ -82,3 +339,79 @@ impl Accept for TcpListener {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+
+    #[test]
+    fn test_from_url() {
+        let url: Url = "tri://0.0.0.0:8888/Greeter?reuse-port=true&acceptors=4&backlog=2048\
+            &tcp-nodelay=true&tcp-keepalive=60&recv-buffer-size=65536&ipv6-only=yes"
+            .parse()
+            .unwrap();
+        let options = SocketOptions::from_url(&url);
+        assert_eq!(
+            options,
+            SocketOptions::default()
+                .with_reuse_port(true)
+                .with_acceptors(4)
+                .with_backlog(2048)
+                .with_nodelay(true)
+                .with_keepalive(Duration::from_secs(60))
+                .with_recv_buffer_size(65536)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_acceptors() {
+        let options = SocketOptions::default()
+            .with_reuse_port(true)
+            .with_acceptors(4)
+            .with_nodelay(true)
+            .with_keepalive(Duration::from_secs(60));
+        let listener = TcpListener::bind_with("127.0.0.1:0".parse().unwrap(), options)
+            .await
+            .unwrap();
+        let addr = listener.local_addr();
+        assert_eq!(listener.listeners.len(), 4);
+        #[cfg(unix)]
+        assert!(SockRef::from(&listener.listeners[3]).reuse_port().unwrap());
+
+        let clients = tokio::spawn(async move {
+            for i in 0..16u8 {
+                let mut stream = TcpStream::connect(addr).await.unwrap();
+                stream.write_all(&[i]).await.unwrap();
+            }
+        });
+        let mut seen = Vec::new();
+        for _ in 0..16 {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            assert!(stream.nodelay().unwrap());
+            assert!(SockRef::from(&stream).keepalive().unwrap());
+            seen.push(stream.read_u8().await.unwrap());
+        }
+        clients.await.unwrap();
+        seen.sort();
+        assert_eq!(seen, (0..16).collect::<Vec<_>>());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_single_acceptor() {
+        // SO_REUSEPORT would let another process take half the connections
+        let options = SocketOptions::default().with_reuse_port(true);
+        let listener = TcpListener::bind_with("127.0.0.1:0".parse().unwrap(), options)
+            .await
+            .unwrap();
+        assert!(!SockRef::from(&listener.listeners[0]).reuse_port().unwrap());
+
+        let options = SocketOptions::default().with_acceptors(4);
+        let listener = TcpListener::bind_with("127.0.0.1:0".parse().unwrap(), options)
+            .await
+            .unwrap();
+        assert_eq!(listener.listeners.len(), 1);
+    }
+}
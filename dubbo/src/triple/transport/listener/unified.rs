@@ -0,0 +1,397 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Port unification: telling the protocol of a connection from its first
+//! bytes, so Triple and Dubbo2 consumers can share one port.
+
+use std::{
+    collections::HashMap,
+    fmt, io,
+    net::SocketAddr,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use bytes::{Buf, Bytes, BytesMut};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, ReadBuf},
+    sync::{mpsc, Mutex},
+    task::JoinHandle,
+};
+
+use super::{BoxListener, Listener};
+use crate::{logger::tracing::debug, triple::transport::io::BoxIO};
+
+/// How long a connection may take to send the bytes telling its protocol.
+pub const SNIFF_TIMEOUT: Duration = Duration::from_secs(10);
+
+const HTTP2_PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+const HTTP1_METHODS: [&[u8]; 9] = [
+    b"GET ",
+    b"POST ",
+    b"PUT ",
+    b"HEAD ",
+    b"DELETE ",
+    b"OPTIONS ",
+    b"PATCH ",
+    b"CONNECT ",
+    b"TRACE ",
+];
+
+const DUBBO2_MAGIC: &[u8] = &[0xda, 0xbb];
+
+// a handshake record of SSL 3.0 or any TLS version
+const TLS_HANDSHAKE: &[u8] = &[0x16, 0x03];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Protocol {
+    /// HTTP/2 with prior knowledge, i.e. Triple and gRPC.
+    Http2,
+    Http1,
+    /// The `dubbo://` protocol of Java Dubbo 2.
+    Dubbo2,
+    /// A TLS ClientHello, the protocol is known after the handshake.
+    Tls,
+}
+
+enum Detected {
+    Protocol(Protocol),
+    Unknown,
+    Incomplete,
+}
+
+/// Whether `prefix` is a prefix of `expected`, or starts with it.
+fn matches(prefix: &[u8], expected: &[u8]) -> Option<bool> {
+    let len = prefix.len().min(expected.len());
+    if prefix[..len] != expected[..len] {
+        Some(false)
+    } else if len < expected.len() {
+        None
+    } else {
+        Some(true)
+    }
+}
+
+fn detect(prefix: &[u8]) -> Detected {
+    let candidates = [
+        (HTTP2_PREFACE, Protocol::Http2),
+        (DUBBO2_MAGIC, Protocol::Dubbo2),
+        (TLS_HANDSHAKE, Protocol::Tls),
+    ]
+    .into_iter()
+    .chain(HTTP1_METHODS.map(|method| (method, Protocol::Http1)));
+
+    let mut incomplete = false;
+    for (expected, protocol) in candidates {
+        match matches(prefix, expected) {
+            Some(true) => return Detected::Protocol(protocol),
+            None => incomplete = true,
+            Some(false) => {}
+        }
+    }
+    if incomplete {
+        Detected::Incomplete
+    } else {
+        Detected::Unknown
+    }
+}
+
+/// Reads from `io` until its protocol is known. The bytes read are replayed
+/// by the returned stream. `None` if the protocol is not supported or the
+/// peer closed the connection first.
+pub async fn sniff<T>(mut io: T) -> io::Result<(Option<Protocol>, Rewind<T>)>
+where
+    T: AsyncRead + Unpin,
+{
+    let mut buf = BytesMut::with_capacity(HTTP2_PREFACE.len());
+    loop {
+        let protocol = match detect(&buf) {
+            Detected::Protocol(protocol) => Some(protocol),
+            Detected::Unknown => None,
+            Detected::Incomplete => {
+                if io.read_buf(&mut buf).await? > 0 {
+                    continue;
+                }
+                None
+            }
+        };
+        return Ok((protocol, Rewind::new(buf.freeze(), io)));
+    }
+}
+
+/// A stream whose first reads return bytes that were already read from it.
+pub struct Rewind<T> {
+    prefix: Bytes,
+    inner: T,
+}
+
+impl<T> Rewind<T> {
+    pub fn new(prefix: Bytes, inner: T) -> Self {
+        Rewind { prefix, inner }
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for Rewind<T> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if self.prefix.has_remaining() {
+            let len = self.prefix.len().min(buf.remaining());
+            buf.put_slice(&self.prefix[..len]);
+            self.prefix.advance(len);
+            return Poll::Ready(Ok(()));
+        }
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for Rewind<T> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// Serves the connections of a protocol sharing the port of a `DubboServer`.
+pub trait ConnectionHandler: Send + Sync + 'static {
+    /// Takes over a connection, without blocking the caller.
+    fn handle(&self, io: BoxIO, peer: SocketAddr);
+}
+
+/// The Dubbo2 exchange server of `remoting_base`.
+impl ConnectionHandler for remoting_base::exchange::ExchangeServer {
+    fn handle(&self, io: BoxIO, peer: SocketAddr) {
+        self.serve_io(io, Some(remoting_net::Address::Ip(peer)));
+    }
+}
+
+/// The handlers of the protocols served next to Triple.
+#[derive(Clone, Default)]
+pub struct ProtocolHandlers(HashMap<Protocol, Arc<dyn ConnectionHandler>>);
+
+impl ProtocolHandlers {
+    pub fn insert(&mut self, protocol: Protocol, handler: Arc<dyn ConnectionHandler>) {
+        self.0.insert(protocol, handler);
+    }
+
+    pub fn get(&self, protocol: Protocol) -> Option<&Arc<dyn ConnectionHandler>> {
+        self.0.get(&protocol)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Debug for ProtocolHandlers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.0.keys()).finish()
+    }
+}
+
+/// Wraps a listener to hand connections of the protocols with a handler
+/// over to it. HTTP and TLS connections without a handler are accepted as
+/// usual, others are closed.
+pub struct UnifiedListener {
+    conns: Mutex<mpsc::Receiver<io::Result<(BoxIO, SocketAddr)>>>,
+    task: JoinHandle<()>,
+}
+
+impl UnifiedListener {
+    pub fn new(inner: BoxListener, handlers: ProtocolHandlers) -> Self {
+        let (tx, rx) = mpsc::channel(128);
+        let task = tokio::spawn(async move {
+            loop {
+                let (io, addr) = match inner.accept().await {
+                    Ok(conn) => conn,
+                    Err(err) => {
+                        if tx.send(Err(err)).await.is_err() {
+                            return;
+                        }
+                        continue;
+                    }
+                };
+                let (tx, handlers) = (tx.clone(), handlers.clone());
+                // a slow peer only holds up its own connection
+                tokio::spawn(async move {
+                    let (protocol, io) = match tokio::time::timeout(SNIFF_TIMEOUT, sniff(io)).await
+                    {
+                        Ok(Ok((Some(protocol), io))) => (protocol, io),
+                        Ok(Ok((None, _))) => {
+                            debug!("unknown protocol, peer address: {:?}", addr);
+                            return;
+                        }
+                        Ok(Err(err)) => {
+                            debug!("sniff failed, peer address: {:?}, err: {:?}", addr, err);
+                            return;
+                        }
+                        Err(_) => {
+                            debug!("sniff timed out, peer address: {:?}", addr);
+                            return;
+                        }
+                    };
+                    dispatch(protocol, BoxIO::new(io), addr, &handlers, &tx).await;
+                });
+            }
+        });
+        UnifiedListener {
+            conns: Mutex::new(rx),
+            task,
+        }
+    }
+}
+
+async fn dispatch(
+    protocol: Protocol,
+    io: BoxIO,
+    addr: SocketAddr,
+    handlers: &ProtocolHandlers,
+    tx: &mpsc::Sender<io::Result<(BoxIO, SocketAddr)>>,
+) {
+    debug!("{:?} connection, peer address: {:?}", protocol, addr);
+    match (handlers.get(protocol), protocol) {
+        (Some(handler), _) => handler.handle(io, addr),
+        (None, Protocol::Http2 | Protocol::Http1 | Protocol::Tls) => {
+            let _ = tx.send(Ok((io, addr))).await;
+        }
+        (None, _) => debug!("no server for {:?}, peer address: {:?}", protocol, addr),
+    }
+}
+
+/// Hands a connection whose TLS handshake is done to the handler of its
+/// protocol. The connection is returned for the Triple server if there is
+/// none, `None` if it was handed over or closed.
+pub(crate) async fn dispatch_tls<T>(
+    io: T,
+    addr: SocketAddr,
+    handlers: &ProtocolHandlers,
+) -> Option<BoxIO>
+where
+    T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (protocol, io) = match tokio::time::timeout(SNIFF_TIMEOUT, sniff(io)).await {
+        Ok(Ok(sniffed)) => sniffed,
+        _ => {
+            debug!("tls sniff failed, peer address: {:?}", addr);
+            return None;
+        }
+    };
+    match protocol.and_then(|protocol| handlers.get(protocol)) {
+        Some(handler) => {
+            handler.handle(BoxIO::new(io), addr);
+            None
+        }
+        None => Some(BoxIO::new(io)),
+    }
+}
+
+#[async_trait]
+impl Listener for UnifiedListener {
+    type Conn = BoxIO;
+
+    async fn accept(&self) -> io::Result<(Self::Conn, SocketAddr)> {
+        match self.conns.lock().await.recv().await {
+            Some(conn) => conn,
+            None => Err(io::Error::other("listener closed")),
+        }
+    }
+}
+
+impl Drop for UnifiedListener {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::AsyncReadExt;
+
+    use super::*;
+
+    fn detected(prefix: &[u8]) -> Option<Option<Protocol>> {
+        match detect(prefix) {
+            Detected::Protocol(protocol) => Some(Some(protocol)),
+            Detected::Unknown => Some(None),
+            Detected::Incomplete => None,
+        }
+    }
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(detected(HTTP2_PREFACE), Some(Some(Protocol::Http2)));
+        assert_eq!(detected(b"PRI * HTTP/2.0"), None);
+        assert_eq!(
+            detected(b"POST /greeter HTTP/1.1"),
+            Some(Some(Protocol::Http1))
+        );
+        assert_eq!(detected(b"P"), None);
+        assert_eq!(detected(b"\xda\xbb\xc2\x00"), Some(Some(Protocol::Dubbo2)));
+        assert_eq!(detected(b"\x16\x03\x01\x02\x00"), Some(Some(Protocol::Tls)));
+        assert_eq!(detected(b"\xda"), None);
+        assert_eq!(detected(b""), None);
+        assert_eq!(detected(b"SSH-2.0-OpenSSH"), Some(None));
+        assert_eq!(detected(b"PUSH"), Some(None));
+    }
+
+    #[tokio::test]
+    async fn test_sniff() {
+        let (mut client, server) = tokio::io::duplex(64);
+        let written = tokio::spawn(async move {
+            // the preface in two writes, followed by a frame
+            tokio::io::AsyncWriteExt::write_all(&mut client, &HTTP2_PREFACE[..5])
+                .await
+                .unwrap();
+            tokio::io::AsyncWriteExt::write_all(&mut client, &HTTP2_PREFACE[5..])
+                .await
+                .unwrap();
+            tokio::io::AsyncWriteExt::write_all(&mut client, b"frame")
+                .await
+                .unwrap();
+        });
+
+        let (protocol, mut io) = sniff(server).await.unwrap();
+        assert_eq!(protocol, Some(Protocol::Http2));
+        written.await.unwrap();
+        let mut buf = vec![0; HTTP2_PREFACE.len() + 5];
+        io.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf[..HTTP2_PREFACE.len()], HTTP2_PREFACE);
+        assert_eq!(&buf[HTTP2_PREFACE.len()..], b"frame");
+
+        // the peer closes before its protocol is known
+        let (client, server) = tokio::io::duplex(64);
+        drop(client);
+        assert_eq!(sniff(server).await.unwrap().0, None);
+    }
+}
@@ -17,10 +17,10 @@
 
 pub mod connection;
 pub mod connector;
-mod io;
+pub mod io;
 pub mod listener;
 pub mod resolver;
 pub mod router;
 pub mod service;
 
-pub use service::DubboServer;
+pub use service::{BoundServer, DubboServer};
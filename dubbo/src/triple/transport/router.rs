@@ -16,6 +16,7 @@
  */
 
 use std::{
+    convert::Infallible,
     fmt,
     pin::Pin,
     task::{Context, Poll},
@@ -28,17 +29,29 @@ use pin_project::pin_project;
 use tower::ServiceExt;
 use tower_service::Service;
 
-use crate::BoxBody;
+use crate::{
+    status::{Code, Status},
+    BoxBody,
+};
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone)]
 pub struct DubboRouter {
     pub router: Router,
 }
 
+impl Default for DubboRouter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl DubboRouter {
     pub fn new() -> DubboRouter {
         Self {
-            router: Router::new(),
+            router: Router::new().fallback(tower::service_fn(|_req: Request<Body>| async {
+                let status = Status::new(Code::Unimplemented, "service not found".to_string());
+                Ok::<_, Infallible>(status.to_http().map(axum::body::boxed))
+            })),
         }
     }
 }
@@ -15,22 +15,44 @@
  * limitations under the License.
  */
 
-use std::{io, net::SocketAddr, sync::Arc};
+use std::{collections::HashMap, net::SocketAddr, path::PathBuf, sync::Arc};
 
-use crate::logger::tracing::{debug, error, info};
+use crate::logger::tracing::{debug, error, info, warn};
 use futures_core::Future;
 use http::{Request, Response};
 use hyper::body::Body;
-use tokio::time::Duration;
+use tokio::{sync::watch, task::JoinSet, time::Duration};
 use tokio_rustls::{
-    rustls,
-    rustls::{Certificate, PrivateKey},
+    rustls::{Certificate, PrivateKey, ServerConfig},
     TlsAcceptor,
 };
 use tower_service::Service;
 
-use super::{listener::get_listener, router::DubboRouter};
-use crate::{triple::transport::io::BoxIO, BoxBody};
+use super::{
+    listener::{
+        get_listener,
+        limited::{InFlight, InFlightService},
+        unified::dispatch_tls,
+        BoxListener, ConnectionHandler, ConnectionLimits, ConnectionStats, LimitedListener,
+        ListenerExt, Protocol, ProtocolHandlers, ProxyProtocolListener, SocketOptions,
+        UnifiedListener,
+    },
+    router::DubboRouter,
+};
+use crate::{
+    triple::{
+        server::{
+            health::{HealthReporter, HealthServer, HEALTH_SERVICE_NAME},
+            tls::{ClientAuth, PeerIdentity, PeerService, ServerTls},
+        },
+        transport::io::BoxIO,
+    },
+    utils::tls::Reloadable,
+    BoxBody,
+};
+
+// as `dubbo.service.shutdown.wait` of dubbo
+const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
 
 #[derive(Default, Clone, Debug)]
 pub struct DubboServer {
@@ -43,8 +65,19 @@ pub struct DubboServer {
     http2_keepalive_timeout: Option<Duration>,
     router: DubboRouter,
     listener: Option<String>,
-    certs: Vec<Certificate>,
-    keys: Vec<PrivateKey>,
+    tls: ServerTls,
+    // service name -> client identities allowed to call it
+    allowed_identities: HashMap<String, Vec<String>>,
+    // servers of the other protocols sharing the port
+    protocols: ProtocolHandlers,
+    proxy_protocol: bool,
+    socket_options: SocketOptions,
+    connection_limits: ConnectionLimits,
+    connection_stats: Arc<ConnectionStats>,
+    health: Option<HealthReporter>,
+    // the services added, for their health
+    service_names: Vec<String>,
+    drain_timeout: Option<Duration>,
 }
 
 impl DubboServer {
@@ -101,10 +134,123 @@ impl DubboServer {
         }
     }
 
-    pub fn with_tls(self, certs: Vec<Certificate>, keys: Vec<PrivateKey>) -> Self {
+    pub fn with_tls(mut self, certs: Vec<Certificate>, keys: Vec<PrivateKey>) -> Self {
+        self.tls.certs = certs;
+        self.tls.keys = keys;
+        self
+    }
+
+    /// Reads the certificate chain and key from PEM files, which are reloaded
+    /// when they change. Serving fails if they cannot be read.
+    pub fn with_tls_files(mut self, cert_file: PathBuf, key_file: PathBuf) -> Self {
+        self.tls.cert_file = Some(cert_file);
+        self.tls.key_file = Some(key_file);
+        self
+    }
+
+    /// Verifies client certificates against `client_ca`, only applies to
+    /// servers with TLS.
+    pub fn with_client_auth(
+        mut self,
+        client_auth: ClientAuth,
+        client_ca: Vec<Certificate>,
+    ) -> Self {
+        self.tls.client_auth = client_auth;
+        self.tls.client_ca = client_ca;
+        self
+    }
+
+    /// Like `with_client_auth`, with the client CA read from a PEM file.
+    pub fn with_client_auth_file(
+        mut self,
+        client_auth: ClientAuth,
+        client_ca_file: PathBuf,
+    ) -> Self {
+        self.tls.client_auth = client_auth;
+        self.tls.client_ca_file = Some(client_ca_file);
+        self
+    }
+
+    /// How often TLS files are checked for changes.
+    pub fn with_tls_reload_interval(mut self, interval: Duration) -> Self {
+        self.tls.reload_interval = interval;
+        self
+    }
+
+    /// Only lets clients with one of `identities` as subject alternative name
+    /// call `service_name`.
+    pub fn with_allowed_identities(
+        mut self,
+        service_name: String,
+        identities: Vec<String>,
+    ) -> Self {
+        self.allowed_identities.insert(service_name, identities);
+        self
+    }
+
+    /// Hands the connections of `protocol` to `handler`, so e.g. Dubbo2
+    /// consumers can use the port of the Triple server. The protocol is told
+    /// from the first bytes of a connection, after the TLS handshake for
+    /// servers with TLS.
+    pub fn with_protocol_handler(
+        mut self,
+        protocol: Protocol,
+        handler: Arc<dyn ConnectionHandler>,
+    ) -> Self {
+        self.protocols.insert(protocol, handler);
+        self
+    }
+
+    /// Reads a PROXY protocol header, version 1 or 2, ahead of every
+    /// connection, for servers behind a load balancer sending one. Calls then
+    /// tell the address of the client rather than the one of the balancer.
+    pub fn with_proxy_protocol(self, proxy_protocol: bool) -> Self {
+        Self {
+            proxy_protocol,
+            ..self
+        }
+    }
+
+    /// Sets up the sockets of the `tcp` listener, e.g. to accept on several
+    /// sockets bound with SO_REUSEPORT.
+    pub fn with_socket_options(self, socket_options: SocketOptions) -> Self {
+        Self {
+            socket_options,
+            ..self
+        }
+    }
+
+    /// Caps the connections served at once, in total and per client address,
+    /// and how fast they are accepted, and closes the HTTP/2 connections that
+    /// served no request for too long.
+    pub fn with_connection_limits(self, connection_limits: ConnectionLimits) -> Self {
+        Self {
+            connection_limits,
+            ..self
+        }
+    }
+
+    /// The counters of the connections, kept while `connection_limits` are
+    /// set.
+    pub fn connection_stats(&self) -> Arc<ConnectionStats> {
+        self.connection_stats.clone()
+    }
+
+    /// Serves `grpc.health.v1.Health` with the statuses of `health`. The
+    /// services of the server are marked serving when it starts, and not
+    /// serving when it shuts down.
+    pub fn with_health(self, health: HealthReporter) -> Self {
+        Self {
+            health: Some(health),
+            ..self
+        }
+    }
+
+    /// How long in-flight calls may take to complete once the server shuts
+    /// down, before their connections are closed. Defaults to 10 seconds.
+    pub fn with_drain_timeout(self, drain_timeout: Duration) -> Self {
         Self {
-            certs: certs,
-            keys: keys,
+            drain_timeout: Some(drain_timeout),
             ..self
         }
     }
@@ -122,8 +268,16 @@ impl DubboServer {
             max_frame_size: None,
             router: DubboRouter::new(),
             listener: None,
-            certs: Vec::new(),
-            keys: Vec::new(),
+            tls: ServerTls::default(),
+            allowed_identities: HashMap::new(),
+            protocols: ProtocolHandlers::default(),
+            proxy_protocol: false,
+            socket_options: SocketOptions::default(),
+            connection_limits: ConnectionLimits::default(),
+            connection_stats: Arc::default(),
+            health: None,
+            service_names: Vec::new(),
+            drain_timeout: None,
         }
     }
 }
@@ -138,7 +292,8 @@ impl DubboServer {
         S::Future: Send + 'static,
         S::Error: Into<crate::Error> + Send + 'static,
     {
-        self.router = self.router.add_service(name, service);
+        self.router = self.router.add_service(name.clone(), service);
+        self.service_names.push(name);
         self
     }
 
@@ -152,15 +307,14 @@ impl DubboServer {
         addr: SocketAddr,
         signal: impl Future<Output = ()>,
     ) -> Result<(), crate::Error> {
-        let svc = self.router.clone();
-        tokio::pin!(signal);
-
-        let http2_keepalive_timeout = self
-            .http2_keepalive_timeout
-            .unwrap_or_else(|| Duration::new(60, 0));
+        self.bind(addr).await?.serve_with_graceful(signal).await
+    }
 
-        let name = match self.listener {
-            Some(v) => v,
+    /// Binds the listener to `addr`, failing if it cannot be bound or the TLS
+    /// files are unusable. Serving is left to the returned server.
+    pub async fn bind(self, addr: SocketAddr) -> Result<BoundServer, crate::Error> {
+        let name = match &self.listener {
+            Some(v) => v.clone(),
             None => {
                 return Err(Box::new(crate::status::DubboError::new(
                     "listener name is empty".to_string(),
@@ -168,25 +322,101 @@ impl DubboServer {
             }
         };
 
-        let acceptor: Option<TlsAcceptor>;
-        if self.certs.len() != 0 && !self.keys.len() != 0 {
-            let mut keys = self.keys;
+        let tls_config = if self.tls.is_enabled() {
+            let alpn_protocols = match self.accept_http2 {
+                true => vec![b"h2".to_vec()],
+                false => vec![b"h2".to_vec(), b"http/1.1".to_vec()],
+            };
+            Some(self.tls.watch(alpn_protocols)?)
+        } else {
+            None
+        };
 
-            let config = rustls::ServerConfig::builder()
-                .with_safe_defaults()
-                .with_no_client_auth()
-                .with_single_cert(self.certs, keys.remove(0))
-                .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        let (listener, local_addr) = get_listener(name, addr, self.socket_options).await?;
+        // the header comes ahead of the bytes telling the protocol; limits
+        // apply to the clients it tells, yet count connections from accept
+        let listener = match (self.connection_limits.is_unlimited(), self.proxy_protocol) {
+            (true, true) => ProxyProtocolListener::new(listener).boxed(),
+            (true, false) => listener,
+            (false, proxy_protocol) => {
+                let limited = LimitedListener::new(
+                    listener,
+                    self.connection_limits,
+                    self.connection_stats.clone(),
+                );
+                match proxy_protocol {
+                    true => limited.with_proxy_protocol().boxed(),
+                    false => limited.boxed(),
+                }
+            }
+        };
+        let listener = match self.protocols.is_empty() {
+            true => listener,
+            false => UnifiedListener::new(listener, self.protocols.clone()).boxed(),
+        };
 
-            acceptor = Some(TlsAcceptor::from(Arc::new(config)));
-        } else {
-            acceptor = None;
+        Ok(BoundServer {
+            server: self,
+            listener,
+            local_addr,
+            tls_config,
+        })
+    }
+}
+
+/// A `DubboServer` bound to its address, see `DubboServer::bind`.
+pub struct BoundServer {
+    server: DubboServer,
+    listener: BoxListener,
+    local_addr: SocketAddr,
+    tls_config: Option<Reloadable<ServerConfig>>,
+}
+
+impl BoundServer {
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Serves until `signal` completes, then stops accepting and lets the
+    /// calls in flight complete, for up to the drain timeout.
+    pub async fn serve_with_graceful(
+        self,
+        signal: impl Future<Output = ()>,
+    ) -> Result<(), crate::Error> {
+        let BoundServer {
+            server,
+            listener,
+            tls_config,
+            ..
+        } = self;
+        let mut router = server.router.clone();
+        if let Some(health) = &server.health {
+            router = router.add_service(
+                HEALTH_SERVICE_NAME.to_string(),
+                HealthServer::new(health.clone()),
+            );
         }
+        let svc = router;
+        tokio::pin!(signal);
+        let drain_timeout = server.drain_timeout.unwrap_or(DEFAULT_DRAIN_TIMEOUT);
 
-        let listener = match get_listener(name, addr).await {
-            Ok(v) => v,
-            Err(err) => return Err(err),
-        };
+        let http2_keepalive_timeout = server
+            .http2_keepalive_timeout
+            .unwrap_or_else(|| Duration::new(60, 0));
+        let allowed_identities = Arc::new(server.allowed_identities);
+        let protocols = server.protocols;
+        let idle_timeout = server.connection_limits.idle_timeout;
+        let connection_stats = server.connection_stats.clone();
+
+        if let Some(health) = &server.health {
+            health.set_serving("");
+            for name in server.service_names.iter() {
+                health.set_serving(name);
+            }
+        }
+        // flipped once shutting down, for the connections to send GOAWAY
+        let (drain_tx, drain_rx) = watch::channel(false);
+        let mut connections = JoinSet::new();
 
         loop {
             tokio::select! {
@@ -194,32 +424,107 @@ impl DubboServer {
                     info!("graceful shutdown");
                     break
                 }
+                Some(_) = connections.join_next(), if !connections.is_empty() => {}
                 res = listener.accept() => {
                     match res {
                         Ok(conn) => {
-                            let (io, local_addr) = conn;
-                            let b :BoxIO;
-
-                            if !acceptor.is_none() {
-                                b = BoxIO::new(acceptor.as_ref().unwrap().clone().accept(io).await?);
-                            } else {
-                                b = io;
-                            }
-
-                            debug!("hyper serve, local address: {:?}", local_addr);
-                            let c = hyper::server::conn::Http::new()
-                                .http2_only(self.accept_http2)
-                                .http2_max_concurrent_streams(self.max_concurrent_streams)
-                                .http2_initial_connection_window_size(self.init_connection_window_size)
-                                .http2_initial_stream_window_size(self.init_stream_window_size)
-                                .http2_keep_alive_interval(self.http2_keepalive_interval)
+                            let (io, peer_addr) = conn;
+                            // renewed certificates apply to new connections
+                            let acceptor = tls_config
+                                .as_ref()
+                                .map(|config| TlsAcceptor::from(config.current()));
+                            let http = hyper::server::conn::Http::new()
+                                .http2_only(server.accept_http2)
+                                .http2_max_concurrent_streams(server.max_concurrent_streams)
+                                .http2_initial_connection_window_size(server.init_connection_window_size)
+                                .http2_initial_stream_window_size(server.init_stream_window_size)
+                                .http2_keep_alive_interval(server.http2_keepalive_interval)
                                 .http2_keep_alive_timeout(http2_keepalive_timeout)
-                                .http2_max_frame_size(self.max_frame_size)
-                                .serve_connection(b,svc.clone()).with_upgrades();
+                                .http2_max_frame_size(server.max_frame_size)
+                                .to_owned();
+                            let svc = svc.clone();
+                            let allowed_identities = allowed_identities.clone();
+                            let protocols = protocols.clone();
+                            let mut drain = drain_rx.clone();
+                            let connection_stats = connection_stats.clone();
+
+                            // a failed handshake only drops its own connection
+                            connections.spawn(async move {
+                                let handshake = async {
+                                    match acceptor {
+                                        Some(acceptor) => match acceptor.accept(io).await {
+                                            Ok(io) => {
+                                                let peer = io
+                                                    .get_ref()
+                                                    .1
+                                                    .peer_certificates()
+                                                    .map(|certs| PeerIdentity::new(certs.to_vec()));
+                                                if protocols.is_empty() {
+                                                    Some((BoxIO::new(io), peer))
+                                                } else {
+                                                    // plain connections were dispatched by the listener
+                                                    dispatch_tls(io, peer_addr, &protocols)
+                                                        .await
+                                                        .map(|io| (io, peer))
+                                                }
+                                            }
+                                            Err(err) => {
+                                                debug!("tls handshake failed, peer address: {:?}, err: {:?}", peer_addr, err);
+                                                None
+                                            }
+                                        },
+                                        None => Some((io, None)),
+                                    }
+                                };
+                                // an idle connection may not hold its slot by never finishing its handshake
+                                let handshake = match idle_timeout {
+                                    Some(timeout) => tokio::time::timeout(timeout, handshake)
+                                        .await
+                                        .unwrap_or_else(|_| {
+                                            debug!("tls handshake timed out, peer address: {:?}", peer_addr);
+                                            None
+                                        }),
+                                    None => handshake.await,
+                                };
+                                let Some((b, peer)): Option<(BoxIO, _)> = handshake else {
+                                    return;
+                                };
+                                let svc = PeerService::new(svc, peer_addr, peer, allowed_identities);
+                                let in_flight = InFlight::new();
+                                let svc = InFlightService::new(svc, in_flight.clone());
+                                let idle = async {
+                                    match idle_timeout {
+                                        Some(timeout) => in_flight.idle(timeout, &connection_stats).await,
+                                        None => std::future::pending().await,
+                                    }
+                                };
 
-                            tokio::spawn(c);
+                                debug!("hyper serve, peer address: {:?}", peer_addr);
+                                let conn = http.serve_connection(b, svc).with_upgrades();
+                                tokio::pin!(conn);
+                                let res = tokio::select! {
+                                    res = &mut conn => res,
+                                    _ = async { drop(drain.wait_for(|draining| *draining).await) } => {
+                                        // GOAWAY, then the calls in flight complete
+                                        conn.as_mut().graceful_shutdown();
+                                        conn.await
+                                    }
+                                    _ = idle => {
+                                        debug!("closing idle connection, peer address: {:?}", peer_addr);
+                                        conn.as_mut().graceful_shutdown();
+                                        conn.await
+                                    }
+                                };
+                                if let Err(err) = res {
+                                    debug!("hyper serve, connection err: {:?}", err);
+                                }
+                            });
                         },
-                        Err(err) => error!("hyper serve, err: {:?}", err),
+                        Err(err) => {
+                            error!("hyper serve, err: {:?}", err);
+                            // e.g. out of file descriptors, until connections close
+                            tokio::time::sleep(Duration::from_millis(100)).await;
+                        }
                     }
                 }
             }
@@ -227,6 +532,24 @@ impl DubboServer {
 
         drop(listener);
 
+        if let Some(health) = &server.health {
+            health.set_all_not_serving();
+        }
+        info!("draining {} connections", connections.len());
+        let _ = drain_tx.send(true);
+        let drained = tokio::time::timeout(drain_timeout, async {
+            while connections.join_next().await.is_some() {}
+        })
+        .await;
+        if drained.is_err() {
+            warn!(
+                "closing {} connections still busy after {:?}",
+                connections.len(),
+                drain_timeout
+            );
+            connections.shutdown().await;
+        }
+
         Ok(())
     }
 }
@@ -15,18 +15,152 @@
  * limitations under the License.
  */
 
+use lazy_static::lazy_static;
 use rustls_pemfile::{certs, ec_private_keys, pkcs8_private_keys, rsa_private_keys};
 use std::{
     fs::File,
     io::{self, BufReader, Cursor, Read},
-    path::Path,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock, Weak},
+    time::{Duration, SystemTime},
 };
+use tokio::sync::broadcast;
 use tokio_rustls::rustls::{Certificate, PrivateKey};
 
+use crate::logger::tracing::{error, info, warn};
+
+/// How often TLS files are checked for changes by default.
+pub const DEFAULT_RELOAD_INTERVAL: Duration = Duration::from_secs(10);
+
+lazy_static! {
+    static ref RELOAD_EVENTS: broadcast::Sender<TlsReloadEvent> = broadcast::channel(16).0;
+}
+
+/// Sent whenever changed TLS files were reloaded.
+#[derive(Debug, Clone)]
+pub struct TlsReloadEvent {
+    pub files: Vec<PathBuf>,
+    /// Set if the new files could not be used, the previous config stays in
+    /// use until the files change again.
+    pub error: Option<String>,
+}
+
+/// Returns a receiver of the reload events of every TLS config of the
+/// process.
+pub fn subscribe_reload_events() -> broadcast::Receiver<TlsReloadEvent> {
+    RELOAD_EVENTS.subscribe()
+}
+
+/// A config built from TLS files, rebuilt whenever one of the files changes.
+pub struct Reloadable<T> {
+    current: Arc<RwLock<Arc<T>>>,
+}
+
+impl<T> Clone for Reloadable<T> {
+    fn clone(&self) -> Self {
+        Reloadable {
+            current: self.current.clone(),
+        }
+    }
+}
+
+impl<T> Reloadable<T>
+where
+    T: Send + Sync + 'static,
+{
+    /// A config that is never reloaded.
+    pub fn fixed(value: T) -> Self {
+        Reloadable {
+            current: Arc::new(RwLock::new(Arc::new(value))),
+        }
+    }
+
+    /// Builds the config and polls `files` every `interval`, rebuilding it
+    /// once they changed. Fails if the initial build fails, later failures
+    /// keep the previous config. Polling stops once every clone is dropped.
+    pub fn watch<F>(files: Vec<PathBuf>, interval: Duration, build: F) -> Result<Self, crate::Error>
+    where
+        F: Fn() -> Result<T, crate::Error> + Send + 'static,
+    {
+        let reloadable = Self::fixed(build()?);
+        if files.is_empty() {
+            return Ok(reloadable);
+        }
+
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => {
+                let current = Arc::downgrade(&reloadable.current);
+                handle.spawn(poll_files(files, interval, current, build));
+            }
+            Err(_) => warn!(
+                "no tokio runtime, tls files {:?} will not be reloaded",
+                files
+            ),
+        }
+        Ok(reloadable)
+    }
+
+    pub fn current(&self) -> Arc<T> {
+        self.current.read().unwrap().clone()
+    }
+}
+
+fn modified(files: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    files
+        .iter()
+        .map(|file| file.metadata().and_then(|v| v.modified()).ok())
+        .collect()
+}
+
+async fn poll_files<T, F>(
+    files: Vec<PathBuf>,
+    interval: Duration,
+    current: Weak<RwLock<Arc<T>>>,
+    build: F,
+) where
+    F: Fn() -> Result<T, crate::Error>,
+{
+    let mut last_modified = modified(&files);
+    loop {
+        tokio::time::sleep(interval).await;
+        let Some(current) = current.upgrade() else {
+            break;
+        };
+
+        let now_modified = modified(&files);
+        if now_modified == last_modified {
+            continue;
+        }
+        last_modified = now_modified;
+
+        let error = match build() {
+            Ok(value) => {
+                *current.write().unwrap() = Arc::new(value);
+                info!("tls files {:?} reloaded", files);
+                None
+            }
+            Err(err) => {
+                error!("error reloading tls files {:?}: {}", files, err);
+                Some(err.to_string())
+            }
+        };
+        let _ = RELOAD_EVENTS.send(TlsReloadEvent {
+            files: files.clone(),
+            error,
+        });
+    }
+}
+
 pub fn load_certs(path: &Path) -> io::Result<Vec<Certificate>> {
-    certs(&mut BufReader::new(File::open(path)?))
-        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid cert"))
-        .map(|mut certs| certs.drain(..).map(Certificate).collect())
+    let certs = certs(&mut BufReader::new(File::open(path)?))
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid cert"))?;
+    if certs.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("no certificate found in {}", path.display()),
+        ));
+    }
+    Ok(certs.into_iter().map(Certificate).collect())
 }
 
 pub fn load_keys(path: &Path) -> io::Result<Vec<PrivateKey>> {
@@ -0,0 +1,163 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Calls a loopback server with the serializations of the codec registry:
+//! the built-in `json` codec, a registered `CustomCodec` and a content-type
+//! the server does not know.
+
+mod common;
+
+use std::{
+    convert::Infallible,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use bytes::{Buf, BufMut};
+use common::Tick;
+use dubbo::{
+    codegen::*,
+    status::{Code, Status},
+    triple::codec::{
+        registry::{self, CustomCodec},
+        DecodeBuf, EncodeBuf,
+    },
+};
+
+const SERVICE_NAME: &str = "org.apache.dubbo.test.Codec";
+
+static ENCODED: AtomicUsize = AtomicUsize::new(0);
+
+/// JSON spelled backwards, which only a peer with the codec can read.
+struct Reversed;
+
+impl CustomCodec for Reversed {
+    fn encode(&self, value: serde_json::Value, dst: &mut EncodeBuf<'_>) -> Result<(), Status> {
+        ENCODED.fetch_add(1, Ordering::SeqCst);
+        let mut bytes = value.to_string().into_bytes();
+        bytes.reverse();
+        dst.put_slice(&bytes);
+        Ok(())
+    }
+
+    fn decode(&self, src: &mut DecodeBuf<'_>) -> Result<serde_json::Value, Status> {
+        let mut bytes = src.copy_to_bytes(src.remaining()).to_vec();
+        bytes.reverse();
+        serde_json::from_slice(&bytes).map_err(|err| Status::new(Code::Internal, err.to_string()))
+    }
+}
+
+#[derive(Clone)]
+struct Echo;
+
+impl Service<Request<Tick>> for Echo {
+    type Response = Response<Tick>;
+    type Error = Status;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<Tick>) -> Self::Future {
+        Box::pin(async move { Ok(Response::new(req.message)) })
+    }
+}
+
+/// Echoes every call, keeping the content-types it was called with.
+#[derive(Clone, Default)]
+struct EchoServer {
+    content_types: Arc<Mutex<Vec<String>>>,
+}
+
+impl Service<http::Request<hyperBody>> for EchoServer {
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<hyperBody>) -> Self::Future {
+        if let Some(content_type) = req.headers().get(http::header::CONTENT_TYPE) {
+            let content_type = content_type.to_str().unwrap_or_default().to_string();
+            self.content_types.lock().unwrap().push(content_type);
+        }
+        Box::pin(async move {
+            let mut server = TripleServer::<Tick, Tick>::new();
+            Ok(server.unary(Echo, req).await)
+        })
+    }
+}
+
+async fn echo(client: &mut TripleClient, seq: u64) -> Result<u64, Status> {
+    let (path, invocation) = common::invocation(SERVICE_NAME, "Echo");
+    let resp = client
+        .unary::<Tick, Tick>(Request::new(Tick { seq }), path, invocation)
+        .await?;
+    Ok(resp.into_parts().1.seq)
+}
+
+// Both serializations share one direct client, see `common::connect`.
+#[tokio::test(flavor = "multi_thread")]
+async fn test_codecs() {
+    registry::register_codec("reversed", Reversed);
+    let server = EchoServer::default();
+    let (addr, _shutdown) = common::serve(SERVICE_NAME, server.clone()).await;
+    let client = common::connect(addr, SERVICE_NAME);
+
+    let mut json = client.clone().with_serialization("json");
+    assert_eq!(echo(&mut json, 1).await.unwrap(), 1);
+
+    let mut reversed = client.with_serialization("reversed");
+    assert_eq!(echo(&mut reversed, 2).await.unwrap(), 2);
+    // the request by the client and the response by the server
+    assert_eq!(ENCODED.load(Ordering::SeqCst), 2);
+
+    assert_eq!(
+        *server.content_types.lock().unwrap(),
+        ["application/grpc+json", "application/grpc+reversed"]
+    );
+}
+
+async fn raw_call(addr: SocketAddr, content_type: &str) -> http::Response<hyperBody> {
+    let client = hyper::Client::builder()
+        .http2_only(true)
+        .build_http::<hyperBody>();
+    let req = http::Request::builder()
+        .method(http::Method::POST)
+        .uri(format!("http://{}/{}/Echo", addr, SERVICE_NAME))
+        .header(http::header::CONTENT_TYPE, content_type)
+        .header(http::header::TE, "trailers")
+        .body(hyperBody::from(common::framed(&Tick::default())))
+        .unwrap();
+    client.request(req).await.unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_unknown_content_type() {
+    let (addr, _shutdown) = common::serve(SERVICE_NAME, EchoServer::default()).await;
+
+    let resp = raw_call(addr, "application/grpc+unknown").await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let status = Status::from_header_map(resp.headers()).expect("grpc-status in headers");
+    assert_eq!(status.code(), Code::Unimplemented);
+}
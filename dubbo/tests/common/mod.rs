@@ -0,0 +1,102 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+#![allow(dead_code)]
+
+use std::{
+    convert::Infallible,
+    net::{SocketAddr, TcpListener},
+    time::Duration,
+};
+
+use dubbo::{codegen::*, triple::transport::DubboServer};
+use tokio::sync::oneshot;
+
+#[derive(Clone, PartialEq, prost::Message, serde::Serialize, serde::Deserialize)]
+pub struct Tick {
+    #[prost(uint64, tag = "1")]
+    pub seq: u64,
+}
+
+/// Encodes `message` as a single uncompressed grpc frame.
+pub fn framed<M: prost::Message>(message: &M) -> Bytes {
+    let mut buf = vec![0u8];
+    buf.extend_from_slice(&(message.encoded_len() as u32).to_be_bytes());
+    message.encode(&mut buf).unwrap();
+    buf.into()
+}
+
+pub fn free_addr() -> SocketAddr {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+}
+
+pub const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+pub fn fixture(name: &str) -> String {
+    format!("{}/{}", FIXTURES, name)
+}
+
+/// Serves `service` on a loopback port until the returned sender is dropped.
+pub async fn serve<S>(service_name: &str, service: S) -> (SocketAddr, oneshot::Sender<()>)
+where
+    S: Service<http::Request<hyperBody>, Response = http::Response<BoxBody>, Error = Infallible>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send + 'static,
+{
+    serve_with(DubboServer::new().add_service(service_name.to_string(), service)).await
+}
+
+/// Like `serve`, with the services and settings of `server`.
+pub async fn serve_with(server: DubboServer) -> (SocketAddr, oneshot::Sender<()>) {
+    let addr = free_addr();
+    let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+    let server = server.with_listener("tcp".to_string());
+    tokio::spawn(async move {
+        let _ = server
+            .serve_with_graceful(addr, async {
+                let _ = shutdown_rx.await;
+            })
+            .await;
+    });
+    // wait for the listener to come up
+    for _ in 0..50 {
+        if tokio::net::TcpStream::connect(addr).await.is_ok() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    (addr, shutdown_tx)
+}
+
+/// Connects a client directly to `addr`. Direct clients of one process share
+/// a single static registry, so a test binary should only connect once.
+pub fn connect(addr: SocketAddr, service_name: &str) -> TripleClient {
+    TripleClient::connect(format!("http://{}?interface={}", addr, service_name))
+}
+
+pub fn invocation(service_name: &str, method: &str) -> (http::uri::PathAndQuery, RpcInvocation) {
+    let path = format!("/{}/{}", service_name, method).try_into().unwrap();
+    let invocation = RpcInvocation::default()
+        .with_service_unique_name(service_name.to_string())
+        .with_method_name(method.to_string());
+    (path, invocation)
+}
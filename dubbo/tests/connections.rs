@@ -0,0 +1,101 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Calls a provider over several pooled HTTP/2 connections, see
+//! `ClientBuilder::with_connections`.
+
+mod common;
+
+use std::{
+    collections::HashSet,
+    convert::Infallible,
+    sync::{Arc, Mutex},
+};
+
+use common::Tick;
+use dubbo::{codegen::*, status::Status};
+
+const SERVICE_NAME: &str = "org.apache.dubbo.test.Connections";
+
+/// Echoes every call, keeping the addresses of the callers.
+#[derive(Clone, Default)]
+struct Echo {
+    callers: Arc<Mutex<Vec<String>>>,
+}
+
+impl Service<Request<Tick>> for Echo {
+    type Response = Response<Tick>;
+    type Error = Status;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<Tick>) -> Self::Future {
+        if let Some(addr) = req.metadata.remote_addr() {
+            self.callers.lock().unwrap().push(addr.to_string());
+        }
+        Box::pin(async move { Ok(Response::new(req.message)) })
+    }
+}
+
+#[derive(Clone, Default)]
+struct EchoServer(Echo);
+
+impl Service<http::Request<hyperBody>> for EchoServer {
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<hyperBody>) -> Self::Future {
+        let echo = self.0.clone();
+        Box::pin(async move {
+            let mut server = TripleServer::<Tick, Tick>::new();
+            Ok(server.unary(echo, req).await)
+        })
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_connections() {
+    let server = EchoServer::default();
+    let (addr, _shutdown) = common::serve(SERVICE_NAME, server.clone()).await;
+    let builder =
+        ClientBuilder::from_static(&format!("http://{}?interface={}", addr, SERVICE_NAME))
+            .with_connections(3);
+    let mut client = TripleClient::new(builder);
+
+    for seq in 0..9 {
+        let (path, invocation) = common::invocation(SERVICE_NAME, "Echo");
+        let resp = client
+            .unary::<Tick, Tick>(Request::new(Tick { seq }), path, invocation)
+            .await
+            .unwrap();
+        assert_eq!(resp.into_parts().1.seq, seq);
+    }
+
+    // the calls take turns on the connections of the pool
+    let callers = server.0.callers.lock().unwrap().clone();
+    assert_eq!(callers.len(), 9);
+    let connections: HashSet<_> = callers.iter().collect();
+    assert_eq!(connections.len(), 3, "callers: {:?}", callers);
+}
@@ -0,0 +1,195 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{collections::HashMap, convert::Infallible, sync::Arc, time::Duration};
+
+use dubbo::{
+    codegen::*,
+    config::{protocol::Protocol, service::ServiceConfig, RootConfig},
+    extension::{
+        registry_extension::{to_extension_url, Registry},
+        EXTENSIONS,
+    },
+    protocol::triple::TRIPLE_SERVICES,
+    utils::boxed_clone::BoxCloneService,
+    Dubbo, StartError, Url,
+};
+use tokio::net::TcpStream;
+
+const SERVICE_NAME: &str = "org.apache.dubbo.test.Handle";
+
+fn register_service() {
+    let service = tower::service_fn(|_req: http::Request<hyperBody>| async {
+        Ok::<_, Infallible>(http::Response::new(empty_body()))
+    });
+    TRIPLE_SERVICES
+        .write()
+        .unwrap()
+        .insert(SERVICE_NAME.to_string(), BoxCloneService::new(service));
+}
+
+fn config(port: u16) -> RootConfig {
+    let mut config = RootConfig::new();
+    config.protocols.insert(
+        "triple".to_string(),
+        Protocol::default()
+            .name("tri".to_string())
+            .ip("127.0.0.1".to_string())
+            .port(port.to_string()),
+    );
+    config.provider.services = HashMap::from([(
+        SERVICE_NAME.to_string(),
+        ServiceConfig::default()
+            .interface(SERVICE_NAME.to_string())
+            .protocol("triple".to_string()),
+    )]);
+    config
+}
+
+#[tokio::test]
+async fn test_start_and_shutdown() {
+    register_service();
+    let mut dubbo = Dubbo::new()
+        .with_config(config(0))
+        .with_shutdown_signals(false);
+    let handle = dubbo.start().await.unwrap();
+
+    let addrs = handle.local_addrs();
+    assert_eq!(addrs.len(), 1);
+    assert_ne!(addrs[0].port(), 0);
+    let service = &handle.services()[0];
+    assert_eq!(service.local_addr, Some(addrs[0]));
+    // the url registered carries the port picked
+    assert_eq!(service.url.port(), Some(addrs[0].port()));
+    TcpStream::connect(addrs[0]).await.unwrap();
+
+    handle.shutdown();
+    tokio::time::timeout(Duration::from_secs(5), handle.wait())
+        .await
+        .expect("shutdown timed out");
+    assert!(TcpStream::connect(addrs[0]).await.is_err());
+}
+
+#[tokio::test]
+async fn test_start_errors() {
+    register_service();
+    let taken = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = taken.local_addr().unwrap().port();
+    let mut dubbo = Dubbo::new()
+        .with_config(config(port))
+        .with_shutdown_signals(false);
+    assert!(matches!(
+        dubbo.start().await.unwrap_err(),
+        StartError::Bind { .. }
+    ));
+
+    let mut config = config(0);
+    config
+        .provider
+        .services
+        .get_mut(SERVICE_NAME)
+        .unwrap()
+        .protocol = "missing".to_string();
+    let mut dubbo = Dubbo::new()
+        .with_config(config)
+        .with_shutdown_signals(false);
+    assert!(matches!(
+        dubbo.start().await.unwrap_err(),
+        StartError::Config(_)
+    ));
+}
+
+#[tokio::test]
+async fn test_init_twice() {
+    register_service();
+    let mut dubbo = Dubbo::new()
+        .with_config(config(0))
+        .with_shutdown_signals(false);
+    dubbo.init().unwrap();
+    dubbo.init().unwrap();
+    let handle = dubbo.start().await.unwrap();
+    assert_eq!(handle.services().len(), 1);
+    handle.shutdown();
+    handle.wait().await;
+}
+
+#[tokio::test]
+async fn test_start_error_unregisters() {
+    register_service();
+    let registry = "static://127.0.0.1:2182";
+    let taken = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = taken.local_addr().unwrap().port();
+    // served first, then the second fails to bind
+    let mut config = config(0);
+    config.protocols.insert(
+        "unbindable".to_string(),
+        Protocol::default()
+            .name("tri".to_string())
+            .ip("127.0.0.1".to_string())
+            .port(port.to_string()),
+    );
+    let other = "org.apache.dubbo.test.Other";
+    config.provider.services.insert(
+        other.to_string(),
+        ServiceConfig::default()
+            .interface(other.to_string())
+            .protocol("unbindable".to_string()),
+    );
+    let mut dubbo = Dubbo::new()
+        .with_config(config)
+        .add_registry(registry)
+        .with_shutdown_signals(false);
+    assert!(matches!(
+        dubbo.start().await.unwrap_err(),
+        StartError::Bind { .. }
+    ));
+
+    let registry = EXTENSIONS
+        .load_registry(to_extension_url(registry.parse().unwrap()))
+        .await
+        .unwrap();
+    let consumer_url: Url = format!("tri://127.0.0.1/{0}?interface={0}", SERVICE_NAME)
+        .parse()
+        .unwrap();
+    let mut changes = registry.subscribe(consumer_url).await.unwrap();
+    assert!(changes.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn test_instances_with_own_configs() {
+    register_service();
+    let mut first = Dubbo::new()
+        .with_config(config(0))
+        .with_shutdown_signals(false);
+    let mut config = config(0);
+    config.protocols.get_mut("triple").unwrap().ip = "127.0.0.2".to_string();
+    let config = Arc::new(config);
+    let mut second = Dubbo::new()
+        .with_config(config.clone())
+        .with_shutdown_signals(false);
+    assert!(Arc::ptr_eq(second.config().unwrap(), &config));
+
+    let first = first.start().await.unwrap();
+    let second = second.start().await.unwrap();
+    assert_eq!(first.local_addrs()[0].ip().to_string(), "127.0.0.1");
+    assert_eq!(second.local_addrs()[0].ip().to_string(), "127.0.0.2");
+
+    for handle in [first, second] {
+        handle.shutdown();
+        handle.wait().await;
+    }
+}
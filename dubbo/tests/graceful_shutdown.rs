@@ -0,0 +1,231 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+mod common;
+
+use std::{
+    convert::Infallible,
+    sync::atomic::Ordering,
+    time::{Duration, Instant},
+};
+
+use common::Tick;
+use dubbo::{
+    codegen::*,
+    invoker::clone_body::CloneBody,
+    status::{Code, Status},
+    triple::{
+        server::health::{
+            HealthCheckRequest, HealthCheckResponse, HealthReporter, ServingStatus,
+            HEALTH_SERVICE_NAME,
+        },
+        transport::{connection::Connection, DubboServer},
+    },
+};
+use prost::Message;
+use tokio::sync::oneshot;
+use tower::ServiceExt;
+
+const SERVICE_NAME: &str = "org.apache.dubbo.test.Slow";
+
+/// Answers after sleeping for `seq` milliseconds.
+#[derive(Clone)]
+struct Slow;
+
+impl Service<Request<Tick>> for Slow {
+    type Response = Response<Tick>;
+    type Error = Status;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<Tick>) -> Self::Future {
+        let seq = req.message.seq;
+        Box::pin(async move {
+            tokio::time::sleep(Duration::from_millis(seq)).await;
+            Ok(Response::new(Tick { seq }))
+        })
+    }
+}
+
+#[derive(Clone)]
+struct SlowServer;
+
+impl Service<http::Request<hyperBody>> for SlowServer {
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<hyperBody>) -> Self::Future {
+        Box::pin(async move {
+            let mut server = TripleServer::<Tick, Tick>::new();
+            Ok(server.unary(Slow, req).await)
+        })
+    }
+}
+
+/// Makes a unary call over `conn`, returning the message answered.
+async fn call<M: Message + Default>(
+    conn: &Connection,
+    addr: std::net::SocketAddr,
+    path: &str,
+    message: &impl Message,
+) -> Result<M, Status> {
+    let req = http::Request::builder()
+        .method(http::Method::POST)
+        .uri(format!("http://{}{}", addr, path))
+        .header(http::header::CONTENT_TYPE, "application/grpc")
+        .header(http::header::TE, "trailers")
+        .body(CloneBody::new(hyperBody::from(common::framed(message))))
+        .unwrap();
+    let resp = conn
+        .clone()
+        .oneshot(req)
+        .await
+        .map_err(|err| Status::new(Code::Unavailable, err.to_string()))?;
+
+    // a trailers-only response tells the status in its headers
+    if let Some(status) = Status::from_header_map(resp.headers()) {
+        if status.code() != Code::Ok {
+            return Err(status);
+        }
+    }
+    let mut body = resp.into_body();
+    let frame = body.data().await.transpose()?;
+    let trailers = body.trailers().await?.unwrap_or_default();
+    match Status::from_header_map(&trailers) {
+        Some(status) if status.code() != Code::Ok => Err(status),
+        _ => Ok(M::decode(&frame.unwrap()[5..]).unwrap()),
+    }
+}
+
+/// Serves `server` until the returned sender fires or is dropped, the handle
+/// completing once the server shut down.
+async fn serve(
+    server: DubboServer,
+) -> (
+    std::net::SocketAddr,
+    oneshot::Sender<()>,
+    tokio::task::JoinHandle<()>,
+) {
+    let addr = common::free_addr();
+    let (tx, rx) = oneshot::channel::<()>();
+    let server = server.with_listener("tcp".to_string());
+    let handle = tokio::spawn(async move {
+        server
+            .serve_with_graceful(addr, async {
+                let _ = rx.await;
+            })
+            .await
+            .unwrap();
+    });
+    for _ in 0..50 {
+        if tokio::net::TcpStream::connect(addr).await.is_ok() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    (addr, tx, handle)
+}
+
+fn connection(addr: std::net::SocketAddr) -> Connection {
+    Connection::new()
+        .with_host(format!("http://{}", addr).parse().unwrap())
+        .build()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_drain() {
+    let health = HealthReporter::new();
+    let server = DubboServer::new()
+        .with_health(health.clone())
+        .add_service(SERVICE_NAME.to_string(), SlowServer);
+    let (addr, shutdown, serving) = serve(server).await;
+    let conn = connection(addr);
+    let check_path = format!("/{}/Check", HEALTH_SERVICE_NAME);
+    let check = HealthCheckRequest {
+        service: SERVICE_NAME.to_string(),
+    };
+
+    let resp: HealthCheckResponse = call(&conn, addr, &check_path, &check).await.unwrap();
+    assert_eq!(resp.status, ServingStatus::Serving as i32);
+    let unknown = HealthCheckRequest {
+        service: "org.apache.dubbo.test.Unknown".to_string(),
+    };
+    let err = call::<HealthCheckResponse>(&conn, addr, &check_path, &unknown)
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::NotFound);
+
+    // in flight while the server shuts down
+    let slow_conn = conn.clone();
+    let slow = tokio::spawn(async move {
+        let path = format!("/{}/Slow", SERVICE_NAME);
+        call::<Tick>(&slow_conn, addr, &path, &Tick { seq: 500 }).await
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    shutdown.send(()).unwrap();
+
+    // the consumer sees the GOAWAY while the call is in flight
+    let goaway = conn.goaway();
+    for _ in 0..20 {
+        if goaway.load(Ordering::SeqCst) {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert!(goaway.load(Ordering::SeqCst));
+    assert!(!slow.is_finished());
+
+    assert_eq!(slow.await.unwrap().unwrap().seq, 500);
+    tokio::time::timeout(Duration::from_secs(2), serving)
+        .await
+        .expect("server not shut down")
+        .unwrap();
+    assert_eq!(health.status(SERVICE_NAME), ServingStatus::NotServing);
+    assert_eq!(health.status(""), ServingStatus::NotServing);
+
+    assert!(goaway.load(Ordering::SeqCst));
+    assert!(tokio::net::TcpStream::connect(addr).await.is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_drain_timeout() {
+    let server = DubboServer::new()
+        .with_drain_timeout(Duration::from_millis(200))
+        .add_service(SERVICE_NAME.to_string(), SlowServer);
+    let (addr, shutdown, serving) = serve(server).await;
+    let conn = connection(addr);
+
+    let slow = tokio::spawn(async move {
+        let path = format!("/{}/Slow", SERVICE_NAME);
+        call::<Tick>(&conn, addr, &path, &Tick { seq: 5000 }).await
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let start = Instant::now();
+    drop(shutdown);
+
+    serving.await.unwrap();
+    assert!(start.elapsed() < Duration::from_secs(2));
+    assert!(slow.await.unwrap().is_err());
+}
@@ -0,0 +1,172 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Triple and Dubbo2 providers of one service behind the same cluster.
+//!
+//! The p2c balancer of the cluster settles ties on the first invoker of the
+//! directory, so a second service only served over Dubbo2 makes sure the same
+//! client goes through the Dubbo2 invoker whatever the order of the first one.
+
+mod common;
+
+use std::{
+    convert::Infallible,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use common::Tick;
+use dubbo::{codegen::*, status::Status};
+use protocol_dubbo2::{Attachments, ResponseData, RpcInvocation};
+use remoting_base::{
+    codec::{dubbo2::DUBBO2, CodecRegistry},
+    exchange::{server::Server, ExchangeServer, Handler},
+};
+use remoting_net::Address;
+
+const SERVICE_NAME: &str = "org.apache.dubbo.test.Counter";
+
+const DUBBO2_SERVICE_NAME: &str = "org.apache.dubbo.test.Dubbo2Counter";
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone)]
+struct Next(Arc<AtomicUsize>);
+
+impl Service<Request<Tick>> for Next {
+    type Response = Response<Tick>;
+    type Error = Status;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<Tick>) -> Self::Future {
+        self.0.fetch_add(1, Ordering::SeqCst);
+        let seq = req.message.seq + 1;
+        Box::pin(async move { Ok(Response::new(Tick { seq })) })
+    }
+}
+
+#[derive(Clone)]
+struct CounterServer(Next);
+
+impl Service<http::Request<hyperBody>> for CounterServer {
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<hyperBody>) -> Self::Future {
+        let next = self.0.clone();
+        Box::pin(async move {
+            let mut server = TripleServer::<Tick, Tick>::new();
+            Ok(server.unary(next, req).await)
+        })
+    }
+}
+
+/// The Dubbo2 provider of both services, takes the `Tick` objects Hessian2
+/// messages are.
+struct Counter {
+    calls: Arc<AtomicUsize>,
+    dubbo2_calls: Arc<AtomicUsize>,
+}
+
+#[async_trait::async_trait]
+impl Handler for Counter {
+    async fn reply(&self, request: remoting_base::Request) -> remoting_base::Response {
+        let invocation = request.body::<RpcInvocation>().unwrap();
+        match invocation.service.as_str() {
+            SERVICE_NAME => self.calls.fetch_add(1, Ordering::SeqCst),
+            DUBBO2_SERVICE_NAME => self.dubbo2_calls.fetch_add(1, Ordering::SeqCst),
+            service => panic!("unknown service {}", service),
+        };
+        assert_eq!(invocation.method, "Next");
+        assert_eq!(invocation.parameter_types, ["Tick"]);
+        assert_eq!(invocation.attachments.get_str("x-trace"), Some("abc"));
+
+        let tick: Tick = protocol_hessian2::from_value(invocation.arguments[0].clone()).unwrap();
+        let value = ResponseData::Value {
+            value: protocol_hessian2::to_value(&Tick { seq: tick.seq + 1 }).unwrap(),
+            attachments: Attachments::new(),
+        };
+        remoting_base::Response::new(request.id, value)
+    }
+}
+
+// The only direct Triple client of this binary, see `common::connect`.
+#[tokio::test(flavor = "multi_thread")]
+async fn test_mixed_providers() {
+    let triple_calls = Arc::new(AtomicUsize::new(0));
+    let (triple_addr, _shutdown) =
+        common::serve(SERVICE_NAME, CounterServer(Next(triple_calls.clone()))).await;
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let dubbo2_calls = Arc::new(AtomicUsize::new(0));
+    let dubbo2_addr = common::free_addr();
+    let codec = CodecRegistry::new().codec(DUBBO2).unwrap();
+    let counter = Counter {
+        calls: calls.clone(),
+        dubbo2_calls: dubbo2_calls.clone(),
+    };
+    let dubbo2 = ExchangeServer::new(Address::Ip(dubbo2_addr), codec, counter);
+    dubbo2.start().await.unwrap();
+
+    let hosts = vec![
+        format!("tri://{}?interface={}", triple_addr, SERVICE_NAME)
+            .parse()
+            .unwrap(),
+        format!("dubbo://{}?interface={}", dubbo2_addr, SERVICE_NAME)
+            .parse()
+            .unwrap(),
+        format!("dubbo://{}?interface={}", dubbo2_addr, DUBBO2_SERVICE_NAME)
+            .parse()
+            .unwrap(),
+    ];
+    dubbo::triple::codec::registry::register_hessian2();
+    let mut client =
+        TripleClient::new(ClientBuilder::new().with_hosts(hosts)).with_serialization("hessian2");
+
+    for service_name in [SERVICE_NAME, DUBBO2_SERVICE_NAME] {
+        for seq in 0..20 {
+            let (path, invocation) = common::invocation(service_name, "Next");
+            let mut req = Request::new(Tick { seq });
+            req.metadata = req
+                .metadata
+                .insert("x-trace".to_string(), "abc".to_string());
+            let call = client.unary::<Tick, Tick>(req, path, invocation);
+            let resp = tokio::time::timeout(TIMEOUT, call).await.unwrap().unwrap();
+            assert_eq!(resp.into_parts().1.seq, seq + 1);
+        }
+    }
+
+    // the invokers of both protocols took the calls of the same cluster
+    assert_eq!(
+        triple_calls.load(Ordering::SeqCst) + calls.load(Ordering::SeqCst),
+        20
+    );
+    assert_eq!(dubbo2_calls.load(Ordering::SeqCst), 20);
+    dubbo2.stop().await.unwrap();
+}
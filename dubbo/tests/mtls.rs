@@ -0,0 +1,225 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+mod common;
+
+use std::{convert::Infallible, net::SocketAddr, path::Path, sync::Arc, time::Duration};
+
+use common::{fixture, Tick};
+use dubbo::{
+    codegen::*,
+    status::{Code, Status},
+    triple::{client::TlsConfig, server::tls::ClientAuth, transport::DubboServer},
+    utils::tls::{load_certs, load_keys},
+};
+use prost::Message;
+use tokio::net::TcpStream;
+use tokio_rustls::{
+    rustls::{ClientConfig, RootCertStore, ServerName},
+    TlsConnector,
+};
+
+const SERVICE_NAME: &str = "org.apache.dubbo.test.Whoami";
+
+const CLIENT_IDENTITY: &str = "spiffe://dubbo.apache.org/client";
+
+#[derive(Clone, PartialEq, prost::Message, serde::Serialize, serde::Deserialize)]
+struct Identity {
+    #[prost(string, repeated, tag = "1")]
+    names: Vec<String>,
+}
+
+/// Answers with the identities of the calling client.
+#[derive(Clone)]
+struct Whoami;
+
+impl Service<Request<Tick>> for Whoami {
+    type Response = Response<Identity>;
+    type Error = Status;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<Tick>) -> Self::Future {
+        let names = req
+            .metadata
+            .peer_identity()
+            .map(|peer| peer.identities().to_vec())
+            .unwrap_or_default();
+        Box::pin(async move { Ok(Response::new(Identity { names })) })
+    }
+}
+
+#[derive(Clone)]
+struct WhoamiServer;
+
+impl Service<http::Request<hyperBody>> for WhoamiServer {
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<hyperBody>) -> Self::Future {
+        Box::pin(async move {
+            let mut server = TripleServer::<Tick, Identity>::new();
+            Ok(server.unary(Whoami, req).await)
+        })
+    }
+}
+
+async fn serve(client_auth: ClientAuth) -> (SocketAddr, tokio::sync::oneshot::Sender<()>) {
+    let server = DubboServer::new()
+        .with_tls(
+            load_certs(Path::new(&fixture("server.crt"))).unwrap(),
+            load_keys(Path::new(&fixture("server.key"))).unwrap(),
+        )
+        .with_client_auth(
+            client_auth,
+            load_certs(Path::new(&fixture("ca.crt"))).unwrap(),
+        )
+        .with_allowed_identities(SERVICE_NAME.to_string(), vec![CLIENT_IDENTITY.to_string()])
+        .add_service(SERVICE_NAME.to_string(), WhoamiServer);
+    common::serve_with(server).await
+}
+
+async fn whoami(addr: SocketAddr, tls: TlsConfig) -> Result<Vec<String>, Status> {
+    let host = format!("http://{}?interface={}", addr, SERVICE_NAME);
+    let mut client = TripleClient::new(
+        ClientBuilder::from_static(&host).with_tls(tls.with_ca_file(&fixture("ca.crt"))),
+    );
+    let (path, invocation) = common::invocation(SERVICE_NAME, "Whoami");
+    let call = client.unary::<Tick, Identity>(Request::new(Tick::default()), path, invocation);
+    let resp = tokio::time::timeout(Duration::from_secs(5), call)
+        .await
+        .expect("mtls call timed out")?;
+    Ok(resp.into_parts().1.names)
+}
+
+// Every client talks to the same loopback server, see `common::connect`.
+#[tokio::test(flavor = "multi_thread")]
+async fn test_required_client_auth() {
+    let (addr, _shutdown) = serve(ClientAuth::Required).await;
+
+    let names = whoami(
+        addr,
+        TlsConfig::new().with_client_cert(&fixture("client.crt"), &fixture("client.key")),
+    )
+    .await
+    .unwrap();
+    assert_eq!(names, [CLIENT_IDENTITY, "client.dubbo.apache.org"]);
+
+    // trusted, but not on the allow-list
+    let status = whoami(
+        addr,
+        TlsConfig::new().with_client_cert(&fixture("other.crt"), &fixture("other.key")),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+
+    // the handshake fails without a client certificate, checked with a plain
+    // client as failover retries a failed call until it succeeds
+    let status = raw_whoami(addr, None).await.unwrap_err();
+    assert_eq!(status.code(), Code::Unavailable);
+}
+
+// Calls the server with a plain hyper client, as the direct clients of a
+// process cannot reach a second server. Failed connections come back as
+// `Unavailable`.
+async fn raw_whoami(addr: SocketAddr, cert: Option<&str>) -> Result<Identity, Status> {
+    let mut roots = RootCertStore::empty();
+    for cert in load_certs(Path::new(&fixture("ca.crt"))).unwrap() {
+        roots.add(&cert).unwrap();
+    }
+    let builder = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots);
+    let mut config = match cert {
+        Some(name) => {
+            let certs = load_certs(Path::new(&fixture(&format!("{}.crt", name)))).unwrap();
+            let mut keys = load_keys(Path::new(&fixture(&format!("{}.key", name)))).unwrap();
+            builder
+                .with_client_auth_cert(certs, keys.remove(0))
+                .unwrap()
+        }
+        None => builder.with_no_client_auth(),
+    };
+    config.alpn_protocols = vec![b"h2".to_vec()];
+
+    let unavailable = |err: &dyn std::fmt::Display| Status::new(Code::Unavailable, err.to_string());
+    let tcp = TcpStream::connect(addr).await.unwrap();
+    let tls = TlsConnector::from(Arc::new(config))
+        .connect(ServerName::try_from("localhost").unwrap(), tcp)
+        .await
+        .map_err(|err| unavailable(&err))?;
+    assert_eq!(tls.get_ref().1.alpn_protocol(), Some(&b"h2"[..]));
+
+    let (mut sender, conn) = hyper::client::conn::Builder::new()
+        .http2_only(true)
+        .handshake::<_, hyperBody>(tls)
+        .await
+        .map_err(|err| unavailable(&err))?;
+    tokio::spawn(conn);
+
+    let req = http::Request::builder()
+        .method(http::Method::POST)
+        .uri(format!("https://localhost/{}/Whoami", SERVICE_NAME))
+        .header(http::header::CONTENT_TYPE, "application/grpc")
+        .header(http::header::TE, "trailers")
+        .body(hyperBody::from(common::framed(&Tick::default())))
+        .unwrap();
+    let resp = sender
+        .send_request(req)
+        .await
+        .map_err(|err| unavailable(&err))?;
+    if let Some(status) = Status::from_header_map(resp.headers()) {
+        return Err(status);
+    }
+
+    let mut body = resp.into_body();
+    let frame = hyper::body::HttpBody::data(&mut body)
+        .await
+        .unwrap()
+        .unwrap();
+    let trailers = hyper::body::HttpBody::trailers(&mut body)
+        .await
+        .unwrap()
+        .unwrap();
+    let status = Status::from_header_map(&trailers).unwrap();
+    assert_eq!(status.code(), Code::Ok);
+    Ok(Identity::decode(&frame[5..]).unwrap())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_optional_client_auth() {
+    let (addr, _shutdown) = serve(ClientAuth::Optional).await;
+
+    let identity = raw_whoami(addr, Some("client")).await.unwrap();
+    assert_eq!(identity.names[0], CLIENT_IDENTITY);
+
+    // anonymous clients connect, but are not authorized for the service
+    let status = raw_whoami(addr, None).await.unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+
+    let status = raw_whoami(addr, Some("other")).await.unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+}
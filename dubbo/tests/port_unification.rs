@@ -0,0 +1,221 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Triple and Dubbo2 consumers calling through the same port.
+
+mod common;
+
+use std::{convert::Infallible, net::SocketAddr, path::Path, sync::Arc, time::Duration};
+
+use common::{fixture, Tick};
+use dubbo::{
+    codegen::*,
+    status::{Code, Status},
+    triple::transport::{listener::Protocol, DubboServer},
+    utils::tls::{load_certs, load_keys},
+};
+use futures::{SinkExt, StreamExt};
+use prost::Message;
+use protocol_dubbo2::{hessian2::Value, Attachments, Dubbo2Codec, ResponseData, RpcInvocation};
+use remoting_base::{
+    codec::{dubbo2::DUBBO2, CodecRegistry},
+    exchange::{client::Client, ClientConfig, ExchangeClient, ExchangeServer, Handler},
+};
+use remoting_net::Address;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+use tokio_rustls::{
+    rustls::{ClientConfig as TlsClientConfig, RootCertStore, ServerName},
+    TlsConnector,
+};
+use tokio_util::codec::Framed;
+
+const SERVICE_NAME: &str = "org.apache.dubbo.test.Greeter";
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone)]
+struct Echo;
+
+impl Service<Request<Tick>> for Echo {
+    type Response = Response<Tick>;
+    type Error = Status;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<Tick>) -> Self::Future {
+        Box::pin(async move { Ok(Response::new(req.message)) })
+    }
+}
+
+#[derive(Clone)]
+struct EchoServer;
+
+impl Service<http::Request<hyperBody>> for EchoServer {
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<hyperBody>) -> Self::Future {
+        Box::pin(async move {
+            let mut server = TripleServer::<Tick, Tick>::new();
+            Ok(server.unary(Echo, req).await)
+        })
+    }
+}
+
+/// The Dubbo2 side of the service, greets the name it is called with.
+struct Greeter;
+
+#[async_trait::async_trait]
+impl Handler for Greeter {
+    async fn reply(&self, request: remoting_base::Request) -> remoting_base::Response {
+        let invocation = request.body::<RpcInvocation>().unwrap();
+        let name = invocation.arguments[0].as_str().unwrap_or_default();
+        let value = ResponseData::Value {
+            value: Value::from(format!("hello {}", name)),
+            attachments: Attachments::new(),
+        };
+        remoting_base::Response::new(request.id, value)
+    }
+}
+
+fn greeting(name: &str) -> RpcInvocation {
+    RpcInvocation::new(SERVICE_NAME, "greet").with_argument("java.lang.String", Value::from(name))
+}
+
+fn greeted(data: Option<&ResponseData>) -> &str {
+    match data {
+        Some(ResponseData::Value { value, .. }) => value.as_str().unwrap(),
+        _ => panic!("not a value: {:?}", data),
+    }
+}
+
+fn unified_server(addr: SocketAddr) -> DubboServer {
+    let codec = CodecRegistry::new().codec(DUBBO2).unwrap();
+    let dubbo2 = ExchangeServer::new(Address::Ip(addr), codec, Greeter);
+    DubboServer::new()
+        .add_service(SERVICE_NAME.to_string(), EchoServer)
+        .with_protocol_handler(Protocol::Dubbo2, Arc::new(dubbo2))
+}
+
+// The only direct Triple client of this binary, see `common::connect`.
+#[tokio::test(flavor = "multi_thread")]
+async fn test_shared_port() {
+    let (addr, _shutdown) = common::serve_with(unified_server(common::free_addr())).await;
+
+    let mut client = common::connect(addr, SERVICE_NAME);
+    let (path, invocation) = common::invocation(SERVICE_NAME, "Echo");
+    let call = client.unary::<Tick, Tick>(Request::new(Tick { seq: 7 }), path, invocation);
+    let resp = tokio::time::timeout(TIMEOUT, call).await.unwrap().unwrap();
+    assert_eq!(resp.into_parts().1.seq, 7);
+
+    let codec = CodecRegistry::new().codec(DUBBO2).unwrap();
+    let dubbo2 = ExchangeClient::new(Address::Ip(addr), codec, ClientConfig::default());
+    let resp = dubbo2
+        .request(remoting_base::Request::new(greeting("dubbo")), TIMEOUT)
+        .await
+        .unwrap();
+    assert_eq!(greeted(resp.body::<ResponseData>()), "hello dubbo");
+
+    // connections of unknown protocols are closed
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream.write_all(b"SSH-2.0-OpenSSH_9.6\r\n").await.unwrap();
+    let mut buf = Vec::new();
+    let read = tokio::time::timeout(TIMEOUT, stream.read_to_end(&mut buf)).await;
+    assert!(matches!(read, Ok(Ok(0))));
+}
+
+/// Connects with `alpn` as the only ALPN protocol, none if empty.
+async fn tls_connect(addr: SocketAddr, alpn: &[u8]) -> tokio_rustls::client::TlsStream<TcpStream> {
+    let mut roots = RootCertStore::empty();
+    for cert in load_certs(Path::new(&fixture("ca.crt"))).unwrap() {
+        roots.add(&cert).unwrap();
+    }
+    let mut config = TlsClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    if !alpn.is_empty() {
+        config.alpn_protocols = vec![alpn.to_vec()];
+    }
+
+    let tcp = TcpStream::connect(addr).await.unwrap();
+    TlsConnector::from(Arc::new(config))
+        .connect(ServerName::try_from("localhost").unwrap(), tcp)
+        .await
+        .unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_shared_tls_port() {
+    let server = unified_server(common::free_addr()).with_tls(
+        load_certs(Path::new(&fixture("server.crt"))).unwrap(),
+        load_keys(Path::new(&fixture("server.key"))).unwrap(),
+    );
+    let (addr, _shutdown) = common::serve_with(server).await;
+
+    // Dubbo2 after the handshake, Java consumers negotiate no protocol
+    let tls = tls_connect(addr, b"").await;
+    let mut frames = Framed::new(tls, Dubbo2Codec::new());
+    let request = protocol_dubbo2::Request::new(3, greeting("tls"));
+    frames
+        .send(protocol_dubbo2::Message::Request(request))
+        .await
+        .unwrap();
+    let Some(Ok(protocol_dubbo2::Message::Response(resp))) = frames.next().await else {
+        panic!("no dubbo2 response");
+    };
+    assert_eq!(resp.id, 3);
+    assert_eq!(greeted(Some(&resp.data)), "hello tls");
+
+    // Triple after the handshake
+    let tls = tls_connect(addr, b"h2").await;
+    let (mut sender, conn) = hyper::client::conn::Builder::new()
+        .http2_only(true)
+        .handshake::<_, hyperBody>(tls)
+        .await
+        .unwrap();
+    tokio::spawn(conn);
+    let req = http::Request::builder()
+        .method(http::Method::POST)
+        .uri(format!("https://localhost/{}/Echo", SERVICE_NAME))
+        .header(http::header::CONTENT_TYPE, "application/grpc")
+        .header(http::header::TE, "trailers")
+        .body(hyperBody::from(common::framed(&Tick { seq: 9 })))
+        .unwrap();
+    let mut body = sender.send_request(req).await.unwrap().into_body();
+    let frame = hyper::body::HttpBody::data(&mut body)
+        .await
+        .unwrap()
+        .unwrap();
+    let trailers = hyper::body::HttpBody::trailers(&mut body)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(Status::from_header_map(&trailers).unwrap().code(), Code::Ok);
+    assert_eq!(Tick::decode(&frame[5..]).unwrap().seq, 9);
+}
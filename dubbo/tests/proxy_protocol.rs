@@ -0,0 +1,132 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+mod common;
+
+use std::{convert::Infallible, net::SocketAddr};
+
+use common::Tick;
+use dubbo::{
+    codegen::*,
+    status::{Code, Status},
+    triple::transport::DubboServer,
+};
+use prost::Message;
+use remoting_net::proxy_protocol::ProxyHeader;
+use tokio::{io::AsyncWriteExt, net::TcpStream};
+
+const SERVICE_NAME: &str = "org.apache.dubbo.test.Whereami";
+
+#[derive(Clone, PartialEq, prost::Message, serde::Serialize, serde::Deserialize)]
+struct Location {
+    #[prost(string, tag = "1")]
+    addr: String,
+}
+
+/// Answers with the address of the calling client.
+#[derive(Clone)]
+struct Whereami;
+
+impl Service<Request<Tick>> for Whereami {
+    type Response = Response<Location>;
+    type Error = Status;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<Tick>) -> Self::Future {
+        let addr = req
+            .metadata
+            .remote_addr()
+            .map(|addr| addr.to_string())
+            .unwrap_or_default();
+        Box::pin(async move { Ok(Response::new(Location { addr })) })
+    }
+}
+
+#[derive(Clone)]
+struct WhereamiServer;
+
+impl Service<http::Request<hyperBody>> for WhereamiServer {
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<hyperBody>) -> Self::Future {
+        Box::pin(async move {
+            let mut server = TripleServer::<Tick, Location>::new();
+            Ok(server.unary(Whereami, req).await)
+        })
+    }
+}
+
+/// Calls the server over a connection starting with `header`.
+async fn whereami(addr: SocketAddr, header: &[u8]) -> String {
+    let mut tcp = TcpStream::connect(addr).await.unwrap();
+    tcp.write_all(header).await.unwrap();
+
+    let (mut sender, conn) = hyper::client::conn::Builder::new()
+        .http2_only(true)
+        .handshake::<_, hyperBody>(tcp)
+        .await
+        .unwrap();
+    tokio::spawn(conn);
+
+    let req = http::Request::builder()
+        .method(http::Method::POST)
+        .uri(format!("http://{}/{}/Whereami", addr, SERVICE_NAME))
+        .header(http::header::CONTENT_TYPE, "application/grpc")
+        .header(http::header::TE, "trailers")
+        .body(hyperBody::from(common::framed(&Tick::default())))
+        .unwrap();
+    let resp = sender.send_request(req).await.unwrap();
+
+    let mut body = resp.into_body();
+    let frame = hyper::body::HttpBody::data(&mut body)
+        .await
+        .unwrap()
+        .unwrap();
+    let trailers = hyper::body::HttpBody::trailers(&mut body)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(Status::from_header_map(&trailers).unwrap().code(), Code::Ok);
+    Location::decode(&frame[5..]).unwrap().addr
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_proxy_protocol() {
+    let server = DubboServer::new()
+        .with_proxy_protocol(true)
+        .add_service(SERVICE_NAME.to_string(), WhereamiServer);
+    let (addr, _shutdown) = common::serve_with(server).await;
+
+    let client: SocketAddr = "192.168.0.1:56324".parse().unwrap();
+    let header = ProxyHeader::new(client, addr);
+    assert_eq!(whereami(addr, &header.to_v1()).await, client.to_string());
+    assert_eq!(whereami(addr, &header.to_v2()).await, client.to_string());
+
+    // health checks of the balancer keep the address of the connection
+    let local = whereami(addr, b"PROXY UNKNOWN\r\n").await;
+    assert!(local.starts_with("127.0.0.1:"));
+}
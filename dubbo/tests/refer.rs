@@ -0,0 +1,215 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+mod common;
+
+use std::{
+    convert::Infallible,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use common::Tick;
+use dubbo::{
+    codegen::*,
+    extension::registry_extension::{proxy::RegistryProxy, Registry},
+    invoker::clone_body::CloneBody,
+    protocol::{triple::triple_protocol::TripleProtocol, Protocol},
+    registry::{protocol::RegistryProtocol, registry::StaticRegistry},
+    status::{Code, Status},
+    triple::compression::CompressionEncoding,
+    Url,
+};
+use dubbo_base::Node;
+use futures_util::StreamExt;
+use tower::ServiceExt;
+
+const SERVICE_NAME: &str = "org.apache.dubbo.test.Refer";
+
+/// Answers with the tick it was sent, plus one. The tick 0 is answered
+/// after a second.
+#[derive(Clone)]
+struct Next;
+
+impl Service<Request<Tick>> for Next {
+    type Response = Response<Tick>;
+    type Error = Status;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<Tick>) -> Self::Future {
+        let seq = req.message.seq + 1;
+        Box::pin(async move {
+            if seq == 1 {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+            Ok(Response::new(Tick { seq }))
+        })
+    }
+}
+
+/// Serves `Next`, keeping the headers of the last call.
+#[derive(Clone, Default)]
+struct NextServer {
+    headers: Arc<Mutex<http::HeaderMap>>,
+}
+
+impl Service<http::Request<hyperBody>> for NextServer {
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<hyperBody>) -> Self::Future {
+        *self.headers.lock().unwrap() = req.headers().clone();
+        Box::pin(async move {
+            let mut server = TripleServer::<Tick, Tick>::new();
+            Ok(server.unary(Next, req).await)
+        })
+    }
+}
+
+fn next_request(seq: u64) -> http::Request<CloneBody> {
+    http::Request::builder()
+        .header("path", format!("/{}/Next", SERVICE_NAME))
+        .header(http::header::CONTENT_TYPE, "application/grpc")
+        .body(CloneBody::new(hyperBody::from(common::framed(&Tick {
+            seq,
+        }))))
+        .unwrap()
+}
+
+/// Calls `Next` through `invoker`, as a client stack would.
+async fn next<I>(invoker: &mut I, seq: u64) -> Tick
+where
+    I: Service<http::Request<CloneBody>, Response = http::Response<BoxBody>>,
+    I::Error: std::fmt::Debug,
+{
+    let resp = invoker
+        .ready()
+        .await
+        .unwrap()
+        .call(next_request(seq))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    // answered gzipped, as the invoker accepts
+    let decoder = Box::new(ProstCodec::<Tick, Tick>::default().decoder());
+    let mut decoding = Decoding::new(
+        resp.into_body(),
+        decoder,
+        Some(CompressionEncoding::Gzip),
+        true,
+    );
+    decoding.next().await.unwrap().unwrap()
+}
+
+fn provider_url(addr: std::net::SocketAddr) -> Url {
+    format!("tri://{}/{}?interface={}", addr, SERVICE_NAME, SERVICE_NAME)
+        .parse()
+        .unwrap()
+}
+
+/// A registry serving the provider at `addr`, built in place rather than
+/// loaded by `EXTENSIONS`, which serves from the runtime of its first test.
+fn static_registry(addr: std::net::SocketAddr) -> RegistryProxy {
+    let url = StaticRegistry::to_extension_url(vec![provider_url(addr)]);
+    let registry: Box<dyn Registry + Send + Sync> = Box::new(StaticRegistry::new(url));
+    registry.into()
+}
+
+#[tokio::test]
+async fn test_refer() {
+    let (addr, _shutdown) = common::serve(SERVICE_NAME, NextServer::default()).await;
+
+    let mut invoker = TripleProtocol::new()
+        .refer(provider_url(addr))
+        .await
+        .unwrap();
+    assert_eq!(next(&mut invoker, 1).await.seq, 2);
+
+    // discovered by the registry of the protocol
+    let registry = static_registry(addr);
+    let consumer_url: Url = format!(
+        "tri://127.0.0.1/{}?interface={}&retries=0&loadbalance=random",
+        SERVICE_NAME, SERVICE_NAME
+    )
+    .parse()
+    .unwrap();
+    let mut invoker = RegistryProtocol::new()
+        .with_registries(vec![registry])
+        .refer(consumer_url.clone())
+        .await
+        .unwrap();
+    assert_eq!(invoker.get_url().as_str(), consumer_url.as_str());
+    assert_eq!(next(&mut invoker, 41).await.seq, 42);
+}
+
+#[tokio::test]
+async fn test_refer_options() {
+    let server = NextServer::default();
+    let (addr, _shutdown) = common::serve(SERVICE_NAME, server.clone()).await;
+    let registry = static_registry(addr);
+    let consumer_url: Url = format!(
+        "tri://127.0.0.1/{}?interface={}&group=canary&version=1.0.0&timeout=300",
+        SERVICE_NAME, SERVICE_NAME
+    )
+    .parse()
+    .unwrap();
+    let mut invoker = RegistryProtocol::new()
+        .with_registries(vec![registry])
+        .refer(consumer_url)
+        .await
+        .unwrap();
+
+    // sent with the group, version and deadline of the url, as a client would
+    assert_eq!(next(&mut invoker, 1).await.seq, 2);
+    let headers = server.headers.lock().unwrap().clone();
+    assert_eq!(headers["tri-service-group"], "canary");
+    assert_eq!(headers["tri-service-version"], "1.0.0");
+    assert_eq!(headers["grpc-timeout"], "300m");
+
+    let err = invoker
+        .ready()
+        .await
+        .unwrap()
+        .call(next_request(0))
+        .await
+        .unwrap_err();
+    let status = err.downcast_ref::<Status>().expect("a status");
+    assert_eq!(status.code(), Code::DeadlineExceeded);
+}
+
+#[tokio::test]
+async fn test_refer_errors() {
+    let consumer_url: Url = format!(
+        "tri://127.0.0.1/{}?interface={}",
+        SERVICE_NAME, SERVICE_NAME
+    )
+    .parse()
+    .unwrap();
+    assert!(RegistryProtocol::new().refer(consumer_url).await.is_err());
+
+    let anonymous: Url = "tri://127.0.0.1/".parse().unwrap();
+    assert!(RegistryProtocol::new().refer(anonymous).await.is_err());
+}
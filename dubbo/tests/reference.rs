@@ -0,0 +1,208 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+mod common;
+
+use std::{collections::HashMap, convert::Infallible, time::Duration};
+
+use common::Tick;
+use dubbo::{
+    codegen::*,
+    config::{consumer::ReferenceConfig, registry::RegistryConfig, RootConfig},
+    status::{Code, Status},
+    Dubbo, ReferenceError,
+};
+
+const SERVICE_NAME: &str = "org.apache.dubbo.test.Reference";
+
+#[derive(Clone, PartialEq, prost::Message, serde::Serialize, serde::Deserialize)]
+struct Served {
+    #[prost(string, tag = "1")]
+    group: String,
+    #[prost(string, tag = "2")]
+    version: String,
+}
+
+/// Answers with the group and version called after sleeping for `seq`
+/// milliseconds.
+#[derive(Clone)]
+struct Echo;
+
+impl Service<Request<Tick>> for Echo {
+    type Response = Response<Served>;
+    type Error = Status;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<Tick>) -> Self::Future {
+        let header = |name: &str| req.metadata.get(name).cloned().unwrap_or_default();
+        let served = Served {
+            group: header("tri-service-group"),
+            version: header("tri-service-version"),
+        };
+        let seq = req.message.seq;
+        Box::pin(async move {
+            tokio::time::sleep(Duration::from_millis(seq)).await;
+            Ok(Response::new(served))
+        })
+    }
+}
+
+#[derive(Clone)]
+struct EchoServer;
+
+impl Service<http::Request<hyperBody>> for EchoServer {
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<hyperBody>) -> Self::Future {
+        Box::pin(async move {
+            let mut server = TripleServer::<Tick, Served>::new();
+            Ok(server.unary(Echo, req).await)
+        })
+    }
+}
+
+/// A client as generated by `dubbo-build`.
+#[derive(Clone)]
+struct EchoClient {
+    inner: TripleClient,
+}
+
+impl NewClient for EchoClient {
+    const INTERFACE: &'static str = SERVICE_NAME;
+
+    fn new_client(builder: ClientBuilder) -> Self {
+        Self {
+            inner: TripleClient::new(builder),
+        }
+    }
+}
+
+impl EchoClient {
+    async fn echo(&mut self, seq: u64) -> Result<Served, Status> {
+        let (path, invocation) = common::invocation(SERVICE_NAME, "Echo");
+        let resp = self
+            .inner
+            .unary(Request::new(Tick { seq }), path, invocation)
+            .await?;
+        Ok(resp.into_parts().1)
+    }
+}
+
+fn dubbo(references: HashMap<String, ReferenceConfig>) -> Dubbo {
+    let mut config = RootConfig::new();
+    config.consumer.references = references;
+    config.registries.insert(
+        "zk".to_string(),
+        RegistryConfig {
+            protocol: "zookeeper".to_string(),
+            address: "127.0.0.1:2181".to_string(),
+        },
+    );
+    Dubbo::new().with_config(config)
+}
+
+#[tokio::test]
+async fn test_reference() {
+    let (addr, _shutdown) = common::serve(SERVICE_NAME, EchoServer).await;
+    let url = format!("tri://{}", addr);
+    let mut dubbo = dubbo(HashMap::from([
+        (
+            "EchoClientImpl".to_string(),
+            ReferenceConfig::default()
+                .url(url.clone())
+                .group("blue".to_string())
+                .version("1.0.0".to_string())
+                .loadbalance("random".to_string()),
+        ),
+        (
+            "ImpatientClientImpl".to_string(),
+            ReferenceConfig::default()
+                .url(url)
+                .timeout(50)
+                .cluster("failfast".to_string()),
+        ),
+    ]));
+
+    let mut client = dubbo.reference::<EchoClient>("EchoClientImpl").unwrap();
+    let served = client.echo(0).await.unwrap();
+    assert_eq!(served.group, "blue");
+    assert_eq!(served.version, "1.0.0");
+
+    let mut client = dubbo
+        .reference::<EchoClient>("ImpatientClientImpl")
+        .unwrap();
+    let err = client.echo(500).await.unwrap_err();
+    assert_eq!(err.code(), Code::DeadlineExceeded);
+    client.echo(0).await.unwrap();
+}
+
+#[test]
+fn test_reference_errors() {
+    let mut dubbo = dubbo(HashMap::from([
+        (
+            "UnknownCluster".to_string(),
+            ReferenceConfig::default()
+                .url("tri://127.0.0.1:20000".to_string())
+                .cluster("forking".to_string()),
+        ),
+        (
+            "UnknownLoadbalance".to_string(),
+            ReferenceConfig::default()
+                .url("tri://127.0.0.1:20000".to_string())
+                .loadbalance("roundrobin".to_string()),
+        ),
+        (
+            "UnknownRegistry".to_string(),
+            ReferenceConfig::default().registry_ids(vec!["nacos".to_string()]),
+        ),
+        ("Unreachable".to_string(), ReferenceConfig::default()),
+        (
+            "UnknownProtocol".to_string(),
+            ReferenceConfig {
+                protocol: "dubbo".to_string(),
+                ..ReferenceConfig::default().url("dubbo://127.0.0.1:20880".to_string())
+            },
+        ),
+    ]));
+
+    assert!(matches!(
+        dubbo.reference::<EchoClient>("Missing"),
+        Err(ReferenceError::NotFound(_))
+    ));
+    for name in [
+        "UnknownCluster",
+        "UnknownLoadbalance",
+        "UnknownRegistry",
+        "Unreachable",
+        "UnknownProtocol",
+    ] {
+        match dubbo.reference::<EchoClient>(name) {
+            Err(ReferenceError::Invalid { name: invalid, .. }) => assert_eq!(invalid, name),
+            _ => panic!("{} should be invalid", name),
+        }
+    }
+}
@@ -0,0 +1,237 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Checks the error responses of the Triple protocol against the gRPC spec:
+//! errors raised before any message are trailers-only responses, errors
+//! raised while streaming end the stream with `grpc-status` trailers and the
+//! client maps anything else to a proper status.
+
+mod common;
+
+use std::{convert::Infallible, net::SocketAddr, pin::Pin};
+
+use common::Tick;
+use dubbo::{
+    codegen::*,
+    status::{Code, Status},
+};
+use futures_util::{stream, Stream, StreamExt};
+
+const SERVICE_NAME: &str = "org.apache.dubbo.test.Conformance";
+
+const FAIL_MESSAGE: &str = "héllo 100%\nwörld";
+
+type TickStream = Pin<Box<dyn Stream<Item = Result<Tick, Status>> + Send>>;
+
+/// Fails every call before sending any message.
+#[derive(Clone)]
+struct Fail;
+
+impl Service<Request<Tick>> for Fail {
+    type Response = Response<Tick>;
+    type Error = Status;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _req: Request<Tick>) -> Self::Future {
+        Box::pin(async { Err(Status::new(Code::NotFound, FAIL_MESSAGE.to_string())) })
+    }
+}
+
+/// Sends two messages, then fails the stream.
+#[derive(Clone)]
+struct FailLater;
+
+impl Service<Request<Tick>> for FailLater {
+    type Response = Response<TickStream>;
+    type Error = Status;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _req: Request<Tick>) -> Self::Future {
+        let items = vec![
+            Ok(Tick { seq: 1 }),
+            Ok(Tick { seq: 2 }),
+            Err(Status::new(Code::ResourceExhausted, "quota".to_string())),
+        ];
+        let stream: TickStream = Box::pin(stream::iter(items));
+        Box::pin(async move { Ok(Response::new(stream)) })
+    }
+}
+
+fn raw_response(
+    status: http::StatusCode,
+    content_type: &str,
+    body: Bytes,
+) -> http::Response<BoxBody> {
+    http::Response::builder()
+        .status(status)
+        .header(http::header::CONTENT_TYPE, content_type)
+        .body(
+            http_body::Full::new(body)
+                .map_err(|err| match err {})
+                .boxed_unsync(),
+        )
+        .unwrap()
+}
+
+#[derive(Clone)]
+struct ConformanceServer;
+
+impl Service<http::Request<hyperBody>> for ConformanceServer {
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<hyperBody>) -> Self::Future {
+        match req.uri().path().rsplit('/').next().unwrap_or_default() {
+            "Fail" => Box::pin(async move {
+                let mut server = TripleServer::<Tick, Tick>::new();
+                Ok(server.unary(Fail, req).await)
+            }),
+            "FailLater" => Box::pin(async move {
+                let mut server = TripleServer::<Tick, Tick>::new();
+                Ok(server.server_streaming(FailLater, req).await)
+            }),
+            // a message without any trailers, as sent by a broken peer
+            "NoStatus" => Box::pin(async move {
+                Ok(raw_response(
+                    http::StatusCode::OK,
+                    "application/grpc",
+                    common::framed(&Tick { seq: 1 }),
+                ))
+            }),
+            // a proxy answering in place of the server
+            "Unavailable" => Box::pin(async move {
+                Ok(raw_response(
+                    http::StatusCode::SERVICE_UNAVAILABLE,
+                    "text/plain",
+                    Bytes::from_static(b"upstream connect error"),
+                ))
+            }),
+            _ => Box::pin(async move {
+                Ok(Status::new(Code::Unimplemented, "method not found".to_string()).to_http())
+            }),
+        }
+    }
+}
+
+async fn raw_call(addr: SocketAddr, path: &str) -> http::Response<hyperBody> {
+    let client = hyper::Client::builder()
+        .http2_only(true)
+        .build_http::<hyperBody>();
+    let req = http::Request::builder()
+        .method(http::Method::POST)
+        .uri(format!("http://{}{}", addr, path))
+        .header(http::header::CONTENT_TYPE, "application/grpc")
+        .header(http::header::TE, "trailers")
+        .body(hyperBody::from(common::framed(&Tick::default())))
+        .unwrap();
+    client.request(req).await.unwrap()
+}
+
+async fn assert_trailers_only(resp: http::Response<hyperBody>, code: Code) -> Status {
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    assert_eq!(
+        resp.headers().get(http::header::CONTENT_TYPE).unwrap(),
+        "application/grpc"
+    );
+    let status = Status::from_header_map(resp.headers()).expect("grpc-status in headers");
+    assert_eq!(status.code(), code);
+
+    let mut body = resp.into_body();
+    assert!(body.data().await.is_none());
+    assert!(body.trailers().await.unwrap().is_none());
+    status
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_trailers_only_responses() {
+    let (addr, _shutdown) = common::serve(SERVICE_NAME, ConformanceServer).await;
+
+    // handler error before the first message
+    let resp = raw_call(addr, &format!("/{}/Fail", SERVICE_NAME)).await;
+    assert_eq!(
+        resp.headers().get("grpc-message").unwrap(),
+        "h%C3%A9llo 100%25%0Aw%C3%B6rld"
+    );
+    let status = assert_trailers_only(resp, Code::NotFound).await;
+    assert_eq!(status.message(), FAIL_MESSAGE);
+
+    // unknown method of a known service
+    let resp = raw_call(addr, &format!("/{}/Missing", SERVICE_NAME)).await;
+    assert_trailers_only(resp, Code::Unimplemented).await;
+
+    // unknown service
+    let resp = raw_call(addr, "/org.apache.dubbo.test.Missing/Fail").await;
+    assert_trailers_only(resp, Code::Unimplemented).await;
+}
+
+async fn unary_error(client: &mut TripleClient, method: &str) -> Status {
+    let (path, invocation) = common::invocation(SERVICE_NAME, method);
+    match client
+        .unary::<Tick, Tick>(Request::new(Tick::default()), path, invocation)
+        .await
+    {
+        Ok(_) => panic!("{} should fail", method),
+        Err(status) => status,
+    }
+}
+
+// Every scenario runs against the same loopback server, see `common::connect`.
+#[tokio::test(flavor = "multi_thread")]
+async fn test_client_status() {
+    let (addr, _shutdown) = common::serve(SERVICE_NAME, ConformanceServer).await;
+    let mut client = common::connect(addr, SERVICE_NAME);
+
+    let status = unary_error(&mut client, "Fail").await;
+    assert_eq!(status.code(), Code::NotFound);
+    assert_eq!(status.message(), FAIL_MESSAGE);
+
+    let status = unary_error(&mut client, "Missing").await;
+    assert_eq!(status.code(), Code::Unimplemented);
+
+    let status = unary_error(&mut client, "NoStatus").await;
+    assert_eq!(status.code(), Code::Unknown);
+
+    let status = unary_error(&mut client, "Unavailable").await;
+    assert_eq!(status.code(), Code::Unavailable);
+
+    // messages sent before the error are still delivered
+    let (path, invocation) = common::invocation(SERVICE_NAME, "FailLater");
+    let resp = client
+        .server_streaming::<Tick, Tick>(Request::new(Tick::default()), path, invocation)
+        .await
+        .unwrap();
+    let (_, mut stream) = resp.into_parts();
+    assert_eq!(stream.next().await.unwrap().unwrap().seq, 1);
+    assert_eq!(stream.next().await.unwrap().unwrap().seq, 2);
+    let status = stream.next().await.unwrap().unwrap_err();
+    assert_eq!(status.code(), Code::ResourceExhausted);
+    assert_eq!(status.message(), "quota");
+    assert!(stream.next().await.is_none());
+}
@@ -0,0 +1,193 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+mod common;
+
+use std::{
+    convert::Infallible,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use common::Tick;
+use dubbo::{codegen::*, status::Status};
+use futures_util::{Stream, StreamExt};
+use tokio::sync::{mpsc, oneshot};
+use tokio_stream::wrappers::ReceiverStream;
+
+const SERVICE_NAME: &str = "org.apache.dubbo.test.Ticker";
+
+type TickStream = Pin<Box<dyn Stream<Item = Result<Tick, Status>> + Send>>;
+
+/// Produces ticks until the request is cancelled, then reports the number of
+/// produced ticks on `cancelled`.
+#[derive(Clone, Default)]
+struct Ticker {
+    cancelled: Arc<std::sync::Mutex<Option<oneshot::Sender<u64>>>>,
+    produced: Arc<AtomicUsize>,
+}
+
+impl Ticker {
+    fn on_cancelled(&self) -> oneshot::Receiver<u64> {
+        let (tx, rx) = oneshot::channel();
+        *self.cancelled.lock().unwrap() = Some(tx);
+        rx
+    }
+
+    fn start(&self, request: &Request<impl Send>) -> TickStream {
+        let (tx, rx) = mpsc::channel(1);
+        let token = request.metadata.cancellation();
+        let cancelled = self.cancelled.clone();
+        let produced = self.produced.clone();
+        tokio::spawn(async move {
+            let mut seq = 0;
+            loop {
+                tokio::select! {
+                    _ = token.cancelled() => break,
+                    _ = tokio::time::sleep(Duration::from_millis(10)) => {}
+                }
+                seq += 1;
+                produced.fetch_add(1, Ordering::SeqCst);
+                // a closed channel is not a cancellation, keep going until the token fires
+                let _ = tx.send(Ok(Tick { seq })).await;
+            }
+            if let Some(tx) = cancelled.lock().unwrap().take() {
+                let _ = tx.send(seq);
+            }
+        });
+        Box::pin(ReceiverStream::new(rx))
+    }
+}
+
+impl Service<Request<Tick>> for Ticker {
+    type Response = Response<TickStream>;
+    type Error = Status;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<Tick>) -> Self::Future {
+        let stream = self.start(&req);
+        Box::pin(async move { Ok(Response::new(stream)) })
+    }
+}
+
+impl Service<Request<Decoding<Tick>>> for Ticker {
+    type Response = Response<TickStream>;
+    type Error = Status;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<Decoding<Tick>>) -> Self::Future {
+        let stream = self.start(&req);
+        Box::pin(async move { Ok(Response::new(stream)) })
+    }
+}
+
+#[derive(Clone)]
+struct TickerServer(Ticker);
+
+impl Service<http::Request<hyperBody>> for TickerServer {
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<hyperBody>) -> Self::Future {
+        let ticker = self.0.clone();
+        match req.uri().path().ends_with("/Watch") {
+            true => Box::pin(async move {
+                let mut server = TripleServer::<Tick, Tick>::new();
+                Ok(server.server_streaming(ticker, req).await)
+            }),
+            false => Box::pin(async move {
+                let mut server = TripleServer::<Tick, Tick>::new();
+                Ok(server.bidi_streaming(ticker, req).await)
+            }),
+        }
+    }
+}
+
+// Every scenario runs against the same loopback server, see `common::connect`.
+#[tokio::test(flavor = "multi_thread")]
+async fn test_streaming_cancelled_on_drop() {
+    let ticker = Ticker::default();
+    let (addr, _shutdown) = common::serve(SERVICE_NAME, TickerServer(ticker.clone())).await;
+    let mut client = common::connect(addr, SERVICE_NAME);
+
+    server_streaming_cancelled_on_drop(&mut client, &ticker).await;
+    bidi_streaming_cancelled_on_drop(&mut client, &ticker).await;
+}
+
+async fn server_streaming_cancelled_on_drop(client: &mut TripleClient, ticker: &Ticker) {
+    let cancelled = ticker.on_cancelled();
+    let (path, invocation) = common::invocation(SERVICE_NAME, "Watch");
+    let resp = client
+        .server_streaming::<Tick, Tick>(Request::new(Tick { seq: 0 }), path, invocation)
+        .await
+        .unwrap();
+
+    let (_, mut stream) = resp.into_parts();
+    for expected in 1..=3 {
+        let tick = stream.next().await.unwrap().unwrap();
+        assert_eq!(tick.seq, expected);
+    }
+    drop(stream);
+
+    let seq = tokio::time::timeout(Duration::from_secs(5), cancelled)
+        .await
+        .expect("server handler was not cancelled")
+        .unwrap();
+    assert!(seq >= 3);
+}
+
+async fn bidi_streaming_cancelled_on_drop(client: &mut TripleClient, ticker: &Ticker) {
+    let cancelled = ticker.on_cancelled();
+    let (path, invocation) = common::invocation(SERVICE_NAME, "Chat");
+    let (req_tx, req_rx) = mpsc::channel::<Tick>(1);
+    let resp = client
+        .bidi_streaming::<Tick, Tick>(ReceiverStream::new(req_rx), path, invocation)
+        .await
+        .unwrap();
+
+    let (_, mut stream) = resp.into_parts();
+    stream.next().await.unwrap().unwrap();
+    drop(stream);
+    drop(req_tx);
+
+    tokio::time::timeout(Duration::from_secs(5), cancelled)
+        .await
+        .expect("server handler was not cancelled")
+        .unwrap();
+
+    // nothing is produced once the handler observed the cancellation
+    let total = ticker.produced.load(Ordering::SeqCst);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(ticker.produced.load(Ordering::SeqCst), total);
+}
@@ -0,0 +1,147 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+mod common;
+
+use std::{convert::Infallible, net::SocketAddr, path::Path, time::Duration};
+
+use common::{fixture, Tick};
+use dubbo::{
+    codegen::*,
+    status::Status,
+    triple::{
+        client::TlsConfig,
+        transport::{connector::https_connector::HttpsConnector, DubboServer},
+    },
+    utils::tls::{load_certs, load_keys},
+};
+
+const SERVICE_NAME: &str = "org.apache.dubbo.test.Echo";
+
+#[derive(Clone)]
+struct Echo;
+
+impl Service<Request<Tick>> for Echo {
+    type Response = Response<Tick>;
+    type Error = Status;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<Tick>) -> Self::Future {
+        Box::pin(async move { Ok(Response::new(req.message)) })
+    }
+}
+
+#[derive(Clone)]
+struct EchoServer;
+
+impl Service<http::Request<hyperBody>> for EchoServer {
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<hyperBody>) -> Self::Future {
+        Box::pin(async move {
+            let mut server = TripleServer::<Tick, Tick>::new();
+            Ok(server.unary(Echo, req).await)
+        })
+    }
+}
+
+fn client(addr: SocketAddr, builder: impl FnOnce(ClientBuilder) -> ClientBuilder) -> TripleClient {
+    let host = format!("http://{}?interface={}", addr, SERVICE_NAME);
+    TripleClient::new(builder(ClientBuilder::from_static(&host)))
+}
+
+async fn echo(client: &mut TripleClient, seq: u64) -> Result<u64, Status> {
+    let (path, invocation) = common::invocation(SERVICE_NAME, "Echo");
+    let call = client.unary::<Tick, Tick>(Request::new(Tick { seq }), path, invocation);
+    let resp = tokio::time::timeout(Duration::from_secs(5), call)
+        .await
+        .expect("tls call timed out")?;
+    Ok(resp.into_parts().1.seq)
+}
+
+// Connects to `addr` with `connector` and completes the TLS handshake. Failed
+// handshakes are checked here rather than through a client, whose failover
+// retries a failed call until it succeeds.
+async fn handshake(addr: SocketAddr, mut connector: HttpsConnector) -> Result<(), StdError> {
+    let uri = format!("https://{}", addr).parse().unwrap();
+    connector.call(uri).await.map(drop)
+}
+
+// Every client talks to the same loopback server, see `common::connect`.
+#[tokio::test(flavor = "multi_thread")]
+async fn test_tls_client() {
+    let server = DubboServer::new()
+        .with_tls(
+            load_certs(Path::new(&fixture("server.crt"))).unwrap(),
+            load_keys(Path::new(&fixture("server.key"))).unwrap(),
+        )
+        .add_service(SERVICE_NAME.to_string(), EchoServer);
+    let (addr, _shutdown) = common::serve_with(server).await;
+
+    // the server certificate covers 127.0.0.1
+    let mut custom_ca = client(addr, |builder| {
+        builder.with_tls(TlsConfig::new().with_ca_file(&fixture("ca.crt")))
+    });
+    assert_eq!(echo(&mut custom_ca, 1).await.unwrap(), 1);
+    assert_eq!(echo(&mut custom_ca, 2).await.unwrap(), 2);
+
+    let mut server_name = client(addr, |builder| {
+        builder.with_tls(
+            TlsConfig::new()
+                .with_ca_file(&fixture("ca.crt"))
+                .with_server_name("localhost"),
+        )
+    });
+    assert_eq!(echo(&mut server_name, 3).await.unwrap(), 3);
+
+    let mut insecure = client(addr, |builder| {
+        builder.with_tls(TlsConfig::new().with_insecure_skip_verify(true))
+    });
+    assert_eq!(echo(&mut insecure, 4).await.unwrap(), 4);
+
+    let wrong_name = TlsConfig::new()
+        .with_ca_file(&fixture("ca.crt"))
+        .with_server_name("dubbo.apache.org");
+    let connector = HttpsConnector::new().with_tls(wrong_name.build().unwrap());
+    assert!(handshake(addr, connector).await.is_err());
+
+    // the test CA is not a native root
+    assert!(handshake(addr, HttpsConnector::new()).await.is_err());
+
+    // failed handshakes leave the server running
+    assert_eq!(echo(&mut custom_ca, 5).await.unwrap(), 5);
+}
+
+#[test]
+fn test_invalid_tls_config() {
+    let builder = || {
+        ClientBuilder::from_static("http://127.0.0.1:8888?interface=echo")
+            .with_tls(TlsConfig::new().with_ca_file(&fixture("missing.crt")))
+    };
+    assert!(builder().try_build().is_err());
+    assert!(TripleClient::try_new(builder()).is_err());
+}
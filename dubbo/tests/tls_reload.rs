@@ -0,0 +1,246 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Renews the certificates of running servers and clients by rewriting
+//! their files.
+
+mod common;
+
+use std::{
+    convert::Infallible,
+    fs,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use common::{fixture, Tick};
+use dubbo::{
+    codegen::*,
+    status::{Code, Status},
+    triple::{client::TlsConfig, server::tls::ClientAuth, transport::DubboServer},
+    utils::tls::{load_certs, subscribe_reload_events, TlsReloadEvent},
+};
+use tokio::{net::TcpStream, sync::broadcast};
+use tokio_rustls::{
+    rustls::{Certificate, ClientConfig, RootCertStore, ServerName},
+    TlsConnector,
+};
+
+const SERVICE_NAME: &str = "org.apache.dubbo.test.Reload";
+
+const RELOAD_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Clone)]
+struct Echo;
+
+impl Service<Request<Tick>> for Echo {
+    type Response = Response<Tick>;
+    type Error = Status;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<Tick>) -> Self::Future {
+        Box::pin(async move { Ok(Response::new(req.message)) })
+    }
+}
+
+#[derive(Clone)]
+struct EchoServer;
+
+impl Service<http::Request<hyperBody>> for EchoServer {
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<hyperBody>) -> Self::Future {
+        Box::pin(async move {
+            let mut server = TripleServer::<Tick, Tick>::new();
+            Ok(server.unary(Echo, req).await)
+        })
+    }
+}
+
+async fn echo(client: &mut TripleClient, seq: u64) -> Result<u64, Status> {
+    let (path, invocation) = common::invocation(SERVICE_NAME, "Echo");
+    let call = client.unary::<Tick, Tick>(Request::new(Tick { seq }), path, invocation);
+    let resp = tokio::time::timeout(Duration::from_secs(5), call)
+        .await
+        .expect("tls call timed out")?;
+    Ok(resp.into_parts().1.seq)
+}
+
+/// A scratch directory holding copies of fixtures, so they can be rewritten.
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir =
+        std::env::temp_dir().join(format!("dubbo-tls-reload-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn install(fixture_name: &str, dest: &Path) {
+    fs::copy(fixture(fixture_name), dest).unwrap();
+}
+
+/// Waits for the next reload of the config watching `file`.
+async fn reloaded(events: &mut broadcast::Receiver<TlsReloadEvent>, file: &Path) -> TlsReloadEvent {
+    loop {
+        let event = tokio::time::timeout(Duration::from_secs(5), events.recv())
+            .await
+            .expect("no tls reload event")
+            .unwrap();
+        if event.files.iter().any(|v| v == file) {
+            return event;
+        }
+    }
+}
+
+/// The certificate presented by the server at `addr`.
+async fn served_cert(addr: SocketAddr) -> Certificate {
+    let mut roots = RootCertStore::empty();
+    for cert in load_certs(Path::new(&fixture("ca.crt"))).unwrap() {
+        roots.add(&cert).unwrap();
+    }
+    let config = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+
+    let tcp = TcpStream::connect(addr).await.unwrap();
+    let tls = TlsConnector::from(Arc::new(config))
+        .connect(ServerName::try_from("localhost").unwrap(), tcp)
+        .await
+        .unwrap();
+    tls.get_ref().1.peer_certificates().unwrap()[0].clone()
+}
+
+fn first_cert(fixture_name: &str) -> Certificate {
+    load_certs(Path::new(&fixture(fixture_name)))
+        .unwrap()
+        .remove(0)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_server_reload() {
+    let dir = scratch_dir("server");
+    let (cert_file, key_file) = (dir.join("server.crt"), dir.join("server.key"));
+    install("server.crt", &cert_file);
+    install("server.key", &key_file);
+
+    let mut events = subscribe_reload_events();
+    let server = DubboServer::new()
+        .with_tls_files(cert_file.clone(), key_file.clone())
+        .with_tls_reload_interval(RELOAD_INTERVAL)
+        .add_service(SERVICE_NAME.to_string(), EchoServer);
+    let (addr, _shutdown) = common::serve_with(server).await;
+    assert_eq!(served_cert(addr).await, first_cert("server.crt"));
+
+    // the pair is written in one go, so the key may be picked up by a later
+    // poll than the certificate
+    install("server-renewed.key", &key_file);
+    install("server-renewed.crt", &cert_file);
+    for attempt in 0.. {
+        reloaded(&mut events, &cert_file).await;
+        if served_cert(addr).await == first_cert("server-renewed.crt") {
+            break;
+        }
+        assert!(attempt < 5, "the renewed certificate is not served");
+    }
+
+    // broken files are reported and the renewed certificate stays in use
+    tokio::time::sleep(RELOAD_INTERVAL).await;
+    fs::write(&cert_file, "not a certificate").unwrap();
+    let event = reloaded(&mut events, &cert_file).await;
+    assert!(event.error.is_some());
+    assert_eq!(served_cert(addr).await, first_cert("server-renewed.crt"));
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn test_server_invalid_files() {
+    let dir = scratch_dir("invalid");
+    let missing = DubboServer::new()
+        .with_tls_files(dir.join("missing.crt"), dir.join("missing.key"))
+        .with_listener("tcp".to_string())
+        .add_service(SERVICE_NAME.to_string(), EchoServer);
+    assert!(missing.serve(common::free_addr()).await.is_err());
+
+    let key_file = dir.join("server.key");
+    fs::write(&key_file, "not a key").unwrap();
+    let invalid_key = DubboServer::new()
+        .with_tls_files(PathBuf::from(fixture("server.crt")), key_file)
+        .with_listener("tcp".to_string())
+        .add_service(SERVICE_NAME.to_string(), EchoServer);
+    assert!(invalid_key.serve(common::free_addr()).await.is_err());
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+// Every client connects to the same loopback server, see `common::connect`.
+#[tokio::test(flavor = "multi_thread")]
+async fn test_client_reload() {
+    let dir = scratch_dir("client");
+    let (cert_file, key_file) = (dir.join("client.crt"), dir.join("client.key"));
+    install("client.crt", &cert_file);
+    install("client.key", &key_file);
+
+    let server = DubboServer::new()
+        .with_tls_files(
+            PathBuf::from(fixture("server.crt")),
+            PathBuf::from(fixture("server.key")),
+        )
+        .with_client_auth_file(ClientAuth::Required, PathBuf::from(fixture("ca.crt")))
+        .with_allowed_identities(
+            SERVICE_NAME.to_string(),
+            vec!["spiffe://dubbo.apache.org/client".to_string()],
+        )
+        .add_service(SERVICE_NAME.to_string(), EchoServer);
+    let (addr, _shutdown) = common::serve_with(server).await;
+
+    let mut events = subscribe_reload_events();
+    let tls = TlsConfig::new()
+        .with_ca_file(&fixture("ca.crt"))
+        .with_client_cert(cert_file.to_str().unwrap(), key_file.to_str().unwrap())
+        .with_reload_interval(RELOAD_INTERVAL);
+    let host = format!("http://{}?interface={}", addr, SERVICE_NAME);
+    let mut client = TripleClient::new(ClientBuilder::from_static(&host).with_tls(tls));
+
+    assert_eq!(echo(&mut client, 1).await.unwrap(), 1);
+
+    // a certificate that is trusted, but not allowed to call the service
+    install("other.key", &key_file);
+    install("other.crt", &cert_file);
+    for seq in 2.. {
+        reloaded(&mut events, &cert_file).await;
+        match echo(&mut client, seq).await {
+            Err(status) if status.code() == Code::PermissionDenied => break,
+            _ => assert!(seq < 10, "the renewed client certificate is not used"),
+        }
+    }
+
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -15,7 +15,7 @@
  * limitations under the License.
  */
 
-use dubbo::codegen::*;
+use dubbo::{codegen::*, triple::client::TlsConfig};
 use example_echo::generated::generated::{echo_client::EchoClient, EchoRequest};
 use futures_util::StreamExt;
 
@@ -32,7 +32,9 @@ impl Filter for FakeFilter {
 async fn main() {
     dubbo::logger::init();
 
-    let builder = ClientBuilder::from_static(&"https://127.0.0.1:8889").with_timeout(1000000);
+    let builder = ClientBuilder::from_static(&"https://127.0.0.1:8889")
+        .with_timeout(1000000)
+        .with_tls(TlsConfig::new().with_ca_file("fixtures/ca.crt"));
     let mut cli = EchoClient::new(builder);
 
     let resp = cli
@@ -86,6 +86,12 @@ pub mod echo_client {
             self.inner.bidi_streaming(request, path, invocation).await
         }
     }
+    impl NewClient for EchoClient {
+        const INTERFACE: &'static str = "grpc.examples.echo.Echo";
+        fn new_client(builder: ClientBuilder) -> Self {
+            Self::new(builder)
+        }
+    }
 }
 /// Generated server implementations.
 pub mod echo_server {
@@ -68,7 +68,7 @@ async fn main() {
         .with_config(r)
         .add_registry("nacos://127.0.0.1:8848/");
 
-    f.start().await;
+    f.start().await.unwrap().wait().await;
 }
 
 #[allow(dead_code)]
@@ -15,13 +15,12 @@
  * limitations under the License.
  */
 
-use std::{any::Any, sync::Arc};
+use std::sync::Arc;
 
+/// What a consumer calls, independent of the protocol it is sent with.
 pub trait Invocation {
+    fn get_target_service_unique_name(&self) -> String;
     fn get_method_name(&self) -> String;
-    fn get_parameter_types(&self) -> Vec<String>;
-    fn get_arguments(&self) -> Vec<String>;
-    fn get_reply(&self) -> Arc<dyn Any>;
 }
 
 pub type BoxInvocation = Arc<dyn Invocation + Send + Sync>;
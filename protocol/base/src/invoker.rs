@@ -14,10 +14,6 @@
  * See the License for the specific language governing permissions and
  * limitations under the License.
  */
-use crate::{
-    invocation::BoxInvocation,
-    output::{BoxOutput, RPCOutput},
-};
 use dubbo_base::{Node, Url};
 use std::{
     fmt::{Display, Formatter},
@@ -26,25 +22,26 @@ use std::{
         Arc,
     },
 };
+use tower_service::Service;
+
+/// The invoker of one provider: a [`Service`] of the requests of its
+/// protocol. Invokers of every protocol that take the same requests can be
+/// mixed behind one cluster and load balancer.
+///
+/// This replaces the `Invoker` trait with an associated `Output` and
+/// `invoke(&self, BoxInvocation)`, which no invoker implemented: call the
+/// invoker as a [`Service`] instead.
+pub trait Invoker<Req>: Node + Service<Req> {}
 
+impl<T, Req> Invoker<Req> for T where T: Node + Service<Req> {}
+
+/// The [`Node`] state shared by invokers.
 pub struct BaseInvoker {
     url: Arc<Url>,
     available: AtomicBool,
     destroyed: AtomicBool,
 }
 
-pub trait Invoker {
-    type Output;
-    fn invoke(&self, invocation: BoxInvocation) -> Self::Output;
-}
-
-impl Invoker for BaseInvoker {
-    type Output = BoxOutput<String>;
-    fn invoke(&self, _invocation: BoxInvocation) -> Self::Output {
-        Arc::new(RPCOutput::default())
-    }
-}
-
 impl Node for BaseInvoker {
     fn get_url(&self) -> Arc<Url> {
         self.url.clone()
@@ -81,4 +78,31 @@ impl BaseInvoker {
             destroyed: AtomicBool::new(false),
         }
     }
+
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_destroy() {
+        let invoker = BaseInvoker::new(
+            "tri://127.0.0.1:8888/org.apache.dubbo.Greeter"
+                .parse()
+                .unwrap(),
+        );
+        assert!(invoker.is_available());
+        assert_eq!(
+            invoker.to_string(),
+            r#"Invoker { protocol: "tri", host: Some("127.0.0.1"), path: "/org.apache.dubbo.Greeter" }"#
+        );
+
+        invoker.destroy();
+        assert!(!invoker.is_available());
+        assert!(invoker.is_destroyed());
+    }
 }
@@ -87,9 +87,9 @@ where
 
     fn get_attachment_or_default(&self, key: &str, default_value: &str) -> String {
         self.attachments
-            .contains_key(key)
-            .then(|| self.attachments.get(key).unwrap().clone())
-            .unwrap_or(default_value.to_string())
+            .get(key)
+            .map(|value| value.clone())
+            .unwrap_or_else(|| default_value.to_string())
     }
 }
 
@@ -104,6 +104,7 @@ mod tests {
         assert_eq!(result.get().unwrap().as_str(), "r");
         result.add_attachment("hello", "world");
         let string = result.get_attachment_or_default("hello", "test");
-        println!("{}", string);
+        tracing::info!("attachment hello: {}", string);
+        assert_eq!(string, "world");
     }
 }
@@ -0,0 +1,99 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use bytes::{Buf, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::{
+    error::Error,
+    header::{Header, HEADER_LENGTH},
+    message::Message,
+};
+
+/// The default payload limit of Java Dubbo, 8 MiB.
+pub const DEFAULT_MAX_PAYLOAD: usize = 8 * 1024 * 1024;
+
+/// Frames Dubbo requests and responses, for use with `tokio_util::codec::Framed`.
+#[derive(Debug, Clone)]
+pub struct Dubbo2Codec {
+    max_payload: usize,
+}
+
+impl Default for Dubbo2Codec {
+    fn default() -> Self {
+        Dubbo2Codec {
+            max_payload: DEFAULT_MAX_PAYLOAD,
+        }
+    }
+}
+
+impl Dubbo2Codec {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_payload(self, max_payload: usize) -> Self {
+        Self { max_payload }
+    }
+}
+
+impl Decoder for Dubbo2Codec {
+    type Item = Message;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if src.len() < HEADER_LENGTH {
+            return Ok(None);
+        }
+        let header = Header::decode(&src[..HEADER_LENGTH])?;
+        let body_len = header.body_len as usize;
+        if body_len > self.max_payload {
+            return Err(Error::PayloadTooLarge(body_len, self.max_payload));
+        }
+        if src.len() < HEADER_LENGTH + body_len {
+            src.reserve(HEADER_LENGTH + body_len - src.len());
+            return Ok(None);
+        }
+
+        src.advance(HEADER_LENGTH);
+        let body = src.split_to(body_len);
+        Message::decode(&header, &body).map(Some)
+    }
+}
+
+impl Encoder<Message> for Dubbo2Codec {
+    type Error = Error;
+
+    fn encode(&mut self, item: Message, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.encode(&item, dst)
+    }
+}
+
+impl Encoder<&Message> for Dubbo2Codec {
+    type Error = Error;
+
+    fn encode(&mut self, item: &Message, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let start = dst.len();
+        item.encode(dst);
+        let body_len = dst.len() - start - HEADER_LENGTH;
+        if body_len > self.max_payload {
+            dst.truncate(start);
+            return Err(Error::PayloadTooLarge(body_len, self.max_payload));
+        }
+        Ok(())
+    }
+}
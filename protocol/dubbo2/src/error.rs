@@ -0,0 +1,42 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::io;
+
+use thiserror::Error;
+
+use crate::header::HEADER_LENGTH;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("io error: {0}")]
+    Io(#[from] io::Error),
+    #[error("header of {0} bytes, expected {HEADER_LENGTH}.")]
+    ShortHeader(usize),
+    #[error("bad magic {0:#06x}, not a dubbo frame.")]
+    BadMagic(u16),
+    #[error("payload of {0} bytes exceeds the limit of {1} bytes.")]
+    PayloadTooLarge(usize, usize),
+    #[error("unsupported serialization id {0}.")]
+    UnsupportedSerialization(u8),
+    #[error("unknown response status {0}.")]
+    UnknownStatus(u8),
+    #[error("hessian2 error: {0}")]
+    Hessian2(#[from] crate::hessian2::Error),
+    #[error("bad request body: {0}")]
+    BadBody(String),
+}
@@ -0,0 +1,158 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use bytes::{BufMut, BytesMut};
+
+use crate::error::Error;
+
+pub const MAGIC: u16 = 0xdabb;
+pub const HEADER_LENGTH: usize = 16;
+
+pub const FLAG_REQUEST: u8 = 0x80;
+pub const FLAG_TWO_WAY: u8 = 0x40;
+pub const FLAG_EVENT: u8 = 0x20;
+pub const SERIALIZATION_MASK: u8 = 0x1f;
+
+/// The serialization id of Hessian2, the default of Java Dubbo.
+pub const HESSIAN2_SERIALIZATION_ID: u8 = 2;
+
+/// The fixed header in front of every frame:
+///
+/// ```text
+/// | magic (2) | flag (1) | status (1) | request id (8) | body length (4) |
+/// ```
+///
+/// The flag holds the request, two-way and event bits and the
+/// serialization id, the status is only set on responses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    pub flag: u8,
+    pub status: u8,
+    pub id: u64,
+    pub body_len: u32,
+}
+
+impl Header {
+    /// Parses the first `HEADER_LENGTH` bytes of `src`.
+    pub fn decode(src: &[u8]) -> Result<Header, Error> {
+        if src.len() < HEADER_LENGTH {
+            return Err(Error::ShortHeader(src.len()));
+        }
+        let magic = u16::from_be_bytes([src[0], src[1]]);
+        if magic != MAGIC {
+            return Err(Error::BadMagic(magic));
+        }
+        let mut id = [0; 8];
+        id.copy_from_slice(&src[4..12]);
+        let mut body_len = [0; 4];
+        body_len.copy_from_slice(&src[12..16]);
+        Ok(Header {
+            flag: src[2],
+            status: src[3],
+            id: u64::from_be_bytes(id),
+            body_len: u32::from_be_bytes(body_len),
+        })
+    }
+
+    pub fn encode(&self, dst: &mut BytesMut) {
+        dst.put_u16(MAGIC);
+        dst.put_u8(self.flag);
+        dst.put_u8(self.status);
+        dst.put_u64(self.id);
+        dst.put_u32(self.body_len);
+    }
+
+    pub fn is_request(&self) -> bool {
+        self.flag & FLAG_REQUEST != 0
+    }
+
+    pub fn is_two_way(&self) -> bool {
+        self.flag & FLAG_TWO_WAY != 0
+    }
+
+    pub fn is_event(&self) -> bool {
+        self.flag & FLAG_EVENT != 0
+    }
+
+    pub fn serialization_id(&self) -> u8 {
+        self.flag & SERIALIZATION_MASK
+    }
+}
+
+/// The status of a response, as defined by `org.apache.dubbo.remoting.exchange.Response`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Status {
+    Ok = 20,
+    ClientTimeout = 30,
+    ServerTimeout = 31,
+    ChannelInactive = 35,
+    BadRequest = 40,
+    BadResponse = 50,
+    ServiceNotFound = 60,
+    ServiceError = 70,
+    ServerError = 80,
+    ClientError = 90,
+    ServerThreadpoolExhausted = 100,
+}
+
+impl TryFrom<u8> for Status {
+    type Error = Error;
+
+    fn try_from(status: u8) -> Result<Self, Self::Error> {
+        let status = match status {
+            20 => Status::Ok,
+            30 => Status::ClientTimeout,
+            31 => Status::ServerTimeout,
+            35 => Status::ChannelInactive,
+            40 => Status::BadRequest,
+            50 => Status::BadResponse,
+            60 => Status::ServiceNotFound,
+            70 => Status::ServiceError,
+            80 => Status::ServerError,
+            90 => Status::ClientError,
+            100 => Status::ServerThreadpoolExhausted,
+            _ => return Err(Error::UnknownStatus(status)),
+        };
+        Ok(status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        let header = Header {
+            flag: FLAG_REQUEST | HESSIAN2_SERIALIZATION_ID,
+            status: 0,
+            id: 7,
+            body_len: 3,
+        };
+        let mut dst = BytesMut::new();
+        header.encode(&mut dst);
+        assert_eq!(Header::decode(&dst).unwrap(), header);
+        assert!(matches!(
+            Header::decode(&dst[..HEADER_LENGTH - 1]),
+            Err(Error::ShortHeader(15))
+        ));
+        assert!(matches!(Header::decode(&[]), Err(Error::ShortHeader(0))));
+        dst[0] = 0;
+        assert!(matches!(Header::decode(&dst), Err(Error::BadMagic(0x00bb))));
+    }
+}
@@ -14,17 +14,22 @@
  * See the License for the specific language governing permissions and
  * limitations under the License.
  */
-pub fn add(left: usize, right: usize) -> usize {
-    left + right
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+//! The Dubbo protocol of Java Dubbo 2, the `dubbo://` protocol: a 16 byte
+//! header in front of Hessian2 serialized invocations and results.
 
-    #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
-    }
-}
+pub mod codec;
+pub mod error;
+pub mod header;
+pub mod message;
+
+pub use protocol_hessian2 as hessian2;
+
+pub use codec::Dubbo2Codec;
+pub use error::Error;
+pub use message::{
+    Attachments, Message, Request, RequestData, Response, ResponseData, RpcInvocation,
+};
+
+/// The protocol version sent in every invocation.
+pub const DUBBO_VERSION: &str = "2.0.2";
@@ -0,0 +1,547 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use bytes::{BufMut, BytesMut};
+
+use crate::{
+    error::Error,
+    header::{
+        Header, Status, FLAG_EVENT, FLAG_REQUEST, FLAG_TWO_WAY, HEADER_LENGTH,
+        HESSIAN2_SERIALIZATION_ID,
+    },
+    hessian2::{self, Value},
+    DUBBO_VERSION,
+};
+
+// the first value of a response body, see `org.apache.dubbo.rpc.protocol.dubbo.DubboCodec`
+const RESPONSE_WITH_EXCEPTION: i32 = 0;
+const RESPONSE_VALUE: i32 = 1;
+const RESPONSE_NULL_VALUE: i32 = 2;
+const RESPONSE_WITH_EXCEPTION_WITH_ATTACHMENTS: i32 = 3;
+const RESPONSE_VALUE_WITH_ATTACHMENTS: i32 = 4;
+const RESPONSE_NULL_VALUE_WITH_ATTACHMENTS: i32 = 5;
+
+/// A frame of the Dubbo protocol.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    Request(Request),
+    Response(Response),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request {
+    pub id: u64,
+    /// Whether the peer answers the request, unset for oneway calls.
+    pub two_way: bool,
+    pub data: RequestData,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RequestData {
+    Invocation(RpcInvocation),
+    /// An event, `Value::Null` for heartbeats.
+    Event(Value),
+    /// A request whose body could not be decoded, it should be answered
+    /// with `Status::BadRequest`.
+    Broken(String),
+}
+
+impl Request {
+    pub fn new(id: u64, invocation: RpcInvocation) -> Self {
+        Request {
+            id,
+            two_way: true,
+            data: RequestData::Invocation(invocation),
+        }
+    }
+
+    pub fn heartbeat(id: u64) -> Self {
+        Request {
+            id,
+            two_way: true,
+            data: RequestData::Event(Value::Null),
+        }
+    }
+
+    pub fn is_heartbeat(&self) -> bool {
+        matches!(self.data, RequestData::Event(Value::Null))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub id: u64,
+    pub status: Status,
+    pub data: ResponseData,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResponseData {
+    /// The answer to an event, `Value::Null` for heartbeats.
+    Event(Value),
+    /// The return value of an invocation, `Value::Null` for `void` methods.
+    Value {
+        value: Value,
+        attachments: Attachments,
+    },
+    /// The Java throwable thrown by the invoked method.
+    Exception {
+        exception: Value,
+        attachments: Attachments,
+    },
+    /// The error message of a response whose status is not `Status::Ok`.
+    Error(String),
+}
+
+impl Response {
+    pub fn new(id: u64, value: Value) -> Self {
+        Response {
+            id,
+            status: Status::Ok,
+            data: ResponseData::Value {
+                value,
+                attachments: Attachments::new(),
+            },
+        }
+    }
+
+    pub fn error(id: u64, status: Status, message: String) -> Self {
+        Response {
+            id,
+            status,
+            data: ResponseData::Error(message),
+        }
+    }
+
+    pub fn heartbeat(id: u64) -> Self {
+        Response {
+            id,
+            status: Status::Ok,
+            data: ResponseData::Event(Value::Null),
+        }
+    }
+
+    pub fn is_heartbeat(&self) -> bool {
+        matches!(self.data, ResponseData::Event(Value::Null))
+    }
+}
+
+/// The attachments of an invocation or result. They keep the order they
+/// were read in, so a decoded frame encodes to the same bytes.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Attachments(Vec<(String, Value)>);
+
+impl Attachments {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.0.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        self.get(key).and_then(Value::as_str)
+    }
+
+    /// Sets `key`, replacing an existing value in place.
+    pub fn insert(&mut self, key: String, value: Value) {
+        match self.0.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, v)) => *v = value,
+            None => self.0.push((key, value)),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &(String, Value)> {
+        self.0.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn to_value(&self) -> Value {
+        Value::map(
+            self.0
+                .iter()
+                .map(|(k, v)| (Value::String(k.clone()), v.clone()))
+                .collect(),
+        )
+    }
+
+    fn from_value(value: Value) -> Result<Self, Error> {
+        match value {
+            Value::Null => Ok(Attachments::new()),
+            Value::Map { entries, .. } => entries
+                .into_iter()
+                .map(|(k, v)| match k {
+                    Value::String(k) => Ok((k, v)),
+                    k => Err(Error::BadBody(format!("attachment key {:?}", k))),
+                })
+                .collect::<Result<_, _>>()
+                .map(Attachments),
+            value => Err(Error::BadBody(format!("attachments {:?}", value))),
+        }
+    }
+}
+
+impl FromIterator<(String, Value)> for Attachments {
+    fn from_iter<T: IntoIterator<Item = (String, Value)>>(iter: T) -> Self {
+        let mut attachments = Attachments::new();
+        for (k, v) in iter {
+            attachments.insert(k, v);
+        }
+        attachments
+    }
+}
+
+/// A call of `method` on the Java interface `service`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RpcInvocation {
+    pub dubbo_version: String,
+    pub service: String,
+    pub version: Option<String>,
+    pub method: String,
+    /// The Java types of the parameters, e.g. `int` or `java.lang.String[]`.
+    pub parameter_types: Vec<String>,
+    /// The arguments, a `Value::Ref` among them refers to a value of
+    /// `hessian2::Refs::new(&arguments)`.
+    pub arguments: Vec<Value>,
+    pub attachments: Attachments,
+}
+
+impl RpcInvocation {
+    pub fn new(service: &str, method: &str) -> Self {
+        RpcInvocation {
+            dubbo_version: DUBBO_VERSION.to_string(),
+            service: service.to_string(),
+            version: None,
+            method: method.to_string(),
+            parameter_types: Vec::new(),
+            arguments: Vec::new(),
+            attachments: Attachments::new(),
+        }
+    }
+
+    pub fn with_version(self, version: &str) -> Self {
+        Self {
+            version: Some(version.to_string()),
+            ..self
+        }
+    }
+
+    /// Appends an argument of the Java type `parameter_type`.
+    pub fn with_argument(mut self, parameter_type: &str, argument: Value) -> Self {
+        self.parameter_types.push(parameter_type.to_string());
+        self.arguments.push(argument);
+        self
+    }
+
+    pub fn with_attachment(mut self, key: &str, value: Value) -> Self {
+        self.attachments.insert(key.to_string(), value);
+        self
+    }
+
+    /// The parameter types as JVM descriptor, e.g. `ILjava/lang/String;`.
+    pub fn parameter_types_desc(&self) -> String {
+        self.parameter_types
+            .iter()
+            .map(|v| name_to_desc(v))
+            .collect()
+    }
+}
+
+/// Converts a Java type name such as `int[]` or `java.lang.String` to its
+/// JVM descriptor, like `ReflectUtils.name2desc`.
+pub fn name_to_desc(name: &str) -> String {
+    let mut name = name;
+    let mut desc = String::new();
+    while let Some(component) = name.strip_suffix("[]") {
+        desc.push('[');
+        name = component;
+    }
+    let primitive = match name {
+        "void" => "V",
+        "boolean" => "Z",
+        "byte" => "B",
+        "char" => "C",
+        "double" => "D",
+        "float" => "F",
+        "int" => "I",
+        "long" => "J",
+        "short" => "S",
+        // `Class.getName` of an array, e.g. `[Ljava.lang.String;`
+        _ if name.starts_with('[') => {
+            desc.push_str(&name.replace('.', "/"));
+            return desc;
+        }
+        _ => {
+            desc.push('L');
+            desc.push_str(&name.replace('.', "/"));
+            desc.push(';');
+            return desc;
+        }
+    };
+    desc.push_str(primitive);
+    desc
+}
+
+/// Splits a descriptor of parameter types into Java type names, like
+/// `ReflectUtils.desc2name` for each parameter.
+pub fn desc_to_names(desc: &str) -> Result<Vec<String>, Error> {
+    let bad_desc = || Error::BadBody(format!("parameter types desc {}", desc));
+    let mut names = Vec::new();
+    let mut rest = desc;
+    while !rest.is_empty() {
+        let dims = rest.len() - rest.trim_start_matches('[').len();
+        rest = &rest[dims..];
+        let (name, len) = match rest.as_bytes().first().ok_or_else(bad_desc)? {
+            b'V' => ("void".to_string(), 1),
+            b'Z' => ("boolean".to_string(), 1),
+            b'B' => ("byte".to_string(), 1),
+            b'C' => ("char".to_string(), 1),
+            b'D' => ("double".to_string(), 1),
+            b'F' => ("float".to_string(), 1),
+            b'I' => ("int".to_string(), 1),
+            b'J' => ("long".to_string(), 1),
+            b'S' => ("short".to_string(), 1),
+            b'L' => {
+                let end = rest.find(';').ok_or_else(bad_desc)?;
+                (rest[1..end].replace('/', "."), end + 1)
+            }
+            _ => return Err(bad_desc()),
+        };
+        names.push(format!("{}{}", name, "[]".repeat(dims)));
+        rest = &rest[len..];
+    }
+    Ok(names)
+}
+
+impl Message {
+    pub(crate) fn encode(&self, dst: &mut BytesMut) {
+        let mut body = hessian2::Encoder::new();
+        let (flag, status, id) = match self {
+            Message::Request(req) => {
+                let mut flag = FLAG_REQUEST | HESSIAN2_SERIALIZATION_ID;
+                if req.two_way {
+                    flag |= FLAG_TWO_WAY;
+                }
+                match &req.data {
+                    RequestData::Invocation(inv) => {
+                        body.write_string(&inv.dubbo_version);
+                        body.write_string(&inv.service);
+                        body.write_string_opt(inv.version.as_deref());
+                        body.write_string(&inv.method);
+                        body.write_string(&inv.parameter_types_desc());
+                        for arg in &inv.arguments {
+                            body.write(arg);
+                        }
+                        body.write(&inv.attachments.to_value());
+                    }
+                    RequestData::Event(event) => {
+                        flag |= FLAG_EVENT;
+                        body.write(event);
+                    }
+                    RequestData::Broken(message) => body.write_string(message),
+                }
+                (flag, 0, req.id)
+            }
+            Message::Response(resp) => {
+                let mut flag = HESSIAN2_SERIALIZATION_ID;
+                match &resp.data {
+                    ResponseData::Event(event) => {
+                        flag |= FLAG_EVENT;
+                        body.write(event);
+                    }
+                    ResponseData::Value { value, attachments } => {
+                        if value.is_null() {
+                            body.write_int(RESPONSE_NULL_VALUE_WITH_ATTACHMENTS);
+                        } else {
+                            body.write_int(RESPONSE_VALUE_WITH_ATTACHMENTS);
+                            body.write(value);
+                        }
+                        body.write(&attachments.to_value());
+                    }
+                    ResponseData::Exception {
+                        exception,
+                        attachments,
+                    } => {
+                        body.write_int(RESPONSE_WITH_EXCEPTION_WITH_ATTACHMENTS);
+                        body.write(exception);
+                        body.write(&attachments.to_value());
+                    }
+                    ResponseData::Error(message) => body.write_string(message),
+                }
+                (flag, resp.status as u8, resp.id)
+            }
+        };
+
+        let body = body.into_bytes();
+        dst.reserve(HEADER_LENGTH + body.len());
+        Header {
+            flag,
+            status,
+            id,
+            body_len: body.len() as u32,
+        }
+        .encode(dst);
+        dst.put(body);
+    }
+
+    /// Decodes the body of a frame. Bodies that cannot be decoded are
+    /// returned as broken requests or client error responses, so the
+    /// connection can carry on.
+    pub(crate) fn decode(header: &Header, body: &[u8]) -> Result<Message, Error> {
+        if header.serialization_id() != HESSIAN2_SERIALIZATION_ID {
+            return Err(Error::UnsupportedSerialization(header.serialization_id()));
+        }
+
+        if header.is_request() {
+            let data = match decode_request(header, body) {
+                Ok(data) => data,
+                Err(err) => RequestData::Broken(err.to_string()),
+            };
+            return Ok(Message::Request(Request {
+                id: header.id,
+                two_way: header.is_two_way(),
+                data,
+            }));
+        }
+
+        let status = Status::try_from(header.status)?;
+        let resp = match decode_response(header, status, body) {
+            Ok(data) => Response {
+                id: header.id,
+                status,
+                data,
+            },
+            Err(err) => Response::error(header.id, Status::ClientError, err.to_string()),
+        };
+        Ok(Message::Response(resp))
+    }
+}
+
+fn decode_request(header: &Header, body: &[u8]) -> Result<RequestData, Error> {
+    let mut dec = hessian2::Decoder::new(body);
+    if header.is_event() {
+        return Ok(RequestData::Event(dec.read()?));
+    }
+
+    let required = |v: Option<String>, name: &str| {
+        v.ok_or_else(|| Error::BadBody(format!("missing {}", name)))
+    };
+    let dubbo_version = dec
+        .read_string_opt()?
+        .unwrap_or_else(|| DUBBO_VERSION.to_string());
+    let service = required(dec.read_string_opt()?, "service")?;
+    let version = dec.read_string_opt()?;
+    let method = required(dec.read_string_opt()?, "method")?;
+    let parameter_types = desc_to_names(&dec.read_string_opt()?.unwrap_or_default())?;
+    let arguments = parameter_types
+        .iter()
+        .map(|_| dec.read())
+        .collect::<Result<_, _>>()?;
+    let attachments = match dec.is_empty() {
+        true => Attachments::new(),
+        false => Attachments::from_value(dec.read()?)?,
+    };
+
+    Ok(RequestData::Invocation(RpcInvocation {
+        dubbo_version,
+        service,
+        version,
+        method,
+        parameter_types,
+        arguments,
+        attachments,
+    }))
+}
+
+fn decode_response(header: &Header, status: Status, body: &[u8]) -> Result<ResponseData, Error> {
+    let mut dec = hessian2::Decoder::new(body);
+    if status != Status::Ok {
+        return Ok(ResponseData::Error(
+            dec.read_string_opt()?.unwrap_or_default(),
+        ));
+    }
+    if header.is_event() {
+        return Ok(ResponseData::Event(dec.read()?));
+    }
+
+    let flag = dec.read_int()?;
+    let value = match flag {
+        RESPONSE_NULL_VALUE | RESPONSE_NULL_VALUE_WITH_ATTACHMENTS => Value::Null,
+        RESPONSE_VALUE
+        | RESPONSE_VALUE_WITH_ATTACHMENTS
+        | RESPONSE_WITH_EXCEPTION
+        | RESPONSE_WITH_EXCEPTION_WITH_ATTACHMENTS => dec.read()?,
+        flag => return Err(Error::BadBody(format!("response flag {}", flag))),
+    };
+    let attachments = match flag {
+        RESPONSE_WITH_EXCEPTION_WITH_ATTACHMENTS
+        | RESPONSE_VALUE_WITH_ATTACHMENTS
+        | RESPONSE_NULL_VALUE_WITH_ATTACHMENTS => Attachments::from_value(dec.read()?)?,
+        _ => Attachments::new(),
+    };
+
+    match flag {
+        RESPONSE_WITH_EXCEPTION | RESPONSE_WITH_EXCEPTION_WITH_ATTACHMENTS => {
+            Ok(ResponseData::Exception {
+                exception: value,
+                attachments,
+            })
+        }
+        _ => Ok(ResponseData::Value { value, attachments }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parameter_types_desc() {
+        let names = [
+            "int",
+            "java.lang.String",
+            "long[][]",
+            "java.util.Map[]",
+            "[Ljava.lang.String;",
+        ];
+        let desc: String = names.iter().map(|v| name_to_desc(v)).collect();
+        assert_eq!(
+            desc,
+            "ILjava/lang/String;[[J[Ljava/util/Map;[Ljava/lang/String;"
+        );
+        assert_eq!(
+            desc_to_names(&desc).unwrap(),
+            [
+                "int",
+                "java.lang.String",
+                "long[][]",
+                "java.util.Map[]",
+                "java.lang.String[]"
+            ]
+        );
+        assert!(desc_to_names("Ljava/lang/String").is_err());
+        assert!(desc_to_names("[").is_err());
+    }
+}
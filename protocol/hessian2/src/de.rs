@@ -0,0 +1,409 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use serde::{
+    de::{self, value::StrDeserializer, DeserializeOwned, Visitor},
+    forward_to_deserialize_any, Deserializer,
+};
+
+use crate::{value::DATE_TOKEN, Decoder, Error, Refs, Value, DEFAULT_MAX_DEPTH};
+
+/// How many times the values of the input may be deserialized through
+/// references, so that a chain of references cannot blow up.
+const MAX_REF_EXPANSION: usize = 10;
+
+static NULL: Value = Value::Null;
+
+/// Deserializes a single Hessian value from `bytes`.
+pub fn from_slice<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Error> {
+    let mut decoder = Decoder::new(bytes);
+    let value = decoder.read()?;
+    if !decoder.is_empty() {
+        return Err(Error::Message(format!(
+            "{} trailing bytes",
+            bytes.len() - decoder.position()
+        )));
+    }
+    from_value(value)
+}
+
+/// Deserializes `T` from a Hessian value. Objects deserialize like maps of
+/// their fields, Java enum constants like unit variants and references like
+/// the value they refer to, except references to enclosing values, such as
+/// the `cause` of a throwable without one, which deserialize like `None`.
+pub fn from_value<T: DeserializeOwned>(value: Value) -> Result<T, Error> {
+    deserialize(&value, |de| T::deserialize(de))
+}
+
+fn deserialize<R>(
+    value: &Value,
+    f: impl FnOnce(ValueDeserializer<'_, '_>) -> Result<R, Error>,
+) -> Result<R, Error> {
+    let limit = MAX_REF_EXPANSION.saturating_mul(count(value)) + 100;
+    let mut state = State {
+        refs: Refs::new([value]),
+        limit,
+        budget: limit,
+        ancestors: Vec::new(),
+    };
+    f(ValueDeserializer {
+        value,
+        state: &mut state,
+    })
+}
+
+// the number of values of `value`
+fn count(value: &Value) -> usize {
+    1 + match value {
+        Value::List { items, .. } => items.iter().map(count).sum(),
+        Value::Map { entries, .. } => entries.iter().map(|(k, v)| count(k) + count(v)).sum(),
+        Value::Object { fields, .. } => fields.iter().map(|(_, v)| count(v)).sum(),
+        _ => 0,
+    }
+}
+
+struct State<'a> {
+    refs: Refs<'a>,
+    // the indices of the lists, maps and objects being deserialized
+    ancestors: Vec<usize>,
+    // the values that may be deserialized, and those left
+    limit: usize,
+    budget: usize,
+}
+
+impl<'a> State<'a> {
+    // the value `value` refers to, `None` for an enclosing one
+    fn resolve(&mut self, value: &'a Value) -> Result<Option<&'a Value>, Error> {
+        self.budget = self.budget.checked_sub(1).ok_or(Error::LimitExceeded(
+            "values read through references",
+            self.limit,
+        ))?;
+        match value {
+            Value::Ref(index) if self.ancestors.contains(index) => Ok(None),
+            Value::Ref(index) => self
+                .refs
+                .get(*index)
+                .map(Some)
+                .ok_or(Error::UndefinedRef("value", *index)),
+            value => Ok(Some(value)),
+        }
+    }
+
+    fn nested<R>(
+        &mut self,
+        value: &'a Value,
+        f: impl FnOnce(&mut Self) -> Result<R, Error>,
+    ) -> Result<R, Error> {
+        if self.ancestors.len() >= DEFAULT_MAX_DEPTH {
+            return Err(Error::TooDeep(DEFAULT_MAX_DEPTH));
+        }
+        self.ancestors
+            .push(self.refs.index_of(value).unwrap_or(usize::MAX));
+        let result = f(self);
+        self.ancestors.pop();
+        result
+    }
+}
+
+struct ValueDeserializer<'s, 'a> {
+    value: &'a Value,
+    state: &'s mut State<'a>,
+}
+
+impl<'de, 's, 'a> Deserializer<'de> for ValueDeserializer<'s, 'a> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let Some(value) = self.state.resolve(self.value)? else {
+            return visitor.visit_unit();
+        };
+        match value {
+            Value::Null | Value::Ref(_) => visitor.visit_unit(),
+            Value::Bool(v) => visitor.visit_bool(*v),
+            Value::Int(v) => visitor.visit_i32(*v),
+            Value::Long(v) | Value::Date(v) => visitor.visit_i64(*v),
+            Value::Double(v) => visitor.visit_f64(*v),
+            Value::String(v) => visitor.visit_str(v),
+            Value::Binary(v) => visitor.visit_bytes(v),
+            Value::List { items, .. } => self.state.nested(value, |state| {
+                visitor.visit_seq(SeqDeserializer {
+                    items: items.iter(),
+                    state,
+                })
+            }),
+            Value::Map { entries, .. } => self.state.nested(value, |state| {
+                visitor.visit_map(MapDeserializer {
+                    entries: entries
+                        .iter()
+                        .map(|(k, v)| (Key::Value(k), v))
+                        .collect::<Vec<_>>()
+                        .into_iter(),
+                    value: None,
+                    state,
+                })
+            }),
+            Value::Object { fields, .. } => self.state.nested(value, |state| {
+                visitor.visit_map(MapDeserializer {
+                    entries: fields
+                        .iter()
+                        .map(|(k, v)| (Key::Str(k), v))
+                        .collect::<Vec<_>>()
+                        .into_iter(),
+                    value: None,
+                    state,
+                })
+            }),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.state.resolve(self.value)? {
+            None | Some(Value::Null) => visitor.visit_none(),
+            Some(value) => visitor.visit_some(ValueDeserializer {
+                value,
+                state: self.state,
+            }),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match (name, self.value) {
+            (DATE_TOKEN, Value::Date(millis)) => visitor.visit_i64(*millis),
+            _ => visitor.visit_newtype_struct(self),
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let (variant, value) = match self.state.resolve(self.value)?.unwrap_or(&NULL) {
+            Value::String(variant) => (variant, None),
+            // a Java enum constant
+            Value::Object { fields, .. } if fields.len() == 1 => match &fields[0] {
+                (_, Value::String(variant)) => (variant, None),
+                field => return Err(Error::Message(format!("invalid enum {:?}", field))),
+            },
+            Value::Map { entries, .. } if entries.len() == 1 => match &entries[0] {
+                (Value::String(variant), value) => (variant, Some(value)),
+                (key, _) => {
+                    return Err(Error::Message(format!("invalid enum variant {:?}", key)));
+                }
+            },
+            value => return Err(Error::Message(format!("invalid enum {:?}", value))),
+        };
+        visitor.visit_enum(EnumDeserializer {
+            variant,
+            value,
+            state: self.state,
+        })
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+impl<'de> Deserializer<'de> for Value {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        deserialize(&self, |de| de.deserialize_any(visitor))
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        deserialize(&self, |de| de.deserialize_option(visitor))
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        deserialize(&self, |de| de.deserialize_newtype_struct(name, visitor))
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        deserialize(&self, |de| de.deserialize_enum(name, variants, visitor))
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+struct SeqDeserializer<'s, 'a> {
+    items: std::slice::Iter<'a, Value>,
+    state: &'s mut State<'a>,
+}
+
+impl<'de, 's, 'a> de::SeqAccess<'de> for SeqDeserializer<'s, 'a> {
+    type Error = Error;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        self.items
+            .next()
+            .map(|value| {
+                seed.deserialize(ValueDeserializer {
+                    value,
+                    state: self.state,
+                })
+            })
+            .transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
+
+// the key of a map entry or the name of an object field
+enum Key<'a> {
+    Value(&'a Value),
+    Str(&'a str),
+}
+
+struct MapDeserializer<'s, 'a> {
+    entries: std::vec::IntoIter<(Key<'a>, &'a Value)>,
+    value: Option<&'a Value>,
+    state: &'s mut State<'a>,
+}
+
+impl<'de, 's, 'a> de::MapAccess<'de> for MapDeserializer<'s, 'a> {
+    type Error = Error;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        match self.entries.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                match key {
+                    Key::Value(key) => seed.deserialize(ValueDeserializer {
+                        value: key,
+                        state: self.state,
+                    }),
+                    Key::Str(key) => seed.deserialize(StrDeserializer::<Error>::new(key)),
+                }
+                .map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        match self.value.take() {
+            Some(value) => seed.deserialize(ValueDeserializer {
+                value,
+                state: self.state,
+            }),
+            None => Err(Error::Message("map key without a value".to_string())),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
+struct EnumDeserializer<'s, 'a> {
+    variant: &'a str,
+    value: Option<&'a Value>,
+    state: &'s mut State<'a>,
+}
+
+impl<'de, 's, 'a> de::EnumAccess<'de> for EnumDeserializer<'s, 'a> {
+    type Error = Error;
+    type Variant = VariantDeserializer<'s, 'a>;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, VariantDeserializer<'s, 'a>), Error> {
+        let variant = seed.deserialize(StrDeserializer::<Error>::new(self.variant))?;
+        Ok((
+            variant,
+            VariantDeserializer {
+                value: self.value,
+                state: self.state,
+            },
+        ))
+    }
+}
+
+struct VariantDeserializer<'s, 'a> {
+    value: Option<&'a Value>,
+    state: &'s mut State<'a>,
+}
+
+impl<'s, 'a> VariantDeserializer<'s, 'a> {
+    fn deserializer(self) -> ValueDeserializer<'s, 'a> {
+        ValueDeserializer {
+            value: self.value.unwrap_or(&NULL),
+            state: self.state,
+        }
+    }
+}
+
+impl<'de, 's, 'a> de::VariantAccess<'de> for VariantDeserializer<'s, 'a> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        match self.value {
+            None | Some(Value::Null) => Ok(()),
+            Some(value) => Err(Error::Message(format!(
+                "unexpected value {:?} of a unit variant",
+                value
+            ))),
+        }
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        seed.deserialize(self.deserializer())
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        self.deserializer().deserialize_any(visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserializer().deserialize_any(visitor)
+    }
+}
@@ -0,0 +1,400 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::{Error, Value};
+
+/// How deep lists, maps and objects may nest unless set otherwise.
+pub const DEFAULT_MAX_DEPTH: usize = 128;
+
+/// How many times the input size the class and type names copied into the
+/// values may take, so that short references to long names cannot blow up.
+const MAX_NAME_EXPANSION: usize = 64;
+
+/// Reads Hessian values from a buffer. Class definitions, types and
+/// references are shared by all values read from one decoder.
+///
+/// References to lists, maps and objects are read as `Value::Ref` rather
+/// than copies, see `Refs` to look them up.
+pub struct Decoder<'a> {
+    input: &'a [u8],
+    pos: usize,
+    classes: Vec<(String, Vec<String>)>,
+    types: Vec<String>,
+    // the lists, maps and objects started so far
+    refs: usize,
+    depth: usize,
+    max_depth: usize,
+    // the bytes of the names copied from class definitions and types
+    copied: usize,
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(input: &'a [u8]) -> Self {
+        Decoder {
+            input,
+            pos: 0,
+            classes: Vec::new(),
+            types: Vec::new(),
+            refs: 0,
+            depth: 0,
+            max_depth: DEFAULT_MAX_DEPTH,
+            copied: 0,
+        }
+    }
+
+    /// Fails reading lists, maps and objects nested deeper than `max_depth`.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// The number of bytes read so far.
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pos >= self.input.len()
+    }
+
+    pub fn read(&mut self) -> Result<Value, Error> {
+        let mut tag = self.read_u8()?;
+        // the definition of a class precedes its first instance
+        while tag == b'C' {
+            self.read_class_def()?;
+            tag = self.read_u8()?;
+        }
+        self.read_tagged(tag)
+    }
+
+    /// Reads a string, `N` is read as `None`.
+    pub fn read_string_opt(&mut self) -> Result<Option<String>, Error> {
+        match self.peek()? {
+            b'N' => {
+                self.pos += 1;
+                Ok(None)
+            }
+            tag if is_string(tag) => {
+                self.pos += 1;
+                self.read_string(tag).map(Some)
+            }
+            tag => Err(Error::UnexpectedTag(tag, "string")),
+        }
+    }
+
+    pub fn read_int(&mut self) -> Result<i32, Error> {
+        let tag = self.read_u8()?;
+        match self.read_tagged(tag)? {
+            Value::Int(v) => Ok(v),
+            _ => Err(Error::UnexpectedTag(tag, "int")),
+        }
+    }
+
+    fn read_tagged(&mut self, tag: u8) -> Result<Value, Error> {
+        let value = match tag {
+            b'N' => Value::Null,
+            b'T' => Value::Bool(true),
+            b'F' => Value::Bool(false),
+
+            0x80..=0xbf => Value::Int(tag as i32 - 0x90),
+            0xc0..=0xcf => Value::Int(((tag as i32 - 0xc8) << 8) + self.read_u8()? as i32),
+            0xd0..=0xd7 => {
+                let v = self.read_u16()? as i32;
+                Value::Int(((tag as i32 - 0xd4) << 16) + v)
+            }
+            b'I' => Value::Int(i32::from_be_bytes(self.read_array()?)),
+
+            0xd8..=0xef => Value::Long(tag as i64 - 0xe0),
+            0xf0..=0xff => Value::Long(((tag as i64 - 0xf8) << 8) + self.read_u8()? as i64),
+            0x38..=0x3f => {
+                let v = self.read_u16()? as i64;
+                Value::Long(((tag as i64 - 0x3c) << 16) + v)
+            }
+            0x59 => Value::Long(i32::from_be_bytes(self.read_array()?) as i64),
+            b'L' => Value::Long(i64::from_be_bytes(self.read_array()?)),
+
+            0x5b => Value::Double(0.0),
+            0x5c => Value::Double(1.0),
+            0x5d => Value::Double(self.read_u8()? as i8 as f64),
+            0x5e => Value::Double(i16::from_be_bytes(self.read_array()?) as f64),
+            0x5f => Value::Double(0.001 * i32::from_be_bytes(self.read_array()?) as f64),
+            b'D' => Value::Double(f64::from_be_bytes(self.read_array()?)),
+
+            0x4a => Value::Date(i64::from_be_bytes(self.read_array()?)),
+            0x4b => Value::Date(i32::from_be_bytes(self.read_array()?) as i64 * 60000),
+
+            tag if is_string(tag) => Value::String(self.read_string(tag)?),
+            0x20..=0x2f | 0x34..=0x37 | b'A' | b'B' => Value::Binary(self.read_binary(tag)?),
+
+            b'U' | b'V' | b'W' | b'X' | 0x70..=0x7f => self.nested(|d| d.read_list(tag))?,
+            b'H' | b'M' => self.nested(|d| d.read_map(tag))?,
+            b'O' => {
+                let def = self.read_int()? as usize;
+                self.nested(|d| d.read_object(def))?
+            }
+            0x60..=0x6f => self.nested(|d| d.read_object((tag - 0x60) as usize))?,
+            b'Q' => {
+                let index = self.read_int()? as usize;
+                match index < self.refs {
+                    true => Value::Ref(index),
+                    false => return Err(Error::UndefinedRef("value", index)),
+                }
+            }
+            tag => return Err(Error::UnexpectedTag(tag, "value")),
+        };
+        Ok(value)
+    }
+
+    fn read_string(&mut self, mut tag: u8) -> Result<String, Error> {
+        let mut units = Vec::new();
+        loop {
+            let (len, last) = match tag {
+                0x00..=0x1f => (tag as usize, true),
+                0x30..=0x33 => (
+                    (((tag - 0x30) as usize) << 8) + self.read_u8()? as usize,
+                    true,
+                ),
+                b'S' => (self.read_u16()? as usize, true),
+                b'R' => (self.read_u16()? as usize, false),
+                tag => return Err(Error::UnexpectedTag(tag, "string chunk")),
+            };
+            self.read_utf16(len, &mut units)?;
+            if last {
+                break;
+            }
+            tag = self.read_u8()?;
+        }
+        String::from_utf16(&units).map_err(|_| Error::InvalidUtf8)
+    }
+
+    /// Reads `len` UTF-16 code units, each written as one to three bytes.
+    /// Four-byte sequences of non-Java writers are accepted as well.
+    fn read_utf16(&mut self, len: usize, units: &mut Vec<u16>) -> Result<(), Error> {
+        let end = units.len() + len;
+        while units.len() < end {
+            let b0 = self.read_u8()? as u32;
+            let ch = if b0 < 0x80 {
+                b0
+            } else if b0 & 0xe0 == 0xc0 {
+                ((b0 & 0x1f) << 6) | self.read_continuation()?
+            } else if b0 & 0xf0 == 0xe0 {
+                let b1 = self.read_continuation()?;
+                ((b0 & 0x0f) << 12) | (b1 << 6) | self.read_continuation()?
+            } else if b0 & 0xf8 == 0xf0 {
+                let b1 = self.read_continuation()?;
+                let b2 = self.read_continuation()?;
+                ((b0 & 0x07) << 18) | (b1 << 12) | (b2 << 6) | self.read_continuation()?
+            } else {
+                return Err(Error::InvalidUtf8);
+            };
+            match char::from_u32(ch) {
+                Some(c) => units.extend_from_slice(c.encode_utf16(&mut [0; 2])),
+                // one half of a surrogate pair, Java writes them separately
+                None if ch <= 0xffff => units.push(ch as u16),
+                None => return Err(Error::InvalidUtf8),
+            }
+        }
+        Ok(())
+    }
+
+    fn read_continuation(&mut self) -> Result<u32, Error> {
+        let b = self.read_u8()?;
+        if b & 0xc0 != 0x80 {
+            return Err(Error::InvalidUtf8);
+        }
+        Ok((b & 0x3f) as u32)
+    }
+
+    fn read_binary(&mut self, mut tag: u8) -> Result<Vec<u8>, Error> {
+        let mut data = Vec::new();
+        loop {
+            let (len, last) = match tag {
+                0x20..=0x2f => ((tag - 0x20) as usize, true),
+                0x34..=0x37 => (
+                    (((tag - 0x34) as usize) << 8) + self.read_u8()? as usize,
+                    true,
+                ),
+                b'B' => (self.read_u16()? as usize, true),
+                b'A' => (self.read_u16()? as usize, false),
+                tag => return Err(Error::UnexpectedTag(tag, "binary chunk")),
+            };
+            data.extend_from_slice(self.read_slice(len)?);
+            if last {
+                return Ok(data);
+            }
+            tag = self.read_u8()?;
+        }
+    }
+
+    // reads a list, map or object at one more level of nesting
+    fn nested(
+        &mut self,
+        read: impl FnOnce(&mut Self) -> Result<Value, Error>,
+    ) -> Result<Value, Error> {
+        if self.depth >= self.max_depth {
+            return Err(Error::TooDeep(self.max_depth));
+        }
+        self.depth += 1;
+        self.refs += 1;
+        let value = read(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn read_list(&mut self, tag: u8) -> Result<Value, Error> {
+        let (typ, len) = match tag {
+            b'U' => (Some(self.read_type()?), None),
+            b'V' => {
+                let typ = self.read_type()?;
+                (Some(typ), Some(self.read_int()? as usize))
+            }
+            b'W' => (None, None),
+            b'X' => (None, Some(self.read_int()? as usize)),
+            0x70..=0x77 => (Some(self.read_type()?), Some((tag - 0x70) as usize)),
+            _ => (None, Some((tag - 0x78) as usize)),
+        };
+
+        let mut items = Vec::with_capacity(len.unwrap_or_default().min(1024));
+        match len {
+            Some(len) => {
+                for _ in 0..len {
+                    items.push(self.read()?);
+                }
+            }
+            None => {
+                while !self.read_end()? {
+                    items.push(self.read()?);
+                }
+            }
+        }
+        Ok(Value::List { typ, items })
+    }
+
+    fn read_map(&mut self, tag: u8) -> Result<Value, Error> {
+        let typ = match tag {
+            b'M' => Some(self.read_type()?),
+            _ => None,
+        };
+        let mut entries = Vec::new();
+        while !self.read_end()? {
+            let key = self.read()?;
+            entries.push((key, self.read()?));
+        }
+        Ok(Value::Map { typ, entries })
+    }
+
+    fn read_class_def(&mut self) -> Result<(), Error> {
+        let class = self
+            .read_string_opt()?
+            .ok_or(Error::UnexpectedTag(b'N', "class name"))?;
+        let len = self.read_int()?;
+        let mut fields = Vec::new();
+        for _ in 0..len {
+            fields.push(
+                self.read_string_opt()?
+                    .ok_or(Error::UnexpectedTag(b'N', "field name"))?,
+            );
+        }
+        self.classes.push((class, fields));
+        Ok(())
+    }
+
+    fn read_object(&mut self, def: usize) -> Result<Value, Error> {
+        let (class, names) = self
+            .classes
+            .get(def)
+            .cloned()
+            .ok_or(Error::UndefinedRef("class", def))?;
+        self.copy(class.len() + names.iter().map(String::len).sum::<usize>())?;
+        let mut fields = Vec::with_capacity(names.len());
+        for name in names {
+            fields.push((name, self.read()?));
+        }
+        Ok(Value::Object { class, fields })
+    }
+
+    /// Reads the type of a list or map, either a name or a reference to a
+    /// name read before.
+    fn read_type(&mut self) -> Result<String, Error> {
+        let tag = self.peek()?;
+        if is_string(tag) {
+            self.pos += 1;
+            let typ = self.read_string(tag)?;
+            self.types.push(typ.clone());
+            return Ok(typ);
+        }
+        let index = self.read_int()? as usize;
+        let typ = self
+            .types
+            .get(index)
+            .cloned()
+            .ok_or(Error::UndefinedRef("type", index))?;
+        self.copy(typ.len())?;
+        Ok(typ)
+    }
+
+    fn copy(&mut self, len: usize) -> Result<(), Error> {
+        let limit = MAX_NAME_EXPANSION.saturating_mul(self.input.len());
+        self.copied = self.copied.saturating_add(len);
+        match self.copied > limit {
+            true => Err(Error::LimitExceeded("copied names", limit)),
+            false => Ok(()),
+        }
+    }
+
+    fn read_end(&mut self) -> Result<bool, Error> {
+        if self.peek()? == b'Z' {
+            self.pos += 1;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    fn peek(&self) -> Result<u8, Error> {
+        self.input
+            .get(self.pos)
+            .copied()
+            .ok_or(Error::UnexpectedEof)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, Error> {
+        let b = self.peek()?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn read_u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_be_bytes(self.read_array()?))
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.read_slice(N)?);
+        Ok(array)
+    }
+
+    fn read_slice(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        let end = self.pos.checked_add(len).ok_or(Error::UnexpectedEof)?;
+        let slice = self.input.get(self.pos..end).ok_or(Error::UnexpectedEof)?;
+        self.pos = end;
+        Ok(slice)
+    }
+}
+
+fn is_string(tag: u8) -> bool {
+    matches!(tag, 0x00..=0x1f | 0x30..=0x33 | b'S' | b'R')
+}
@@ -0,0 +1,313 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+
+use bytes::{BufMut, BytesMut};
+
+use crate::Value;
+
+/// The longest string or binary chunk, longer values are split.
+const CHUNK_SIZE: usize = 0x8000;
+
+/// Writes Hessian values the way `com.alibaba.com.caucho.hessian.io.Hessian2Output`
+/// does, so the bytes match those of Java peers.
+#[derive(Default)]
+pub struct Encoder {
+    buf: BytesMut,
+    classes: HashMap<(String, Vec<String>), usize>,
+    types: HashMap<String, usize>,
+}
+
+impl Encoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn into_bytes(self) -> BytesMut {
+        self.buf
+    }
+
+    pub fn write(&mut self, value: &Value) {
+        match value {
+            Value::Null => self.write_null(),
+            Value::Bool(v) => self.write_bool(*v),
+            Value::Int(v) => self.write_int(*v),
+            Value::Long(v) => self.write_long(*v),
+            Value::Double(v) => self.write_double(*v),
+            Value::Date(v) => self.write_date(*v),
+            Value::String(v) => self.write_string(v),
+            Value::Binary(v) => self.write_binary(v),
+            Value::List { typ, items } => {
+                self.write_list_begin(items.len(), typ.as_deref());
+                for item in items {
+                    self.write(item);
+                }
+            }
+            Value::Map { typ, entries } => {
+                match typ {
+                    Some(typ) => {
+                        self.buf.put_u8(b'M');
+                        self.write_type(typ);
+                    }
+                    None => self.buf.put_u8(b'H'),
+                }
+                for (key, value) in entries {
+                    self.write(key);
+                    self.write(value);
+                }
+                self.buf.put_u8(b'Z');
+            }
+            Value::Object { class, fields } => {
+                let names: Vec<&str> = fields.iter().map(|(name, _)| name.as_str()).collect();
+                self.write_object_begin(class, &names);
+                for (_, value) in fields {
+                    self.write(value);
+                }
+            }
+            Value::Ref(index) => {
+                self.buf.put_u8(b'Q');
+                self.write_int(*index as i32);
+            }
+        }
+    }
+
+    pub fn write_null(&mut self) {
+        self.buf.put_u8(b'N');
+    }
+
+    pub fn write_bool(&mut self, v: bool) {
+        self.buf.put_u8(if v { b'T' } else { b'F' });
+    }
+
+    pub fn write_int(&mut self, v: i32) {
+        match v {
+            -0x10..=0x2f => self.buf.put_u8((0x90 + v) as u8),
+            -0x800..=0x7ff => {
+                self.buf.put_u8((0xc8 + (v >> 8)) as u8);
+                self.buf.put_u8(v as u8);
+            }
+            -0x40000..=0x3ffff => {
+                self.buf.put_u8((0xd4 + (v >> 16)) as u8);
+                self.buf.put_u16(v as u16);
+            }
+            _ => {
+                self.buf.put_u8(b'I');
+                self.buf.put_i32(v);
+            }
+        }
+    }
+
+    pub fn write_long(&mut self, v: i64) {
+        match v {
+            -0x08..=0x0f => self.buf.put_u8((0xe0 + v) as u8),
+            -0x800..=0x7ff => {
+                self.buf.put_u8((0xf8 + (v >> 8)) as u8);
+                self.buf.put_u8(v as u8);
+            }
+            -0x40000..=0x3ffff => {
+                self.buf.put_u8((0x3c + (v >> 16)) as u8);
+                self.buf.put_u16(v as u16);
+            }
+            _ if v == v as i32 as i64 => {
+                self.buf.put_u8(0x59);
+                self.buf.put_i32(v as i32);
+            }
+            _ => {
+                self.buf.put_u8(b'L');
+                self.buf.put_i64(v);
+            }
+        }
+    }
+
+    pub fn write_double(&mut self, v: f64) {
+        let int = v as i32;
+        if int as f64 == v {
+            match int {
+                0 => return self.buf.put_u8(0x5b),
+                1 => return self.buf.put_u8(0x5c),
+                -0x80..=0x7f => {
+                    self.buf.put_u8(0x5d);
+                    return self.buf.put_i8(int as i8);
+                }
+                -0x8000..=0x7fff => {
+                    self.buf.put_u8(0x5e);
+                    return self.buf.put_i16(int as i16);
+                }
+                _ => {}
+            }
+        }
+
+        let mills = (v * 1000.0) as i32;
+        if 0.001 * mills as f64 == v {
+            self.buf.put_u8(0x5f);
+            self.buf.put_i32(mills);
+        } else {
+            self.buf.put_u8(b'D');
+            self.buf.put_f64(v);
+        }
+    }
+
+    /// Writes a `java.util.Date` given in milliseconds since the epoch.
+    pub fn write_date(&mut self, millis: i64) {
+        let minutes = millis / 60000;
+        if millis % 60000 == 0 && minutes == minutes as i32 as i64 {
+            self.buf.put_u8(0x4b);
+            self.buf.put_i32(minutes as i32);
+        } else {
+            self.buf.put_u8(0x4a);
+            self.buf.put_i64(millis);
+        }
+    }
+
+    /// Writes a string, lengths count UTF-16 code units and characters
+    /// outside the BMP are written as surrogate pairs, like Java does.
+    pub fn write_string(&mut self, v: &str) {
+        let units: Vec<u16> = v.encode_utf16().collect();
+        let mut rest = &units[..];
+        while rest.len() > CHUNK_SIZE {
+            let mut len = CHUNK_SIZE;
+            // never split a surrogate pair
+            if (0xd800..0xdc00).contains(&rest[len - 1]) {
+                len -= 1;
+            }
+            self.buf.put_u8(b'R');
+            self.buf.put_u16(len as u16);
+            self.put_utf16(&rest[..len]);
+            rest = &rest[len..];
+        }
+
+        let len = rest.len();
+        if len <= 0x1f {
+            self.buf.put_u8(len as u8);
+        } else if len <= 0x3ff {
+            self.buf.put_u8(0x30 + (len >> 8) as u8);
+            self.buf.put_u8(len as u8);
+        } else {
+            self.buf.put_u8(b'S');
+            self.buf.put_u16(len as u16);
+        }
+        self.put_utf16(rest);
+    }
+
+    pub fn write_string_opt(&mut self, v: Option<&str>) {
+        match v {
+            Some(v) => self.write_string(v),
+            None => self.write_null(),
+        }
+    }
+
+    pub fn write_binary(&mut self, v: &[u8]) {
+        let mut rest = v;
+        while rest.len() > CHUNK_SIZE {
+            self.buf.put_u8(b'A');
+            self.buf.put_u16(CHUNK_SIZE as u16);
+            self.buf.put_slice(&rest[..CHUNK_SIZE]);
+            rest = &rest[CHUNK_SIZE..];
+        }
+
+        let len = rest.len();
+        if len < 0x10 {
+            self.buf.put_u8(0x20 + len as u8);
+        } else if len < 0x400 {
+            self.buf.put_u8(0x34 + (len >> 8) as u8);
+            self.buf.put_u8(len as u8);
+        } else {
+            self.buf.put_u8(b'B');
+            self.buf.put_u16(len as u16);
+        }
+        self.buf.put_slice(rest);
+    }
+
+    /// Starts a list of `len` items, the items are written next.
+    pub fn write_list_begin(&mut self, len: usize, typ: Option<&str>) {
+        match typ {
+            Some(typ) if len <= 7 => {
+                self.buf.put_u8(0x70 + len as u8);
+                self.write_type(typ);
+            }
+            Some(typ) => {
+                self.buf.put_u8(b'V');
+                self.write_type(typ);
+                self.write_int(len as i32);
+            }
+            None if len <= 7 => self.buf.put_u8(0x78 + len as u8),
+            None => {
+                self.buf.put_u8(b'X');
+                self.write_int(len as i32);
+            }
+        }
+    }
+
+    /// Starts an instance of `class`, defining the class on first use. The
+    /// field values are written next.
+    pub fn write_object_begin(&mut self, class: &str, fields: &[&str]) {
+        let key = (
+            class.to_string(),
+            fields.iter().map(|v| v.to_string()).collect::<Vec<_>>(),
+        );
+        let def = match self.classes.get(&key) {
+            Some(def) => *def,
+            None => {
+                self.buf.put_u8(b'C');
+                self.write_string(class);
+                self.write_int(fields.len() as i32);
+                for field in fields {
+                    self.write_string(field);
+                }
+                let def = self.classes.len();
+                self.classes.insert(key, def);
+                def
+            }
+        };
+
+        if def <= 0x0f {
+            self.buf.put_u8(0x60 + def as u8);
+        } else {
+            self.buf.put_u8(b'O');
+            self.write_int(def as i32);
+        }
+    }
+
+    fn write_type(&mut self, typ: &str) {
+        match self.types.get(typ) {
+            Some(index) => self.write_int(*index as i32),
+            None => {
+                self.types.insert(typ.to_string(), self.types.len());
+                self.write_string(typ);
+            }
+        }
+    }
+
+    /// Java writes every UTF-16 code unit on its own, so characters outside
+    /// the BMP take two three-byte sequences.
+    fn put_utf16(&mut self, units: &[u16]) {
+        for &unit in units {
+            let unit = unit as u32;
+            if unit < 0x80 {
+                self.buf.put_u8(unit as u8);
+            } else if unit < 0x800 {
+                self.buf.put_u8((0xc0 | (unit >> 6)) as u8);
+                self.buf.put_u8((0x80 | (unit & 0x3f)) as u8);
+            } else {
+                self.buf.put_u8((0xe0 | (unit >> 12)) as u8);
+                self.buf.put_u8((0x80 | ((unit >> 6) & 0x3f)) as u8);
+                self.buf.put_u8((0x80 | (unit & 0x3f)) as u8);
+            }
+        }
+    }
+}
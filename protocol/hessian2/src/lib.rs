@@ -0,0 +1,350 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Hessian 2.0 serialization as used by Java Dubbo, see
+//! <http://hessian.caucho.com/doc/hessian-serialization.html>.
+//!
+//! Values are written and read as a dynamic [`Value`] tree, the class
+//! definitions and type references of one stream are tracked by the
+//! [`Encoder`] and [`Decoder`]. Serde types are converted with [`to_vec`]
+//! and [`from_slice`].
+
+mod de;
+mod decode;
+mod encode;
+mod ser;
+mod value;
+
+pub use de::{from_slice, from_value};
+pub use decode::{Decoder, DEFAULT_MAX_DEPTH};
+pub use encode::Encoder;
+pub use ser::{to_value, to_vec};
+pub use value::{Date, Refs, Value};
+
+use std::fmt::Display;
+
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum Error {
+    #[error("unexpected end of input.")]
+    UnexpectedEof,
+    #[error("unexpected tag {0:#04x} while reading {1}.")]
+    UnexpectedTag(u8, &'static str),
+    #[error("invalid utf-8 in string.")]
+    InvalidUtf8,
+    #[error("undefined {0} reference {1}.")]
+    UndefinedRef(&'static str, usize),
+    #[error("values nested deeper than {0}.")]
+    TooDeep(usize),
+    #[error("{0} exceed the limit of {1}.")]
+    LimitExceeded(&'static str, usize),
+    #[error("{0}")]
+    Message(String),
+}
+
+impl serde::ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::Message(msg.to_string())
+    }
+}
+
+impl serde::de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::Message(msg.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(value: &Value) -> Vec<u8> {
+        let mut encoder = Encoder::new();
+        encoder.write(value);
+        encoder.into_bytes().to_vec()
+    }
+
+    fn decode(bytes: &[u8]) -> Value {
+        let mut decoder = Decoder::new(bytes);
+        let value = decoder.read().unwrap();
+        assert!(decoder.is_empty());
+        value
+    }
+
+    /// Checks both directions against the bytes of the Hessian spec.
+    fn assert_bytes(value: Value, bytes: &[u8]) {
+        assert_eq!(encode(&value), bytes, "encoding {:?}", value);
+        assert_eq!(decode(bytes), value);
+    }
+
+    #[test]
+    fn test_numbers() {
+        assert_bytes(Value::Int(0), &[0x90]);
+        assert_bytes(Value::Int(-16), &[0x80]);
+        assert_bytes(Value::Int(47), &[0xbf]);
+        assert_bytes(Value::Int(48), &[0xc8, 0x30]);
+        assert_bytes(Value::Int(-2048), &[0xc0, 0x00]);
+        assert_bytes(Value::Int(2047), &[0xcf, 0xff]);
+        assert_bytes(Value::Int(-262144), &[0xd0, 0x00, 0x00]);
+        assert_bytes(Value::Int(262143), &[0xd7, 0xff, 0xff]);
+        assert_bytes(Value::Int(262144), b"I\x00\x04\x00\x00");
+
+        assert_bytes(Value::Long(0), &[0xe0]);
+        assert_bytes(Value::Long(-8), &[0xd8]);
+        assert_bytes(Value::Long(15), &[0xef]);
+        assert_bytes(Value::Long(-2048), &[0xf0, 0x00]);
+        assert_bytes(Value::Long(2047), &[0xff, 0xff]);
+        assert_bytes(Value::Long(-262144), &[0x38, 0x00, 0x00]);
+        assert_bytes(Value::Long(262143), &[0x3f, 0xff, 0xff]);
+        assert_bytes(Value::Long(262144), &[0x59, 0x00, 0x04, 0x00, 0x00]);
+        assert_bytes(Value::Long(1 << 31), b"L\x00\x00\x00\x00\x80\x00\x00\x00");
+
+        assert_bytes(Value::Double(0.0), &[0x5b]);
+        assert_bytes(Value::Double(1.0), &[0x5c]);
+        assert_bytes(Value::Double(-128.0), &[0x5d, 0x80]);
+        assert_bytes(Value::Double(-32768.0), &[0x5e, 0x80, 0x00]);
+        assert_bytes(Value::Double(12.25), &[0x5f, 0x00, 0x00, 0x2f, 0xda]);
+        assert_bytes(
+            Value::Double(0.0001),
+            &[b'D', 0x3f, 0x1a, 0x36, 0xe2, 0xeb, 0x1c, 0x43, 0x2d],
+        );
+
+        assert_bytes(Value::Bool(true), b"T");
+        assert_bytes(Value::Null, b"N");
+    }
+
+    #[test]
+    fn test_dates() {
+        // 09:51:31 May 8, 1998 UTC
+        assert_bytes(
+            Value::Date(894621091000),
+            &[0x4a, 0x00, 0x00, 0x00, 0xd0, 0x4b, 0x92, 0x84, 0xb8],
+        );
+        assert_bytes(Value::Date(894621060000), &[0x4b, 0x00, 0xe3, 0x83, 0x8f]);
+    }
+
+    #[test]
+    fn test_strings() {
+        assert_bytes(Value::from(""), &[0x00]);
+        assert_bytes(Value::from("hello"), b"\x05hello");
+        assert_bytes(Value::from("\u{c3}"), &[0x01, 0xc3, 0x83]);
+        // a surrogate pair, each half written on its own
+        assert_bytes(
+            Value::from("\u{1f600}"),
+            &[0x02, 0xed, 0xa0, 0xbd, 0xed, 0xb8, 0x80],
+        );
+
+        let medium = "a".repeat(0x3ff);
+        let bytes = encode(&Value::from(medium.as_str()));
+        assert_eq!(&bytes[..2], &[0x33, 0xff]);
+        assert_eq!(decode(&bytes), Value::from(medium));
+
+        let long = "é".repeat(0x8000 + 2);
+        let bytes = encode(&Value::from(long.as_str()));
+        assert_eq!(&bytes[..3], b"R\x80\x00");
+        assert_eq!(&bytes[3 + 0x10000..3 + 0x10000 + 1], &[0x02]);
+        assert_eq!(decode(&bytes), Value::from(long));
+
+        // four-byte sequences of other writers
+        assert_eq!(
+            decode(&[0x02, 0xf0, 0x9f, 0x98, 0x80]),
+            Value::from("\u{1f600}")
+        );
+    }
+
+    #[test]
+    fn test_binary() {
+        assert_bytes(Value::from(vec![1, 2, 3]), &[0x23, 1, 2, 3]);
+
+        let data = vec![7u8; 0x8000 + 0x10];
+        let bytes = encode(&Value::from(data.clone()));
+        assert_eq!(&bytes[..3], b"A\x80\x00");
+        assert_eq!(&bytes[3 + 0x8000..3 + 0x8000 + 2], &[0x34, 0x10]);
+        assert_eq!(decode(&bytes), Value::from(data));
+    }
+
+    #[test]
+    fn test_lists_and_maps() {
+        assert_bytes(
+            Value::list(vec![Value::Int(0), Value::from("foobar")]),
+            b"\x7a\x90\x06foobar",
+        );
+
+        // the second list refers to the type of the first
+        let ints = |items: Vec<i32>| Value::List {
+            typ: Some("[int".to_string()),
+            items: items.into_iter().map(Value::Int).collect(),
+        };
+        let mut encoder = Encoder::new();
+        encoder.write(&ints(vec![0, 1]));
+        encoder.write(&ints(vec![2]));
+        let bytes = encoder.into_bytes();
+        assert_eq!(&bytes[..], b"\x72\x04[int\x90\x91\x71\x90\x92");
+        let mut decoder = Decoder::new(&bytes);
+        assert_eq!(decoder.read().unwrap(), ints(vec![0, 1]));
+        assert_eq!(decoder.read().unwrap(), ints(vec![2]));
+
+        // variable length lists are only read
+        assert_eq!(
+            decode(b"\x57\x90\x91Z"),
+            Value::list(vec![Value::Int(0), Value::Int(1)])
+        );
+
+        assert_bytes(
+            Value::map(vec![
+                (Value::Int(1), Value::from("fee")),
+                (Value::Int(16), Value::from("fie")),
+                (Value::Int(256), Value::from("foe")),
+            ]),
+            b"H\x91\x03fee\xa0\x03fie\xc9\x00\x03foeZ",
+        );
+        assert_bytes(
+            Value::Map {
+                typ: Some("com.caucho.test.Car".to_string()),
+                entries: vec![(Value::Int(1), Value::from("rs"))],
+            },
+            b"M\x13com.caucho.test.Car\x91\x02rsZ",
+        );
+    }
+
+    #[test]
+    fn test_objects() {
+        let car = |color: &str, model: &str| {
+            Value::object(
+                "example.Car",
+                vec![("color", Value::from(color)), ("model", Value::from(model))],
+            )
+        };
+        let mut encoder = Encoder::new();
+        encoder.write(&car("red", "corvette"));
+        encoder.write(&car("green", "civic"));
+        let bytes = encoder.into_bytes();
+        assert_eq!(
+            &bytes[..],
+            &b"C\x0bexample.Car\x92\x05color\x05model\x60\x03red\x08corvette\x60\x05green\x05civic"
+                [..]
+        );
+        let mut decoder = Decoder::new(&bytes);
+        assert_eq!(decoder.read().unwrap(), car("red", "corvette"));
+        assert_eq!(decoder.read().unwrap(), car("green", "civic"));
+
+        // instances may refer to their definition with `O`
+        assert_eq!(
+            decode(b"C\x0bexample.Car\x92\x05color\x05modelO\x90\x03red\x08corvette"),
+            car("red", "corvette")
+        );
+    }
+
+    #[test]
+    fn test_refs() {
+        // a list element referring to a finished list is read as a reference
+        let inner = Value::list(vec![Value::Int(1)]);
+        let outer = decode(b"\x7a\x79\x91\x51\x91");
+        assert_eq!(outer, Value::list(vec![inner.clone(), Value::Ref(1)]));
+        let refs = Refs::new([&outer]);
+        assert_eq!(refs.len(), 2);
+        assert_eq!(refs.get(0), Some(&outer));
+        assert_eq!(refs.get(1), Some(&inner));
+        assert_eq!(refs.index_of(refs.get(1).unwrap()), Some(1));
+
+        // a linked list node pointing at itself
+        let node = Value::object(
+            "LinkedList",
+            vec![("head", Value::Int(1)), ("tail", Value::Ref(0))],
+        );
+        assert_bytes(node, b"C\x0aLinkedList\x92\x04head\x04tail\x60\x91\x51\x90");
+    }
+
+    #[test]
+    fn test_hostile() {
+        // lists nested past the limit fail instead of overflowing the stack
+        let mut bytes = vec![0x79; 100_000];
+        bytes.push(0x90);
+        assert_eq!(
+            Decoder::new(&bytes).read(),
+            Err(Error::TooDeep(DEFAULT_MAX_DEPTH))
+        );
+        let mut bytes = vec![0x79; 10];
+        bytes.push(0x90);
+        assert_eq!(
+            Decoder::new(&bytes).with_max_depth(9).read(),
+            Err(Error::TooDeep(9))
+        );
+        assert!(Decoder::new(&bytes).with_max_depth(10).read().is_ok());
+        // as do class definitions preceding each other
+        let mut bytes = b"C\x01A\x90".repeat(100_000);
+        bytes.push(0x60);
+        assert_eq!(
+            decode(&bytes),
+            Value::Object {
+                class: "A".to_string(),
+                fields: vec![]
+            }
+        );
+
+        // each list holds two references to the one before, read as such
+        // rather than doubling the size of each
+        let mut bytes = vec![0x58, 0xc8, 0x40, 0x7a, 0x90, 0x90];
+        for i in 1..64 {
+            bytes.push(0x7a);
+            bytes.extend_from_slice(&[0x51, 0xc8, i, 0x51, 0xc8, i]);
+        }
+        let value = Decoder::new(&bytes).read().unwrap();
+        let Value::List { items, .. } = &value else {
+            panic!("{:?}", value);
+        };
+        assert_eq!(items.len(), 64);
+        assert_eq!(items[63], Value::list(vec![Value::Ref(63), Value::Ref(63)]));
+
+        // long class names referred to by short instances
+        let name = "a".repeat(0x3ff);
+        let mut bytes = vec![b'C', 0x33, 0xff];
+        bytes.extend_from_slice(name.as_bytes());
+        bytes.push(0x90);
+        bytes.push(0x58);
+        bytes.extend_from_slice(b"I\x00\x01\x00\x00");
+        bytes.resize(bytes.len() + 0x10000, 0x60);
+        assert!(matches!(
+            Decoder::new(&bytes).read(),
+            Err(Error::LimitExceeded("copied names", _))
+        ));
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert_eq!(Decoder::new(b"").read(), Err(Error::UnexpectedEof));
+        assert_eq!(Decoder::new(b"\x05hel").read(), Err(Error::UnexpectedEof));
+        assert_eq!(Decoder::new(b"\x01\xff").read(), Err(Error::InvalidUtf8));
+        assert_eq!(
+            Decoder::new(b"\x60").read(),
+            Err(Error::UndefinedRef("class", 0))
+        );
+        assert_eq!(
+            Decoder::new(b"\x51\x90").read(),
+            Err(Error::UndefinedRef("value", 0))
+        );
+        assert_eq!(
+            Decoder::new(b"\x71\x91").read(),
+            Err(Error::UndefinedRef("type", 1))
+        );
+        assert_eq!(
+            Decoder::new(b"\x7a\x90Z").read(),
+            Err(Error::UnexpectedTag(b'Z', "value"))
+        );
+    }
+}
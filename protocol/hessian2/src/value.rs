@@ -0,0 +1,254 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// The newtype name `Date` serializes with, written as a Hessian date
+/// instead of a long.
+pub(crate) const DATE_TOKEN: &str = "$hessian2::Date";
+
+/// A Hessian value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Int(i32),
+    Long(i64),
+    Double(f64),
+    /// Milliseconds since the epoch, in UTC.
+    Date(i64),
+    String(String),
+    Binary(Vec<u8>),
+    /// A list, `typ` is the Java type of typed lists such as `[string`.
+    List {
+        typ: Option<String>,
+        items: Vec<Value>,
+    },
+    /// A map, `typ` is the Java class of typed maps.
+    Map {
+        typ: Option<String>,
+        entries: Vec<(Value, Value)>,
+    },
+    /// An instance of the Java class `class`, with its fields in declaration
+    /// order.
+    Object {
+        class: String,
+        fields: Vec<(String, Value)>,
+    },
+    /// A reference to a list, map or object read before, counted in the
+    /// order they were started, see `Refs`. Java throwables without a cause
+    /// refer to themselves this way.
+    Ref(usize),
+}
+
+impl Value {
+    /// An untyped list, written for a `java.util.ArrayList`.
+    pub fn list(items: Vec<Value>) -> Self {
+        Value::List { typ: None, items }
+    }
+
+    /// An untyped map, written for a `java.util.HashMap`.
+    pub fn map(entries: Vec<(Value, Value)>) -> Self {
+        Value::Map { typ: None, entries }
+    }
+
+    pub fn object(class: &str, fields: Vec<(&str, Value)>) -> Self {
+        Value::Object {
+            class: class.to_string(),
+            fields: fields
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect(),
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// The integer value of an `Int` or `Long`.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Int(v) => Some(*v as i64),
+            Value::Long(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// Looks up a field of an object or a string key of a map.
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        match self {
+            Value::Object { fields, .. } => fields.iter().find(|(k, _)| k == name).map(|(_, v)| v),
+            Value::Map { entries, .. } => entries
+                .iter()
+                .find(|(k, _)| k.as_str() == Some(name))
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+}
+
+/// The lists, maps and objects of the values read by one `Decoder`, by the
+/// index `Value::Ref` refers to them with.
+pub struct Refs<'a> {
+    values: Vec<&'a Value>,
+    indices: HashMap<*const Value, usize>,
+}
+
+impl<'a> Refs<'a> {
+    /// Indexes `values`, in the order they were read.
+    pub fn new(values: impl IntoIterator<Item = &'a Value>) -> Self {
+        let mut refs = Refs {
+            values: Vec::new(),
+            indices: HashMap::new(),
+        };
+        for value in values {
+            refs.index(value);
+        }
+        refs
+    }
+
+    fn index(&mut self, value: &'a Value) {
+        let children: Box<dyn Iterator<Item = &'a Value>> = match value {
+            Value::List { items, .. } => Box::new(items.iter()),
+            Value::Map { entries, .. } => Box::new(entries.iter().flat_map(|(k, v)| [k, v])),
+            Value::Object { fields, .. } => Box::new(fields.iter().map(|(_, v)| v)),
+            _ => return,
+        };
+        self.indices.insert(value, self.values.len());
+        self.values.push(value);
+        for child in children {
+            self.index(child);
+        }
+    }
+
+    /// The value `Value::Ref(index)` refers to.
+    pub fn get(&self, index: usize) -> Option<&'a Value> {
+        self.values.get(index).copied()
+    }
+
+    /// The index of `value`, one of the indexed lists, maps or objects.
+    pub fn index_of(&self, value: &Value) -> Option<usize> {
+        self.indices.get(&(value as *const Value)).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+impl From<bool> for Value {
+    fn from(v: bool) -> Self {
+        Value::Bool(v)
+    }
+}
+
+impl From<i32> for Value {
+    fn from(v: i32) -> Self {
+        Value::Int(v)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(v: i64) -> Self {
+        Value::Long(v)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(v: f64) -> Self {
+        Value::Double(v)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(v: &str) -> Self {
+        Value::String(v.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(v: String) -> Self {
+        Value::String(v)
+    }
+}
+
+impl From<Vec<u8>> for Value {
+    fn from(v: Vec<u8>) -> Self {
+        Value::Binary(v)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(v: Option<T>) -> Self {
+        v.map(Into::into).unwrap_or(Value::Null)
+    }
+}
+
+/// A `java.util.Date`, in milliseconds since the epoch. Other serde formats
+/// see the plain number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Date(pub i64);
+
+impl Serialize for Date {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(DATE_TOKEN, &self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Date {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct DateVisitor;
+
+        impl<'de> de::Visitor<'de> for DateVisitor {
+            type Value = Date;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a date in milliseconds")
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Date, E> {
+                Ok(Date(v))
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Date, E> {
+                i64::try_from(v)
+                    .map(Date)
+                    .map_err(|_| E::custom("date out of range"))
+            }
+
+            fn visit_newtype_struct<D: Deserializer<'de>>(self, d: D) -> Result<Date, D::Error> {
+                i64::deserialize(d).map(Date)
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(DATE_TOKEN, DateVisitor)
+    }
+}
@@ -0,0 +1,230 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::BTreeMap;
+
+use protocol_hessian2::{
+    from_slice, from_value, to_value, to_vec, Date, Encoder, Error, Value, DEFAULT_MAX_DEPTH,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename = "org.apache.dubbo.samples.Level")]
+enum Level {
+    #[serde(rename = "LOW")]
+    Low,
+    #[serde(rename = "HIGH")]
+    High,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename = "org.apache.dubbo.samples.User")]
+struct User {
+    name: String,
+    age: i32,
+    level: Level,
+    tags: Vec<String>,
+    scores: BTreeMap<String, f64>,
+    created: Date,
+    manager: Option<Box<User>>,
+}
+
+fn user(name: &str, manager: Option<User>) -> User {
+    User {
+        name: name.to_string(),
+        age: 30,
+        level: Level::High,
+        tags: vec!["a".to_string(), "b".to_string()],
+        scores: [("math".to_string(), 1.5)].into_iter().collect(),
+        created: Date(894621060000),
+        manager: manager.map(Box::new),
+    }
+}
+
+#[test]
+fn test_roundtrip() {
+    let value = user("bob", Some(user("alice", None)));
+    let bytes = to_vec(&value).unwrap();
+    assert_eq!(from_slice::<User>(&bytes).unwrap(), value);
+}
+
+#[test]
+fn test_object_bytes() {
+    #[derive(Serialize)]
+    #[serde(rename = "example.Car")]
+    struct Car {
+        color: &'static str,
+        model: &'static str,
+    }
+
+    let bytes = to_vec(&Car {
+        color: "red",
+        model: "corvette",
+    })
+    .unwrap();
+    assert_eq!(
+        bytes,
+        b"C\x0bexample.Car\x92\x05color\x05model\x60\x03red\x08corvette"
+    );
+
+    // java enum constants are objects holding their name
+    assert_eq!(
+        to_value(&Level::Low).unwrap(),
+        Value::object(
+            "org.apache.dubbo.samples.Level",
+            vec![("name", Value::from("LOW"))]
+        )
+    );
+    assert_eq!(to_value(&Date(60000)).unwrap(), Value::Date(60000));
+    assert_eq!(to_value(&7u32).unwrap(), Value::Long(7));
+    assert!(to_vec(&u64::MAX).is_err());
+}
+
+#[test]
+fn test_java_values() {
+    // the manager refers to the user written first, a field the struct does
+    // not know is skipped
+    let user = Value::object(
+        "org.apache.dubbo.samples.User",
+        vec![
+            ("name", Value::from("alice")),
+            ("age", Value::Int(30)),
+            (
+                "level",
+                Value::object(
+                    "org.apache.dubbo.samples.Level",
+                    vec![("name", Value::from("HIGH"))],
+                ),
+            ),
+            (
+                "tags",
+                Value::list(vec![Value::from("a"), Value::from("b")]),
+            ),
+            (
+                "scores",
+                Value::map(vec![(Value::from("math"), Value::Double(1.5))]),
+            ),
+            ("created", Value::Date(894621060000)),
+            ("manager", Value::Null),
+            ("email", Value::from("alice@example.com")),
+        ],
+    );
+    let mut encoder = Encoder::new();
+    encoder.write(&Value::list(vec![user.clone(), Value::Ref(1)]));
+    let users: Vec<User> = from_slice(&encoder.into_bytes()).unwrap();
+    assert_eq!(users.len(), 2);
+    assert_eq!(users[0], self::user("alice", None));
+    assert_eq!(users[1], users[0]);
+
+    // a throwable whose cause is itself
+    #[derive(Deserialize)]
+    struct Throwable {
+        #[serde(rename = "detailMessage")]
+        detail_message: Option<String>,
+        cause: Option<Box<Throwable>>,
+    }
+    let exception = Value::object(
+        "java.lang.IllegalStateException",
+        vec![
+            ("detailMessage", Value::from("closed")),
+            ("cause", Value::Ref(0)),
+        ],
+    );
+    let throwable: Throwable = from_value(exception).unwrap();
+    assert_eq!(throwable.detail_message.as_deref(), Some("closed"));
+    assert!(throwable.cause.is_none());
+
+    // other formats see dates as numbers
+    assert_eq!(from_value::<i64>(Value::Date(5)).unwrap(), 5);
+    assert!(from_slice::<String>(b"\x05hello\x90").is_err());
+}
+
+#[test]
+fn test_enums() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Shape {
+        Empty,
+        Circle(f64),
+        Rect { w: i32, h: i32 },
+    }
+
+    for shape in [Shape::Empty, Shape::Circle(2.5), Shape::Rect { w: 2, h: 3 }] {
+        let bytes = to_vec(&shape).unwrap();
+        assert_eq!(from_slice::<Shape>(&bytes).unwrap(), shape);
+    }
+    assert_eq!(
+        to_value(&Shape::Circle(2.5)).unwrap(),
+        Value::map(vec![(Value::from("Circle"), Value::Double(2.5))])
+    );
+    // the bare constant name is accepted as well
+    assert_eq!(from_value::<Level>(Value::from("LOW")).unwrap(), Level::Low);
+}
+
+#[test]
+fn test_hostile_refs() {
+    #[derive(Debug, Deserialize)]
+    #[serde(untagged)]
+    enum Tree {
+        Leaf(i32),
+        Node(Vec<Tree>),
+    }
+
+    impl Tree {
+        fn sum(&self) -> i32 {
+            match self {
+                Tree::Leaf(v) => *v,
+                Tree::Node(items) => items.iter().map(Tree::sum).sum(),
+            }
+        }
+    }
+
+    // `n` lists after a first one, each referring `refs` times to the one
+    // before
+    let chain = |n: u8, refs: usize| {
+        let mut bytes = vec![0x58, 0xc8, n + 1, 0x79, 0x91];
+        for i in 1..=n {
+            bytes.push(0x78 + refs as u8);
+            for _ in 0..refs {
+                bytes.extend_from_slice(&[0x51, 0xc8, i]);
+            }
+        }
+        bytes
+    };
+
+    // each reference doubles the values to deserialize
+    assert_eq!(from_slice::<Tree>(&chain(4, 2)).unwrap().sum(), 31);
+    assert!(matches!(
+        from_slice::<Tree>(&chain(64, 2)),
+        Err(Error::LimitExceeded(..))
+    ));
+    // or nests one level deeper
+    assert_eq!(from_slice::<Tree>(&chain(30, 1)).unwrap().sum(), 31);
+    assert!(matches!(
+        from_slice::<Tree>(&chain(200, 1)),
+        Err(Error::LimitExceeded(..))
+    ));
+
+    let mut value = Value::Int(1);
+    for _ in 0..DEFAULT_MAX_DEPTH {
+        value = Value::list(vec![value]);
+    }
+    assert_eq!(from_value::<Tree>(value.clone()).unwrap().sum(), 1);
+    assert_eq!(
+        from_value::<Tree>(Value::list(vec![value])).unwrap_err(),
+        Error::TooDeep(DEFAULT_MAX_DEPTH)
+    );
+}
@@ -15,26 +15,209 @@
  * limitations under the License.
  */
 use dubbo_base::{Node, Url};
-
-use protocol_base::{
-    invocation::BoxInvocation,
-    invoker::{BaseInvoker, Invoker},
+use http::{HeaderValue, Uri};
+use pin_project::pin_project;
+use protocol_base::invoker::BaseInvoker;
+use std::{
+    fmt::{Debug, Formatter},
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
 };
-use std::sync::Arc;
+use thiserror::Error;
+use tower_service::Service;
+
+/// A request the invoker cannot turn into a Triple request.
+#[derive(Debug, Error)]
+pub enum InvalidRequest {
+    #[error("the request has no path header")]
+    MissingPath,
+    #[error("invalid path {0:?}")]
+    InvalidPath(String),
+}
 
-pub struct TripleInvoker {
+/// Invokes the Triple provider of its url over `C`, an HTTP/2 connection.
+///
+/// Requests carry the target path in their `path` header, the invoker turns
+/// them into Triple requests to the provider.
+pub struct TripleInvoker<C> {
     base: BaseInvoker,
+    conn: C,
+    scheme: &'static str,
 }
 
-impl Invoker for TripleInvoker {
-    type Output = ();
+impl<C> TripleInvoker<C> {
+    pub fn new(url: Url, conn: C) -> Self {
+        Self {
+            base: BaseInvoker::new(url),
+            conn,
+            scheme: "http",
+        }
+    }
+
+    /// Marks `conn` as a TLS connection, requests then use `https`.
+    pub fn with_tls(self, tls: bool) -> Self {
+        Self {
+            scheme: if tls { "https" } else { "http" },
+            ..self
+        }
+    }
+
+    pub fn conn(&self) -> &C {
+        &self.conn
+    }
+
+    pub fn map_request<B>(
+        &self,
+        req: http::Request<B>,
+    ) -> Result<http::Request<B>, InvalidRequest> {
+        let (parts, body) = req.into_parts();
+
+        let path = parts
+            .headers
+            .get("path")
+            .ok_or(InvalidRequest::MissingPath)?;
+        let invalid_path =
+            || InvalidRequest::InvalidPath(String::from_utf8_lossy(path.as_bytes()).into());
+        let path_and_query = path.to_str().map_err(|_| invalid_path())?;
+
+        let authority = self.base.url().authority();
+
+        let uri = Uri::builder()
+            .scheme(self.scheme)
+            .authority(authority)
+            .path_and_query(path_and_query)
+            .build()
+            .map_err(|_| invalid_path())?;
 
-    fn invoke(&self, _invocation: BoxInvocation) -> Self::Output {
-        todo!()
+        let mut req = http::Request::builder()
+            .version(http::Version::HTTP_2)
+            .uri(uri.clone())
+            .method("POST")
+            .body(body)
+            .unwrap();
+
+        // add header of source
+        for (k, v) in parts.headers.iter() {
+            req.headers_mut().insert(k, v.to_owned());
+        }
+        req.headers_mut()
+            .insert("method", HeaderValue::from_static("POST"));
+        req.headers_mut().insert(
+            "scheme",
+            HeaderValue::from_str(uri.scheme_str().unwrap()).unwrap(),
+        );
+        req.headers_mut()
+            .insert("path", HeaderValue::from_str(uri.path()).unwrap());
+        req.headers_mut().insert(
+            "authority",
+            HeaderValue::from_str(uri.authority().unwrap().as_str()).unwrap(),
+        );
+        if !req.headers().contains_key(http::header::CONTENT_TYPE) {
+            req.headers_mut().insert(
+                http::header::CONTENT_TYPE,
+                HeaderValue::from_static("application/grpc+proto"),
+            );
+        }
+        req.headers_mut()
+            .insert("user-agent", HeaderValue::from_static("dubbo-rust/0.1.0"));
+        req.headers_mut()
+            .insert("te", HeaderValue::from_static("trailers"));
+        // the group and version of the client, if it has any
+        if !req.headers().contains_key("tri-service-version") {
+            req.headers_mut().insert(
+                "tri-service-version",
+                HeaderValue::from_static("dubbo-rust/0.1.0"),
+            );
+        }
+        if !req.headers().contains_key("tri-service-group") {
+            req.headers_mut()
+                .insert("tri-service-group", HeaderValue::from_static("cluster"));
+        }
+        req.headers_mut().insert(
+            "tri-unit-info",
+            HeaderValue::from_static("dubbo-rust/0.1.0"),
+        );
+
+        req.headers_mut()
+            .insert("grpc-encoding", http::HeaderValue::from_static("gzip"));
+
+        req.headers_mut().insert(
+            "grpc-accept-encoding",
+            http::HeaderValue::from_static("gzip"),
+        );
+
+        // // const (
+        // //     TripleContentType    = "application/grpc+proto"
+        // //     TripleUserAgent      = "grpc-go/1.35.0-dev"
+        // //     TripleServiceVersion = "tri-service-version"
+        // //     TripleAttachement    = "tri-attachment"
+        // //     TripleServiceGroup   = "tri-service-group"
+        // //     TripleRequestID      = "tri-req-id"
+        // //     TripleTraceID        = "tri-trace-traceid"
+        // //     TripleTraceRPCID     = "tri-trace-rpcid"
+        // //     TripleTraceProtoBin  = "tri-trace-proto-bin"
+        // //     TripleUnitInfo       = "tri-unit-info"
+        // // )
+        Ok(req)
+    }
+}
+
+impl<C> Debug for TripleInvoker<C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(format!("{:?}", self.base.url()).as_str())
     }
 }
 
-impl Node for TripleInvoker {
+impl<C, B> Service<http::Request<B>> for TripleInvoker<C>
+where
+    C: Service<http::Request<B>>,
+    C::Error: From<InvalidRequest>,
+{
+    type Response = C::Response;
+
+    type Error = C::Error;
+
+    type Future = ResponseFuture<C::Future, C::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.conn.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        match self.map_request(req) {
+            Ok(req) => ResponseFuture::Call(self.conn.call(req)),
+            Err(err) => ResponseFuture::Invalid(Some(err.into())),
+        }
+    }
+}
+
+/// The response of a [`TripleInvoker`], failed at once for an
+/// [`InvalidRequest`].
+#[pin_project(project = ResponseFutureProj)]
+pub enum ResponseFuture<F, E> {
+    Call(#[pin] F),
+    Invalid(Option<E>),
+}
+
+impl<F, T, E> Future for ResponseFuture<F, E>
+where
+    F: Future<Output = Result<T, E>>,
+{
+    type Output = Result<T, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.project() {
+            ResponseFutureProj::Call(call) => call.poll(cx),
+            ResponseFutureProj::Invalid(err) => {
+                Poll::Ready(Err(err.take().expect("polled after completion")))
+            }
+        }
+    }
+}
+
+impl<C> Node for TripleInvoker<C> {
     fn get_url(&self) -> Arc<Url> {
         self.base.get_url()
     }
@@ -44,10 +227,68 @@ impl Node for TripleInvoker {
     }
 
     fn destroy(&self) {
-        todo!()
+        self.base.destroy()
     }
 
     fn is_destroyed(&self) -> bool {
         self.base.is_destroyed()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use protocol_base::invoker::Invoker;
+    use tower::{service_fn, ServiceExt};
+
+    use super::*;
+
+    type StdError = Box<dyn std::error::Error + Send + Sync>;
+
+    fn assert_invoker<I: Invoker<http::Request<()>>>(_: &I) {}
+
+    #[test]
+    fn test_map_request() {
+        let conn = service_fn(|req: http::Request<()>| async move { Ok::<_, StdError>(req) });
+        let invoker =
+            TripleInvoker::new("tri://127.0.0.1:8888".parse().unwrap(), conn).with_tls(true);
+        assert_invoker(&invoker);
+
+        let req = http::Request::builder()
+            .header("path", "/org.apache.dubbo.Greeter/greet")
+            .header(http::header::CONTENT_TYPE, "application/grpc+json")
+            .body(())
+            .unwrap();
+        let req = futures::executor::block_on(invoker.oneshot(req)).unwrap();
+
+        assert_eq!(
+            req.uri(),
+            "https://127.0.0.1:8888/org.apache.dubbo.Greeter/greet"
+        );
+        assert_eq!(req.version(), http::Version::HTTP_2);
+        assert_eq!(req.headers()["path"], "/org.apache.dubbo.Greeter/greet");
+        assert_eq!(req.headers()["content-type"], "application/grpc+json");
+        assert_eq!(req.headers()["te"], "trailers");
+    }
+
+    #[test]
+    fn test_missing_path() {
+        let conn = service_fn(|req: http::Request<()>| async move { Ok::<_, StdError>(req) });
+        let invoker = TripleInvoker::new("tri://127.0.0.1:8888".parse().unwrap(), conn);
+
+        let req = http::Request::builder().body(()).unwrap();
+        let err = futures::executor::block_on(invoker.oneshot(req)).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<InvalidRequest>(),
+            Some(InvalidRequest::MissingPath)
+        ));
+    }
+
+    #[test]
+    fn test_destroy() {
+        let invoker = TripleInvoker::new("tri://127.0.0.1:8888".parse().unwrap(), ());
+        assert!(invoker.is_available());
+        invoker.destroy();
+        assert!(invoker.is_destroyed());
+        assert!(!invoker.is_available());
+    }
+}
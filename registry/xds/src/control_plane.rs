@@ -0,0 +1,392 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! A management server of small meshes, serving the providers of a registry
+//! over xDS to Envoy, proxyless gRPC and the xds registry alike.
+
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use dubbo::{
+    extension::registry_extension::{Registry, ServiceChange},
+    logger::tracing::{debug, warn},
+    params::registry_param::InterfaceName,
+    StdError, Url,
+};
+use remoting_xds::{
+    protocol::{
+        cluster::{
+            cluster::{DiscoveryType, EdsClusterConfig},
+            Cluster,
+        },
+        core::{
+            Address, AggregatedConfigSource, ApiVersion, ConfigSource, HealthStatus, SocketAddress,
+        },
+        endpoint::{
+            lb_endpoint::HostIdentifier, ClusterLoadAssignment, Endpoint, LbEndpoint,
+            LocalityLbEndpoints,
+        },
+        listener::{
+            http_connection_manager::RouteSpecifier, ApiListener, HttpConnectionManager,
+            HttpFilter, Listener, Rds, Router,
+        },
+        route::{
+            route::Action, route_action::ClusterSpecifier, route_match::PathSpecifier, Route,
+            RouteAction, RouteConfiguration, RouteMatch, VirtualHost,
+        },
+        Resource, CLUSTER_LOAD_ASSIGNMENT_TYPE_URL, CLUSTER_TYPE_URL, LISTENER_TYPE_URL,
+        ROUTE_CONFIGURATION_TYPE_URL,
+    },
+    server::SnapshotCache,
+    XdsError, XdsServer,
+};
+use tokio::{net::TcpListener, task::AbortHandle};
+
+const ROUTER_FILTER: &str = "envoy.filters.http.router";
+
+/// The services watched at most by default.
+const DEFAULT_MAX_SERVICES: usize = 1024;
+
+/// Publishes every service it watches as a listener, a route configuration,
+/// a cluster and the endpoints of the cluster, all named after the service.
+/// Clients find a service by its listener, `org.apache.dubbo.Greeter` say,
+/// and call `/org.apache.dubbo.Greeter/{method}` on its providers.
+///
+/// Dubbo2 providers are left out, xDS clients speak HTTP/2.
+#[derive(Clone)]
+pub struct ControlPlane {
+    registry: Arc<dyn Registry + Send + Sync>,
+    server: XdsServer,
+    watched: Arc<Mutex<HashMap<String, Watch>>>,
+    // the id of the next watch
+    next_id: Arc<AtomicU64>,
+    allowed: Option<Arc<HashSet<String>>>,
+    max_services: usize,
+}
+
+/// The registry subscription of a watched service.
+struct Watch {
+    // tells a watch from the one that replaced it while subscribing
+    id: u64,
+    url: Url,
+    // `None` while the subscription is being made
+    task: Option<AbortHandle>,
+}
+
+impl ControlPlane {
+    pub fn new(registry: Box<dyn Registry + Send + Sync>) -> Self {
+        ControlPlane {
+            registry: Arc::from(registry),
+            server: XdsServer::new(SnapshotCache::new()),
+            watched: Arc::new(Mutex::new(HashMap::new())),
+            next_id: Arc::default(),
+            allowed: None,
+            max_services: DEFAULT_MAX_SERVICES,
+        }
+    }
+
+    /// Watches only the services in `services`, any other name a client asks
+    /// for is left unanswered.
+    pub fn with_allowed_services<I>(self, services: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        ControlPlane {
+            allowed: Some(Arc::new(services.into_iter().map(Into::into).collect())),
+            ..self
+        }
+    }
+
+    /// Watches at most `max_services` services at a time, 1024 by default.
+    pub fn with_max_services(self, max_services: usize) -> Self {
+        ControlPlane {
+            max_services,
+            ..self
+        }
+    }
+
+    /// The ADS service, to serve next to other gRPC services.
+    pub fn server(&self) -> XdsServer {
+        self.server.clone()
+    }
+
+    /// Subscribes `service` in the registry and keeps its resources up to
+    /// date with the providers there. Fails for a service that is not
+    /// allowed, or when `max_services` services are watched already.
+    pub async fn watch_service(&self, service: &str) -> Result<(), StdError> {
+        self.watch(service).await.map(|_| ())
+    }
+
+    /// Like `watch_service`, telling whether the service was not watched yet.
+    async fn watch(&self, service: &str) -> Result<bool, StdError> {
+        if let Some(allowed) = &self.allowed {
+            if !allowed.contains(service) {
+                return Err(format!("service {} is not allowed", service).into());
+            }
+        }
+
+        let mut url: Url = format!("consumer://127.0.0.1:8888/{}", service).parse()?;
+        url.add_query_param(InterfaceName::new(service.to_string()));
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        {
+            let mut watched = self.watched.lock().unwrap();
+            if watched.contains_key(service) {
+                return Ok(false);
+            }
+            if watched.len() >= self.max_services {
+                return Err(format!(
+                    "{} services are watched already, {} is not",
+                    watched.len(),
+                    service
+                )
+                .into());
+            }
+            let watch = Watch {
+                id,
+                url: url.clone(),
+                task: None,
+            };
+            watched.insert(service.to_string(), watch);
+        }
+
+        let mut changes = match self.registry.subscribe(url.clone()).await {
+            Ok(changes) => changes,
+            Err(err) => {
+                let mut watched = self.watched.lock().unwrap();
+                if watched.get(service).is_some_and(|watch| watch.id == id) {
+                    watched.remove(service);
+                }
+                return Err(err);
+            }
+        };
+
+        let cache = self.server.cache().clone();
+        {
+            let mut watched = self.watched.lock().unwrap();
+            match watched.get_mut(service) {
+                Some(watch) if watch.id == id => {
+                    publish(&cache, service);
+                    update_endpoints(&cache, service, &BTreeSet::new());
+
+                    let name = service.to_string();
+                    let task = tokio::spawn(async move {
+                        let mut providers = BTreeSet::new();
+                        while let Some(change) = changes.recv().await {
+                            match change {
+                                Ok(ServiceChange::Insert(url, _)) => providers.insert(url),
+                                Ok(ServiceChange::Remove(url)) => providers.remove(&url),
+                                Err(err) => {
+                                    warn!("providers of {} went wrong: {}", name, err);
+                                    continue;
+                                }
+                            };
+                            update_endpoints(&cache, &name, &providers);
+                        }
+                        debug!("registry stopped sending the providers of {}", name);
+                    });
+
+                    watch.task = Some(task.abort_handle());
+                    return Ok(true);
+                }
+                // watched again meanwhile, the new watch has a subscription
+                // of its own
+                Some(_) => return Ok(false),
+                // unwatched while subscribing, when there was nothing to take
+                // down or unsubscribe yet
+                None => unpublish(&cache, service),
+            }
+        }
+        self.registry.unsubscribe(url).await?;
+        Ok(false)
+    }
+
+    /// Stops watching `service`: unsubscribes it in the registry and takes
+    /// down its resources.
+    pub async fn unwatch_service(&self, service: &str) -> Result<(), StdError> {
+        let Some(watch) = self.watched.lock().unwrap().remove(service) else {
+            return Ok(());
+        };
+        if let Some(task) = watch.task {
+            task.abort();
+        }
+        unpublish(self.server.cache(), service);
+        self.registry.unsubscribe(watch.url).await
+    }
+
+    /// Serves the clients coming in on `listener` until `signal` resolves,
+    /// watching the services they ask for on the way. A service watched for
+    /// the clients is unwatched once no client asks for it.
+    pub async fn serve<F>(self, listener: TcpListener, signal: F) -> Result<(), XdsError>
+    where
+        F: Future<Output = ()>,
+    {
+        let mut requested = self.server.requested();
+        let control_plane = self.clone();
+        let watcher = tokio::spawn(async move {
+            // the services watched here rather than by `watch_service`
+            let mut on_demand = HashSet::new();
+            loop {
+                let services = requested.borrow_and_update().clone();
+                let gone: Vec<String> = on_demand
+                    .iter()
+                    .filter(|service| !services.contains(*service))
+                    .cloned()
+                    .collect();
+                for service in gone {
+                    on_demand.remove(&service);
+                    if let Err(err) = control_plane.unwatch_service(&service).await {
+                        warn!("failed to unwatch {}: {}", service, err);
+                    }
+                }
+                for service in services {
+                    match control_plane.watch(&service).await {
+                        Ok(true) => {
+                            on_demand.insert(service);
+                        }
+                        Ok(false) => {}
+                        Err(err) => warn!("failed to watch {}: {}", service, err),
+                    }
+                }
+                if requested.changed().await.is_err() {
+                    return;
+                }
+            }
+        });
+
+        let result = self.server.serve_with_shutdown(listener, signal).await;
+        watcher.abort();
+        result
+    }
+}
+
+fn ads() -> ConfigSource {
+    ConfigSource {
+        ads: Some(AggregatedConfigSource {}),
+        resource_api_version: ApiVersion::V3 as i32,
+    }
+}
+
+/// Publishes the listener, route configuration and cluster of `service`,
+/// these do not change with its providers.
+fn publish(cache: &SnapshotCache, service: &str) {
+    let manager = HttpConnectionManager {
+        stat_prefix: service.to_string(),
+        route_specifier: Some(RouteSpecifier::Rds(Rds {
+            config_source: Some(ads()),
+            route_config_name: service.to_string(),
+        })),
+        http_filters: vec![HttpFilter {
+            name: ROUTER_FILTER.to_string(),
+            typed_config: Some(Router {}.to_any()),
+        }],
+    };
+    let listener = Listener {
+        name: service.to_string(),
+        address: None,
+        api_listener: Some(ApiListener {
+            api_listener: Some(manager.to_any()),
+        }),
+    };
+    let route_config = RouteConfiguration {
+        name: service.to_string(),
+        virtual_hosts: vec![VirtualHost {
+            name: service.to_string(),
+            domains: vec![service.to_string()],
+            routes: vec![Route {
+                r#match: Some(RouteMatch {
+                    path_specifier: Some(PathSpecifier::Prefix("/".to_string())),
+                    ..Default::default()
+                }),
+                action: Some(Action::Route(RouteAction {
+                    cluster_specifier: Some(ClusterSpecifier::Cluster(service.to_string())),
+                })),
+                name: String::new(),
+            }],
+        }],
+    };
+    let cluster = Cluster {
+        name: service.to_string(),
+        r#type: DiscoveryType::Eds as i32,
+        eds_cluster_config: Some(EdsClusterConfig {
+            eds_config: Some(ads()),
+            service_name: service.to_string(),
+        }),
+        load_assignment: None,
+    };
+
+    cache.update(|snapshot| {
+        let listener = snapshot.set(LISTENER_TYPE_URL, service, listener.to_any());
+        let route_config =
+            snapshot.set(ROUTE_CONFIGURATION_TYPE_URL, service, route_config.to_any());
+        let cluster = snapshot.set(CLUSTER_TYPE_URL, service, cluster.to_any());
+        listener || route_config || cluster
+    });
+}
+
+/// Takes down every resource of `service`.
+fn unpublish(cache: &SnapshotCache, service: &str) {
+    cache.update(|snapshot| {
+        let listener = snapshot.remove(LISTENER_TYPE_URL, service);
+        let route_config = snapshot.remove(ROUTE_CONFIGURATION_TYPE_URL, service);
+        let cluster = snapshot.remove(CLUSTER_TYPE_URL, service);
+        let endpoints = snapshot.remove(CLUSTER_LOAD_ASSIGNMENT_TYPE_URL, service);
+        listener || route_config || cluster || endpoints
+    });
+}
+
+fn update_endpoints(cache: &SnapshotCache, service: &str, providers: &BTreeSet<String>) {
+    let lb_endpoints = providers
+        .iter()
+        .filter_map(|url| url.parse::<Url>().ok())
+        .filter(|url| url.protocol() != "dubbo")
+        .filter_map(|url| {
+            let address = SocketAddress {
+                address: url.host()?.to_string(),
+                port_value: url.port()? as u32,
+            };
+            Some(LbEndpoint {
+                host_identifier: Some(HostIdentifier::Endpoint(Endpoint {
+                    address: Some(Address {
+                        socket_address: Some(address),
+                    }),
+                })),
+                health_status: HealthStatus::Healthy as i32,
+                load_balancing_weight: None,
+            })
+        })
+        .collect();
+    let load_assignment = ClusterLoadAssignment {
+        cluster_name: service.to_string(),
+        endpoints: vec![LocalityLbEndpoints {
+            lb_endpoints,
+            load_balancing_weight: None,
+            priority: 0,
+        }],
+    };
+    cache.set(
+        CLUSTER_LOAD_ASSIGNMENT_TYPE_URL,
+        service,
+        load_assignment.to_any(),
+    );
+}
@@ -0,0 +1,354 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! A registry that takes the providers of a service, and the routes between
+//! them, from an xDS management server.
+
+mod control_plane;
+
+pub use control_plane::ControlPlane;
+
+use std::{collections::BTreeSet, sync::Arc};
+
+use async_trait::async_trait;
+use dubbo::{
+    codegen::RpcInvocation,
+    extension::{
+        registry_extension::{DiscoverStream, Registry, ServiceChange},
+        Extension,
+    },
+    invocation::Invocation,
+    invoker::clone_invoker::CloneInvoker,
+    logger::tracing::debug,
+    params::registry_param::{InterfaceName, RegistryUrl},
+    protocol::BoxInvoker,
+    route::router::{BoxRouter, Router},
+    url::UrlParam,
+    StdError, Url,
+};
+use remoting_xds::{protocol::core::Node, resource::Route, ServiceSnapshot, XdsClient};
+use tokio::sync::{mpsc, watch};
+
+/// The name of the router the registry gives for the services it subscribes.
+pub const XDS_ROUTER: &str = "xds";
+
+const DEFAULT_PORT: u16 = 15010;
+const DEFAULT_NODE_ID: &str = "dubbo-rust";
+const DEFAULT_PROTOCOL: &str = "tri";
+
+pub struct XdsRegistry {
+    client: XdsClient,
+    url: Url,
+    /// The protocol the providers of the management server speak.
+    protocol: String,
+}
+
+impl XdsRegistry {
+    pub fn new(client: XdsClient, url: Url) -> Self {
+        let protocol = url
+            .query_param_by_key("protocol")
+            .unwrap_or_else(|| DEFAULT_PROTOCOL.to_string());
+        XdsRegistry {
+            client,
+            url,
+            protocol,
+        }
+    }
+}
+
+#[async_trait]
+impl Registry for XdsRegistry {
+    async fn register(&self, url: Url) -> Result<(), StdError> {
+        // the management server publishes the providers
+        debug!("xds registry ignores the registration of {}", url);
+        Ok(())
+    }
+
+    async fn unregister(&self, url: Url) -> Result<(), StdError> {
+        debug!("xds registry ignores the unregistration of {}", url);
+        Ok(())
+    }
+
+    async fn subscribe(&self, url: Url) -> Result<DiscoverStream, StdError> {
+        let service = url
+            .query::<InterfaceName>()
+            .ok_or("subscribe url without interface")?
+            .value();
+        let mut snapshot = self.client.watch_service(&service).await?;
+
+        let (tx, rx) = mpsc::channel(64);
+        let protocol = self.protocol.clone();
+        tokio::spawn(async move {
+            let mut urls = BTreeSet::new();
+            loop {
+                let next = provider_urls(&protocol, &service, &snapshot.borrow_and_update());
+                for url in urls.difference(&next) {
+                    if tx
+                        .send(Ok(ServiceChange::Remove(url.clone())))
+                        .await
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+                for url in next.difference(&urls) {
+                    if tx
+                        .send(Ok(ServiceChange::Insert(url.clone(), ())))
+                        .await
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+                urls = next;
+
+                tokio::select! {
+                    changed = snapshot.changed() => {
+                        if changed.is_err() {
+                            return;
+                        }
+                    }
+                    _ = tx.closed() => return,
+                }
+            }
+        });
+
+        Ok(rx)
+    }
+
+    async fn unsubscribe(&self, _url: Url) -> Result<(), StdError> {
+        // the subscription ends once its stream is dropped
+        Ok(())
+    }
+
+    /// Routes the calls by the routes of the service, which the subscription
+    /// watches already.
+    async fn routers(&self, url: Url) -> Vec<(String, BoxRouter)> {
+        let Some(service) = url.query::<InterfaceName>() else {
+            return Vec::new();
+        };
+        match self.client.watch_service(&service.value()).await {
+            Ok(snapshot) => {
+                let router: BoxRouter = Arc::new(XdsRouter(snapshot));
+                vec![(XDS_ROUTER.to_string(), router)]
+            }
+            Err(err) => {
+                debug!("xds registry cannot route {}: {}", url, err);
+                Vec::new()
+            }
+        }
+    }
+
+    fn url(&self) -> &Url {
+        &self.url
+    }
+}
+
+#[async_trait]
+impl Extension for XdsRegistry {
+    type Target = Box<dyn Registry + Send + Sync + 'static>;
+
+    fn name() -> String {
+        "xds".to_string()
+    }
+
+    async fn create(url: Url) -> Result<Self::Target, StdError> {
+        // url example:
+        // extension://0.0.0.0?extension-type=registry&extension-name=xds&registry=xds://127.0.0.1:15010
+        let registry_url = url.query::<RegistryUrl>().unwrap();
+        let registry_url = registry_url.value();
+
+        let host = registry_url.host().ok_or("xds registry url without host")?;
+        let port = registry_url.port().unwrap_or(DEFAULT_PORT);
+        let node = Node {
+            id: registry_url
+                .query_param_by_key("node-id")
+                .unwrap_or_else(|| DEFAULT_NODE_ID.to_string()),
+            user_agent_name: "dubbo-rust".to_string(),
+            ..Default::default()
+        };
+
+        let client = XdsClient::connect(format!("http://{}:{}", host, port), node)?;
+        Ok(Box::new(XdsRegistry::new(client, registry_url)))
+    }
+}
+
+/// The urls of the healthy providers of `service`.
+fn provider_urls(protocol: &str, service: &str, snapshot: &ServiceSnapshot) -> BTreeSet<String> {
+    snapshot
+        .addresses()
+        .into_iter()
+        .map(|address| {
+            format!(
+                "{}://{}/{}?interface={}",
+                protocol, address, service, service
+            )
+        })
+        .collect()
+}
+
+/// Sends a call to the providers of the cluster its route picks.
+struct XdsRouter(watch::Receiver<ServiceSnapshot>);
+
+impl Router for XdsRouter {
+    fn route(
+        &self,
+        invokers: Vec<CloneInvoker<BoxInvoker>>,
+        invocation: &RpcInvocation,
+    ) -> Vec<CloneInvoker<BoxInvoker>> {
+        let snapshot = self.0.borrow();
+        // no route configuration yet
+        if snapshot.routes.is_none() {
+            return invokers;
+        }
+
+        let path = format!(
+            "/{}/{}",
+            invocation.get_target_service_unique_name(),
+            invocation.get_method_name()
+        );
+        let metadata = invocation.get_metadata();
+        let header = |name: &str| metadata.get(name).map(String::as_str);
+        let Some(cluster) = snapshot.route(&path, header).and_then(Route::pick_cluster) else {
+            debug!("no xds route of {}", path);
+            return Vec::new();
+        };
+
+        let addresses: BTreeSet<_> = snapshot
+            .endpoints(cluster)
+            .iter()
+            .filter(|endpoint| endpoint.healthy)
+            .map(|endpoint| endpoint.address.as_str())
+            .collect();
+        invokers
+            .into_iter()
+            .filter(|invoker| {
+                let url = invoker.url();
+                let address = format!(
+                    "{}:{}",
+                    url.host().unwrap_or_default(),
+                    url.port().unwrap_or_default()
+                );
+                addresses.contains(address.as_str())
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dubbo::{invocation::Metadata, invoker::NewInvoker, svc::NewService};
+    use remoting_xds::resource::{
+        ClusterWeight, Endpoint, HeaderMatch, HeaderMatchKind, PathMatch, RouteMatch,
+    };
+
+    const SERVICE_NAME: &str = "org.apache.dubbo.Greeter";
+
+    fn endpoint(port: u16, healthy: bool) -> Endpoint {
+        Endpoint {
+            address: format!("127.0.0.1:{}", port),
+            weight: 1,
+            healthy,
+        }
+    }
+
+    fn route(headers: Vec<HeaderMatch>, cluster: &str) -> Route {
+        Route {
+            name: String::new(),
+            matcher: RouteMatch {
+                path: PathMatch::Prefix(format!("/{}/", SERVICE_NAME)),
+                case_sensitive: true,
+                headers,
+            },
+            clusters: vec![ClusterWeight {
+                name: cluster.to_string(),
+                weight: 1,
+            }],
+        }
+    }
+
+    /// Canary calls go to 8002, the others to 8001.
+    fn snapshot() -> ServiceSnapshot {
+        let canary = HeaderMatch {
+            name: "x-canary".to_string(),
+            kind: HeaderMatchKind::Exact("true".to_string()),
+            invert: false,
+        };
+        ServiceSnapshot {
+            routes: Some(vec![route(vec![canary], "canary"), route(vec![], "stable")]),
+            clusters: [
+                ("canary".to_string(), vec![endpoint(8002, true)]),
+                (
+                    "stable".to_string(),
+                    vec![endpoint(8001, true), endpoint(8003, false)],
+                ),
+            ]
+            .into(),
+        }
+    }
+
+    fn invocation(metadata: Metadata) -> RpcInvocation {
+        RpcInvocation::default()
+            .with_service_unique_name(SERVICE_NAME.to_string())
+            .with_method_name("greet".to_string())
+            .with_metadata(metadata)
+    }
+
+    fn ports(invokers: &[CloneInvoker<BoxInvoker>]) -> Vec<u16> {
+        invokers.iter().filter_map(|i| i.url().port()).collect()
+    }
+
+    #[test]
+    fn test_provider_urls() {
+        let urls = provider_urls("tri", SERVICE_NAME, &snapshot());
+        assert_eq!(
+            urls.into_iter().collect::<Vec<_>>(),
+            [
+                "tri://127.0.0.1:8001/org.apache.dubbo.Greeter?interface=org.apache.dubbo.Greeter",
+                "tri://127.0.0.1:8002/org.apache.dubbo.Greeter?interface=org.apache.dubbo.Greeter",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_router() {
+        let invokers: Vec<_> = provider_urls("tri", SERVICE_NAME, &snapshot())
+            .into_iter()
+            .map(|url| NewInvoker::new().new_service(url))
+            .collect();
+        let (tx, rx) = watch::channel(ServiceSnapshot::default());
+        let router = XdsRouter(rx);
+
+        // everything goes before the routes arrive
+        let routed = router.route(invokers.clone(), &invocation(Metadata::new()));
+        assert_eq!(ports(&routed), [8001, 8002]);
+
+        tx.send(snapshot()).unwrap();
+        let routed = router.route(invokers.clone(), &invocation(Metadata::new()));
+        assert_eq!(ports(&routed), [8001]);
+        let canary = Metadata::new().insert("x-canary".to_string(), "true".to_string());
+        let routed = router.route(invokers.clone(), &invocation(canary));
+        assert_eq!(ports(&routed), [8002]);
+
+        // no route of another service
+        let echo = RpcInvocation::default()
+            .with_service_unique_name("org.apache.dubbo.Echo".to_string())
+            .with_method_name("echo".to_string());
+        assert!(router.route(invokers, &echo).is_empty());
+    }
+}
@@ -0,0 +1,266 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use dubbo::{
+    extension::registry_extension::{DiscoverStream, Registry},
+    registry::registry::StaticRegistry,
+    StdError, Url,
+};
+use dubbo_registry_xds::ControlPlane;
+use remoting_xds::{
+    protocol::{
+        core::Node, CLUSTER_LOAD_ASSIGNMENT_TYPE_URL, CLUSTER_TYPE_URL, LISTENER_TYPE_URL,
+        ROUTE_CONFIGURATION_TYPE_URL,
+    },
+    server::SnapshotCache,
+    ServiceSnapshot, XdsClient,
+};
+use tokio::{
+    net::TcpListener,
+    sync::{oneshot, watch, Notify},
+};
+
+const SERVICE_NAME: &str = "org.apache.dubbo.Greeter";
+
+/// Lets the test register providers after the control plane took the registry.
+struct SharedRegistry(Arc<StaticRegistry>);
+
+#[async_trait]
+impl Registry for SharedRegistry {
+    async fn register(&self, url: Url) -> Result<(), StdError> {
+        self.0.register(url).await
+    }
+
+    async fn unregister(&self, url: Url) -> Result<(), StdError> {
+        self.0.unregister(url).await
+    }
+
+    async fn subscribe(&self, url: Url) -> Result<DiscoverStream, StdError> {
+        self.0.subscribe(url).await
+    }
+
+    async fn unsubscribe(&self, url: Url) -> Result<(), StdError> {
+        self.0.unsubscribe(url).await
+    }
+
+    fn url(&self) -> &Url {
+        self.0.url()
+    }
+}
+
+/// Holds every subscription until it is released, counting the
+/// unsubscriptions.
+#[derive(Clone, Default)]
+struct SlowRegistry {
+    registry: Arc<StaticRegistry>,
+    subscribing: Arc<Notify>,
+    release: Arc<Notify>,
+    unsubscribed: Arc<AtomicUsize>,
+}
+
+#[async_trait]
+impl Registry for SlowRegistry {
+    async fn register(&self, url: Url) -> Result<(), StdError> {
+        self.registry.register(url).await
+    }
+
+    async fn unregister(&self, url: Url) -> Result<(), StdError> {
+        self.registry.unregister(url).await
+    }
+
+    async fn subscribe(&self, url: Url) -> Result<DiscoverStream, StdError> {
+        self.subscribing.notify_one();
+        self.release.notified().await;
+        self.registry.subscribe(url).await
+    }
+
+    async fn unsubscribe(&self, url: Url) -> Result<(), StdError> {
+        self.unsubscribed.fetch_add(1, Ordering::SeqCst);
+        self.registry.unsubscribe(url).await
+    }
+
+    fn url(&self) -> &Url {
+        self.registry.url()
+    }
+}
+
+fn provider(protocol: &str, port: u16) -> Url {
+    format!(
+        "{}://127.0.0.1:{}/{}?interface={}",
+        protocol, port, SERVICE_NAME, SERVICE_NAME
+    )
+    .parse()
+    .unwrap()
+}
+
+async fn wait_for_addresses(snapshot: &mut watch::Receiver<ServiceSnapshot>, addresses: &[&str]) {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !snapshot
+            .borrow_and_update()
+            .addresses()
+            .iter()
+            .eq(addresses)
+        {
+            snapshot.changed().await.unwrap();
+        }
+    })
+    .await
+    .unwrap_or_else(|_| panic!("addresses are {:?}", snapshot.borrow().addresses()));
+}
+
+#[tokio::test]
+async fn test_control_plane() {
+    let registry = Arc::new(StaticRegistry::default());
+    registry.register(provider("tri", 8001)).await.unwrap();
+    registry.register(provider("dubbo", 8002)).await.unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("http://{}", listener.local_addr().unwrap());
+    let (_shutdown, signal) = oneshot::channel::<()>();
+    let control_plane = ControlPlane::new(Box::new(SharedRegistry(registry.clone())));
+    tokio::spawn(control_plane.serve(listener, async {
+        let _ = signal.await;
+    }));
+
+    // the service is watched as the client asks for it
+    let node = Node {
+        id: "test".to_string(),
+        ..Default::default()
+    };
+    let client = XdsClient::connect(endpoint, node).unwrap();
+    let mut snapshot = client.watch_service(SERVICE_NAME).await.unwrap();
+    wait_for_addresses(&mut snapshot, &["127.0.0.1:8001"]).await;
+
+    let path = format!("/{}/greet", SERVICE_NAME);
+    let route = snapshot.borrow().route(&path, |_| None).cloned();
+    assert_eq!(route.unwrap().pick_cluster(), Some(SERVICE_NAME));
+
+    registry.register(provider("tri", 8003)).await.unwrap();
+    wait_for_addresses(&mut snapshot, &["127.0.0.1:8001", "127.0.0.1:8003"]).await;
+
+    registry.unregister(provider("tri", 8001)).await.unwrap();
+    wait_for_addresses(&mut snapshot, &["127.0.0.1:8003"]).await;
+}
+
+#[tokio::test]
+async fn test_watch_limits() {
+    let registry = || Box::new(StaticRegistry::default());
+
+    let allowed = ControlPlane::new(registry()).with_allowed_services([SERVICE_NAME]);
+    allowed.watch_service(SERVICE_NAME).await.unwrap();
+    assert!(allowed
+        .watch_service("org.apache.dubbo.Other")
+        .await
+        .is_err());
+
+    let limited = ControlPlane::new(registry()).with_max_services(1);
+    limited.watch_service(SERVICE_NAME).await.unwrap();
+    // watched already
+    limited.watch_service(SERVICE_NAME).await.unwrap();
+    assert!(limited
+        .watch_service("org.apache.dubbo.Other")
+        .await
+        .is_err());
+
+    limited.unwatch_service(SERVICE_NAME).await.unwrap();
+    limited
+        .watch_service("org.apache.dubbo.Other")
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_unwatch_unrequested() {
+    let registry = Arc::new(StaticRegistry::default());
+    registry.register(provider("tri", 8001)).await.unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("http://{}", listener.local_addr().unwrap());
+    let (_shutdown, signal) = oneshot::channel::<()>();
+    let control_plane = ControlPlane::new(Box::new(SharedRegistry(registry.clone())));
+    let cache = control_plane.server().cache().clone();
+    tokio::spawn(control_plane.serve(listener, async {
+        let _ = signal.await;
+    }));
+    let listeners = move || cache.snapshot().resources(LISTENER_TYPE_URL, None).len();
+
+    let node = Node {
+        id: "test".to_string(),
+        ..Default::default()
+    };
+    let client = XdsClient::connect(endpoint, node).unwrap();
+    let mut snapshot = client.watch_service(SERVICE_NAME).await.unwrap();
+    wait_for_addresses(&mut snapshot, &["127.0.0.1:8001"]).await;
+    assert_eq!(listeners(), 1);
+
+    // the stream closes with the last clone of the client
+    drop(client);
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while listeners() != 0 {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("service still watched");
+}
+
+/// The resources of every type published.
+fn resources(cache: &SnapshotCache) -> usize {
+    [
+        LISTENER_TYPE_URL,
+        ROUTE_CONFIGURATION_TYPE_URL,
+        CLUSTER_TYPE_URL,
+        CLUSTER_LOAD_ASSIGNMENT_TYPE_URL,
+    ]
+    .into_iter()
+    .map(|type_url| cache.snapshot().resources(type_url, None).len())
+    .sum()
+}
+
+#[tokio::test]
+async fn test_unwatch_while_subscribing() {
+    let registry = SlowRegistry::default();
+    let control_plane = ControlPlane::new(Box::new(registry.clone()));
+    let cache = control_plane.server().cache().clone();
+
+    let watch = tokio::spawn({
+        let control_plane = control_plane.clone();
+        async move { control_plane.watch_service(SERVICE_NAME).await }
+    });
+    registry.subscribing.notified().await;
+    control_plane.unwatch_service(SERVICE_NAME).await.unwrap();
+    registry.release.notify_one();
+    watch.await.unwrap().unwrap();
+
+    // nothing is left of the service once the subscription completed
+    assert_eq!(resources(&cache), 0);
+    // by the unwatch, before the registry had the subscription, and after it
+    assert_eq!(registry.unsubscribed.load(Ordering::SeqCst), 2);
+
+    registry.release.notify_one();
+    control_plane.watch_service(SERVICE_NAME).await.unwrap();
+    assert_eq!(resources(&cache), 4);
+}
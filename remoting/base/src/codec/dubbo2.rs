@@ -0,0 +1,231 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! The `dubbo` protocol of `protocol_dubbo2` as an exchange codec.
+//!
+//! Request bodies are `RpcInvocation`s, response bodies are
+//! `ResponseData::Value` or `ResponseData::Exception`. Events carry a
+//! Hessian2 `Value`, heartbeats carry nothing.
+
+use std::sync::Arc;
+
+use bytes::BytesMut;
+use protocol_dubbo2::{
+    header::{Status, HESSIAN2_SERIALIZATION_ID},
+    hessian2::Value,
+    RequestData, ResponseData, RpcInvocation, DUBBO_VERSION,
+};
+use tokio_util::codec::{Decoder, Encoder};
+
+use super::{Codec, ProtocolName};
+use crate::{error::CodecError, exchange::Message, Request, Response};
+
+pub const DUBBO2: ProtocolName = "dubbo";
+
+#[derive(Debug, Clone, Default)]
+pub struct Dubbo2Codec {
+    inner: protocol_dubbo2::Dubbo2Codec,
+}
+
+impl Dubbo2Codec {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_payload(self, max_payload: usize) -> Self {
+        Dubbo2Codec {
+            inner: self.inner.with_max_payload(max_payload),
+        }
+    }
+
+    fn encode(
+        &self,
+        message: protocol_dubbo2::Message,
+        dst: &mut BytesMut,
+    ) -> Result<(), CodecError> {
+        self.inner.clone().encode(&message, dst)?;
+        Ok(())
+    }
+}
+
+fn event_value(body: &Option<crate::BoxedExchangeBody>) -> Result<Value, CodecError> {
+    match body {
+        None => Ok(Value::Null),
+        Some(body) => body
+            .downcast_ref::<Value>()
+            .cloned()
+            .ok_or(CodecError::UnsupportedBody(DUBBO2)),
+    }
+}
+
+impl Codec for Dubbo2Codec {
+    fn encode_request(&self, request: &Request, dst: &mut BytesMut) -> Result<(), CodecError> {
+        let data = if request.event {
+            RequestData::Event(event_value(&request.body)?)
+        } else {
+            let invocation = request
+                .body::<RpcInvocation>()
+                .ok_or(CodecError::UnsupportedBody(DUBBO2))?;
+            RequestData::Invocation(invocation.clone())
+        };
+        let request = protocol_dubbo2::Request {
+            id: request.id,
+            two_way: request.two_way,
+            data,
+        };
+        self.encode(protocol_dubbo2::Message::Request(request), dst)
+    }
+
+    fn encode_response(&self, response: &Response, dst: &mut BytesMut) -> Result<(), CodecError> {
+        let status = Status::try_from(response.status)?;
+        let data = if response.event {
+            ResponseData::Event(event_value(&response.body)?)
+        } else if status != Status::Ok {
+            ResponseData::Error(response.error.clone().unwrap_or_default())
+        } else {
+            match response.body::<ResponseData>() {
+                Some(data @ (ResponseData::Value { .. } | ResponseData::Exception { .. })) => {
+                    data.clone()
+                }
+                _ => return Err(CodecError::UnsupportedBody(DUBBO2)),
+            }
+        };
+        let response = protocol_dubbo2::Response {
+            id: response.id,
+            status,
+            data,
+        };
+        self.encode(protocol_dubbo2::Message::Response(response), dst)
+    }
+
+    fn decode(&self, src: &mut BytesMut) -> Result<Option<Message>, CodecError> {
+        let Some(message) = self.inner.clone().decode(src)? else {
+            return Ok(None);
+        };
+
+        let message = match message {
+            protocol_dubbo2::Message::Request(request) => {
+                let mut req = Request::heartbeat(request.id);
+                req.serial_id = HESSIAN2_SERIALIZATION_ID;
+                req.two_way = request.two_way;
+                match request.data {
+                    RequestData::Invocation(invocation) => {
+                        req.version = invocation.dubbo_version.clone();
+                        req.event = false;
+                        req.body = Some(Arc::new(invocation));
+                    }
+                    RequestData::Event(Value::Null) => {}
+                    RequestData::Event(value) => req.body = Some(Arc::new(value)),
+                    RequestData::Broken(error) => {
+                        req.event = false;
+                        req.error = Some(error);
+                    }
+                }
+                Message::Request(req)
+            }
+            protocol_dubbo2::Message::Response(response) => {
+                let mut resp = Response::heartbeat(response.id);
+                resp.version = DUBBO_VERSION.to_string();
+                resp.serial_id = HESSIAN2_SERIALIZATION_ID;
+                resp.status = response.status as u8;
+                match response.data {
+                    ResponseData::Event(Value::Null) => {}
+                    ResponseData::Event(value) => resp.body = Some(Arc::new(value)),
+                    ResponseData::Error(error) => {
+                        resp.event = false;
+                        resp.error = Some(error);
+                    }
+                    data => {
+                        resp.event = false;
+                        resp.body = Some(Arc::new(data));
+                    }
+                }
+                Message::Response(resp)
+            }
+        };
+        Ok(Some(message))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use protocol_dubbo2::Attachments;
+
+    use super::*;
+    use crate::exchange::status;
+
+    fn roundtrip(codec: &Dubbo2Codec, message: Message) -> Message {
+        let mut bytes = BytesMut::new();
+        match &message {
+            Message::Request(request) => codec.encode_request(request, &mut bytes).unwrap(),
+            Message::Response(response) => codec.encode_response(response, &mut bytes).unwrap(),
+        }
+        let decoded = codec.decode(&mut bytes).unwrap().unwrap();
+        assert!(bytes.is_empty());
+        decoded
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let codec = Dubbo2Codec::new();
+
+        let invocation = RpcInvocation::new("org.apache.dubbo.Greeter", "greet")
+            .with_argument("java.lang.String", Value::from("dubbo"));
+        let mut request = Request::new(invocation.clone());
+        request.id = 7;
+        let Message::Request(decoded) = roundtrip(&codec, Message::Request(request)) else {
+            panic!("not a request");
+        };
+        assert_eq!(decoded.id, 7);
+        assert!(decoded.two_way && !decoded.event);
+        assert_eq!(decoded.body::<RpcInvocation>(), Some(&invocation));
+
+        let Message::Request(decoded) = roundtrip(&codec, Message::Request(Request::heartbeat(8)))
+        else {
+            panic!("not a request");
+        };
+        assert!(decoded.is_heartbeat());
+
+        let data = ResponseData::Value {
+            value: Value::from("hello dubbo"),
+            attachments: Attachments::new(),
+        };
+        let Message::Response(decoded) =
+            roundtrip(&codec, Message::Response(Response::new(7, data.clone())))
+        else {
+            panic!("not a response");
+        };
+        assert!(decoded.is_ok());
+        assert_eq!(decoded.body::<ResponseData>(), Some(&data));
+
+        let error = Response::error(9, status::SERVICE_ERROR, "boom".to_string());
+        let Message::Response(decoded) = roundtrip(&codec, Message::Response(error)) else {
+            panic!("not a response");
+        };
+        assert_eq!(decoded.status, status::SERVICE_ERROR);
+        assert_eq!(decoded.error.as_deref(), Some("boom"));
+    }
+
+    #[test]
+    fn test_unsupported_body() {
+        let codec = Dubbo2Codec::new();
+        let mut bytes = BytesMut::new();
+        let res = codec.encode_request(&Request::new("not an invocation"), &mut bytes);
+        assert!(matches!(res, Err(CodecError::UnsupportedBody(DUBBO2))));
+        assert!(bytes.is_empty());
+    }
+}
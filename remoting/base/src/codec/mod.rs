@@ -0,0 +1,190 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::Arc;
+
+use bytes::BytesMut;
+use dashmap::DashMap;
+
+use crate::{error::CodecError, exchange::Message, Request, Response};
+
+pub mod dubbo2;
+
+pub type ProtocolName = &'static str;
+
+#[derive(Clone)]
+pub struct BoxedCodec(Arc<dyn Codec>);
+
+impl BoxedCodec {
+    pub fn new(codec: Arc<dyn Codec>) -> Self {
+        BoxedCodec(codec)
+    }
+}
+
+/// Turns exchange requests and responses into frames of a protocol.
+pub trait Codec: Sync + Send {
+    fn encode_request(&self, request: &Request, dst: &mut BytesMut) -> Result<(), CodecError>;
+    fn encode_response(&self, response: &Response, dst: &mut BytesMut) -> Result<(), CodecError>;
+    /// Decodes the next frame of `src`, `None` until a whole frame arrived.
+    fn decode(&self, src: &mut BytesMut) -> Result<Option<Message>, CodecError>;
+}
+
+impl Codec for BoxedCodec {
+    fn encode_request(&self, request: &Request, dst: &mut BytesMut) -> Result<(), CodecError> {
+        self.0.encode_request(request, dst)
+    }
+
+    fn encode_response(&self, response: &Response, dst: &mut BytesMut) -> Result<(), CodecError> {
+        self.0.encode_response(response, dst)
+    }
+
+    fn decode(&self, src: &mut BytesMut) -> Result<Option<Message>, CodecError> {
+        self.0.decode(src)
+    }
+}
+
+/// Adapts a `Codec` to `tokio_util::codec` for the framed halves of a
+/// connection.
+pub(crate) struct ExchangeCodec(pub(crate) BoxedCodec);
+
+impl tokio_util::codec::Decoder for ExchangeCodec {
+    type Item = Message;
+    type Error = CodecError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.0.decode(src)
+    }
+}
+
+impl tokio_util::codec::Encoder<Message> for ExchangeCodec {
+    type Error = CodecError;
+
+    fn encode(&mut self, item: Message, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        match &item {
+            Message::Request(request) => self.0.encode_request(request, dst),
+            Message::Response(response) => self.0.encode_response(response, dst),
+        }
+    }
+}
+
+pub struct CodecRegistry {
+    registry: DashMap<ProtocolName, BoxedCodec>,
+}
+
+impl Default for CodecRegistry {
+    fn default() -> Self {
+        CodecRegistry {
+            registry: DashMap::new(),
+        }
+    }
+}
+impl CodecRegistry {
+    /// A registry with the codecs of this crate, `dubbo` for now.
+    pub fn new() -> Self {
+        let mut registry = CodecRegistry::default();
+        registry
+            .set_codec(
+                dubbo2::DUBBO2,
+                BoxedCodec::new(Arc::new(dubbo2::Dubbo2Codec::new())),
+            )
+            .unwrap();
+        registry
+    }
+
+    pub fn get_codec(&self, protocol: &str) -> Option<BoxedCodec> {
+        self.registry.get(protocol).map(|codec| codec.clone())
+    }
+
+    /// The codec of `protocol`, failing for unknown protocols.
+    pub fn codec(&self, protocol: &str) -> Result<BoxedCodec, CodecError> {
+        self.get_codec(protocol)
+            .ok_or_else(|| CodecError::UnknownProtocol(protocol.to_string()))
+    }
+
+    pub fn set_codec(
+        &mut self,
+        protocol: ProtocolName,
+        codec: BoxedCodec,
+    ) -> anyhow::Result<(), CodecError> {
+        if self.registry.contains_key(protocol) {
+            return Err(CodecError::RegistryExistsProtocol(protocol));
+        }
+        self.registry.insert(protocol, codec);
+        Ok(())
+    }
+
+    pub fn is_registered(&self, protocol: ProtocolName) -> bool {
+        self.registry.contains_key(protocol)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use bytes::BytesMut;
+
+    use crate::{
+        codec::{BoxedCodec, CodecRegistry},
+        error::CodecError,
+        exchange::Message,
+        Codec, Request, Response,
+    };
+
+    #[derive(Default)]
+    struct TestCodec;
+    impl Codec for TestCodec {
+        fn encode_request(
+            &self,
+            _request: &Request,
+            _dst: &mut BytesMut,
+        ) -> Result<(), CodecError> {
+            Ok(())
+        }
+
+        fn encode_response(
+            &self,
+            _response: &Response,
+            _dst: &mut BytesMut,
+        ) -> Result<(), CodecError> {
+            Ok(())
+        }
+
+        fn decode(&self, _src: &mut BytesMut) -> Result<Option<Message>, CodecError> {
+            Ok(None)
+        }
+    }
+
+    #[test]
+    fn test_registry() {
+        let mut codec_registry = CodecRegistry::default();
+        codec_registry
+            .set_codec("test", BoxedCodec(Arc::new(TestCodec)))
+            .unwrap();
+        assert!(codec_registry.is_registered("test"));
+        assert!(codec_registry
+            .set_codec("test", BoxedCodec(Arc::new(TestCodec)))
+            .is_err());
+
+        let codec_registry = CodecRegistry::new();
+        assert!(codec_registry.codec("dubbo").is_ok());
+        assert!(matches!(
+            codec_registry.codec("test"),
+            Err(CodecError::UnknownProtocol(_))
+        ));
+    }
+}
@@ -1,32 +1,51 @@
 /*
-* Licensed to the Apache Software Foundation (ASF) under one or more
-* contributor license agreements.  See the NOTICE file distributed with
-* this work for additional information regarding copyright ownership.
-* The ASF licenses this file to You under the Apache License, Version 2.0
-* (the "License"); you may not use this file except in compliance with
-* the License.  You may obtain a copy of the License at
-*
-*     http://www.apache.org/licenses/LICENSE-2.0
-*
-* Unless required by applicable law or agreed to in writing, software
-* distributed under the License is distributed on an "AS IS" BASIS,
-* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
-* See the License for the specific language governing permissions and
-* limitations under the License.
-*/
-use protocol_base::ProtocolName;
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use std::{io, time::Duration};
+
 use thiserror::Error;
 
+use crate::codec::ProtocolName;
+
 #[derive(Error, Debug)]
 pub enum CodecError {
     #[error("unknown codec error.")]
     Unknown,
     #[error("protocol {0} is registered.")]
     RegistryExistsProtocol(ProtocolName),
+    #[error("protocol {0} is not registered.")]
+    UnknownProtocol(String),
+    #[error("io error: {0}")]
+    Io(#[from] io::Error),
+    #[error("the {0} codec cannot encode this body.")]
+    UnsupportedBody(ProtocolName),
+    #[error("dubbo2 error: {0}")]
+    Dubbo2(#[from] protocol_dubbo2::Error),
 }
 
 #[derive(Error, Debug)]
 pub enum ClientError {
     #[error("unknown client error")]
     Unknown,
+    #[error("failed to connect: {0}")]
+    Connect(io::Error),
+    #[error("request {0} timed out after {1:?}")]
+    Timeout(u64, Duration),
+    #[error("the connection is closed")]
+    Closed,
+    #[error("codec error: {0}")]
+    Codec(#[from] CodecError),
 }
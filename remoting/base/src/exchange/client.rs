@@ -15,69 +15,426 @@
  * limitations under the License.
  */
 
-use dubbo_base::Url;
 use std::{
+    io,
     sync::{
-        atomic::{AtomicBool, AtomicI32, Ordering},
-        Arc,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex, Weak,
     },
-    time,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
+use bytes::{Bytes, BytesMut};
+use dashmap::DashMap;
+use futures::StreamExt;
+use remoting_net::{
+    dial::{DefaultMakeTransport, MakeTransport},
+    Address,
+};
+use tokio::{
+    sync::{mpsc, oneshot},
+    task::AbortHandle,
+    time::MissedTickBehavior,
+};
+use tokio_util::codec::FramedRead;
+use tracing::{debug, warn};
+
 use crate::{
+    codec::{BoxedCodec, ExchangeCodec},
     error::ClientError,
-    exchange::{Request, Response},
+    exchange::{write_frames, Message, Request, Response},
+    Codec,
 };
 
 pub struct BoxedClient(Arc<dyn Client>);
 
+impl BoxedClient {
+    pub fn new(client: Arc<dyn Client>) -> Self {
+        BoxedClient(client)
+    }
+}
+
+#[async_trait::async_trait]
 pub trait Client: Sync + Send {
-    fn connect(&self, url: Url) -> Result<(), ClientError>;
-    fn request(&self, request: Request, timeout: Duration) -> Result<Response, ClientError>;
-    fn close(&mut self) -> Result<(), ClientError>;
+    async fn connect(&self) -> Result<(), ClientError>;
+    /// Sends a two-way request and waits for its response.
+    async fn request(&self, request: Request, timeout: Duration) -> Result<Response, ClientError>;
+    /// Sends a one-way request.
+    async fn send(&self, request: Request) -> Result<(), ClientError>;
+    async fn close(&self) -> Result<(), ClientError>;
     fn is_available(&self) -> bool;
 }
 
-pub struct ExchangeClient {
-    connection_timeout: Duration, // timeout when connecting to server
-    address: String,              // listening ip:port
-    client: Option<BoxedClient>,  // dealing with the transports
-    init: AtomicBool,             // whether the client is initialized
-    active: AtomicI32,            // the number of active service bind to this client
+#[derive(Debug, Clone, Copy)]
+pub struct ClientConfig {
+    pub connect_timeout: Duration, // timeout when connecting to server
+    /// A heartbeat is sent once nothing was read for this long.
+    pub heartbeat: Duration,
+    /// The connection is reopened once nothing was read for this long.
+    pub idle_timeout: Duration,
+    /// How long `close` waits for the responses of pending requests.
+    pub close_timeout: Duration,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        ClientConfig {
+            connect_timeout: Duration::from_secs(3),
+            heartbeat: Duration::from_secs(60),
+            idle_timeout: Duration::from_secs(180),
+            close_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+impl ClientConfig {
+    pub fn with_connect_timeout(self, connect_timeout: Duration) -> Self {
+        Self {
+            connect_timeout,
+            ..self
+        }
+    }
+
+    pub fn with_heartbeat(self, heartbeat: Duration) -> Self {
+        Self { heartbeat, ..self }
+    }
+
+    pub fn with_idle_timeout(self, idle_timeout: Duration) -> Self {
+        Self {
+            idle_timeout,
+            ..self
+        }
+    }
+
+    pub fn with_close_timeout(self, close_timeout: Duration) -> Self {
+        Self {
+            close_timeout,
+            ..self
+        }
+    }
+}
+
+/// A client multiplexing requests over one connection to `address`.
+///
+/// Responses are matched to requests by id. The connection is opened on
+/// first use, kept alive with heartbeats and reopened once it is lost or
+/// idle for longer than `ClientConfig::idle_timeout`.
+pub struct ExchangeClient<M: MakeTransport = DefaultMakeTransport> {
+    inner: Arc<Inner<M>>,
+}
+
+struct Inner<M> {
+    address: Address,  // listening ip:port or unix socket
+    make_transport: M, // dealing with the transports
+    codec: BoxedCodec,
+    config: ClientConfig,
+    next_id: AtomicU64,
+    channel: Mutex<Option<Arc<Channel>>>,
+    connecting: tokio::sync::Mutex<()>,
+    heartbeat: AtomicBool, // whether the heartbeat task runs
+    closed: AtomicBool,
 }
 
 impl ExchangeClient {
-    pub fn new(url: Url, client: BoxedClient, connection_timeout: Duration) -> Self {
+    pub fn new(address: Address, codec: BoxedCodec, config: ClientConfig) -> Self {
+        Self::with_transport(address, codec, config, DefaultMakeTransport::new())
+    }
+}
+
+impl<M: MakeTransport> ExchangeClient<M> {
+    /// Connects with `make_transport`, e.g. a `remoting_net::pool::Pool`
+    /// shared by the clients to cap their connections per provider.
+    pub fn with_transport(
+        address: Address,
+        codec: BoxedCodec,
+        config: ClientConfig,
+        make_transport: M,
+    ) -> Self {
         ExchangeClient {
-            connection_timeout,
-            address: url.authority().to_owned(),
-            client: None,
-            init: AtomicBool::new(false),
-            active: AtomicI32::new(0),
+            inner: Arc::new(Inner {
+                address,
+                make_transport,
+                codec,
+                config,
+                next_id: AtomicU64::new(0),
+                channel: Mutex::new(None),
+                connecting: tokio::sync::Mutex::new(()),
+                heartbeat: AtomicBool::new(false),
+                closed: AtomicBool::new(false),
+            }),
         }
     }
+
+    pub fn address(&self) -> &Address {
+        &self.inner.address
+    }
+
+    fn encode(&self, request: &mut Request) -> Result<Bytes, ClientError> {
+        request.id = self.inner.next_id.fetch_add(1, Ordering::Relaxed);
+        let mut buf = BytesMut::new();
+        self.inner.codec.encode_request(request, &mut buf)?;
+        Ok(buf.freeze())
+    }
 }
 
-impl Client for ExchangeClient {
-    fn connect(&self, url: Url) -> Result<(), ClientError> {
-        if self.init.load(Ordering::SeqCst) {
-            return Ok(());
+#[async_trait::async_trait]
+impl<M: MakeTransport> Client for ExchangeClient<M> {
+    async fn connect(&self) -> Result<(), ClientError> {
+        self.inner.channel().await.map(|_| ())
+    }
+
+    async fn request(
+        &self,
+        mut request: Request,
+        timeout: Duration,
+    ) -> Result<Response, ClientError> {
+        request.two_way = true;
+        let frame = self.encode(&mut request)?;
+        let channel = self.inner.channel().await?;
+
+        let (tx, rx) = oneshot::channel();
+        channel.pending.insert(request.id, tx);
+        if channel.send(frame).await.is_err() {
+            channel.pending.remove(&request.id);
+            return Err(ClientError::Closed);
+        }
+        match tokio::time::timeout(timeout, rx).await {
+            Ok(Ok(response)) => Ok(response),
+            Ok(Err(_)) => Err(ClientError::Closed),
+            Err(_) => {
+                channel.pending.remove(&request.id);
+                Err(ClientError::Timeout(request.id, timeout))
+            }
         }
-        Ok(())
     }
 
-    fn request(&self, request: Request, timeout: Duration) -> Result<Response, ClientError> {
-        todo!()
+    async fn send(&self, mut request: Request) -> Result<(), ClientError> {
+        request.two_way = false;
+        let frame = self.encode(&mut request)?;
+        let channel = self.inner.channel().await?;
+        channel.send(frame).await.map_err(|_| ClientError::Closed)
     }
 
-    fn close(&mut self) -> Result<(), ClientError> {
-        self.init.store(false, Ordering::SeqCst);
+    /// Stops taking requests, waits up to `ClientConfig::close_timeout` for
+    /// the pending ones and closes the connection.
+    async fn close(&self) -> Result<(), ClientError> {
+        self.inner.closed.store(true, Ordering::SeqCst);
+        let channel = self.inner.channel.lock().unwrap().take();
+        let Some(channel) = channel else {
+            return Ok(());
+        };
+
+        let deadline = Instant::now() + self.inner.config.close_timeout;
+        while !channel.pending.is_empty() && !channel.is_closed() && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        channel.close();
         Ok(())
     }
 
     fn is_available(&self) -> bool {
-        let client = self.client.as_ref().unwrap();
-        client.0.is_available()
+        !self.inner.closed.load(Ordering::SeqCst)
+            && matches!(&*self.inner.channel.lock().unwrap(), Some(channel) if !channel.is_closed())
+    }
+}
+
+impl<M: MakeTransport> Inner<M> {
+    fn current(&self) -> Option<Arc<Channel>> {
+        self.channel
+            .lock()
+            .unwrap()
+            .clone()
+            .filter(|channel| !channel.is_closed())
+    }
+
+    /// The open channel, connecting first if there is none.
+    async fn channel(self: &Arc<Self>) -> Result<Arc<Channel>, ClientError> {
+        let channel = self.reconnect().await?;
+        if !self.heartbeat.swap(true, Ordering::SeqCst) {
+            tokio::spawn(heartbeat(Arc::downgrade(self)));
+        }
+        Ok(channel)
+    }
+
+    /// Opens a new channel unless the current one is still open.
+    async fn reconnect(&self) -> Result<Arc<Channel>, ClientError> {
+        if self.closed.load(Ordering::SeqCst) {
+            return Err(ClientError::Closed);
+        }
+        if let Some(channel) = self.current() {
+            return Ok(channel);
+        }
+
+        let _connecting = self.connecting.lock().await;
+        if let Some(channel) = self.current() {
+            return Ok(channel);
+        }
+        let connect = self.make_transport.make_transport(self.address.clone());
+        let (read, write) = tokio::time::timeout(self.config.connect_timeout, connect)
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "connect timed out"))
+            .and_then(|res| res)
+            .map_err(ClientError::Connect)?;
+        debug!("[Exchange] connected to {}", self.address);
+
+        let channel = Channel::open(read, write, self.codec.clone());
+        *self.channel.lock().unwrap() = Some(channel.clone());
+        if self.closed.load(Ordering::SeqCst) {
+            channel.close();
+            return Err(ClientError::Closed);
+        }
+        Ok(channel)
+    }
+}
+
+impl<M> Drop for Inner<M> {
+    fn drop(&mut self) {
+        if let Some(channel) = self.channel.get_mut().unwrap().take() {
+            channel.close();
+        }
+    }
+}
+
+/// Sends heartbeats over idle connections and reopens connections that are
+/// lost or idle for too long, until the client is closed or dropped.
+async fn heartbeat<M: MakeTransport>(inner: Weak<Inner<M>>) {
+    let Some(config) = inner.upgrade().map(|inner| inner.config) else {
+        return;
+    };
+    let mut ticker = tokio::time::interval(config.heartbeat);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    ticker.tick().await;
+
+    loop {
+        ticker.tick().await;
+        let Some(inner) = inner.upgrade() else {
+            return;
+        };
+        if inner.closed.load(Ordering::SeqCst) {
+            return;
+        }
+
+        if let Some(channel) = inner.current() {
+            let idle = channel.idle();
+            if idle >= config.idle_timeout {
+                warn!(
+                    "[Exchange] nothing read from {} for {:?}, reconnecting",
+                    inner.address, idle
+                );
+                channel.close();
+            } else {
+                if idle >= config.heartbeat {
+                    let id = inner.next_id.fetch_add(1, Ordering::Relaxed);
+                    let mut buf = BytesMut::new();
+                    if inner
+                        .codec
+                        .encode_request(&Request::heartbeat(id), &mut buf)
+                        .is_ok()
+                    {
+                        let _ = channel.send(buf.freeze()).await;
+                    }
+                }
+                continue;
+            }
+        }
+        if let Err(err) = inner.reconnect().await {
+            debug!("[Exchange] reconnect to {} failed: {}", inner.address, err);
+        }
+    }
+}
+
+/// One connection of a client and the requests waiting for a response on it.
+struct Channel {
+    tx: Mutex<Option<mpsc::Sender<Bytes>>>,
+    pending: DashMap<u64, oneshot::Sender<Response>>,
+    last_read: Mutex<Instant>,
+    closed: AtomicBool,
+    reader: Mutex<Option<AbortHandle>>,
+}
+
+impl Channel {
+    fn open<R, W>(read: R, write: W, codec: BoxedCodec) -> Arc<Channel>
+    where
+        R: tokio::io::AsyncRead + Send + Unpin + 'static,
+        W: tokio::io::AsyncWrite + Send + Unpin + 'static,
+    {
+        let (tx, rx) = mpsc::channel(1024);
+        tokio::spawn(write_frames(write, rx));
+
+        let channel = Arc::new(Channel {
+            tx: Mutex::new(Some(tx)),
+            pending: DashMap::new(),
+            last_read: Mutex::new(Instant::now()),
+            closed: AtomicBool::new(false),
+            reader: Mutex::new(None),
+        });
+        let frames = FramedRead::new(read, ExchangeCodec(codec.clone()));
+        let reader = tokio::spawn(channel.clone().read(frames, codec));
+        *channel.reader.lock().unwrap() = Some(reader.abort_handle());
+        channel
+    }
+
+    async fn read<R>(self: Arc<Self>, mut frames: FramedRead<R, ExchangeCodec>, codec: BoxedCodec)
+    where
+        R: tokio::io::AsyncRead + Send + Unpin,
+    {
+        while let Some(frame) = frames.next().await {
+            *self.last_read.lock().unwrap() = Instant::now();
+            match frame {
+                Ok(Message::Response(response)) => {
+                    match self.pending.remove(&response.id) {
+                        Some((_, tx)) => {
+                            let _ = tx.send(response);
+                        }
+                        // heartbeats and responses that timed out
+                        None => debug!("[Exchange] dropped response {}", response.id),
+                    }
+                }
+                Ok(Message::Request(request)) if request.is_heartbeat() && request.two_way => {
+                    let mut buf = BytesMut::new();
+                    if codec
+                        .encode_response(&Response::heartbeat(request.id), &mut buf)
+                        .is_ok()
+                    {
+                        let _ = self.send(buf.freeze()).await;
+                    }
+                }
+                Ok(Message::Request(request)) => {
+                    debug!("[Exchange] ignored request {} of the server", request.id)
+                }
+                Err(err) => {
+                    warn!("[Exchange] failed to read a frame: {}", err);
+                    break;
+                }
+            }
+        }
+        self.close();
+    }
+
+    async fn send(&self, frame: Bytes) -> Result<(), ClientError> {
+        let tx = self.tx.lock().unwrap().clone();
+        match tx {
+            Some(tx) => tx.send(frame).await.map_err(|_| ClientError::Closed),
+            None => Err(ClientError::Closed),
+        }
+    }
+
+    fn idle(&self) -> Duration {
+        self.last_read.lock().unwrap().elapsed()
+    }
+
+    fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    /// Closes the connection once the queued frames are written, pending
+    /// requests fail with `ClientError::Closed`.
+    fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        self.tx.lock().unwrap().take();
+        if let Some(reader) = self.reader.lock().unwrap().take() {
+            reader.abort();
+        }
+        self.pending.clear();
     }
 }
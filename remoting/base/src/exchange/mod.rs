@@ -15,34 +15,167 @@
  * limitations under the License.
  */
 
-use crate::error::CodecError;
 use std::{any::Any, sync::Arc};
+
+use bytes::Bytes;
+use remoting_net::Address;
+use tokio::{
+    io::{AsyncWrite, AsyncWriteExt},
+    sync::mpsc,
+};
+use tracing::debug;
+
 pub mod client;
 pub mod server;
 
-pub type BoxedExchangeBody = Arc<dyn Any>;
+pub use client::{ClientConfig, ExchangeClient};
+pub use server::{ExchangeServer, Handler, ServerConfig};
+
+pub type BoxedExchangeBody = Arc<dyn Any + Send + Sync>;
+
+/// The response statuses shared by the exchange protocols.
+pub mod status {
+    pub const OK: u8 = 20;
+    pub const CLIENT_TIMEOUT: u8 = 30;
+    pub const SERVER_TIMEOUT: u8 = 31;
+    pub const BAD_REQUEST: u8 = 40;
+    pub const BAD_RESPONSE: u8 = 50;
+    pub const SERVICE_NOT_FOUND: u8 = 60;
+    pub const SERVICE_ERROR: u8 = 70;
+    pub const SERVER_ERROR: u8 = 80;
+    pub const CLIENT_ERROR: u8 = 90;
+}
+
+/// A frame sent over an exchange channel.
+pub enum Message {
+    Request(Request),
+    Response(Response),
+}
 
+#[derive(Clone)]
 pub struct Request {
-    id: u64,
-    version: String, // protocol version
-    serial_id: u8,   // serial ID (ignore)
-    body: Option<BoxedExchangeBody>,
-    two_way: bool,
-    event: bool,
+    pub id: u64,
+    pub version: String, // protocol version
+    pub serial_id: u8,   // serial ID (ignore)
+    pub body: Option<BoxedExchangeBody>,
+    pub two_way: bool,
+    pub event: bool,
+    /// Set when the body could not be decoded, the server answers with
+    /// `BAD_REQUEST`.
+    pub error: Option<String>,
+    /// The client a server received the request from, the one behind the
+    /// load balancer for servers reading the PROXY protocol.
+    pub peer: Option<Address>,
+}
+
+impl Request {
+    /// A two-way request, its id is assigned when it is sent.
+    pub fn new<T: Any + Send + Sync>(body: T) -> Self {
+        Request {
+            id: 0,
+            version: String::new(),
+            serial_id: 0,
+            body: Some(Arc::new(body)),
+            two_way: true,
+            event: false,
+            error: None,
+            peer: None,
+        }
+    }
+
+    pub fn heartbeat(id: u64) -> Self {
+        Request {
+            id,
+            version: String::new(),
+            serial_id: 0,
+            body: None,
+            two_way: true,
+            event: true,
+            error: None,
+            peer: None,
+        }
+    }
+
+    pub fn is_heartbeat(&self) -> bool {
+        self.event && self.body.is_none()
+    }
+
+    pub fn body<T: Any>(&self) -> Option<&T> {
+        self.body.as_ref().and_then(|body| body.downcast_ref())
+    }
 }
 
 pub struct Response {
-    id: u64,
-    version: String, // protocol version
-    serial_id: u8,   // serial ID (ignore)
-    status: u8,
-    body: Option<BoxedExchangeBody>, // mean result
-    event: bool,
-    error: Option<CodecError>,
+    pub id: u64,
+    pub version: String, // protocol version
+    pub serial_id: u8,   // serial ID (ignore)
+    pub status: u8,
+    pub body: Option<BoxedExchangeBody>, // mean result
+    pub event: bool,
+    pub error: Option<String>,
 }
 
 impl Response {
-    fn is_heart_beat(&self) -> bool {
+    pub fn new<T: Any + Send + Sync>(id: u64, body: T) -> Self {
+        Response {
+            id,
+            version: String::new(),
+            serial_id: 0,
+            status: status::OK,
+            body: Some(Arc::new(body)),
+            event: false,
+            error: None,
+        }
+    }
+
+    pub fn error(id: u64, status: u8, message: String) -> Self {
+        Response {
+            id,
+            version: String::new(),
+            serial_id: 0,
+            status,
+            body: None,
+            event: false,
+            error: Some(message),
+        }
+    }
+
+    pub fn heartbeat(id: u64) -> Self {
+        Response {
+            id,
+            version: String::new(),
+            serial_id: 0,
+            status: status::OK,
+            body: None,
+            event: true,
+            error: None,
+        }
+    }
+
+    pub fn is_heartbeat(&self) -> bool {
         self.event && self.body.is_none()
     }
+
+    pub fn is_ok(&self) -> bool {
+        self.status == status::OK
+    }
+
+    pub fn body<T: Any>(&self) -> Option<&T> {
+        self.body.as_ref().and_then(|body| body.downcast_ref())
+    }
+}
+
+/// Writes the encoded frames of a connection until every sender is dropped,
+/// then shuts the connection down.
+pub(crate) async fn write_frames<W: AsyncWrite + Unpin>(
+    mut write: W,
+    mut rx: mpsc::Receiver<Bytes>,
+) {
+    while let Some(frame) = rx.recv().await {
+        if let Err(err) = write.write_all(&frame).await {
+            debug!("[Exchange] write failed: {}", err);
+            return;
+        }
+    }
+    let _ = write.shutdown().await;
 }
@@ -1,48 +1,398 @@
 /*
-* Licensed to the Apache Software Foundation (ASF) under one or more
-* contributor license agreements.  See the NOTICE file distributed with
-* this work for additional information regarding copyright ownership.
-* The ASF licenses this file to You under the Apache License, Version 2.0
-* (the "License"); you may not use this file except in compliance with
-* the License.  You may obtain a copy of the License at
-*
-*     http://www.apache.org/licenses/LICENSE-2.0
-*
-* Unless required by applicable law or agreed to in writing, software
-* distributed under the License is distributed on an "AS IS" BASIS,
-* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
-* See the License for the specific language governing permissions and
-* limitations under the License.
-*/
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 use anyhow::{Error, Result};
-use dubbo_base::Url;
-use std::sync::Arc;
+use bytes::BytesMut;
+use futures::StreamExt;
+use remoting_net::{incoming::Incoming, Address, MakeIncoming};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    sync::{mpsc, watch},
+    task::{JoinHandle, JoinSet},
+};
+use tokio_util::{codec::FramedRead, sync::CancellationToken, task::TaskTracker};
+use tracing::{debug, warn};
+
+use crate::{
+    codec::{BoxedCodec, ExchangeCodec},
+    exchange::{status, write_frames, Message, Request, Response},
+    Codec,
+};
 
 pub struct BoxedServer(Arc<dyn Server>);
 
+impl BoxedServer {
+    pub fn new(server: Arc<dyn Server>) -> Self {
+        BoxedServer(server)
+    }
+}
+
+#[async_trait::async_trait]
 pub trait Server: Sync + Send {
-    fn start(&self) -> Result<(), Error>;
-    fn stop(&self) -> Result<(), Error>;
+    async fn start(&self) -> Result<(), Error>;
+    async fn stop(&self) -> Result<(), Error>;
+}
+
+/// Answers the requests received by an `ExchangeServer`.
+#[async_trait::async_trait]
+pub trait Handler: Send + Sync + 'static {
+    /// The response to `request`, dropped for one-way requests. Its id is
+    /// set to the one of the request.
+    async fn reply(&self, request: Request) -> Response;
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ServerConfig {
+    /// Connections are closed once nothing was read for this long, clients
+    /// send heartbeats to keep them open.
+    pub idle_timeout: Duration,
+    /// How long `stop` waits for the requests being handled.
+    pub close_timeout: Duration,
+    /// Whether connections start with a PROXY protocol header, for servers
+    /// behind a load balancer sending one.
+    pub proxy_protocol: bool,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            idle_timeout: Duration::from_secs(180),
+            close_timeout: Duration::from_secs(10),
+            proxy_protocol: false,
+        }
+    }
 }
 
+impl ServerConfig {
+    pub fn with_idle_timeout(self, idle_timeout: Duration) -> Self {
+        Self {
+            idle_timeout,
+            ..self
+        }
+    }
+
+    pub fn with_close_timeout(self, close_timeout: Duration) -> Self {
+        Self {
+            close_timeout,
+            ..self
+        }
+    }
+
+    pub fn with_proxy_protocol(self, proxy_protocol: bool) -> Self {
+        Self {
+            proxy_protocol,
+            ..self
+        }
+    }
+}
+
+/// Serves the requests of exchange clients on `address`, each request in a
+/// task of its own.
 pub struct ExchangeServer {
-    server: BoxedServer,
-    url: Url,
+    address: Address,
+    codec: BoxedCodec,
+    handler: Arc<dyn Handler>,
+    config: ServerConfig,
+    local_addr: Mutex<Option<Address>>,
+    shutdown: watch::Sender<bool>,
+    task: tokio::sync::Mutex<Option<JoinHandle<()>>>,
+    connections: TaskTracker,
+    // aborts the connections still open after the close timeout
+    abort: CancellationToken,
 }
 
 impl ExchangeServer {
-    pub fn new(url: Url, server: BoxedServer) -> Self {
-        ExchangeServer { server, url }
+    pub fn new<H: Handler>(address: Address, codec: BoxedCodec, handler: H) -> Self {
+        ExchangeServer {
+            address,
+            codec,
+            handler: Arc::new(handler),
+            config: ServerConfig::default(),
+            local_addr: Mutex::new(None),
+            shutdown: watch::channel(false).0,
+            task: tokio::sync::Mutex::new(None),
+            connections: TaskTracker::new(),
+            abort: CancellationToken::new(),
+        }
+    }
+
+    pub fn with_config(self, config: ServerConfig) -> Self {
+        Self { config, ..self }
+    }
+
+    /// The address the server listens on once started, e.g. to find the
+    /// port picked for `127.0.0.1:0`.
+    pub fn local_addr(&self) -> Option<Address> {
+        self.local_addr.lock().unwrap().clone()
+    }
+
+    /// Serves a connection accepted elsewhere, e.g. by a listener shared
+    /// with other protocols. It is closed like the others on `stop`, and
+    /// dropped if the server was stopped already.
+    pub fn serve_io<IO>(&self, io: IO, peer: Option<Address>)
+    where
+        IO: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        if *self.shutdown.borrow() || self.connections.is_closed() {
+            debug!(
+                "[Exchange] refused connection of {:?}, server stopped",
+                peer
+            );
+            return;
+        }
+        self.connections.spawn(serve(
+            io,
+            peer,
+            self.codec.clone(),
+            self.handler.clone(),
+            self.config,
+            self.shutdown.subscribe(),
+            self.abort.clone(),
+        ));
     }
 }
 
+#[async_trait::async_trait]
 impl Server for ExchangeServer {
-    fn start(&self) -> Result<(), Error> {
-        self.server.0.start()
+    /// Binds the address and starts accepting connections.
+    async fn start(&self) -> Result<(), Error> {
+        let mut task = self.task.lock().await;
+        if task.is_some() {
+            return Ok(());
+        }
+        let incoming = self.address.clone().make_incoming().await?;
+        *self.local_addr.lock().unwrap() = incoming.local_addr();
+        debug!("[Exchange] server listening on {}", self.address);
+
+        let (connections, codec, handler, shutdown, abort) = (
+            self.connections.clone(),
+            self.codec.clone(),
+            self.handler.clone(),
+            self.shutdown.subscribe(),
+            self.abort.clone(),
+        );
+        *task = Some(match self.config.proxy_protocol {
+            true => tokio::spawn(accept(
+                incoming.with_proxy_protocol(),
+                connections,
+                codec,
+                handler,
+                self.config,
+                shutdown,
+                abort,
+            )),
+            false => tokio::spawn(accept(
+                incoming,
+                connections,
+                codec,
+                handler,
+                self.config,
+                shutdown,
+                abort,
+            )),
+        });
+        Ok(())
+    }
+
+    /// Stops accepting connections and closes the open ones once their
+    /// requests are answered, waiting at most `ServerConfig::close_timeout`
+    /// before aborting them.
+    async fn stop(&self) -> Result<(), Error> {
+        let _ = self.shutdown.send(true);
+        if let Some(task) = self.task.lock().await.take() {
+            task.await?;
+        }
+
+        self.connections.close();
+        if tokio::time::timeout(self.config.close_timeout, self.connections.wait())
+            .await
+            .is_err()
+        {
+            warn!(
+                "[Exchange] aborting {} connections with requests in flight",
+                self.connections.len()
+            );
+            self.abort.cancel();
+            self.connections.wait().await;
+        }
+
+        #[cfg(target_family = "unix")]
+        if let Address::Unix(path) = &self.address {
+            let _ = std::fs::remove_file(path);
+        }
+        Ok(())
+    }
+}
+
+async fn accept<I: Incoming>(
+    mut incoming: I,
+    connections: TaskTracker,
+    codec: BoxedCodec,
+    handler: Arc<dyn Handler>,
+    config: ServerConfig,
+    mut shutdown: watch::Receiver<bool>,
+    abort: CancellationToken,
+) {
+    loop {
+        tokio::select! {
+            conn = incoming.accept() => match conn {
+                Ok(Some(conn)) => {
+                    let peer = conn.info.peer_addr.clone();
+                    connections.spawn(serve(
+                        conn,
+                        peer,
+                        codec.clone(),
+                        handler.clone(),
+                        config,
+                        shutdown.clone(),
+                        abort.clone(),
+                    ));
+                }
+                Ok(None) => break,
+                Err(err) => {
+                    warn!("[Exchange] failed to accept a connection: {}", err);
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+            },
+            _ = stopped(&mut shutdown) => break,
+        }
     }
+}
+
+/// Reads the requests of one connection until it is closed, idle or the
+/// server stops, then answers the requests in flight and closes it, unless
+/// aborted first.
+async fn serve<IO>(
+    io: IO,
+    peer: Option<Address>,
+    codec: BoxedCodec,
+    handler: Arc<dyn Handler>,
+    config: ServerConfig,
+    shutdown: watch::Receiver<bool>,
+    abort: CancellationToken,
+) where
+    IO: AsyncRead + AsyncWrite + Send + 'static,
+{
+    tokio::select! {
+        _ = serve_requests(io, peer.clone(), codec, handler, config, shutdown) => {}
+        // dropping the requests aborts them
+        _ = abort.cancelled() => debug!("[Exchange] aborted connection of {:?}", peer),
+    }
+}
+
+async fn serve_requests<IO>(
+    io: IO,
+    peer: Option<Address>,
+    codec: BoxedCodec,
+    handler: Arc<dyn Handler>,
+    config: ServerConfig,
+    mut shutdown: watch::Receiver<bool>,
+) where
+    IO: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let (read, write) = tokio::io::split(io);
+    let mut frames = FramedRead::new(read, ExchangeCodec(codec.clone()));
+    let (tx, rx) = mpsc::channel(1024);
+    let writer = tokio::spawn(write_frames(write, rx));
+    let respond = Responder { codec, tx };
+
+    let mut requests = JoinSet::new();
+    loop {
+        tokio::select! {
+            frame = tokio::time::timeout(config.idle_timeout, frames.next()) => match frame {
+                Err(_) => {
+                    debug!("[Exchange] closing idle connection of {:?}", peer);
+                    break;
+                }
+                Ok(None) => break,
+                Ok(Some(Err(err))) => {
+                    warn!("[Exchange] failed to read a frame of {:?}: {}", peer, err);
+                    break;
+                }
+                Ok(Some(Ok(Message::Request(mut request)))) => {
+                    if request.event {
+                        if request.two_way {
+                            respond.send(Response::heartbeat(request.id)).await;
+                        }
+                    } else if let Some(error) = request.error {
+                        if request.two_way {
+                            respond
+                                .send(Response::error(request.id, status::BAD_REQUEST, error))
+                                .await;
+                        }
+                    } else {
+                        let handler = handler.clone();
+                        let respond = respond.clone();
+                        request.peer = peer.clone();
+                        requests.spawn(async move {
+                            let (id, two_way) = (request.id, request.two_way);
+                            let mut response = handler.reply(request).await;
+                            if two_way {
+                                response.id = id;
+                                respond.send(response).await;
+                            }
+                        });
+                    }
+                }
+                Ok(Some(Ok(Message::Response(response)))) => {
+                    debug!("[Exchange] ignored response {} of {:?}", response.id, peer);
+                }
+            },
+            Some(_) = requests.join_next(), if !requests.is_empty() => {}
+            _ = stopped(&mut shutdown) => break,
+        }
+    }
+
+    while requests.join_next().await.is_some() {}
+    drop(respond);
+    let _ = writer.await;
+}
+
+// Resolves once the server stops, also for receivers subscribed after.
+async fn stopped(shutdown: &mut watch::Receiver<bool>) {
+    let _ = shutdown.wait_for(|stopping| *stopping).await;
+}
+
+#[derive(Clone)]
+struct Responder {
+    codec: BoxedCodec,
+    tx: mpsc::Sender<bytes::Bytes>,
+}
 
-    fn stop(&self) -> Result<(), Error> {
-        self.server.0.stop()
+impl Responder {
+    /// Writes `response`, or a `BAD_RESPONSE` error if it cannot be encoded.
+    async fn send(&self, response: Response) {
+        let mut buf = BytesMut::new();
+        if let Err(err) = self.codec.encode_response(&response, &mut buf) {
+            warn!(
+                "[Exchange] failed to encode response {}: {}",
+                response.id, err
+            );
+            buf.clear();
+            let error = Response::error(response.id, status::BAD_RESPONSE, err.to_string());
+            if self.codec.encode_response(&error, &mut buf).is_err() {
+                return;
+            }
+        }
+        let _ = self.tx.send(buf.freeze()).await;
     }
 }
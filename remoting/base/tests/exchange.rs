@@ -0,0 +1,454 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Exchange clients and servers talking Dubbo over loopback TCP and Unix
+//! sockets.
+
+use std::{
+    borrow::Cow,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use futures::{SinkExt, StreamExt};
+use protocol_dubbo2::{hessian2::Value, Attachments, ResponseData, RpcInvocation};
+use remoting_base::{
+    codec::{dubbo2::DUBBO2, BoxedCodec, CodecRegistry},
+    error::ClientError,
+    exchange::{
+        client::Client, server::Server, status, ClientConfig, ExchangeClient, ExchangeServer,
+        Handler, ServerConfig,
+    },
+    Request, Response,
+};
+use remoting_net::{
+    dial::MakeTransport,
+    pool::{Pool, PoolConfig},
+    Address,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+use tokio_util::codec::Framed;
+
+const SERVICE: &str = "org.apache.dubbo.test.EchoService";
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Echoes the first argument, after sleeping for the milliseconds of the
+/// second one.
+struct Echo;
+
+#[async_trait::async_trait]
+impl Handler for Echo {
+    async fn reply(&self, request: Request) -> Response {
+        let invocation = request.body::<RpcInvocation>().unwrap();
+        if let Some(delay) = invocation.arguments.get(1).and_then(Value::as_i64) {
+            tokio::time::sleep(Duration::from_millis(delay as u64)).await;
+        }
+        let value = invocation.arguments[0].clone();
+        Response::new(
+            request.id,
+            ResponseData::Value {
+                value,
+                attachments: Attachments::new(),
+            },
+        )
+    }
+}
+
+fn codec() -> BoxedCodec {
+    CodecRegistry::new().codec(DUBBO2).unwrap()
+}
+
+fn echo(message: &str, delay_ms: i32) -> Request {
+    Request::new(
+        RpcInvocation::new(SERVICE, "echo")
+            .with_argument("java.lang.String", Value::from(message))
+            .with_argument("int", Value::from(delay_ms)),
+    )
+}
+
+fn echoed(response: &Response) -> &str {
+    match response.body::<ResponseData>() {
+        Some(ResponseData::Value { value, .. }) => value.as_str().unwrap(),
+        _ => panic!("not a value, status {}", response.status),
+    }
+}
+
+async fn start(address: Address, config: ServerConfig) -> (ExchangeServer, Address) {
+    let server = ExchangeServer::new(address, codec(), Echo).with_config(config);
+    server.start().await.unwrap();
+    let local_addr = server.local_addr().unwrap();
+    (server, local_addr)
+}
+
+fn loopback() -> Address {
+    Address::Ip("127.0.0.1:0".parse().unwrap())
+}
+
+/// Sends requests whose responses arrive in reverse order.
+async fn requests_in_parallel<M: MakeTransport>(client: Arc<ExchangeClient<M>>) {
+    let calls = (0..20).map(|i| {
+        let client = client.clone();
+        tokio::spawn(async move {
+            let message = format!("hello {}", i);
+            let response = client
+                .request(echo(&message, 200 - i * 10), TIMEOUT)
+                .await
+                .unwrap();
+            assert!(response.is_ok());
+            assert_eq!(echoed(&response), message);
+        })
+    });
+    for call in calls.collect::<Vec<_>>() {
+        call.await.unwrap();
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_tcp() {
+    let (server, addr) = start(loopback(), ServerConfig::default()).await;
+    let client = Arc::new(ExchangeClient::new(addr, codec(), ClientConfig::default()));
+
+    requests_in_parallel(client.clone()).await;
+    assert!(client.is_available());
+    client.send(echo("one way", 0)).await.unwrap();
+
+    // bodies the codec does not know fail before they are sent
+    let res = client
+        .request(Request::new("not an invocation"), TIMEOUT)
+        .await;
+    assert!(matches!(res, Err(ClientError::Codec(_))));
+
+    client.close().await.unwrap();
+    server.stop().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_pooled() {
+    let (server, addr) = start(loopback(), ServerConfig::default()).await;
+    let pool = Pool::with_config(
+        PoolConfig::default()
+            .with_max_per_key(1)
+            .with_checkout_timeout(Duration::from_millis(200)),
+    );
+    let client = |pool: &Pool<_>| {
+        let client = ExchangeClient::with_transport(
+            addr.clone(),
+            codec(),
+            ClientConfig::default(),
+            pool.clone(),
+        );
+        Arc::new(client)
+    };
+
+    let first = client(&pool);
+    requests_in_parallel(first.clone()).await;
+    // the connection of the pool is taken
+    let second = client(&pool);
+    assert!(matches!(
+        second.connect().await,
+        Err(ClientError::Connect(_))
+    ));
+
+    first.close().await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    // closed rather than handed to another client
+    let metrics = pool.metrics(&addr).unwrap();
+    assert_eq!(metrics.connections, 0);
+    assert_eq!(metrics.connections_closed_broken, 1);
+    let second = client(&pool);
+    requests_in_parallel(second.clone()).await;
+    assert_eq!(pool.metrics(&addr).unwrap().connections_created, 2);
+
+    second.close().await.unwrap();
+    server.stop().await.unwrap();
+}
+
+#[cfg(target_family = "unix")]
+#[tokio::test(flavor = "multi_thread")]
+async fn test_unix() {
+    let path = std::env::temp_dir().join(format!("remoting-exchange-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let (server, addr) = start(
+        Address::Unix(Cow::Owned(path.clone())),
+        ServerConfig::default(),
+    )
+    .await;
+    assert_eq!(addr, Address::Unix(Cow::Owned(path.clone())));
+
+    let client = Arc::new(ExchangeClient::new(addr, codec(), ClientConfig::default()));
+    requests_in_parallel(client.clone()).await;
+
+    client.close().await.unwrap();
+    server.stop().await.unwrap();
+    assert!(!path.exists());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_timeout() {
+    let (server, addr) = start(loopback(), ServerConfig::default()).await;
+    let client = ExchangeClient::new(addr, codec(), ClientConfig::default());
+
+    let res = client
+        .request(echo("slow", 500), Duration::from_millis(50))
+        .await;
+    assert!(matches!(res, Err(ClientError::Timeout(_, _))));
+
+    // the late response is dropped and the connection stays usable
+    let response = client.request(echo("fast", 0), TIMEOUT).await.unwrap();
+    assert_eq!(echoed(&response), "fast");
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    let response = client.request(echo("again", 0), TIMEOUT).await.unwrap();
+    assert_eq!(echoed(&response), "again");
+
+    server.stop().await.unwrap();
+}
+
+/// A Dubbo server that only counts connections and heartbeats, and answers
+/// the heartbeats if `answer` is set.
+async fn heartbeat_server(answer: bool) -> (SocketAddr, Arc<AtomicUsize>, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (accepts, heartbeats) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+
+    let counters = (accepts.clone(), heartbeats.clone());
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            counters.0.fetch_add(1, Ordering::SeqCst);
+            let heartbeats = counters.1.clone();
+            tokio::spawn(async move {
+                let mut frames = Framed::new(stream, protocol_dubbo2::Dubbo2Codec::new());
+                while let Some(Ok(protocol_dubbo2::Message::Request(request))) = frames.next().await
+                {
+                    assert!(request.is_heartbeat());
+                    heartbeats.fetch_add(1, Ordering::SeqCst);
+                    if answer {
+                        let response = protocol_dubbo2::Response::heartbeat(request.id);
+                        let message = protocol_dubbo2::Message::Response(response);
+                        frames.send(message).await.unwrap();
+                    }
+                }
+            });
+        }
+    });
+    (addr, accepts, heartbeats)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_heartbeat() {
+    let config = ClientConfig::default()
+        .with_heartbeat(Duration::from_millis(50))
+        .with_idle_timeout(Duration::from_millis(200));
+
+    // answered heartbeats keep the connection open
+    let (addr, accepts, heartbeats) = heartbeat_server(true).await;
+    let client = ExchangeClient::new(Address::Ip(addr), codec(), config);
+    client.connect().await.unwrap();
+    tokio::time::sleep(Duration::from_millis(600)).await;
+    assert_eq!(accepts.load(Ordering::SeqCst), 1);
+    assert!(heartbeats.load(Ordering::SeqCst) >= 3);
+    assert!(client.is_available());
+    client.close().await.unwrap();
+    assert!(!client.is_available());
+
+    // a silent server is given up on and connected to again
+    let (addr, accepts, _) = heartbeat_server(false).await;
+    let client = ExchangeClient::new(Address::Ip(addr), codec(), config);
+    client.connect().await.unwrap();
+    tokio::time::sleep(Duration::from_millis(600)).await;
+    assert!(accepts.load(Ordering::SeqCst) >= 2);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_server_events() {
+    let config = ServerConfig::default().with_idle_timeout(Duration::from_millis(200));
+    let (server, addr) = start(loopback(), config).await;
+    let Address::Ip(addr) = addr else {
+        panic!("not a tcp address");
+    };
+
+    // heartbeats are answered
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut frames = Framed::new(stream, protocol_dubbo2::Dubbo2Codec::new());
+    let heartbeat = protocol_dubbo2::Request::heartbeat(3);
+    frames
+        .send(protocol_dubbo2::Message::Request(heartbeat))
+        .await
+        .unwrap();
+    let Some(Ok(protocol_dubbo2::Message::Response(response))) = frames.next().await else {
+        panic!("no heartbeat response");
+    };
+    assert_eq!(response, protocol_dubbo2::Response::heartbeat(3));
+
+    // broken requests are answered with BAD_REQUEST
+    let mut frame = bytes::BytesMut::new();
+    let invocation = RpcInvocation::new(SERVICE, "echo");
+    let request = protocol_dubbo2::Message::Request(protocol_dubbo2::Request::new(4, invocation));
+    tokio_util::codec::Encoder::encode(
+        &mut protocol_dubbo2::Dubbo2Codec::new(),
+        request,
+        &mut frame,
+    )
+    .unwrap();
+    frame[16] = 0xff;
+    frames.get_mut().write_all(&frame).await.unwrap();
+    let Some(Ok(protocol_dubbo2::Message::Response(response))) = frames.next().await else {
+        panic!("no error response");
+    };
+    assert_eq!(response.id, 4);
+    assert_eq!(response.status as u8, status::BAD_REQUEST);
+
+    // idle connections are closed
+    let mut stream = frames.into_inner();
+    let mut buf = Vec::new();
+    let read = tokio::time::timeout(TIMEOUT, stream.read_to_end(&mut buf)).await;
+    assert!(matches!(read, Ok(Ok(0))));
+
+    server.stop().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_close() {
+    let (server, addr) = start(loopback(), ServerConfig::default()).await;
+
+    // the client answers pending requests before it closes
+    let client = Arc::new(ExchangeClient::new(
+        addr.clone(),
+        codec(),
+        ClientConfig::default(),
+    ));
+    let pending = {
+        let client = client.clone();
+        tokio::spawn(async move { client.request(echo("pending", 200), TIMEOUT).await })
+    };
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    client.close().await.unwrap();
+    assert_eq!(echoed(&pending.await.unwrap().unwrap()), "pending");
+    let res = client.request(echo("closed", 0), TIMEOUT).await;
+    assert!(matches!(res, Err(ClientError::Closed)));
+
+    // so does the server
+    let client = Arc::new(ExchangeClient::new(
+        addr.clone(),
+        codec(),
+        ClientConfig::default(),
+    ));
+    let pending = {
+        let client = client.clone();
+        tokio::spawn(async move { client.request(echo("in flight", 200), TIMEOUT).await })
+    };
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    server.stop().await.unwrap();
+    assert_eq!(echoed(&pending.await.unwrap().unwrap()), "in flight");
+
+    let client = ExchangeClient::new(addr, codec(), ClientConfig::default());
+    assert!(matches!(
+        client.connect().await,
+        Err(ClientError::Connect(_))
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_abort() {
+    let config = ServerConfig::default().with_close_timeout(Duration::from_millis(100));
+    let (server, addr) = start(loopback(), config).await;
+
+    // requests outlasting the close timeout are aborted with their
+    // connection
+    let client = Arc::new(ExchangeClient::new(addr, codec(), ClientConfig::default()));
+    let pending = {
+        let client = client.clone();
+        tokio::spawn(async move { client.request(echo("slow", 60_000), TIMEOUT).await })
+    };
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    tokio::time::timeout(TIMEOUT, server.stop())
+        .await
+        .unwrap()
+        .unwrap();
+    // well before the request times out
+    let res = tokio::time::timeout(Duration::from_secs(1), pending)
+        .await
+        .unwrap();
+    assert!(res.unwrap().is_err());
+
+    // connections handed over after the stop are refused
+    let (mut stream, io) = tokio::io::duplex(1024);
+    server.serve_io(io, None);
+    let mut buf = Vec::new();
+    let read = tokio::time::timeout(TIMEOUT, stream.read_to_end(&mut buf)).await;
+    assert!(matches!(read, Ok(Ok(0))));
+}
+
+/// Answers with the address of the client.
+struct Peer;
+
+#[async_trait::async_trait]
+impl Handler for Peer {
+    async fn reply(&self, request: Request) -> Response {
+        let peer = request
+            .peer
+            .map(|peer| peer.to_string())
+            .unwrap_or_default();
+        Response::new(
+            request.id,
+            ResponseData::Value {
+                value: Value::from(peer.as_str()),
+                attachments: Attachments::new(),
+            },
+        )
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_proxy_protocol() {
+    let config = ServerConfig::default().with_proxy_protocol(true);
+    let server = ExchangeServer::new(loopback(), codec(), Peer).with_config(config);
+    server.start().await.unwrap();
+    let Some(Address::Ip(addr)) = server.local_addr() else {
+        panic!("not a tcp address");
+    };
+
+    let client = "192.168.0.1:56324".parse().unwrap();
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let header = remoting_net::proxy_protocol::ProxyHeader::new(client, addr);
+    stream.write_all(&header.to_v1()).await.unwrap();
+
+    let mut frames = Framed::new(stream, protocol_dubbo2::Dubbo2Codec::new());
+    let invocation = RpcInvocation::new(SERVICE, "echo");
+    let request = protocol_dubbo2::Request::new(5, invocation);
+    frames
+        .send(protocol_dubbo2::Message::Request(request))
+        .await
+        .unwrap();
+    let Some(Ok(protocol_dubbo2::Message::Response(response))) = frames.next().await else {
+        panic!("no response");
+    };
+    let ResponseData::Value { value, .. } = response.data else {
+        panic!("not a value");
+    };
+    assert_eq!(value.as_str(), Some("192.168.0.1:56324"));
+
+    server.stop().await.unwrap();
+}
@@ -317,18 +317,28 @@ impl AsyncWrite for Conn {
 #[cfg(test)]
 mod tests {
     use crate::{dial::DefaultMakeTransport, Address};
-    use tokio::io::AsyncWriteExt;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
 
-    // listen by command: nc -l 8858 -v
     #[tokio::test(flavor = "current_thread")]
     async fn test_write_bytes() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = Vec::new();
+            stream.read_to_end(&mut buf).await.unwrap();
+            buf
+        });
+
         let transport = DefaultMakeTransport::new();
-        let mut conn = transport
-            .make_connection(Address::Ip("127.0.0.1:8858".parse().unwrap()))
-            .await
-            .unwrap();
+        let mut conn = transport.make_connection(Address::Ip(addr)).await.unwrap();
         conn.write_all("\n\rhello dubbo-rust\n\r".to_string().as_bytes())
             .await
             .unwrap();
+        conn.shutdown().await.unwrap();
+        assert_eq!(server.await.unwrap(), b"\n\rhello dubbo-rust\n\r");
     }
 }
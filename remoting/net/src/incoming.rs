@@ -19,7 +19,6 @@ use std::{
     task::{Context, Poll},
 };
 
-use logger::tracing;
 use futures::Stream;
 use pin_project::pin_project;
 use tokio::net::TcpListener;
@@ -29,7 +28,7 @@ use tokio::net::UnixListener;
 use tokio_stream::wrappers::UnixListenerStream;
 use tokio_stream::{wrappers::TcpListenerStream, StreamExt};
 
-use super::{conn::Conn, Address};
+use super::{conn::Conn, proxy_protocol::ProxyHeaderReader, Address};
 
 #[pin_project(project = IncomingProj)]
 #[derive(Debug)]
@@ -61,6 +60,28 @@ impl From<TcpListener> for DefaultIncoming {
     }
 }
 
+impl DefaultIncoming {
+    /// The address the listener is bound to, e.g. to find the port picked
+    /// for `127.0.0.1:0`.
+    pub fn local_addr(&self) -> Option<Address> {
+        match self {
+            DefaultIncoming::Tcp(s) => s.as_ref().local_addr().map(Address::from).ok(),
+            #[cfg(target_family = "unix")]
+            DefaultIncoming::Unix(s) => s
+                .as_ref()
+                .local_addr()
+                .ok()
+                .and_then(|addr| Address::try_from(addr).ok()),
+        }
+    }
+
+    /// Reads the PROXY protocol header of every connection, for listeners
+    /// behind a load balancer sending one.
+    pub fn with_proxy_protocol(self) -> ProxyProtocolIncoming {
+        ProxyProtocolIncoming::new(self)
+    }
+}
+
 #[async_trait::async_trait]
 pub trait Incoming: fmt::Debug + Send + 'static {
     async fn accept(&mut self) -> io::Result<Option<Conn>>;
@@ -78,6 +99,43 @@ impl Incoming for DefaultIncoming {
     }
 }
 
+/// Accepts the connections of a listener behind a load balancer speaking
+/// the PROXY protocol. Their peer address is the one of the client the header
+/// tells, connections without a valid header are closed.
+#[derive(Debug)]
+pub struct ProxyProtocolIncoming {
+    reader: ProxyHeaderReader<Conn, Option<Address>>,
+}
+
+impl ProxyProtocolIncoming {
+    pub fn new<I: Incoming>(inner: I) -> Self {
+        let accepted = futures::stream::unfold(inner, |mut inner| async move {
+            let conn = inner.accept().await.transpose()?;
+            let conn = conn.map(|conn| {
+                let proxy = conn.info.peer_addr.clone();
+                (conn, proxy)
+            });
+            Some((conn, inner))
+        });
+        ProxyProtocolIncoming {
+            reader: ProxyHeaderReader::new(accepted),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Incoming for ProxyProtocolIncoming {
+    async fn accept(&mut self) -> io::Result<Option<Conn>> {
+        let Some((mut conn, _, header)) = self.reader.accept().await.transpose()? else {
+            return Ok(None);
+        };
+        if let Some(header) = header {
+            conn.info.peer_addr = Some(Address::Ip(header.source));
+        }
+        Ok(Some(conn))
+    }
+}
+
 #[async_trait::async_trait]
 pub trait MakeIncoming {
     type Incoming: Incoming;
@@ -112,41 +170,69 @@ impl Stream for DefaultIncoming {
 
 #[cfg(test)]
 mod tests {
-    use logger::tracing::debug;
-    use tokio::{io::AsyncReadExt, net::TcpListener};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    };
     use tokio_stream::wrappers::TcpListenerStream;
+    use tracing::debug;
 
-    use crate::{incoming::Incoming, DefaultIncoming, MakeIncoming};
+    use crate::{
+        incoming::Incoming, proxy_protocol::ProxyHeader, Address, DefaultIncoming, MakeIncoming,
+    };
 
     #[tokio::test]
     async fn test_read_bytes() {
-        let listener = TcpListener::bind("127.0.0.1:8858").await.unwrap();
-        let incoming = DefaultIncoming::Tcp(TcpListenerStream::new(listener))
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut incoming = DefaultIncoming::Tcp(TcpListenerStream::new(listener))
             .make_incoming()
             .await
             .unwrap();
         debug!("[Dubbo-Rust] server start at: {:?}", incoming);
-        let join_handle = tokio::spawn(async move {
-            let mut incoming = incoming;
-            match incoming.accept().await.unwrap() {
-                Some(mut conn) => {
-                    debug!(
-                        "[Dubbo-Rust] recv a connection from: {:?}",
-                        conn.info.peer_addr
-                    );
-                    let mut buf = vec![0; 1024];
-                    let n = conn.read(&mut buf).await.unwrap();
-                    debug!(
-                        "[Dubbo-Rust] recv a connection from: {:?}",
-                        String::from_utf8(buf[..n].to_vec()).unwrap()
-                    );
-                }
-                None => {
-                    debug!("[Dubbo-Rust] recv a connection from: None");
-                }
-            }
-        });
-        tokio::time::sleep(std::time::Duration::from_secs(10)).await;
-        drop(join_handle);
+        let Some(Address::Ip(addr)) = incoming.local_addr() else {
+            panic!("no local address");
+        };
+
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        client.write_all(b"hello dubbo-rust").await.unwrap();
+        drop(client);
+
+        let mut conn = incoming.accept().await.unwrap().unwrap();
+        debug!(
+            "[Dubbo-Rust] recv a connection from: {:?}",
+            conn.info.peer_addr
+        );
+        let mut buf = String::new();
+        conn.read_to_string(&mut buf).await.unwrap();
+        assert_eq!(buf, "hello dubbo-rust");
+    }
+
+    #[tokio::test]
+    async fn test_proxy_protocol() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut incoming = DefaultIncoming::from(listener).with_proxy_protocol();
+
+        // a connection without a header is closed, the next one goes on
+        let mut bad = TcpStream::connect(addr).await.unwrap();
+        bad.write_all(b"hello dubbo-rust").await.unwrap();
+
+        let client = "192.168.0.1:56324".parse().unwrap();
+        let mut good = TcpStream::connect(addr).await.unwrap();
+        good.write_all(&ProxyHeader::new(client, addr).to_v2())
+            .await
+            .unwrap();
+        good.write_all(b"hello dubbo-rust").await.unwrap();
+        drop(good);
+
+        let mut conn = incoming.accept().await.unwrap().unwrap();
+        assert_eq!(conn.info.peer_addr, Some(Address::Ip(client)));
+        let mut buf = String::new();
+        conn.read_to_string(&mut buf).await.unwrap();
+        assert_eq!(buf, "hello dubbo-rust");
+
+        // closed with unread bytes, so either reset or at its end
+        let read = bad.read(&mut [0; 16]).await;
+        assert!(!matches!(read, Ok(len) if len > 0));
     }
 }
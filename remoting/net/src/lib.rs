@@ -18,8 +18,9 @@
 pub mod conn;
 pub mod dial;
 pub mod incoming;
-mod pool;
-mod probe;
+pub mod pool;
+pub mod probe;
+pub mod proxy_protocol;
 
 use std::{borrow::Cow, fmt, net::Ipv6Addr, path::Path};
 
@@ -84,12 +85,7 @@ impl TryFrom<tokio::net::unix::SocketAddr> for Address {
         Ok(Address::Unix(Cow::Owned(
             value
                 .as_pathname()
-                .ok_or_else(|| {
-                    std::io::Error::new(
-                        std::io::ErrorKind::Other,
-                        "unix socket doesn't have an address",
-                    )
-                })?
+                .ok_or_else(|| std::io::Error::other("unix socket doesn't have an address"))?
                 .to_owned(),
         )))
     }
@@ -14,3 +14,612 @@
  * See the License for the specific language governing permissions and
  * limitations under the License.
  */
+
+//! A connection pool keyed by [`Address`].
+//!
+//! Every address owns a [`bb8`] pool that is created on first use, so the
+//! limits configured by [`PoolConfig`] apply per provider.
+//!
+//! A [`Pool`] of [`DefaultMakeTransport`] is a [`MakeTransport`] itself, for
+//! the exchange clients of a process to share the limits of the pool.
+//! Triple clients pool HTTP/2 connections with a [`Connect`] of their own,
+//! when more than one connection per provider is configured.
+
+use std::{
+    collections::HashMap,
+    fmt, io,
+    mem::MaybeUninit,
+    ops::{Deref, DerefMut},
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use bb8::{ErrorSink, ManageConnection, RunError};
+use dashmap::DashMap;
+use socket2::SockRef;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf, ReadHalf, WriteHalf};
+
+use super::{
+    conn::{Conn, ConnStream},
+    dial::{DefaultMakeTransport, MakeTransport},
+    Address,
+};
+
+/// [`Connect`] creates and checks the connections held by a [`Pool`].
+#[async_trait::async_trait]
+pub trait Connect: Send + Sync + 'static {
+    type Connection: Send + 'static;
+
+    async fn connect(&self, addr: &Address) -> io::Result<Self::Connection>;
+
+    /// Checks an idle connection before it is handed out, only called when
+    /// [`PoolConfig::test_on_check_out`] is set.
+    async fn is_valid(&self, _conn: &mut Self::Connection) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Whether a connection given back to the pool should be dropped.
+    fn has_broken(&self, _conn: &mut Self::Connection) -> bool {
+        false
+    }
+}
+
+#[async_trait::async_trait]
+impl Connect for DefaultMakeTransport {
+    type Connection = Conn;
+
+    async fn connect(&self, addr: &Address) -> io::Result<Conn> {
+        self.make_connection(addr.clone()).await
+    }
+
+    async fn is_valid(&self, conn: &mut Conn) -> io::Result<()> {
+        // a peer that has closed the connection leaves it readable with eof
+        let mut buf = [MaybeUninit::uninit(); 1];
+        let peeked = match &conn.stream {
+            ConnStream::Tcp(stream) => SockRef::from(stream).peek(&mut buf),
+            #[cfg(target_family = "unix")]
+            ConnStream::Unix(stream) => SockRef::from(stream).peek(&mut buf),
+        };
+        match peeked {
+            Ok(0) => Err(io::ErrorKind::UnexpectedEof.into()),
+            Err(err) if err.kind() != io::ErrorKind::WouldBlock => Err(err),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PoolConfig {
+    /// The maximum number of connections to one address.
+    pub max_per_key: u32,
+    /// The number of idle connections kept open per address.
+    pub min_idle: Option<u32>,
+    /// Idle connections are closed after this long.
+    pub idle_timeout: Option<Duration>,
+    /// Connections are closed once they are this old, even when in use they
+    /// are dropped as soon as they are given back.
+    pub max_lifetime: Option<Duration>,
+    /// How long a checkout may wait, including the time to connect.
+    pub checkout_timeout: Duration,
+    pub test_on_check_out: bool,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            max_per_key: 8,
+            min_idle: None,
+            idle_timeout: Some(Duration::from_secs(180)),
+            max_lifetime: Some(Duration::from_secs(30 * 60)),
+            checkout_timeout: Duration::from_secs(3),
+            test_on_check_out: true,
+        }
+    }
+}
+
+impl PoolConfig {
+    pub fn with_max_per_key(self, max_per_key: u32) -> Self {
+        Self {
+            max_per_key,
+            ..self
+        }
+    }
+
+    pub fn with_min_idle(self, min_idle: Option<u32>) -> Self {
+        Self { min_idle, ..self }
+    }
+
+    pub fn with_idle_timeout(self, idle_timeout: Option<Duration>) -> Self {
+        Self {
+            idle_timeout,
+            ..self
+        }
+    }
+
+    pub fn with_max_lifetime(self, max_lifetime: Option<Duration>) -> Self {
+        Self {
+            max_lifetime,
+            ..self
+        }
+    }
+
+    pub fn with_checkout_timeout(self, checkout_timeout: Duration) -> Self {
+        Self {
+            checkout_timeout,
+            ..self
+        }
+    }
+
+    pub fn with_test_on_check_out(self, test_on_check_out: bool) -> Self {
+        Self {
+            test_on_check_out,
+            ..self
+        }
+    }
+
+    /// Expired connections are looked for twice per the shortest expiry.
+    fn reaper_rate(&self) -> Duration {
+        [self.idle_timeout, self.max_lifetime]
+            .into_iter()
+            .flatten()
+            .map(|expiry| expiry / 2)
+            .fold(Duration::from_secs(30), Duration::min)
+            .max(Duration::from_millis(10))
+    }
+}
+
+/// A snapshot of the pool of one address.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoolMetrics {
+    pub connections: u32,
+    pub idle_connections: u32,
+    /// Checkouts served without waiting.
+    pub get_direct: u64,
+    /// Checkouts that had to wait for a connection.
+    pub get_waited: u64,
+    pub get_timed_out: u64,
+    pub connections_created: u64,
+    pub connections_closed_broken: u64,
+    /// Connections that failed the health check on checkout.
+    pub connections_closed_invalid: u64,
+    pub connections_closed_max_lifetime: u64,
+    pub connections_closed_idle_timeout: u64,
+}
+
+impl From<bb8::State> for PoolMetrics {
+    fn from(state: bb8::State) -> Self {
+        let stats = state.statistics;
+        Self {
+            connections: state.connections,
+            idle_connections: state.idle_connections,
+            get_direct: stats.get_direct,
+            get_waited: stats.get_waited,
+            get_timed_out: stats.get_timed_out,
+            connections_created: stats.connections_created,
+            connections_closed_broken: stats.connections_closed_broken,
+            connections_closed_invalid: stats.connections_closed_invalid,
+            connections_closed_max_lifetime: stats.connections_closed_max_lifetime,
+            connections_closed_idle_timeout: stats.connections_closed_idle_timeout,
+        }
+    }
+}
+
+pub struct Pool<C: Connect> {
+    connector: Arc<C>,
+    config: PoolConfig,
+    pools: Arc<DashMap<Address, KeyedPool<C>>>,
+}
+
+impl<C: Connect> Clone for Pool<C> {
+    fn clone(&self) -> Self {
+        Self {
+            connector: self.connector.clone(),
+            config: self.config,
+            pools: self.pools.clone(),
+        }
+    }
+}
+
+impl<C: Connect> fmt::Debug for Pool<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pool")
+            .field("config", &self.config)
+            .field("keys", &self.pools.len())
+            .finish()
+    }
+}
+
+impl Pool<DefaultMakeTransport> {
+    pub fn with_config(config: PoolConfig) -> Self {
+        Self::new(DefaultMakeTransport::new(), config)
+    }
+}
+
+impl<C: Connect> Pool<C> {
+    pub fn new(connector: C, config: PoolConfig) -> Self {
+        Self {
+            connector: Arc::new(connector),
+            config,
+            pools: Default::default(),
+        }
+    }
+
+    pub fn config(&self) -> &PoolConfig {
+        &self.config
+    }
+
+    /// Checks out a connection to `addr`, connecting when no idle one is
+    /// left and the address is below [`PoolConfig::max_per_key`].
+    ///
+    /// Must be called within a tokio runtime.
+    pub async fn get(&self, addr: &Address) -> io::Result<PooledConnection<C>> {
+        let keyed = self.keyed(addr);
+        match keyed.pool.get_owned().await {
+            Ok(conn) => Ok(PooledConnection { inner: conn }),
+            Err(RunError::User(err)) => Err(err),
+            // the pool connects in the background, so report why it failed
+            Err(RunError::TimedOut) => Err(keyed.last_error.take().unwrap_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("checkout from the pool of {addr} timed out"),
+                )
+            })),
+        }
+    }
+
+    pub fn metrics(&self, addr: &Address) -> Option<PoolMetrics> {
+        self.pools.get(addr).map(|keyed| keyed.pool.state().into())
+    }
+
+    pub fn all_metrics(&self) -> HashMap<Address, PoolMetrics> {
+        self.pools
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.pool.state().into()))
+            .collect()
+    }
+
+    /// Drops the pool of `addr`, e.g. when the provider goes offline.
+    /// Connections still checked out are closed when they are given back.
+    pub fn remove(&self, addr: &Address) -> bool {
+        self.pools.remove(addr).is_some()
+    }
+
+    fn keyed(&self, addr: &Address) -> KeyedPool<C> {
+        if let Some(keyed) = self.pools.get(addr) {
+            return keyed.clone();
+        }
+
+        self.pools
+            .entry(addr.clone())
+            .or_insert_with(|| {
+                let last_error = LastError::default();
+                let manager = KeyedManager {
+                    connector: self.connector.clone(),
+                    addr: addr.clone(),
+                };
+                let pool = bb8::Pool::builder()
+                    .max_size(self.config.max_per_key)
+                    .min_idle(self.config.min_idle)
+                    .idle_timeout(self.config.idle_timeout)
+                    .max_lifetime(self.config.max_lifetime)
+                    .connection_timeout(self.config.checkout_timeout)
+                    .test_on_check_out(self.config.test_on_check_out)
+                    .retry_connection(false)
+                    .reaper_rate(self.config.reaper_rate())
+                    .error_sink(Box::new(last_error.clone()))
+                    .build_unchecked(manager);
+                KeyedPool { pool, last_error }
+            })
+            .clone()
+    }
+}
+
+/// A connection checked out of a [`Pool`], given back when dropped.
+pub struct PooledConnection<C: Connect> {
+    inner: bb8::PooledConnection<'static, KeyedManager<C>>,
+}
+
+impl<C: Connect> PooledConnection<C> {
+    /// Closes the connection once given back rather than keeping it, e.g.
+    /// when its state is not known to be clean.
+    pub fn discard(&mut self) {
+        self.inner.discarded = true;
+    }
+}
+
+impl<C: Connect> Deref for PooledConnection<C> {
+    type Target = C::Connection;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner.conn
+    }
+}
+
+impl<C: Connect> DerefMut for PooledConnection<C> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner.conn
+    }
+}
+
+/// A pooled connection of the exchange client, used by it alone: it is
+/// discarded once the client is done with it, for no late response to reach
+/// another client.
+pub struct PooledStream(PooledConnection<DefaultMakeTransport>);
+
+impl AsyncRead for PooledStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0.stream).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for PooledStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0.stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0.stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0.stream).poll_shutdown(cx)
+    }
+}
+
+/// Checks out the connections of the transports, so that
+/// [`PoolConfig::max_per_key`] caps the connections to a provider and
+/// `min_idle` connections are ready ahead of the clients.
+#[async_trait::async_trait]
+impl MakeTransport for Pool<DefaultMakeTransport> {
+    type ReadHalf = ReadHalf<PooledStream>;
+    type WriteHalf = WriteHalf<PooledStream>;
+
+    async fn make_transport(&self, addr: Address) -> io::Result<(Self::ReadHalf, Self::WriteHalf)> {
+        let mut conn = self.get(&addr).await?;
+        conn.discard();
+        Ok(tokio::io::split(PooledStream(conn)))
+    }
+
+    /// Ignored, the checkout timeout of the pool applies.
+    fn set_connect_timeout(&mut self, _timeout: Option<Duration>) {}
+
+    /// Ignored, the connections are made by the connector of the pool.
+    fn set_read_timeout(&mut self, _timeout: Option<Duration>) {}
+
+    /// Ignored, the connections are made by the connector of the pool.
+    fn set_write_timeout(&mut self, _timeout: Option<Duration>) {}
+}
+
+struct KeyedPool<C: Connect> {
+    pool: bb8::Pool<KeyedManager<C>>,
+    last_error: LastError,
+}
+
+impl<C: Connect> Clone for KeyedPool<C> {
+    fn clone(&self) -> Self {
+        Self {
+            pool: self.pool.clone(),
+            last_error: self.last_error.clone(),
+        }
+    }
+}
+
+struct KeyedManager<C> {
+    connector: Arc<C>,
+    addr: Address,
+}
+
+/// A connection of a pool, see [`PooledConnection::discard`].
+struct Slot<T> {
+    conn: T,
+    discarded: bool,
+}
+
+#[async_trait::async_trait]
+impl<C: Connect> ManageConnection for KeyedManager<C> {
+    type Connection = Slot<C::Connection>;
+    type Error = io::Error;
+
+    async fn connect(&self) -> io::Result<Self::Connection> {
+        let conn = self.connector.connect(&self.addr).await?;
+        Ok(Slot {
+            conn,
+            discarded: false,
+        })
+    }
+
+    async fn is_valid(&self, slot: &mut Self::Connection) -> io::Result<()> {
+        self.connector.is_valid(&mut slot.conn).await
+    }
+
+    fn has_broken(&self, slot: &mut Self::Connection) -> bool {
+        slot.discarded || self.connector.has_broken(&mut slot.conn)
+    }
+}
+
+/// Keeps the latest error of the connections made in the background.
+#[derive(Debug, Clone, Default)]
+struct LastError(Arc<Mutex<Option<io::Error>>>);
+
+impl LastError {
+    fn take(&self) -> Option<io::Error> {
+        self.0.lock().unwrap().take()
+    }
+}
+
+impl ErrorSink<io::Error> for LastError {
+    fn sink(&self, error: io::Error) {
+        tracing::debug!("pooled connection failed: {}", error);
+        *self.0.lock().unwrap() = Some(error);
+    }
+
+    fn boxed_clone(&self) -> Box<dyn ErrorSink<io::Error>> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        net::{TcpListener, TcpStream},
+        time::{sleep, Instant},
+    };
+
+    use super::*;
+
+    type Accepted = Arc<Mutex<Vec<TcpStream>>>;
+
+    /// Accepts connections and keeps them open until they are cleared.
+    async fn serve() -> (Address, Accepted) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = Address::Ip(listener.local_addr().unwrap());
+        let accepted = Accepted::default();
+        let conns = accepted.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                conns.lock().unwrap().push(stream);
+            }
+        });
+        (addr, accepted)
+    }
+
+    #[tokio::test]
+    async fn test_max_per_key() {
+        let (addr, _accepted) = serve().await;
+        let pool = Pool::with_config(
+            PoolConfig::default()
+                .with_max_per_key(2)
+                .with_checkout_timeout(Duration::from_millis(200)),
+        );
+
+        let first = pool.get(&addr).await.unwrap();
+        let second = pool.get(&addr).await.unwrap();
+        let err = pool.get(&addr).await.err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+        drop(first);
+        let _third = pool.get(&addr).await.unwrap();
+        drop(second);
+
+        let metrics = pool.metrics(&addr).unwrap();
+        assert_eq!(metrics.connections, 2);
+        assert_eq!(metrics.idle_connections, 1);
+        assert_eq!(metrics.connections_created, 2);
+        assert_eq!(metrics.get_timed_out, 1);
+        assert_eq!(pool.all_metrics().len(), 1);
+        assert!(pool.metrics(&serve().await.0).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_idle_timeout() {
+        let (addr, _accepted) = serve().await;
+        let pool = Pool::with_config(
+            PoolConfig::default().with_idle_timeout(Some(Duration::from_millis(100))),
+        );
+
+        drop(pool.get(&addr).await.unwrap());
+        assert_eq!(pool.metrics(&addr).unwrap().idle_connections, 1);
+
+        sleep(Duration::from_millis(400)).await;
+        let metrics = pool.metrics(&addr).unwrap();
+        assert_eq!(metrics.connections, 0);
+        assert_eq!(metrics.connections_closed_idle_timeout, 1);
+    }
+
+    #[tokio::test]
+    async fn test_max_lifetime() {
+        let (addr, _accepted) = serve().await;
+        let pool = Pool::with_config(
+            PoolConfig::default().with_max_lifetime(Some(Duration::from_millis(100))),
+        );
+
+        let conn = pool.get(&addr).await.unwrap();
+        sleep(Duration::from_millis(200)).await;
+        drop(conn);
+
+        let metrics = pool.metrics(&addr).unwrap();
+        assert_eq!(metrics.connections, 0);
+        assert_eq!(metrics.connections_closed_max_lifetime, 1);
+    }
+
+    #[tokio::test]
+    async fn test_check_out_closed() {
+        let (addr, accepted) = serve().await;
+        let pool = Pool::with_config(PoolConfig::default());
+
+        drop(pool.get(&addr).await.unwrap());
+        // the peer closes the idle connection
+        sleep(Duration::from_millis(100)).await;
+        accepted.lock().unwrap().clear();
+        sleep(Duration::from_millis(100)).await;
+
+        let _conn = pool.get(&addr).await.unwrap();
+        let metrics = pool.metrics(&addr).unwrap();
+        assert_eq!(metrics.connections_closed_invalid, 1);
+        assert_eq!(metrics.connections_created, 2);
+    }
+
+    #[tokio::test]
+    async fn test_connect_error() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = Address::Ip(listener.local_addr().unwrap());
+        drop(listener);
+
+        let pool = Pool::with_config(
+            PoolConfig::default().with_checkout_timeout(Duration::from_millis(200)),
+        );
+        let start = Instant::now();
+        let err = pool.get(&addr).await.err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+        assert!(start.elapsed() >= pool.config().checkout_timeout);
+
+        assert!(pool.remove(&addr));
+        assert!(pool.metrics(&addr).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_discard() {
+        let (addr, _accepted) = serve().await;
+        let pool = Pool::with_config(PoolConfig::default());
+
+        let mut conn = pool.get(&addr).await.unwrap();
+        conn.discard();
+        drop(conn);
+        let metrics = pool.metrics(&addr).unwrap();
+        assert_eq!(metrics.connections, 0);
+        assert_eq!(metrics.connections_closed_broken, 1);
+    }
+
+    #[cfg(target_family = "unix")]
+    #[tokio::test]
+    async fn test_check_out_closed_unix() {
+        let path = std::env::temp_dir().join(format!("remoting-pool-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = tokio::net::UnixListener::bind(&path).unwrap();
+        let addr = Address::Unix(std::borrow::Cow::Owned(path.clone()));
+        let pool = Pool::with_config(PoolConfig::default());
+
+        let (conn, accepted) = tokio::join!(pool.get(&addr), listener.accept());
+        drop(conn.unwrap());
+        // the peer closes the idle connection
+        drop(accepted.unwrap());
+        sleep(Duration::from_millis(100)).await;
+
+        let (conn, _accepted) = tokio::join!(pool.get(&addr), listener.accept());
+        conn.unwrap();
+        let metrics = pool.metrics(&addr).unwrap();
+        assert_eq!(metrics.connections_closed_invalid, 1);
+        assert_eq!(metrics.connections_created, 2);
+        let _ = std::fs::remove_file(&path);
+    }
+}
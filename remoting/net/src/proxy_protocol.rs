@@ -0,0 +1,373 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! The PROXY protocol of HAProxy: a header a load balancer sends ahead of the
+//! bytes of a connection, telling the address of the client behind it. Both
+//! the text format of version 1 and the binary one of version 2 are read.
+//!
+//! See <https://www.haproxy.org/download/2.8/doc/proxy-protocol.txt>.
+
+use std::{
+    fmt, io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+
+use futures::{Stream, StreamExt};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt},
+    sync::mpsc,
+    task::JoinHandle,
+};
+
+/// How long a connection may take to send its PROXY protocol header.
+pub const PROXY_HEADER_TIMEOUT: Duration = Duration::from_secs(10);
+
+const V1_PREFIX: &[u8] = b"PROXY ";
+/// The longest header of version 1, `\r\n` included.
+const V1_MAX_LEN: usize = 107;
+
+const V2_SIGNATURE: &[u8] = b"\r\n\r\n\0\r\nQUIT\n";
+const V2_HEADER_LEN: usize = 16;
+
+const V2_LOCAL: u8 = 0x20;
+const V2_PROXY: u8 = 0x21;
+const V2_TCP4: u8 = 0x11;
+const V2_TCP6: u8 = 0x21;
+
+/// The addresses of a proxied connection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProxyHeader {
+    /// The client.
+    pub source: SocketAddr,
+    /// The address the client connected to, i.e. the one of the proxy.
+    pub destination: SocketAddr,
+}
+
+impl ProxyHeader {
+    pub fn new(source: SocketAddr, destination: SocketAddr) -> Self {
+        ProxyHeader {
+            source,
+            destination,
+        }
+    }
+
+    /// The header in the format of version 1.
+    pub fn to_v1(&self) -> Vec<u8> {
+        let family = match (self.source, self.destination) {
+            (SocketAddr::V4(_), SocketAddr::V4(_)) => "TCP4",
+            (SocketAddr::V6(_), SocketAddr::V6(_)) => "TCP6",
+            _ => return b"PROXY UNKNOWN\r\n".to_vec(),
+        };
+        format!(
+            "PROXY {} {} {} {} {}\r\n",
+            family,
+            self.source.ip(),
+            self.destination.ip(),
+            self.source.port(),
+            self.destination.port()
+        )
+        .into_bytes()
+    }
+
+    /// The header in the format of version 2, without TLVs.
+    pub fn to_v2(&self) -> Vec<u8> {
+        let mut header = V2_SIGNATURE.to_vec();
+        let addresses: Vec<u8> = match (self.source.ip(), self.destination.ip()) {
+            (IpAddr::V4(source), IpAddr::V4(destination)) => {
+                header.extend([V2_PROXY, V2_TCP4]);
+                [source.octets(), destination.octets()].concat()
+            }
+            (IpAddr::V6(source), IpAddr::V6(destination)) => {
+                header.extend([V2_PROXY, V2_TCP6]);
+                [source.octets(), destination.octets()].concat()
+            }
+            _ => {
+                header.extend([V2_LOCAL, 0, 0, 0]);
+                return header;
+            }
+        };
+        header.extend((addresses.len() as u16 + 4).to_be_bytes());
+        header.extend(addresses);
+        header.extend(self.source.port().to_be_bytes());
+        header.extend(self.destination.port().to_be_bytes());
+        header
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid proxy protocol header: {}", message),
+    )
+}
+
+/// Reads the header of either version from `io`, and none of the bytes
+/// following it. `None` for connections the proxy opened itself, e.g. for
+/// health checks, and for clients that are not on TCP over IP.
+///
+/// Headers of version 1 are read a byte at a time, as their length is not
+/// known ahead.
+pub async fn read_header<T>(io: &mut T) -> io::Result<Option<ProxyHeader>>
+where
+    T: AsyncRead + Unpin,
+{
+    let mut buf = vec![0; V1_PREFIX.len()];
+    io.read_exact(&mut buf).await?;
+
+    if buf == V1_PREFIX {
+        while !buf.ends_with(b"\r\n") {
+            if buf.len() == V1_MAX_LEN {
+                return Err(invalid("line too long"));
+            }
+            buf.push(io.read_u8().await?);
+        }
+        return parse_v1(&buf);
+    }
+
+    if buf != V2_SIGNATURE[..buf.len()] {
+        return Err(invalid("no signature"));
+    }
+    buf.resize(V2_HEADER_LEN, 0);
+    io.read_exact(&mut buf[V1_PREFIX.len()..]).await?;
+    let len = u16::from_be_bytes([buf[14], buf[15]]) as usize;
+    let mut addresses = vec![0; len];
+    io.read_exact(&mut addresses).await?;
+    parse_v2(&buf, &addresses)
+}
+
+/// Reads the headers of the connections a listener accepts, each on a task
+/// of its own, so that a slow peer only holds up its own connection.
+/// Connections without a valid header within `PROXY_HEADER_TIMEOUT` are
+/// closed.
+///
+/// The connections come with what the listener accepted them with, `A`, the
+/// address of the proxy say.
+pub struct ProxyHeaderReader<IO, A> {
+    conns: mpsc::Receiver<io::Result<(IO, A, Option<ProxyHeader>)>>,
+    task: JoinHandle<()>,
+}
+
+impl<IO, A> ProxyHeaderReader<IO, A>
+where
+    IO: AsyncRead + Unpin + Send + 'static,
+    A: fmt::Debug + Send + 'static,
+{
+    /// Reads the headers of the connections of `accepted`, until it ends.
+    pub fn new<S>(accepted: S) -> Self
+    where
+        S: Stream<Item = io::Result<(IO, A)>> + Send + 'static,
+    {
+        let (tx, conns) = mpsc::channel(128);
+        let task = tokio::spawn(async move {
+            tokio::pin!(accepted);
+            while let Some(conn) = accepted.next().await {
+                let (mut io, accepted_with) = match conn {
+                    Ok(conn) => conn,
+                    Err(err) => {
+                        if tx.send(Err(err)).await.is_err() {
+                            return;
+                        }
+                        continue;
+                    }
+                };
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    match tokio::time::timeout(PROXY_HEADER_TIMEOUT, read_header(&mut io)).await {
+                        Ok(Ok(header)) => {
+                            tracing::trace!(
+                                "[Net] connection of {:?} proxied by {:?}",
+                                header.map(|header| header.source),
+                                accepted_with
+                            );
+                            let _ = tx.send(Ok((io, accepted_with, header))).await;
+                        }
+                        Ok(Err(err)) => {
+                            tracing::debug!(
+                                "[Net] closing connection of {:?}: {}",
+                                accepted_with,
+                                err
+                            )
+                        }
+                        Err(_) => tracing::debug!(
+                            "[Net] closing connection of {:?}: no proxy protocol header in time",
+                            accepted_with
+                        ),
+                    }
+                });
+            }
+        });
+        ProxyHeaderReader { conns, task }
+    }
+
+    /// The next connection with a header, `None` once the listener ended.
+    pub async fn accept(&mut self) -> Option<io::Result<(IO, A, Option<ProxyHeader>)>> {
+        self.conns.recv().await
+    }
+}
+
+impl<IO, A> fmt::Debug for ProxyHeaderReader<IO, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProxyHeaderReader").finish_non_exhaustive()
+    }
+}
+
+impl<IO, A> Drop for ProxyHeaderReader<IO, A> {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Parses a header of version 1, `\r\n` included.
+fn parse_v1(line: &[u8]) -> io::Result<Option<ProxyHeader>> {
+    let line = std::str::from_utf8(line)
+        .ok()
+        .and_then(|line| line.strip_suffix("\r\n"))
+        .ok_or_else(|| invalid("not a line"))?;
+    let mut fields = line.split(' ').skip(1);
+    let family = fields.next();
+    if family == Some("UNKNOWN") {
+        return Ok(None);
+    }
+
+    let fields: Vec<&str> = fields.collect();
+    let [source, destination, source_port, destination_port] = fields[..] else {
+        return Err(invalid("wrong number of fields"));
+    };
+    let ip = |ip: &str| -> io::Result<IpAddr> {
+        let ip = match family {
+            Some("TCP4") => ip.parse::<Ipv4Addr>().map(IpAddr::from),
+            Some("TCP6") => ip.parse::<Ipv6Addr>().map(IpAddr::from),
+            _ => return Err(invalid("unknown protocol")),
+        };
+        ip.map_err(|_| invalid("bad address"))
+    };
+    let port = |port: &str| -> io::Result<u16> { port.parse().map_err(|_| invalid("bad port")) };
+    Ok(Some(ProxyHeader::new(
+        SocketAddr::new(ip(source)?, port(source_port)?),
+        SocketAddr::new(ip(destination)?, port(destination_port)?),
+    )))
+}
+
+/// Parses the 16 bytes starting a header of version 2 and the addresses
+/// following them.
+fn parse_v2(header: &[u8], addresses: &[u8]) -> io::Result<Option<ProxyHeader>> {
+    if header[..V2_SIGNATURE.len()] != *V2_SIGNATURE {
+        return Err(invalid("no signature"));
+    }
+    match header[12] {
+        V2_LOCAL => return Ok(None),
+        V2_PROXY => {}
+        _ => return Err(invalid("unknown version or command")),
+    }
+
+    let (source, destination, ports): (IpAddr, IpAddr, &[u8]) = match header[13] {
+        V2_TCP4 if addresses.len() >= 12 => {
+            let ip =
+                |at: usize| Ipv4Addr::from(<[u8; 4]>::try_from(&addresses[at..at + 4]).unwrap());
+            (ip(0).into(), ip(4).into(), &addresses[8..12])
+        }
+        V2_TCP6 if addresses.len() >= 36 => {
+            let ip =
+                |at: usize| Ipv6Addr::from(<[u8; 16]>::try_from(&addresses[at..at + 16]).unwrap());
+            (ip(0).into(), ip(16).into(), &addresses[32..36])
+        }
+        V2_TCP4 | V2_TCP6 => return Err(invalid("addresses too short")),
+        // UDP, unix sockets and unspecified ones
+        _ => return Ok(None),
+    };
+    Ok(Some(ProxyHeader::new(
+        SocketAddr::new(source, u16::from_be_bytes([ports[0], ports[1]])),
+        SocketAddr::new(destination, u16::from_be_bytes([ports[2], ports[3]])),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::AsyncWriteExt;
+
+    use super::*;
+
+    fn header() -> ProxyHeader {
+        ProxyHeader::new(
+            "192.168.0.1:56324".parse().unwrap(),
+            "10.0.0.1:20000".parse().unwrap(),
+        )
+    }
+
+    async fn read(bytes: &[u8]) -> io::Result<Option<ProxyHeader>> {
+        let mut io = bytes;
+        read_header(&mut io).await
+    }
+
+    #[test]
+    fn test_parse_v1() {
+        let parsed = parse_v1(b"PROXY TCP4 192.168.0.1 10.0.0.1 56324 20000\r\n").unwrap();
+        assert_eq!(parsed, Some(header()));
+
+        let parsed = parse_v1(b"PROXY TCP6 ::1 fe80::1 56324 20000\r\n").unwrap();
+        assert_eq!(parsed.unwrap().source, "[::1]:56324".parse().unwrap());
+
+        assert_eq!(parse_v1(b"PROXY UNKNOWN\r\n").unwrap(), None);
+        assert!(parse_v1(b"PROXY TCP4 ::1 10.0.0.1 56324 20000\r\n").is_err());
+        assert!(parse_v1(b"PROXY TCP4 192.168.0.1 10.0.0.1 56324\r\n").is_err());
+        assert!(parse_v1(b"PROXY TCP4 192.168.0.1 10.0.0.1 56324 70000\r\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_v2() {
+        let bytes = header().to_v2();
+        let parsed = parse_v2(&bytes[..V2_HEADER_LEN], &bytes[V2_HEADER_LEN..]).unwrap();
+        assert_eq!(parsed, Some(header()));
+
+        let v6 = ProxyHeader::new("[::1]:1".parse().unwrap(), "[fe80::1]:2".parse().unwrap());
+        let bytes = v6.to_v2();
+        let parsed = parse_v2(&bytes[..V2_HEADER_LEN], &bytes[V2_HEADER_LEN..]).unwrap();
+        assert_eq!(parsed, Some(v6));
+
+        let mut local = header().to_v2();
+        local[12] = V2_LOCAL;
+        assert_eq!(parse_v2(&local[..V2_HEADER_LEN], &[]).unwrap(), None);
+        assert!(parse_v2(&bytes[..V2_HEADER_LEN], &bytes[V2_HEADER_LEN..20]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_read_header() {
+        for bytes in [header().to_v1(), header().to_v2()] {
+            let (mut client, mut server) = tokio::io::duplex(256);
+            client.write_all(&bytes).await.unwrap();
+            client.write_all(b"payload").await.unwrap();
+            drop(client);
+
+            assert_eq!(read_header(&mut server).await.unwrap(), Some(header()));
+            let mut rest = String::new();
+            server.read_to_string(&mut rest).await.unwrap();
+            assert_eq!(rest, "payload");
+        }
+
+        // TLVs following the addresses belong to the header
+        let mut bytes = header().to_v2();
+        bytes[15] += 3;
+        bytes.extend([0x04, 0, 0]);
+        assert_eq!(read(&bytes).await.unwrap(), Some(header()));
+
+        assert!(read(b"GET / HTTP/1.1\r\n").await.is_err());
+        assert!(read(&[b"PROXY TCP4 ".as_slice(), &[b'1'; 120]].concat())
+            .await
+            .is_err());
+    }
+}
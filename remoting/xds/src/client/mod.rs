@@ -14,3 +14,399 @@
  * See the License for the specific language governing permissions and
  * limitations under the License.
  */
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    time::Duration,
+};
+
+use http::uri::PathAndQuery;
+use tokio::sync::{mpsc, oneshot, watch};
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use tonic::{codec::ProstCodec, transport};
+use tracing::{debug, warn};
+
+use crate::{
+    error::XdsError,
+    protocol::{
+        cluster, core::Node, discovery::DiscoveryRequest, discovery::DiscoveryResponse,
+        discovery::Status, endpoint, listener, route, Any, Resource, ADS_PATH,
+        CLUSTER_LOAD_ASSIGNMENT_TYPE_URL, CLUSTER_TYPE_URL, LISTENER_TYPE_URL,
+        ROUTE_CONFIGURATION_TYPE_URL,
+    },
+    resource::{
+        Cluster, ClusterEndpoints, Endpoint, Listener, LoadAssignment, RouteConfig, RouteSource,
+        ServiceSnapshot,
+    },
+};
+
+const MIN_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// `google.rpc.Code.INVALID_ARGUMENT`, the code of a nack.
+const INVALID_ARGUMENT: i32 = 3;
+
+/// The resource types in the order a service refers to them.
+const TYPE_URLS: [&str; 4] = [
+    LISTENER_TYPE_URL,
+    ROUTE_CONFIGURATION_TYPE_URL,
+    CLUSTER_TYPE_URL,
+    CLUSTER_LOAD_ASSIGNMENT_TYPE_URL,
+];
+
+/// A client of the aggregated discovery service. It follows every watched
+/// service from its listener, named after the service, through the route
+/// configuration and the clusters down to the endpoints, all over one stream
+/// that is reopened with a backoff when it breaks.
+#[derive(Clone, Debug)]
+pub struct XdsClient {
+    commands: mpsc::UnboundedSender<Command>,
+}
+
+enum Command {
+    Watch(String, oneshot::Sender<watch::Receiver<ServiceSnapshot>>),
+}
+
+impl XdsClient {
+    /// Connects to the management server at `endpoint`, e.g.
+    /// `http://127.0.0.1:15010`, in the background. The stream stays open as
+    /// long as a clone of the client is alive.
+    pub fn connect(endpoint: impl Into<String>, node: Node) -> Result<Self, XdsError> {
+        let endpoint = transport::Endpoint::from_shared(endpoint.into())?;
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(AdsWorker::new(endpoint, node, rx).run());
+        Ok(XdsClient { commands: tx })
+    }
+
+    /// Watches the service `name`. The receiver holds what is known of the
+    /// service so far and sees every change of it.
+    pub async fn watch_service(
+        &self,
+        name: &str,
+    ) -> Result<watch::Receiver<ServiceSnapshot>, XdsError> {
+        let (tx, rx) = oneshot::channel();
+        self.commands
+            .send(Command::Watch(name.to_string(), tx))
+            .map_err(|_| XdsError::Closed)?;
+        rx.await.map_err(|_| XdsError::Closed)
+    }
+}
+
+/// The state of the subscription to one resource type.
+#[derive(Default)]
+struct Subscription {
+    names: BTreeSet<String>,
+    /// The version of the last accepted response.
+    version: String,
+    /// The nonce of the last response of the current stream.
+    nonce: String,
+}
+
+struct AdsWorker {
+    endpoint: transport::Endpoint,
+    node: Node,
+    commands: mpsc::UnboundedReceiver<Command>,
+    watchers: HashMap<String, watch::Sender<ServiceSnapshot>>,
+    subscriptions: HashMap<&'static str, Subscription>,
+    listeners: HashMap<String, Listener>,
+    route_configs: HashMap<String, RouteConfig>,
+    clusters: HashMap<String, Cluster>,
+    load_assignments: HashMap<String, Vec<Endpoint>>,
+}
+
+impl AdsWorker {
+    fn new(
+        endpoint: transport::Endpoint,
+        node: Node,
+        commands: mpsc::UnboundedReceiver<Command>,
+    ) -> Self {
+        AdsWorker {
+            endpoint,
+            node,
+            commands,
+            watchers: HashMap::new(),
+            subscriptions: TYPE_URLS
+                .iter()
+                .map(|type_url| (*type_url, Subscription::default()))
+                .collect(),
+            listeners: HashMap::new(),
+            route_configs: HashMap::new(),
+            clusters: HashMap::new(),
+            load_assignments: HashMap::new(),
+        }
+    }
+
+    async fn run(mut self) {
+        let mut backoff = MIN_BACKOFF;
+        loop {
+            match self.stream(&mut backoff).await {
+                Ok(()) => return,
+                Err(err) => warn!("xds stream to {} broke: {}", self.endpoint.uri(), err),
+            }
+
+            // watches keep coming in while the stream is down
+            let sleep = tokio::time::sleep(backoff);
+            tokio::pin!(sleep);
+            loop {
+                tokio::select! {
+                    _ = &mut sleep => break,
+                    command = self.commands.recv() => match command {
+                        Some(command) => {
+                            self.handle_command(command);
+                        }
+                        None => return,
+                    },
+                }
+            }
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    }
+
+    /// Runs one stream until it breaks, or returns `Ok` once every client is
+    /// gone.
+    async fn stream(&mut self, backoff: &mut Duration) -> Result<(), XdsError> {
+        let channel = self.endpoint.connect().await?;
+        let mut grpc = tonic::client::Grpc::new(channel);
+        grpc.ready().await?;
+
+        // the versions of the last stream go along, nonces are per stream
+        let (tx, rx) = mpsc::unbounded_channel();
+        for subscription in self.subscriptions.values_mut() {
+            subscription.nonce.clear();
+        }
+        for type_url in TYPE_URLS {
+            self.send(&tx, type_url, None);
+        }
+
+        let response = grpc
+            .streaming(
+                tonic::Request::new(UnboundedReceiverStream::new(rx)),
+                PathAndQuery::from_static(ADS_PATH),
+                ProstCodec::<DiscoveryRequest, DiscoveryResponse>::default(),
+            )
+            .await?;
+        let mut inbound = response.into_inner();
+        debug!("xds stream to {} opened", self.endpoint.uri());
+
+        loop {
+            tokio::select! {
+                command = self.commands.recv() => match command {
+                    Some(command) => {
+                        for type_url in self.handle_command(command) {
+                            self.send(&tx, type_url, None);
+                        }
+                    }
+                    None => return Ok(()),
+                },
+                response = inbound.message() => match response? {
+                    Some(response) => {
+                        *backoff = MIN_BACKOFF;
+                        self.handle_response(&tx, response);
+                    }
+                    None => return Err(tonic::Status::unavailable("stream closed by the server").into()),
+                },
+            }
+        }
+    }
+
+    /// Returns the resource types whose subscription changed.
+    fn handle_command(&mut self, command: Command) -> Vec<&'static str> {
+        match command {
+            Command::Watch(name, tx) => {
+                let watcher = self
+                    .watchers
+                    .entry(name)
+                    .or_insert_with(|| watch::channel(ServiceSnapshot::default()).0);
+                let _ = tx.send(watcher.subscribe());
+                self.update()
+            }
+        }
+    }
+
+    fn handle_response(
+        &mut self,
+        tx: &mpsc::UnboundedSender<DiscoveryRequest>,
+        response: DiscoveryResponse,
+    ) {
+        let Some(type_url) = TYPE_URLS
+            .iter()
+            .find(|type_url| **type_url == response.type_url)
+        else {
+            warn!("xds response of unknown type {}", response.type_url);
+            return;
+        };
+        let subscription = self.subscriptions.get_mut(type_url).unwrap();
+        subscription.nonce = response.nonce;
+
+        match self.apply(type_url, &response.resources) {
+            Ok(()) => {
+                debug!(
+                    "xds accepted version {} of {}",
+                    response.version_info, type_url
+                );
+                let subscription = self.subscriptions.get_mut(type_url).unwrap();
+                subscription.version = response.version_info;
+                self.send(tx, type_url, None);
+                for type_url in self.update() {
+                    self.send(tx, type_url, None);
+                }
+            }
+            Err(err) => {
+                warn!(
+                    "xds rejected version {} of {}: {}",
+                    response.version_info, type_url, err
+                );
+                let status = Status {
+                    code: INVALID_ARGUMENT,
+                    message: err.to_string(),
+                };
+                self.send(tx, type_url, Some(status));
+            }
+        }
+    }
+
+    /// Acks with the last accepted version, or nacks with `error`.
+    fn send(
+        &self,
+        tx: &mpsc::UnboundedSender<DiscoveryRequest>,
+        type_url: &'static str,
+        error: Option<Status>,
+    ) {
+        let subscription = &self.subscriptions[type_url];
+        // no names would subscribe to all listeners and clusters
+        if subscription.names.is_empty() {
+            return;
+        }
+        let _ = tx.send(DiscoveryRequest {
+            version_info: subscription.version.clone(),
+            node: Some(self.node.clone()),
+            resource_names: subscription.names.iter().cloned().collect(),
+            type_url: type_url.to_string(),
+            response_nonce: subscription.nonce.clone(),
+            error_detail: error,
+        });
+    }
+
+    /// Takes all resources of a response or none of them. Listeners and
+    /// clusters come as the whole state, route configurations and load
+    /// assignments one by one.
+    fn apply(&mut self, type_url: &str, resources: &[Any]) -> Result<(), XdsError> {
+        match type_url {
+            LISTENER_TYPE_URL => {
+                self.listeners = decode::<listener::Listener, Listener>(resources)?
+                    .into_iter()
+                    .map(|listener| (listener.name.clone(), listener))
+                    .collect();
+            }
+            ROUTE_CONFIGURATION_TYPE_URL => {
+                for route_config in decode::<route::RouteConfiguration, RouteConfig>(resources)? {
+                    self.route_configs
+                        .insert(route_config.name.clone(), route_config);
+                }
+            }
+            CLUSTER_TYPE_URL => {
+                self.clusters = decode::<cluster::Cluster, Cluster>(resources)?
+                    .into_iter()
+                    .map(|cluster| (cluster.name.clone(), cluster))
+                    .collect();
+            }
+            _ => {
+                let assignments =
+                    decode::<endpoint::ClusterLoadAssignment, LoadAssignment>(resources)?;
+                for assignment in assignments {
+                    self.load_assignments
+                        .insert(assignment.name, assignment.endpoints);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Follows the watched services down to their endpoints, publishes the
+    /// snapshots that changed and returns the resource types whose
+    /// subscription changed.
+    fn update(&mut self) -> Vec<&'static str> {
+        self.watchers.retain(|_, watcher| !watcher.is_closed());
+
+        let mut names: HashMap<&'static str, BTreeSet<String>> = HashMap::new();
+        for (service, watcher) in &self.watchers {
+            names
+                .entry(LISTENER_TYPE_URL)
+                .or_default()
+                .insert(service.clone());
+
+            let route_config = match self.listeners.get(service).map(|l| &l.route_config) {
+                Some(RouteSource::Rds(name)) => {
+                    names
+                        .entry(ROUTE_CONFIGURATION_TYPE_URL)
+                        .or_default()
+                        .insert(name.clone());
+                    self.route_configs.get(name)
+                }
+                Some(RouteSource::Inline(route_config)) => Some(route_config),
+                None => None,
+            };
+            let routes = route_config.map(|route_config| {
+                route_config
+                    .virtual_host(service)
+                    .map(|virtual_host| virtual_host.routes.clone())
+                    .unwrap_or_default()
+            });
+
+            let mut clusters = BTreeMap::new();
+            for route in routes.iter().flatten() {
+                for cluster in &route.clusters {
+                    names
+                        .entry(CLUSTER_TYPE_URL)
+                        .or_default()
+                        .insert(cluster.name.clone());
+                    let endpoints = match self.clusters.get(&cluster.name).map(|c| &c.endpoints) {
+                        Some(ClusterEndpoints::Static(endpoints)) => Some(endpoints),
+                        Some(ClusterEndpoints::Eds(name)) => {
+                            names
+                                .entry(CLUSTER_LOAD_ASSIGNMENT_TYPE_URL)
+                                .or_default()
+                                .insert(name.clone());
+                            self.load_assignments.get(name)
+                        }
+                        None => None,
+                    };
+                    if let Some(endpoints) = endpoints {
+                        clusters.insert(cluster.name.clone(), endpoints.clone());
+                    }
+                }
+            }
+
+            let snapshot = ServiceSnapshot { routes, clusters };
+            watcher.send_if_modified(|current| {
+                if *current == snapshot {
+                    return false;
+                }
+                *current = snapshot;
+                true
+            });
+        }
+
+        let mut changed = Vec::new();
+        for type_url in TYPE_URLS {
+            let names = names.remove(type_url).unwrap_or_default();
+            let subscription = self.subscriptions.get_mut(type_url).unwrap();
+            if subscription.names != names {
+                subscription.names = names;
+                changed.push(type_url);
+            }
+        }
+        changed
+    }
+}
+
+fn decode<P, R>(resources: &[Any]) -> Result<Vec<R>, XdsError>
+where
+    P: Resource,
+    R: TryFrom<P, Error = XdsError>,
+{
+    resources
+        .iter()
+        .map(|any| P::from_any(any).and_then(R::try_from))
+        .collect()
+}
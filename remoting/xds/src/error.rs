@@ -0,0 +1,52 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum XdsError {
+    #[error("transport error: {0}")]
+    Transport(#[from] tonic::transport::Error),
+    #[error("stream error: {0}")]
+    Status(Box<tonic::Status>),
+    #[error("decode error: {0}")]
+    Decode(#[from] prost::DecodeError),
+    #[error("expected a resource of {expected}, found {found}")]
+    UnexpectedType {
+        expected: &'static str,
+        found: String,
+    },
+    #[error("invalid resource {name}: {reason}")]
+    InvalidResource { name: String, reason: String },
+    #[error("the xds client is closed")]
+    Closed,
+}
+
+impl From<tonic::Status> for XdsError {
+    fn from(status: tonic::Status) -> Self {
+        XdsError::Status(Box::new(status))
+    }
+}
+
+impl XdsError {
+    pub(crate) fn invalid(name: &str, reason: impl Into<String>) -> Self {
+        XdsError::InvalidResource {
+            name: name.to_string(),
+            reason: reason.into(),
+        }
+    }
+}
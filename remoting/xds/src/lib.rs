@@ -15,7 +15,16 @@
  * limitations under the License.
  */
 
-mod client;
+pub mod client;
+pub mod error;
+pub mod protocol;
+pub mod resource;
+pub mod server;
+
+pub use client::XdsClient;
+pub use error::XdsError;
+pub use resource::ServiceSnapshot;
+pub use server::XdsServer;
 
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,57 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! `envoy.config.cluster.v3`
+
+use super::{core::ConfigSource, endpoint::ClusterLoadAssignment};
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Cluster {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(enumeration = "cluster::DiscoveryType", tag = "2")]
+    pub r#type: i32,
+    #[prost(message, optional, tag = "3")]
+    pub eds_cluster_config: Option<cluster::EdsClusterConfig>,
+    /// The endpoints of a `STATIC` cluster.
+    #[prost(message, optional, tag = "33")]
+    pub load_assignment: Option<ClusterLoadAssignment>,
+}
+
+#[allow(clippy::module_inception)]
+pub mod cluster {
+    use super::ConfigSource;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum DiscoveryType {
+        Static = 0,
+        StrictDns = 1,
+        LogicalDns = 2,
+        Eds = 3,
+        OriginalDst = 4,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct EdsClusterConfig {
+        #[prost(message, optional, tag = "1")]
+        pub eds_config: Option<ConfigSource>,
+        /// The name of the load assignment, the cluster name when empty.
+        #[prost(string, tag = "2")]
+        pub service_name: String,
+    }
+}
@@ -0,0 +1,64 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! `envoy.config.endpoint.v3`
+
+use super::core::{Address, HealthStatus};
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ClusterLoadAssignment {
+    #[prost(string, tag = "1")]
+    pub cluster_name: String,
+    #[prost(message, repeated, tag = "2")]
+    pub endpoints: Vec<LocalityLbEndpoints>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct LocalityLbEndpoints {
+    #[prost(message, repeated, tag = "2")]
+    pub lb_endpoints: Vec<LbEndpoint>,
+    #[prost(message, optional, tag = "3")]
+    pub load_balancing_weight: Option<u32>,
+    #[prost(uint32, tag = "5")]
+    pub priority: u32,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct LbEndpoint {
+    #[prost(oneof = "lb_endpoint::HostIdentifier", tags = "1")]
+    pub host_identifier: Option<lb_endpoint::HostIdentifier>,
+    #[prost(enumeration = "HealthStatus", tag = "2")]
+    pub health_status: i32,
+    #[prost(message, optional, tag = "4")]
+    pub load_balancing_weight: Option<u32>,
+}
+
+pub mod lb_endpoint {
+    use super::Endpoint;
+
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum HostIdentifier {
+        #[prost(message, tag = "1")]
+        Endpoint(Endpoint),
+    }
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Endpoint {
+    #[prost(message, optional, tag = "1")]
+    pub address: Option<Address>,
+}
@@ -0,0 +1,83 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! `envoy.config.listener.v3` and the http connection manager filter, the
+//! api listener of a service refers to its route configuration through it.
+
+use super::{core::Address, core::ConfigSource, route::RouteConfiguration};
+use prost_types::Any;
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Listener {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(message, optional, tag = "2")]
+    pub address: Option<Address>,
+    #[prost(message, optional, tag = "19")]
+    pub api_listener: Option<ApiListener>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ApiListener {
+    /// An `HttpConnectionManager`.
+    #[prost(message, optional, tag = "1")]
+    pub api_listener: Option<Any>,
+}
+
+/// `envoy.extensions.filters.network.http_connection_manager.v3`
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HttpConnectionManager {
+    #[prost(string, tag = "2")]
+    pub stat_prefix: String,
+    #[prost(oneof = "http_connection_manager::RouteSpecifier", tags = "3, 4")]
+    pub route_specifier: Option<http_connection_manager::RouteSpecifier>,
+    /// Proxyless gRPC clients want the router filter last.
+    #[prost(message, repeated, tag = "5")]
+    pub http_filters: Vec<HttpFilter>,
+}
+
+pub mod http_connection_manager {
+    use super::{Rds, RouteConfiguration};
+
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum RouteSpecifier {
+        #[prost(message, tag = "3")]
+        Rds(Rds),
+        #[prost(message, tag = "4")]
+        RouteConfig(RouteConfiguration),
+    }
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Rds {
+    #[prost(message, optional, tag = "1")]
+    pub config_source: Option<ConfigSource>,
+    #[prost(string, tag = "2")]
+    pub route_config_name: String,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HttpFilter {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(message, optional, tag = "4")]
+    pub typed_config: Option<Any>,
+}
+
+/// `envoy.extensions.filters.http.router.v3`
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Router {}
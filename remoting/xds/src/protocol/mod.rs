@@ -0,0 +1,94 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! The subset of the xDS v3 API the client and the management server speak,
+//! written out by hand as there is no `protoc` at build time. Field numbers
+//! follow the envoy protos, fields the crate does not use are left out and
+//! skipped as unknown fields when decoding.
+
+pub mod cluster;
+pub mod core;
+pub mod discovery;
+pub mod endpoint;
+pub mod listener;
+pub mod route;
+
+use prost::Message;
+pub use prost_types::Any;
+
+use crate::error::XdsError;
+
+/// The method of the aggregated discovery service, all resource types share
+/// its stream.
+pub const ADS_PATH: &str =
+    "/envoy.service.discovery.v3.AggregatedDiscoveryService/StreamAggregatedResources";
+
+pub const LISTENER_TYPE_URL: &str = "type.googleapis.com/envoy.config.listener.v3.Listener";
+pub const ROUTE_CONFIGURATION_TYPE_URL: &str =
+    "type.googleapis.com/envoy.config.route.v3.RouteConfiguration";
+pub const CLUSTER_TYPE_URL: &str = "type.googleapis.com/envoy.config.cluster.v3.Cluster";
+pub const CLUSTER_LOAD_ASSIGNMENT_TYPE_URL: &str =
+    "type.googleapis.com/envoy.config.endpoint.v3.ClusterLoadAssignment";
+pub const ROUTER_TYPE_URL: &str =
+    "type.googleapis.com/envoy.extensions.filters.http.router.v3.Router";
+pub const HTTP_CONNECTION_MANAGER_TYPE_URL: &str = "type.googleapis.com/envoy.extensions.filters.network.http_connection_manager.v3.HttpConnectionManager";
+
+/// A message that travels packed into an `Any`.
+pub trait Resource: Message + Default + Sized {
+    const TYPE_URL: &'static str;
+
+    fn to_any(&self) -> Any {
+        Any {
+            type_url: Self::TYPE_URL.to_string(),
+            value: self.encode_to_vec(),
+        }
+    }
+
+    fn from_any(any: &Any) -> Result<Self, XdsError> {
+        if any.type_url != Self::TYPE_URL {
+            return Err(XdsError::UnexpectedType {
+                expected: Self::TYPE_URL,
+                found: any.type_url.clone(),
+            });
+        }
+        Ok(Self::decode(any.value.as_slice())?)
+    }
+}
+
+impl Resource for listener::Listener {
+    const TYPE_URL: &'static str = LISTENER_TYPE_URL;
+}
+
+impl Resource for listener::HttpConnectionManager {
+    const TYPE_URL: &'static str = HTTP_CONNECTION_MANAGER_TYPE_URL;
+}
+
+impl Resource for listener::Router {
+    const TYPE_URL: &'static str = ROUTER_TYPE_URL;
+}
+
+impl Resource for route::RouteConfiguration {
+    const TYPE_URL: &'static str = ROUTE_CONFIGURATION_TYPE_URL;
+}
+
+impl Resource for cluster::Cluster {
+    const TYPE_URL: &'static str = CLUSTER_TYPE_URL;
+}
+
+impl Resource for endpoint::ClusterLoadAssignment {
+    const TYPE_URL: &'static str = CLUSTER_LOAD_ASSIGNMENT_TYPE_URL;
+}
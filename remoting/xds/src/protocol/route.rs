@@ -0,0 +1,137 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! `envoy.config.route.v3`
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RouteConfiguration {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(message, repeated, tag = "2")]
+    pub virtual_hosts: Vec<VirtualHost>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct VirtualHost {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(string, repeated, tag = "2")]
+    pub domains: Vec<String>,
+    #[prost(message, repeated, tag = "3")]
+    pub routes: Vec<Route>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Route {
+    #[prost(message, optional, tag = "1")]
+    pub r#match: Option<RouteMatch>,
+    #[prost(oneof = "route::Action", tags = "2")]
+    pub action: Option<route::Action>,
+    #[prost(string, tag = "14")]
+    pub name: String,
+}
+
+#[allow(clippy::module_inception)]
+pub mod route {
+    use super::RouteAction;
+
+    /// Redirects and direct responses are no use to a consumer, they decode
+    /// as a route without action.
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Action {
+        #[prost(message, tag = "2")]
+        Route(RouteAction),
+    }
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RouteMatch {
+    #[prost(oneof = "route_match::PathSpecifier", tags = "1, 2")]
+    pub path_specifier: Option<route_match::PathSpecifier>,
+    #[prost(message, optional, tag = "4")]
+    pub case_sensitive: Option<bool>,
+    #[prost(message, repeated, tag = "6")]
+    pub headers: Vec<HeaderMatcher>,
+}
+
+pub mod route_match {
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum PathSpecifier {
+        #[prost(string, tag = "1")]
+        Prefix(String),
+        #[prost(string, tag = "2")]
+        Path(String),
+    }
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HeaderMatcher {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(oneof = "header_matcher::HeaderMatchSpecifier", tags = "4, 7, 9, 10")]
+    pub header_match_specifier: Option<header_matcher::HeaderMatchSpecifier>,
+    #[prost(bool, tag = "8")]
+    pub invert_match: bool,
+}
+
+pub mod header_matcher {
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum HeaderMatchSpecifier {
+        #[prost(string, tag = "4")]
+        ExactMatch(String),
+        #[prost(bool, tag = "7")]
+        PresentMatch(bool),
+        #[prost(string, tag = "9")]
+        PrefixMatch(String),
+        #[prost(string, tag = "10")]
+        SuffixMatch(String),
+    }
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RouteAction {
+    #[prost(oneof = "route_action::ClusterSpecifier", tags = "1, 3")]
+    pub cluster_specifier: Option<route_action::ClusterSpecifier>,
+}
+
+pub mod route_action {
+    use super::WeightedCluster;
+
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum ClusterSpecifier {
+        #[prost(string, tag = "1")]
+        Cluster(String),
+        #[prost(message, tag = "3")]
+        WeightedClusters(WeightedCluster),
+    }
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct WeightedCluster {
+    #[prost(message, repeated, tag = "1")]
+    pub clusters: Vec<weighted_cluster::ClusterWeight>,
+}
+
+pub mod weighted_cluster {
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct ClusterWeight {
+        #[prost(string, tag = "1")]
+        pub name: String,
+        #[prost(message, optional, tag = "2")]
+        pub weight: Option<u32>,
+    }
+}
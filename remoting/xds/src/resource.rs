@@ -0,0 +1,606 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! The resources of the client cache, validated out of their protos.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use rand::Rng;
+
+use crate::{
+    error::XdsError,
+    protocol::{
+        self,
+        cluster::cluster::DiscoveryType,
+        core::HealthStatus,
+        listener::{http_connection_manager::RouteSpecifier, HttpConnectionManager},
+        route::{
+            header_matcher::HeaderMatchSpecifier, route::Action, route_action::ClusterSpecifier,
+            route_match::PathSpecifier,
+        },
+        Resource,
+    },
+};
+
+/// The api listener of a service, named after the service.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Listener {
+    pub name: String,
+    pub route_config: RouteSource,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RouteSource {
+    /// The name of the route configuration to fetch over RDS.
+    Rds(String),
+    Inline(RouteConfig),
+}
+
+impl TryFrom<protocol::listener::Listener> for Listener {
+    type Error = XdsError;
+
+    fn try_from(listener: protocol::listener::Listener) -> Result<Self, Self::Error> {
+        let name = listener.name;
+        let manager = listener
+            .api_listener
+            .and_then(|api_listener| api_listener.api_listener)
+            .ok_or_else(|| XdsError::invalid(&name, "no api listener"))?;
+        let manager = HttpConnectionManager::from_any(&manager)?;
+        let route_config = match manager.route_specifier {
+            Some(RouteSpecifier::Rds(rds)) => RouteSource::Rds(rds.route_config_name),
+            Some(RouteSpecifier::RouteConfig(route_config)) => {
+                RouteSource::Inline(route_config.try_into()?)
+            }
+            None => return Err(XdsError::invalid(&name, "no route configuration")),
+        };
+        Ok(Listener { name, route_config })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RouteConfig {
+    pub name: String,
+    pub virtual_hosts: Vec<VirtualHost>,
+}
+
+impl RouteConfig {
+    /// Returns the virtual host serving `domain`. An exact domain wins over a
+    /// suffix wildcard like `*.example`, that over a prefix wildcard like
+    /// `example.*` and that over `*`.
+    pub fn virtual_host(&self, domain: &str) -> Option<&VirtualHost> {
+        self.virtual_hosts
+            .iter()
+            .filter_map(|virtual_host| {
+                let score = virtual_host
+                    .domains
+                    .iter()
+                    .filter_map(|pattern| domain_score(pattern, domain))
+                    .max()?;
+                Some((score, virtual_host))
+            })
+            .max_by_key(|(score, _)| *score)
+            .map(|(_, virtual_host)| virtual_host)
+    }
+}
+
+fn domain_score(pattern: &str, domain: &str) -> Option<(u8, usize)> {
+    if pattern == domain {
+        Some((3, pattern.len()))
+    } else if pattern == "*" {
+        Some((0, 0))
+    } else if let Some(suffix) = pattern.strip_prefix('*') {
+        domain.ends_with(suffix).then_some((2, pattern.len()))
+    } else if let Some(prefix) = pattern.strip_suffix('*') {
+        domain.starts_with(prefix).then_some((1, pattern.len()))
+    } else {
+        None
+    }
+}
+
+impl TryFrom<protocol::route::RouteConfiguration> for RouteConfig {
+    type Error = XdsError;
+
+    fn try_from(route_config: protocol::route::RouteConfiguration) -> Result<Self, Self::Error> {
+        let name = route_config.name;
+        let virtual_hosts = route_config
+            .virtual_hosts
+            .into_iter()
+            .map(|virtual_host| {
+                let routes = virtual_host
+                    .routes
+                    .into_iter()
+                    .map(|route| route_from(&name, route))
+                    .collect::<Result<_, _>>()?;
+                Ok(VirtualHost {
+                    name: virtual_host.name,
+                    domains: virtual_host.domains,
+                    routes,
+                })
+            })
+            .collect::<Result<_, XdsError>>()?;
+        Ok(RouteConfig {
+            name,
+            virtual_hosts,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct VirtualHost {
+    pub name: String,
+    pub domains: Vec<String>,
+    pub routes: Vec<Route>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Route {
+    pub name: String,
+    pub matcher: RouteMatch,
+    /// Empty for the routes that do not forward to a cluster, e.g. redirects.
+    pub clusters: Vec<ClusterWeight>,
+}
+
+impl Route {
+    /// Whether a call of `path`, e.g. `/org.apache.dubbo.Greeter/greet`, with
+    /// the headers `header` looks up takes this route.
+    pub fn matches<'a>(&self, path: &str, header: impl Fn(&str) -> Option<&'a str>) -> bool {
+        self.matcher.matches_path(path)
+            && self
+                .matcher
+                .headers
+                .iter()
+                .all(|matcher| matcher.matches(header(&matcher.name)))
+    }
+
+    /// Picks one of the clusters of the route by weight.
+    pub fn pick_cluster(&self) -> Option<&str> {
+        let total: u64 = self.clusters.iter().map(|c| c.weight as u64).sum();
+        if total == 0 {
+            return self.clusters.first().map(|c| c.name.as_str());
+        }
+        let mut pick = rand::thread_rng().gen_range(0..total);
+        for cluster in &self.clusters {
+            if pick < cluster.weight as u64 {
+                return Some(&cluster.name);
+            }
+            pick -= cluster.weight as u64;
+        }
+        None
+    }
+}
+
+fn route_from(route_config: &str, route: protocol::route::Route) -> Result<Route, XdsError> {
+    let matcher = route
+        .r#match
+        .ok_or_else(|| XdsError::invalid(route_config, "route without match"))?;
+    let path = match matcher.path_specifier {
+        Some(PathSpecifier::Prefix(prefix)) => PathMatch::Prefix(prefix),
+        Some(PathSpecifier::Path(path)) => PathMatch::Path(path),
+        None => {
+            return Err(XdsError::invalid(
+                route_config,
+                "route match without path specifier",
+            ))
+        }
+    };
+    let headers = matcher
+        .headers
+        .into_iter()
+        .map(|header| HeaderMatch {
+            name: header.name,
+            kind: match header.header_match_specifier {
+                Some(HeaderMatchSpecifier::ExactMatch(value)) => HeaderMatchKind::Exact(value),
+                Some(HeaderMatchSpecifier::PresentMatch(present)) => {
+                    HeaderMatchKind::Present(present)
+                }
+                Some(HeaderMatchSpecifier::PrefixMatch(value)) => HeaderMatchKind::Prefix(value),
+                Some(HeaderMatchSpecifier::SuffixMatch(value)) => HeaderMatchKind::Suffix(value),
+                None => HeaderMatchKind::Present(true),
+            },
+            invert: header.invert_match,
+        })
+        .collect();
+    let clusters = match route.action {
+        Some(Action::Route(action)) => match action.cluster_specifier {
+            Some(ClusterSpecifier::Cluster(name)) => vec![ClusterWeight { name, weight: 1 }],
+            Some(ClusterSpecifier::WeightedClusters(weighted)) => weighted
+                .clusters
+                .into_iter()
+                .map(|cluster| ClusterWeight {
+                    name: cluster.name,
+                    weight: cluster.weight.unwrap_or_default(),
+                })
+                .collect(),
+            None => Vec::new(),
+        },
+        None => Vec::new(),
+    };
+    Ok(Route {
+        name: route.name,
+        matcher: RouteMatch {
+            path,
+            case_sensitive: matcher.case_sensitive.unwrap_or(true),
+            headers,
+        },
+        clusters,
+    })
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RouteMatch {
+    pub path: PathMatch,
+    pub case_sensitive: bool,
+    pub headers: Vec<HeaderMatch>,
+}
+
+impl RouteMatch {
+    fn matches_path(&self, path: &str) -> bool {
+        let (pattern, exact) = match &self.path {
+            PathMatch::Prefix(prefix) => (prefix, false),
+            PathMatch::Path(full) => (full, true),
+        };
+        if self.case_sensitive {
+            if exact {
+                path == pattern
+            } else {
+                path.starts_with(pattern.as_str())
+            }
+        } else {
+            let (path, pattern) = (path.to_lowercase(), pattern.to_lowercase());
+            if exact {
+                path == pattern
+            } else {
+                path.starts_with(&pattern)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PathMatch {
+    Prefix(String),
+    Path(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct HeaderMatch {
+    pub name: String,
+    pub kind: HeaderMatchKind,
+    pub invert: bool,
+}
+
+impl HeaderMatch {
+    fn matches(&self, value: Option<&str>) -> bool {
+        let matched = match (&self.kind, value) {
+            (HeaderMatchKind::Present(present), value) => *present == value.is_some(),
+            (_, None) => return false,
+            (HeaderMatchKind::Exact(expected), Some(value)) => value == expected,
+            (HeaderMatchKind::Prefix(prefix), Some(value)) => value.starts_with(prefix.as_str()),
+            (HeaderMatchKind::Suffix(suffix), Some(value)) => value.ends_with(suffix.as_str()),
+        };
+        matched != self.invert
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum HeaderMatchKind {
+    Exact(String),
+    Present(bool),
+    Prefix(String),
+    Suffix(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClusterWeight {
+    pub name: String,
+    pub weight: u32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cluster {
+    pub name: String,
+    pub endpoints: ClusterEndpoints,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ClusterEndpoints {
+    /// The name of the load assignment to fetch over EDS.
+    Eds(String),
+    Static(Vec<Endpoint>),
+}
+
+impl TryFrom<protocol::cluster::Cluster> for Cluster {
+    type Error = XdsError;
+
+    fn try_from(cluster: protocol::cluster::Cluster) -> Result<Self, Self::Error> {
+        let name = cluster.name;
+        let endpoints = match DiscoveryType::from_i32(cluster.r#type) {
+            Some(DiscoveryType::Eds) => {
+                let service_name = cluster
+                    .eds_cluster_config
+                    .map(|config| config.service_name)
+                    .filter(|service_name| !service_name.is_empty())
+                    .unwrap_or_else(|| name.clone());
+                ClusterEndpoints::Eds(service_name)
+            }
+            Some(DiscoveryType::Static) => {
+                let assignment = cluster
+                    .load_assignment
+                    .ok_or_else(|| XdsError::invalid(&name, "static cluster without endpoints"))?;
+                ClusterEndpoints::Static(LoadAssignment::try_from(assignment)?.endpoints)
+            }
+            _ => {
+                return Err(XdsError::invalid(
+                    &name,
+                    format!("unsupported discovery type {}", cluster.r#type),
+                ))
+            }
+        };
+        Ok(Cluster { name, endpoints })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoadAssignment {
+    pub name: String,
+    pub endpoints: Vec<Endpoint>,
+}
+
+impl TryFrom<protocol::endpoint::ClusterLoadAssignment> for LoadAssignment {
+    type Error = XdsError;
+
+    /// Keeps the endpoints of the highest priority, i.e. the lowest value, the
+    /// others only take over in a failover the consumer does not do.
+    fn try_from(
+        assignment: protocol::endpoint::ClusterLoadAssignment,
+    ) -> Result<Self, Self::Error> {
+        let name = assignment.cluster_name;
+        let Some(priority) = assignment.endpoints.iter().map(|e| e.priority).min() else {
+            return Ok(LoadAssignment {
+                name,
+                endpoints: Vec::new(),
+            });
+        };
+        let mut endpoints = Vec::new();
+        for locality in assignment.endpoints {
+            if locality.priority != priority {
+                continue;
+            }
+            for lb_endpoint in locality.lb_endpoints {
+                let address = lb_endpoint
+                    .host_identifier
+                    .map(|protocol::endpoint::lb_endpoint::HostIdentifier::Endpoint(e)| e)
+                    .and_then(|endpoint| endpoint.address)
+                    .and_then(|address| address.socket_address)
+                    .ok_or_else(|| XdsError::invalid(&name, "endpoint without socket address"))?;
+                let healthy = matches!(
+                    HealthStatus::from_i32(lb_endpoint.health_status),
+                    Some(HealthStatus::Unknown | HealthStatus::Healthy | HealthStatus::Degraded)
+                );
+                endpoints.push(Endpoint {
+                    address: format!("{}:{}", address.address, address.port_value),
+                    weight: lb_endpoint.load_balancing_weight.unwrap_or(1),
+                    healthy,
+                });
+            }
+        }
+        Ok(LoadAssignment { name, endpoints })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Endpoint {
+    /// `host:port`
+    pub address: String,
+    pub weight: u32,
+    pub healthy: bool,
+}
+
+/// What the client knows of a service: the routes of its virtual host and the
+/// endpoints of the clusters they forward to.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ServiceSnapshot {
+    /// `None` until the listener and the route configuration of the service
+    /// arrived.
+    pub routes: Option<Vec<Route>>,
+    pub clusters: BTreeMap<String, Vec<Endpoint>>,
+}
+
+impl ServiceSnapshot {
+    /// The first route a call of `path` with the headers `header` looks up
+    /// matches.
+    pub fn route<'a>(
+        &self,
+        path: &str,
+        header: impl Fn(&str) -> Option<&'a str> + Copy,
+    ) -> Option<&Route> {
+        self.routes
+            .as_ref()?
+            .iter()
+            .find(|route| route.matches(path, header))
+    }
+
+    pub fn endpoints(&self, cluster: &str) -> &[Endpoint] {
+        self.clusters
+            .get(cluster)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// The healthy endpoints of all clusters of the service.
+    pub fn addresses(&self) -> BTreeSet<&str> {
+        self.clusters
+            .values()
+            .flatten()
+            .filter(|endpoint| endpoint.healthy)
+            .map(|endpoint| endpoint.address.as_str())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{
+        core::{Address, SocketAddress},
+        endpoint::{lb_endpoint::HostIdentifier, LbEndpoint, LocalityLbEndpoints},
+    };
+
+    fn route(path: PathMatch, headers: Vec<HeaderMatch>, clusters: &[(&str, u32)]) -> Route {
+        Route {
+            name: String::new(),
+            matcher: RouteMatch {
+                path,
+                case_sensitive: true,
+                headers,
+            },
+            clusters: clusters
+                .iter()
+                .map(|(name, weight)| ClusterWeight {
+                    name: name.to_string(),
+                    weight: *weight,
+                })
+                .collect(),
+        }
+    }
+
+    fn lb_endpoint(port: u32, health_status: HealthStatus) -> LbEndpoint {
+        LbEndpoint {
+            host_identifier: Some(HostIdentifier::Endpoint(protocol::endpoint::Endpoint {
+                address: Some(Address {
+                    socket_address: Some(SocketAddress {
+                        address: "127.0.0.1".to_string(),
+                        port_value: port,
+                    }),
+                }),
+            })),
+            health_status: health_status as i32,
+            load_balancing_weight: None,
+        }
+    }
+
+    #[test]
+    fn test_route_match() {
+        let no_headers = |_: &str| None;
+        let prefix = route(PathMatch::Prefix("/svc/".into()), vec![], &[("a", 1)]);
+        assert!(prefix.matches("/svc/greet", no_headers));
+        assert!(!prefix.matches("/other/greet", no_headers));
+
+        let mut path = route(PathMatch::Path("/svc/Greet".into()), vec![], &[("a", 1)]);
+        assert!(!path.matches("/svc/greet", no_headers));
+        path.matcher.case_sensitive = false;
+        assert!(path.matches("/svc/greet", no_headers));
+
+        let canary = route(
+            PathMatch::Prefix("/".into()),
+            vec![HeaderMatch {
+                name: "x-canary".into(),
+                kind: HeaderMatchKind::Exact("true".into()),
+                invert: false,
+            }],
+            &[("canary", 1)],
+        );
+        assert!(canary.matches("/svc/greet", |_| Some("true")));
+        assert!(!canary.matches("/svc/greet", |_| Some("false")));
+        assert!(!canary.matches("/svc/greet", no_headers));
+
+        let absent = route(
+            PathMatch::Prefix("/".into()),
+            vec![HeaderMatch {
+                name: "x-canary".into(),
+                kind: HeaderMatchKind::Present(true),
+                invert: true,
+            }],
+            &[("stable", 1)],
+        );
+        assert!(absent.matches("/svc/greet", no_headers));
+        assert!(!absent.matches("/svc/greet", |_| Some("true")));
+    }
+
+    #[test]
+    fn test_pick_cluster() {
+        let weighted = route(PathMatch::Prefix("/".into()), vec![], &[("a", 0), ("b", 3)]);
+        for _ in 0..16 {
+            assert_eq!(weighted.pick_cluster(), Some("b"));
+        }
+        let none = route(PathMatch::Prefix("/".into()), vec![], &[]);
+        assert_eq!(none.pick_cluster(), None);
+    }
+
+    #[test]
+    fn test_virtual_host() {
+        let virtual_host = |name: &str, domain: &str| VirtualHost {
+            name: name.to_string(),
+            domains: vec![domain.to_string()],
+            routes: Vec::new(),
+        };
+        let route_config = RouteConfig {
+            name: "routes".to_string(),
+            virtual_hosts: vec![
+                virtual_host("any", "*"),
+                virtual_host("prefix", "org.apache.*"),
+                virtual_host("exact", "org.apache.dubbo.Greeter"),
+            ],
+        };
+        let name = |domain| route_config.virtual_host(domain).map(|v| v.name.as_str());
+        assert_eq!(name("org.apache.dubbo.Greeter"), Some("exact"));
+        assert_eq!(name("org.apache.dubbo.Echo"), Some("prefix"));
+        assert_eq!(name("com.example.Echo"), Some("any"));
+    }
+
+    #[test]
+    fn test_load_assignment() {
+        let assignment = protocol::endpoint::ClusterLoadAssignment {
+            cluster_name: "greeter".to_string(),
+            endpoints: vec![
+                LocalityLbEndpoints {
+                    lb_endpoints: vec![lb_endpoint(8000, HealthStatus::Healthy)],
+                    load_balancing_weight: None,
+                    priority: 1,
+                },
+                LocalityLbEndpoints {
+                    lb_endpoints: vec![
+                        lb_endpoint(8001, HealthStatus::Unknown),
+                        lb_endpoint(8002, HealthStatus::Draining),
+                    ],
+                    load_balancing_weight: None,
+                    priority: 0,
+                },
+            ],
+        };
+        let assignment = LoadAssignment::try_from(assignment).unwrap();
+        assert_eq!(assignment.name, "greeter");
+        let endpoints: Vec<_> = assignment
+            .endpoints
+            .iter()
+            .map(|e| (e.address.as_str(), e.healthy))
+            .collect();
+        assert_eq!(
+            endpoints,
+            [("127.0.0.1:8001", true), ("127.0.0.1:8002", false)]
+        );
+
+        let snapshot = ServiceSnapshot {
+            routes: Some(Vec::new()),
+            clusters: [("greeter".to_string(), assignment.endpoints)].into(),
+        };
+        assert_eq!(
+            snapshot.addresses().into_iter().collect::<Vec<_>>(),
+            ["127.0.0.1:8001"]
+        );
+    }
+}
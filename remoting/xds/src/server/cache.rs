@@ -0,0 +1,155 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    sync::Arc,
+};
+
+use tokio::sync::watch;
+
+use crate::protocol::Any;
+
+/// The resources a server hands out. Every type has a version of its own,
+/// bumped whenever one of its resources changes.
+#[derive(Clone, Debug, Default)]
+pub struct Snapshot {
+    types: HashMap<String, Resources>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct Resources {
+    version: u64,
+    resources: BTreeMap<String, Any>,
+}
+
+impl Snapshot {
+    pub fn version(&self, type_url: &str) -> u64 {
+        self.types.get(type_url).map_or(0, |r| r.version)
+    }
+
+    /// The resources of `type_url` named in `names`, all of them for `None`.
+    pub fn resources(&self, type_url: &str, names: Option<&BTreeSet<String>>) -> Vec<Any> {
+        let Some(resources) = self.types.get(type_url) else {
+            return Vec::new();
+        };
+        match names {
+            Some(names) => names
+                .iter()
+                .filter_map(|name| resources.resources.get(name).cloned())
+                .collect(),
+            None => resources.resources.values().cloned().collect(),
+        }
+    }
+
+    /// Sets the resource `name` of `type_url`, returns whether it changed.
+    pub fn set(&mut self, type_url: &str, name: &str, resource: Any) -> bool {
+        let resources = self.types.entry(type_url.to_string()).or_default();
+        if resources.resources.get(name) == Some(&resource) {
+            return false;
+        }
+        resources.resources.insert(name.to_string(), resource);
+        resources.version += 1;
+        true
+    }
+
+    /// Removes the resource `name` of `type_url`, returns whether it was there.
+    pub fn remove(&mut self, type_url: &str, name: &str) -> bool {
+        let Some(resources) = self.types.get_mut(type_url) else {
+            return false;
+        };
+        if resources.resources.remove(name).is_none() {
+            return false;
+        }
+        resources.version += 1;
+        true
+    }
+}
+
+/// The snapshot the streams of a server follow.
+#[derive(Clone, Debug)]
+pub struct SnapshotCache {
+    tx: Arc<watch::Sender<Snapshot>>,
+}
+
+impl SnapshotCache {
+    pub fn new() -> Self {
+        SnapshotCache {
+            tx: Arc::new(watch::channel(Snapshot::default()).0),
+        }
+    }
+
+    /// Changes the snapshot at once, the streams push the types that changed
+    /// afterwards.
+    pub fn update(&self, f: impl FnOnce(&mut Snapshot) -> bool) {
+        self.tx.send_if_modified(f);
+    }
+
+    pub fn set(&self, type_url: &str, name: &str, resource: Any) {
+        self.update(|snapshot| snapshot.set(type_url, name, resource));
+    }
+
+    pub fn remove(&self, type_url: &str, name: &str) {
+        self.update(|snapshot| snapshot.remove(type_url, name));
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        self.tx.borrow().clone()
+    }
+
+    pub(crate) fn subscribe(&self) -> watch::Receiver<Snapshot> {
+        self.tx.subscribe()
+    }
+}
+
+impl Default for SnapshotCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn any(value: &[u8]) -> Any {
+        Any {
+            type_url: "test".to_string(),
+            value: value.to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_versions() {
+        let mut snapshot = Snapshot::default();
+        assert_eq!(snapshot.version("a"), 0);
+
+        assert!(snapshot.set("a", "x", any(b"1")));
+        assert!(!snapshot.set("a", "x", any(b"1")));
+        assert!(snapshot.set("a", "y", any(b"2")));
+        assert_eq!(snapshot.version("a"), 2);
+        assert_eq!(snapshot.version("b"), 0);
+
+        let names = BTreeSet::from(["y".to_string(), "z".to_string()]);
+        assert_eq!(snapshot.resources("a", Some(&names)), [any(b"2")]);
+        assert_eq!(snapshot.resources("a", None).len(), 2);
+
+        assert!(snapshot.remove("a", "x"));
+        assert!(!snapshot.remove("a", "x"));
+        assert_eq!(snapshot.version("a"), 3);
+    }
+}
@@ -14,3 +14,292 @@
  * See the License for the specific language governing permissions and
  * limitations under the License.
  */
+
+mod cache;
+
+pub use cache::{Snapshot, SnapshotCache};
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    convert::Infallible,
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll},
+};
+
+use tokio::{
+    net::TcpListener,
+    sync::{mpsc, watch},
+};
+use tokio_stream::wrappers::{ReceiverStream, TcpListenerStream};
+use tonic::{
+    body::BoxBody,
+    codec::{ProstCodec, Streaming},
+    server::NamedService,
+    transport::Body,
+    Status,
+};
+use tower_service::Service;
+use tracing::{debug, warn};
+
+use crate::{
+    error::XdsError,
+    protocol::{
+        discovery::{DiscoveryRequest, DiscoveryResponse},
+        CLUSTER_TYPE_URL, LISTENER_TYPE_URL,
+    },
+};
+
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+
+/// A state of the world ADS server handing out what is in its cache, to the
+/// xDS client of this crate, Envoy and proxyless gRPC alike. It is a tonic
+/// service, so it can share a server with other gRPC services too.
+#[derive(Clone)]
+pub struct XdsServer {
+    cache: SnapshotCache,
+    streams: Arc<Streams>,
+}
+
+/// The listener and cluster names every open stream asks for.
+struct Streams {
+    next_id: AtomicU64,
+    names: Mutex<HashMap<u64, BTreeSet<String>>>,
+    requested: watch::Sender<BTreeSet<String>>,
+}
+
+impl Streams {
+    fn set(&self, id: u64, names: Option<BTreeSet<String>>) {
+        let mut streams = self.names.lock().unwrap();
+        match names {
+            Some(names) => streams.insert(id, names),
+            None => streams.remove(&id),
+        };
+        let requested: BTreeSet<String> = streams.values().flatten().cloned().collect();
+        self.requested.send_if_modified(|current| {
+            if *current == requested {
+                return false;
+            }
+            *current = requested;
+            true
+        });
+    }
+}
+
+impl XdsServer {
+    pub fn new(cache: SnapshotCache) -> Self {
+        XdsServer {
+            cache,
+            streams: Arc::new(Streams {
+                next_id: AtomicU64::new(0),
+                names: Mutex::new(HashMap::new()),
+                requested: watch::channel(BTreeSet::new()).0,
+            }),
+        }
+    }
+
+    pub fn cache(&self) -> &SnapshotCache {
+        &self.cache
+    }
+
+    /// The listener and cluster names the connected clients ask for, so that
+    /// resources can be made as they are needed. Wildcard subscriptions do not
+    /// name anything.
+    pub fn requested(&self) -> watch::Receiver<BTreeSet<String>> {
+        self.streams.requested.subscribe()
+    }
+
+    /// Serves the clients coming in on `listener` until `signal` resolves.
+    pub async fn serve_with_shutdown<F>(
+        self,
+        listener: TcpListener,
+        signal: F,
+    ) -> Result<(), XdsError>
+    where
+        F: Future<Output = ()>,
+    {
+        tonic::transport::Server::builder()
+            .add_service(self)
+            .serve_with_incoming_shutdown(TcpListenerStream::new(listener), signal)
+            .await?;
+        Ok(())
+    }
+}
+
+impl NamedService for XdsServer {
+    const NAME: &'static str = "envoy.service.discovery.v3.AggregatedDiscoveryService";
+}
+
+impl Service<http::Request<Body>> for XdsServer {
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<Body>) -> Self::Future {
+        let server = self.clone();
+        Box::pin(async move {
+            let mut grpc = tonic::server::Grpc::new(ProstCodec::<
+                DiscoveryResponse,
+                DiscoveryRequest,
+            >::default());
+            Ok(grpc.streaming(AdsService(server), req).await)
+        })
+    }
+}
+
+struct AdsService(XdsServer);
+
+impl tonic::server::StreamingService<DiscoveryRequest> for AdsService {
+    type Response = DiscoveryResponse;
+    type ResponseStream = ReceiverStream<Result<DiscoveryResponse, Status>>;
+    type Future = BoxFuture<Result<tonic::Response<Self::ResponseStream>, Status>>;
+
+    fn call(&mut self, request: tonic::Request<Streaming<DiscoveryRequest>>) -> Self::Future {
+        let (tx, rx) = mpsc::channel(16);
+        let stream = AdsStream {
+            id: self.0.streams.next_id.fetch_add(1, Ordering::Relaxed),
+            node: String::new(),
+            snapshot: self.0.cache.subscribe(),
+            streams: self.0.streams.clone(),
+            watches: HashMap::new(),
+            nonce: 0,
+            tx,
+        };
+        tokio::spawn(stream.run(request.into_inner()));
+        Box::pin(async move { Ok(tonic::Response::new(ReceiverStream::new(rx))) })
+    }
+}
+
+/// What a stream subscribed of one type.
+#[derive(Default)]
+struct Watch {
+    /// `None` for all resources of the type.
+    names: Option<BTreeSet<String>>,
+    /// The version of the last response.
+    version: Option<u64>,
+    nonce: String,
+}
+
+struct AdsStream {
+    id: u64,
+    /// The id of the client node.
+    node: String,
+    snapshot: watch::Receiver<Snapshot>,
+    streams: Arc<Streams>,
+    watches: HashMap<String, Watch>,
+    nonce: u64,
+    tx: mpsc::Sender<Result<DiscoveryResponse, Status>>,
+}
+
+impl AdsStream {
+    async fn run(mut self, mut inbound: Streaming<DiscoveryRequest>) {
+        loop {
+            let result = tokio::select! {
+                request = inbound.message() => match request {
+                    Ok(Some(request)) => self.handle_request(request).await,
+                    Ok(None) => break,
+                    Err(status) => {
+                        debug!("xds stream of {} broke: {}", self.node, status);
+                        break;
+                    }
+                },
+                changed = self.snapshot.changed() => match changed {
+                    Ok(()) => self.push().await,
+                    Err(_) => break,
+                },
+            };
+            if result.is_err() {
+                break;
+            }
+        }
+        self.streams.set(self.id, None);
+    }
+
+    async fn handle_request(&mut self, request: DiscoveryRequest) -> Result<(), ()> {
+        if let Some(node) = &request.node {
+            self.node = node.id.clone();
+        }
+        let type_url = request.type_url;
+        let watch = self.watches.entry(type_url.clone()).or_default();
+
+        if let Some(error) = request.error_detail {
+            warn!(
+                "{} rejected {} of version {}: {}",
+                self.node, type_url, request.version_info, error.message
+            );
+            return Ok(());
+        }
+        // an ack or nack of a response that was overtaken by another one
+        if !request.response_nonce.is_empty() && request.response_nonce != watch.nonce {
+            return Ok(());
+        }
+
+        // no names subscribe to all listeners and clusters
+        let wildcard = request.resource_names.is_empty()
+            && (type_url == LISTENER_TYPE_URL || type_url == CLUSTER_TYPE_URL);
+        let names = (!wildcard).then(|| request.resource_names.into_iter().collect());
+        let changed = watch.names != names;
+        watch.names = names;
+        if changed {
+            self.update_requested();
+        }
+
+        let watch = &self.watches[&type_url];
+        if changed || watch.version != Some(self.snapshot.borrow().version(&type_url)) {
+            self.respond(&type_url).await?;
+        }
+        Ok(())
+    }
+
+    /// Pushes the types whose version went past the last response.
+    async fn push(&mut self) -> Result<(), ()> {
+        let outdated: Vec<String> = {
+            let snapshot = self.snapshot.borrow_and_update();
+            self.watches
+                .iter()
+                .filter(|(type_url, watch)| watch.version != Some(snapshot.version(type_url)))
+                .map(|(type_url, _)| type_url.clone())
+                .collect()
+        };
+        for type_url in outdated {
+            self.respond(&type_url).await?;
+        }
+        Ok(())
+    }
+
+    async fn respond(&mut self, type_url: &str) -> Result<(), ()> {
+        self.nonce += 1;
+        let watch = self.watches.get_mut(type_url).unwrap();
+        let response = {
+            let snapshot = self.snapshot.borrow();
+            let version = snapshot.version(type_url);
+            watch.version = Some(version);
+            watch.nonce = self.nonce.to_string();
+            DiscoveryResponse {
+                version_info: version.to_string(),
+                resources: snapshot.resources(type_url, watch.names.as_ref()),
+                type_url: type_url.to_string(),
+                nonce: watch.nonce.clone(),
+            }
+        };
+        self.tx.send(Ok(response)).await.map_err(|_| ())
+    }
+
+    fn update_requested(&self) {
+        let names = [LISTENER_TYPE_URL, CLUSTER_TYPE_URL]
+            .iter()
+            .filter_map(|type_url| self.watches.get(*type_url)?.names.as_ref())
+            .flatten()
+            .cloned()
+            .collect();
+        self.streams.set(self.id, Some(names));
+    }
+}
@@ -0,0 +1,171 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+mod common;
+
+use common::{eds_cluster, listener, load_assignment, route_config, wait_for, FakeAds};
+use dubbo_remoting_xds::{
+    protocol::{
+        core::Node, listener::Listener, Resource, CLUSTER_LOAD_ASSIGNMENT_TYPE_URL,
+        CLUSTER_TYPE_URL, LISTENER_TYPE_URL, ROUTE_CONFIGURATION_TYPE_URL,
+    },
+    ServiceSnapshot, XdsClient,
+};
+
+const SERVICE_NAME: &str = "org.apache.dubbo.Greeter";
+
+fn node() -> Node {
+    Node {
+        id: "test".to_string(),
+        ..Default::default()
+    }
+}
+
+/// Serves the greeter over RDS and EDS, the providers listen on `ports`.
+fn serve_greeter(ads: &FakeAds, ports: &[u16]) {
+    ads.set(
+        LISTENER_TYPE_URL,
+        "1",
+        vec![listener(SERVICE_NAME, "greeter-routes")],
+    );
+    ads.set(
+        ROUTE_CONFIGURATION_TYPE_URL,
+        "1",
+        vec![route_config("greeter-routes", &[("/", "greeter")])],
+    );
+    ads.set(CLUSTER_TYPE_URL, "1", vec![eds_cluster("greeter")]);
+    ads.set(
+        CLUSTER_LOAD_ASSIGNMENT_TYPE_URL,
+        "1",
+        vec![load_assignment("greeter", ports)],
+    );
+}
+
+fn addresses(snapshot: &ServiceSnapshot) -> Vec<&str> {
+    snapshot.addresses().into_iter().collect()
+}
+
+#[tokio::test]
+async fn test_watch_service() {
+    let ads = FakeAds::start().await;
+    serve_greeter(&ads, &[8001, 8002]);
+
+    let client = XdsClient::connect(ads.endpoint(), node()).unwrap();
+    let mut snapshot = client.watch_service(SERVICE_NAME).await.unwrap();
+    wait_for(&mut snapshot, |s| addresses(s).len() == 2).await;
+
+    let snapshot = snapshot.borrow().clone();
+    assert_eq!(addresses(&snapshot), ["127.0.0.1:8001", "127.0.0.1:8002"]);
+    let route = snapshot.route("/org.apache.dubbo.Greeter/greet", |_| None);
+    assert_eq!(route.and_then(|r| r.pick_cluster()), Some("greeter"));
+
+    // the client follows the service down one type after the other
+    let request = ads.next_request(LISTENER_TYPE_URL).await;
+    assert_eq!(request.resource_names, [SERVICE_NAME]);
+    assert_eq!(request.node.unwrap().id, "test");
+    let request = ads.next_request(ROUTE_CONFIGURATION_TYPE_URL).await;
+    assert_eq!(request.resource_names, ["greeter-routes"]);
+    let request = ads.next_request(CLUSTER_TYPE_URL).await;
+    assert_eq!(request.resource_names, ["greeter"]);
+    let request = ads.next_request(CLUSTER_LOAD_ASSIGNMENT_TYPE_URL).await;
+    assert_eq!(request.resource_names, ["greeter"]);
+}
+
+#[tokio::test]
+async fn test_endpoints_update() {
+    let ads = FakeAds::start().await;
+    serve_greeter(&ads, &[8001]);
+
+    let client = XdsClient::connect(ads.endpoint(), node()).unwrap();
+    let mut snapshot = client.watch_service(SERVICE_NAME).await.unwrap();
+    wait_for(&mut snapshot, |s| addresses(s) == ["127.0.0.1:8001"]).await;
+
+    ads.set(
+        CLUSTER_LOAD_ASSIGNMENT_TYPE_URL,
+        "2",
+        vec![load_assignment("greeter", &[8002, 8003])],
+    );
+    wait_for(&mut snapshot, |s| {
+        addresses(s) == ["127.0.0.1:8002", "127.0.0.1:8003"]
+    })
+    .await;
+
+    // the listener is gone, so is the service
+    ads.set(LISTENER_TYPE_URL, "2", vec![]);
+    wait_for(&mut snapshot, |s| *s == ServiceSnapshot::default()).await;
+}
+
+#[tokio::test]
+async fn test_nack() {
+    let ads = FakeAds::start().await;
+    serve_greeter(&ads, &[8001]);
+
+    let client = XdsClient::connect(ads.endpoint(), node()).unwrap();
+    let mut snapshot = client.watch_service(SERVICE_NAME).await.unwrap();
+    wait_for(&mut snapshot, |s| addresses(s).len() == 1).await;
+
+    // a listener without api listener
+    let invalid = Listener {
+        name: SERVICE_NAME.to_string(),
+        ..Default::default()
+    };
+    ads.set(LISTENER_TYPE_URL, "2", vec![invalid.to_any()]);
+
+    let request = loop {
+        let request = ads.next_request(LISTENER_TYPE_URL).await;
+        if request.error_detail.is_some() {
+            break request;
+        }
+    };
+    // the client stays on the last version it accepted
+    assert_eq!(request.version_info, "1");
+    assert!(request
+        .error_detail
+        .unwrap()
+        .message
+        .contains("no api listener"));
+    assert_eq!(addresses(&snapshot.borrow()), ["127.0.0.1:8001"]);
+}
+
+#[tokio::test]
+async fn test_reconnect() {
+    let ads = FakeAds::start().await;
+    serve_greeter(&ads, &[8001]);
+
+    let client = XdsClient::connect(ads.endpoint(), node()).unwrap();
+    let mut snapshot = client.watch_service(SERVICE_NAME).await.unwrap();
+    wait_for(&mut snapshot, |s| addresses(s).len() == 1).await;
+
+    ads.break_streams();
+
+    // the new stream starts from the versions of the old one
+    let request = loop {
+        let request = ads.next_request(CLUSTER_LOAD_ASSIGNMENT_TYPE_URL).await;
+        if request.response_nonce.is_empty() && !request.version_info.is_empty() {
+            break request;
+        }
+    };
+    assert_eq!(request.version_info, "1");
+    assert_eq!(request.resource_names, ["greeter"]);
+
+    ads.set(
+        CLUSTER_LOAD_ASSIGNMENT_TYPE_URL,
+        "2",
+        vec![load_assignment("greeter", &[8002])],
+    );
+    wait_for(&mut snapshot, |s| addresses(s) == ["127.0.0.1:8002"]).await;
+}
@@ -0,0 +1,354 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! An in-process fake of an ADS management server. It sends every resource
+//! of a type whenever a stream has not seen the current version of the type.
+
+#![allow(dead_code)]
+
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    future::Future,
+    net::SocketAddr,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use dubbo_remoting_xds::protocol::{
+    cluster::{
+        cluster::{DiscoveryType, EdsClusterConfig},
+        Cluster,
+    },
+    core::{Address, AggregatedConfigSource, ConfigSource, HealthStatus, SocketAddress},
+    discovery::{DiscoveryRequest, DiscoveryResponse},
+    endpoint::{
+        lb_endpoint::HostIdentifier, ClusterLoadAssignment, Endpoint, LbEndpoint,
+        LocalityLbEndpoints,
+    },
+    listener::{
+        http_connection_manager::RouteSpecifier, ApiListener, HttpConnectionManager, Listener, Rds,
+    },
+    route::{
+        route::Action, route_action::ClusterSpecifier, route_match::PathSpecifier, Route,
+        RouteAction, RouteConfiguration, RouteMatch, VirtualHost,
+    },
+    Any, Resource,
+};
+use tokio::{
+    net::TcpListener,
+    sync::{broadcast, mpsc, oneshot, watch},
+};
+use tokio_stream::wrappers::{ReceiverStream, TcpListenerStream};
+use tonic::{
+    body::BoxBody,
+    codec::{ProstCodec, Streaming},
+    server::NamedService,
+    transport::Body,
+    Status,
+};
+use tower::Service;
+
+pub const TIMEOUT: Duration = Duration::from_secs(5);
+
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+
+/// The version and the resources of every type.
+type Resources = HashMap<String, (String, Vec<Any>)>;
+
+struct Shared {
+    resources: watch::Receiver<Resources>,
+    requests: mpsc::UnboundedSender<DiscoveryRequest>,
+    kill: broadcast::Sender<()>,
+}
+
+pub struct FakeAds {
+    pub addr: SocketAddr,
+    resources: watch::Sender<Resources>,
+    requests: tokio::sync::Mutex<mpsc::UnboundedReceiver<DiscoveryRequest>>,
+    kill: broadcast::Sender<()>,
+    _shutdown: oneshot::Sender<()>,
+}
+
+impl FakeAds {
+    pub async fn start() -> FakeAds {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (resources, resources_rx) = watch::channel(Resources::new());
+        let (requests_tx, requests) = mpsc::unbounded_channel();
+        let (kill, _) = broadcast::channel(1);
+        let (shutdown, shutdown_rx) = oneshot::channel::<()>();
+
+        let ads = Ads(Arc::new(Shared {
+            resources: resources_rx,
+            requests: requests_tx,
+            kill: kill.clone(),
+        }));
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(ads)
+                .serve_with_incoming_shutdown(TcpListenerStream::new(listener), async {
+                    let _ = shutdown_rx.await;
+                }),
+        );
+
+        FakeAds {
+            addr,
+            resources,
+            requests: tokio::sync::Mutex::new(requests),
+            kill,
+            _shutdown: shutdown,
+        }
+    }
+
+    pub fn endpoint(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Replaces the resources of `type_url`, the streams get them right away.
+    pub fn set(&self, type_url: &str, version: &str, resources: Vec<Any>) {
+        self.resources.send_modify(|all| {
+            all.insert(type_url.to_string(), (version.to_string(), resources));
+        });
+    }
+
+    /// Waits for the next request of `type_url`, skipping the ones of other
+    /// types.
+    pub async fn next_request(&self, type_url: &str) -> DiscoveryRequest {
+        let mut requests = self.requests.lock().await;
+        tokio::time::timeout(TIMEOUT, async {
+            loop {
+                let request = requests.recv().await.unwrap();
+                if request.type_url == type_url {
+                    return request;
+                }
+            }
+        })
+        .await
+        .expect("no request")
+    }
+
+    /// Breaks the open streams.
+    pub fn break_streams(&self) {
+        let _ = self.kill.send(());
+    }
+}
+
+#[derive(Clone)]
+struct Ads(Arc<Shared>);
+
+impl NamedService for Ads {
+    const NAME: &'static str = "envoy.service.discovery.v3.AggregatedDiscoveryService";
+}
+
+impl Service<http::Request<Body>> for Ads {
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<Body>) -> Self::Future {
+        let stream = AdsStream(self.0.clone());
+        Box::pin(async move {
+            let mut grpc = tonic::server::Grpc::new(ProstCodec::<
+                DiscoveryResponse,
+                DiscoveryRequest,
+            >::default());
+            Ok(grpc.streaming(stream, req).await)
+        })
+    }
+}
+
+struct AdsStream(Arc<Shared>);
+
+impl tonic::server::StreamingService<DiscoveryRequest> for AdsStream {
+    type Response = DiscoveryResponse;
+    type ResponseStream = ReceiverStream<Result<DiscoveryResponse, Status>>;
+    type Future = BoxFuture<Result<tonic::Response<Self::ResponseStream>, Status>>;
+
+    fn call(&mut self, request: tonic::Request<Streaming<DiscoveryRequest>>) -> Self::Future {
+        let shared = self.0.clone();
+        let mut inbound = request.into_inner();
+        let (tx, rx) = mpsc::channel(16);
+        tokio::spawn(async move {
+            let mut resources = shared.resources.clone();
+            let mut kill = shared.kill.subscribe();
+            // the version of every type the stream got
+            let mut sent = HashMap::<String, String>::new();
+            let mut nonce = 0;
+            loop {
+                let responses: Vec<_> = tokio::select! {
+                    request = inbound.message() => {
+                        let Ok(Some(request)) = request else { return };
+                        let _ = shared.requests.send(request.clone());
+                        sent.entry(request.type_url.clone()).or_default();
+                        let all = resources.borrow();
+                        respond(&all, &request.type_url, &mut sent, &mut nonce)
+                            .into_iter()
+                            .collect()
+                    }
+                    changed = resources.changed() => {
+                        if changed.is_err() {
+                            return;
+                        }
+                        let all = resources.borrow();
+                        let subscribed: Vec<_> = sent.keys().cloned().collect();
+                        subscribed
+                            .iter()
+                            .filter_map(|type_url| respond(&all, type_url, &mut sent, &mut nonce))
+                            .collect()
+                    }
+                    _ = kill.recv() => return,
+                };
+                for response in responses {
+                    if tx.send(Ok(response)).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+        Box::pin(async move { Ok(tonic::Response::new(ReceiverStream::new(rx))) })
+    }
+}
+
+/// Returns the resources of `type_url` unless the stream got their version.
+fn respond(
+    all: &Resources,
+    type_url: &str,
+    sent: &mut HashMap<String, String>,
+    nonce: &mut u64,
+) -> Option<DiscoveryResponse> {
+    let (version, resources) = all.get(type_url)?;
+    if sent.get(type_url) == Some(version) {
+        return None;
+    }
+    sent.insert(type_url.to_string(), version.clone());
+    *nonce += 1;
+    Some(DiscoveryResponse {
+        version_info: version.clone(),
+        resources: resources.clone(),
+        type_url: type_url.to_string(),
+        nonce: nonce.to_string(),
+    })
+}
+
+pub fn listener(name: &str, route_config_name: &str) -> Any {
+    let manager = HttpConnectionManager {
+        stat_prefix: name.to_string(),
+        route_specifier: Some(RouteSpecifier::Rds(Rds {
+            config_source: Some(ads()),
+            route_config_name: route_config_name.to_string(),
+        })),
+        http_filters: vec![],
+    };
+    Listener {
+        name: name.to_string(),
+        address: None,
+        api_listener: Some(ApiListener {
+            api_listener: Some(manager.to_any()),
+        }),
+    }
+    .to_any()
+}
+
+/// A route configuration of one virtual host for all domains, `routes` are
+/// path prefixes and the clusters they forward to.
+pub fn route_config(name: &str, routes: &[(&str, &str)]) -> Any {
+    RouteConfiguration {
+        name: name.to_string(),
+        virtual_hosts: vec![VirtualHost {
+            name: name.to_string(),
+            domains: vec!["*".to_string()],
+            routes: routes
+                .iter()
+                .map(|(prefix, cluster)| Route {
+                    r#match: Some(RouteMatch {
+                        path_specifier: Some(PathSpecifier::Prefix(prefix.to_string())),
+                        ..Default::default()
+                    }),
+                    action: Some(Action::Route(RouteAction {
+                        cluster_specifier: Some(ClusterSpecifier::Cluster(cluster.to_string())),
+                    })),
+                    name: String::new(),
+                })
+                .collect(),
+        }],
+    }
+    .to_any()
+}
+
+pub fn eds_cluster(name: &str) -> Any {
+    Cluster {
+        name: name.to_string(),
+        r#type: DiscoveryType::Eds as i32,
+        eds_cluster_config: Some(EdsClusterConfig {
+            eds_config: Some(ads()),
+            service_name: String::new(),
+        }),
+        load_assignment: None,
+    }
+    .to_any()
+}
+
+pub fn load_assignment(cluster: &str, ports: &[u16]) -> Any {
+    ClusterLoadAssignment {
+        cluster_name: cluster.to_string(),
+        endpoints: vec![LocalityLbEndpoints {
+            lb_endpoints: ports
+                .iter()
+                .map(|port| LbEndpoint {
+                    host_identifier: Some(HostIdentifier::Endpoint(Endpoint {
+                        address: Some(Address {
+                            socket_address: Some(SocketAddress {
+                                address: "127.0.0.1".to_string(),
+                                port_value: *port as u32,
+                            }),
+                        }),
+                    })),
+                    health_status: HealthStatus::Healthy as i32,
+                    load_balancing_weight: None,
+                })
+                .collect(),
+            load_balancing_weight: None,
+            priority: 0,
+        }],
+    }
+    .to_any()
+}
+
+fn ads() -> ConfigSource {
+    ConfigSource {
+        ads: Some(AggregatedConfigSource {}),
+        resource_api_version: 2,
+    }
+}
+
+/// Waits until the snapshot of `rx` passes `f`.
+pub async fn wait_for<T>(rx: &mut watch::Receiver<T>, f: impl Fn(&T) -> bool) {
+    tokio::time::timeout(TIMEOUT, async {
+        while !f(&rx.borrow_and_update()) {
+            rx.changed().await.unwrap();
+        }
+    })
+    .await
+    .expect("timed out waiting for a snapshot")
+}
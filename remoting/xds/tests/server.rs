@@ -0,0 +1,251 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one or more
+ * contributor license agreements.  See the NOTICE file distributed with
+ * this work for additional information regarding copyright ownership.
+ * The ASF licenses this file to You under the Apache License, Version 2.0
+ * (the "License"); you may not use this file except in compliance with
+ * the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+mod common;
+
+use std::time::Duration;
+
+use common::{eds_cluster, listener, load_assignment, route_config, wait_for};
+use dubbo_remoting_xds::{
+    protocol::{
+        core::Node,
+        discovery::{DiscoveryRequest, DiscoveryResponse, Status},
+        ADS_PATH, CLUSTER_LOAD_ASSIGNMENT_TYPE_URL, CLUSTER_TYPE_URL, LISTENER_TYPE_URL,
+        ROUTE_CONFIGURATION_TYPE_URL,
+    },
+    server::SnapshotCache,
+    XdsClient, XdsServer,
+};
+use http::uri::PathAndQuery;
+use tokio::{
+    net::TcpListener,
+    sync::{mpsc, oneshot},
+    time::timeout,
+};
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use tonic::codec::{ProstCodec, Streaming};
+
+const SERVICE_NAME: &str = "org.apache.dubbo.Greeter";
+
+struct Server {
+    server: XdsServer,
+    endpoint: String,
+    _shutdown: oneshot::Sender<()>,
+}
+
+async fn start() -> Server {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("http://{}", listener.local_addr().unwrap());
+    let server = XdsServer::new(SnapshotCache::new());
+    let (shutdown, signal) = oneshot::channel::<()>();
+    tokio::spawn(server.clone().serve_with_shutdown(listener, async {
+        let _ = signal.await;
+    }));
+    Server {
+        server,
+        endpoint,
+        _shutdown: shutdown,
+    }
+}
+
+/// A bare ADS stream, to look at the protocol itself.
+struct Stream {
+    tx: mpsc::UnboundedSender<DiscoveryRequest>,
+    rx: Streaming<DiscoveryResponse>,
+}
+
+impl Stream {
+    async fn open(endpoint: &str) -> Stream {
+        let channel = tonic::transport::Endpoint::from_shared(endpoint.to_string())
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+        let mut grpc = tonic::client::Grpc::new(channel);
+        grpc.ready().await.unwrap();
+        let (tx, rx) = mpsc::unbounded_channel();
+        let rx = grpc
+            .streaming(
+                tonic::Request::new(UnboundedReceiverStream::new(rx)),
+                PathAndQuery::from_static(ADS_PATH),
+                ProstCodec::<DiscoveryRequest, DiscoveryResponse>::default(),
+            )
+            .await
+            .unwrap()
+            .into_inner();
+        Stream { tx, rx }
+    }
+
+    fn request(&self, type_url: &str, names: &[&str], last: Option<&DiscoveryResponse>) {
+        self.tx
+            .send(DiscoveryRequest {
+                version_info: last.map(|r| r.version_info.clone()).unwrap_or_default(),
+                node: Some(Node {
+                    id: "test".to_string(),
+                    ..Default::default()
+                }),
+                resource_names: names.iter().map(|name| name.to_string()).collect(),
+                type_url: type_url.to_string(),
+                response_nonce: last.map(|r| r.nonce.clone()).unwrap_or_default(),
+                error_detail: None,
+            })
+            .unwrap();
+    }
+
+    async fn response(&mut self) -> DiscoveryResponse {
+        timeout(Duration::from_secs(5), self.rx.message())
+            .await
+            .expect("no response")
+            .unwrap()
+            .unwrap()
+    }
+
+    async fn no_response(&mut self) {
+        let response = timeout(Duration::from_millis(200), self.rx.message()).await;
+        assert!(response.is_err(), "unexpected response {:?}", response);
+    }
+}
+
+#[tokio::test]
+async fn test_wildcard() {
+    let server = start().await;
+    let cache = server.server.cache();
+    cache.set(CLUSTER_TYPE_URL, "a", eds_cluster("a"));
+    cache.set(CLUSTER_TYPE_URL, "b", eds_cluster("b"));
+
+    let mut stream = Stream::open(&server.endpoint).await;
+    stream.request(CLUSTER_TYPE_URL, &[], None);
+    let response = stream.response().await;
+    assert_eq!(response.resources.len(), 2);
+    assert_eq!(response.version_info, "2");
+
+    // no names is no wildcard for endpoints
+    stream.request(CLUSTER_LOAD_ASSIGNMENT_TYPE_URL, &[], None);
+    cache.set(
+        CLUSTER_LOAD_ASSIGNMENT_TYPE_URL,
+        "a",
+        load_assignment("a", &[8001]),
+    );
+    let response = stream.response().await;
+    assert_eq!(response.type_url, CLUSTER_LOAD_ASSIGNMENT_TYPE_URL);
+    assert!(response.resources.is_empty());
+}
+
+#[tokio::test]
+async fn test_ack_and_push() {
+    let server = start().await;
+    let cache = server.server.cache();
+    cache.set(
+        CLUSTER_LOAD_ASSIGNMENT_TYPE_URL,
+        "a",
+        load_assignment("a", &[8001]),
+    );
+
+    let mut stream = Stream::open(&server.endpoint).await;
+    stream.request(CLUSTER_LOAD_ASSIGNMENT_TYPE_URL, &["a", "b"], None);
+    let response = stream.response().await;
+    assert_eq!(response.resources.len(), 1);
+
+    // the ack gets nothing back
+    stream.request(
+        CLUSTER_LOAD_ASSIGNMENT_TYPE_URL,
+        &["a", "b"],
+        Some(&response),
+    );
+    stream.no_response().await;
+
+    // neither does a nack, but the next version is pushed
+    let mut nack = DiscoveryRequest {
+        response_nonce: response.nonce.clone(),
+        type_url: CLUSTER_LOAD_ASSIGNMENT_TYPE_URL.to_string(),
+        resource_names: vec!["a".to_string(), "b".to_string()],
+        ..Default::default()
+    };
+    nack.error_detail = Some(Status {
+        code: 3,
+        message: "bad".to_string(),
+    });
+    stream.tx.send(nack).unwrap();
+    stream.no_response().await;
+
+    cache.set(
+        CLUSTER_LOAD_ASSIGNMENT_TYPE_URL,
+        "b",
+        load_assignment("b", &[8002]),
+    );
+    let pushed = stream.response().await;
+    assert_eq!(pushed.resources.len(), 2);
+    assert_ne!(pushed.nonce, response.nonce);
+
+    // types that did not change are left alone
+    cache.set(CLUSTER_TYPE_URL, "a", eds_cluster("a"));
+    stream.no_response().await;
+}
+
+#[tokio::test]
+async fn test_requested() {
+    let server = start().await;
+    let mut requested = server.server.requested();
+
+    let mut stream = Stream::open(&server.endpoint).await;
+    stream.request(LISTENER_TYPE_URL, &[SERVICE_NAME], None);
+    stream.response().await;
+    wait_for(&mut requested, |names| names.contains(SERVICE_NAME)).await;
+
+    drop(stream);
+    wait_for(&mut requested, |names| names.is_empty()).await;
+}
+
+#[tokio::test]
+async fn test_client() {
+    let server = start().await;
+    let cache = server.server.cache();
+    cache.set(
+        LISTENER_TYPE_URL,
+        SERVICE_NAME,
+        listener(SERVICE_NAME, "greeter-routes"),
+    );
+    cache.set(
+        ROUTE_CONFIGURATION_TYPE_URL,
+        "greeter-routes",
+        route_config("greeter-routes", &[("/", "greeter")]),
+    );
+    cache.set(CLUSTER_TYPE_URL, "greeter", eds_cluster("greeter"));
+    cache.set(
+        CLUSTER_LOAD_ASSIGNMENT_TYPE_URL,
+        "greeter",
+        load_assignment("greeter", &[8001]),
+    );
+
+    let client = XdsClient::connect(
+        server.endpoint.clone(),
+        Node {
+            id: "test".to_string(),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let mut snapshot = client.watch_service(SERVICE_NAME).await.unwrap();
+    wait_for(&mut snapshot, |s| s.addresses().len() == 1).await;
+
+    cache.set(
+        CLUSTER_LOAD_ASSIGNMENT_TYPE_URL,
+        "greeter",
+        load_assignment("greeter", &[8001, 8002]),
+    );
+    wait_for(&mut snapshot, |s| s.addresses().len() == 2).await;
+}